@@ -0,0 +1,843 @@
+// Copyright 2015 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+use std::cmp::max;
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use hexpos::Pos;
+use terrain::Terrain;
+use mapgen::defense_score;
+use unit::{Unit, UnitID, UnitType, Player, Promotion, MAX_HP};
+use city::{CityID, CityStatus, Production, CITY_RADIUS};
+use building::Building;
+use map::{LivePath, LiveMap};
+use combat::DmgRange;
+use religion::Belief;
+
+// Positions come out of hashmaps in an arbitrary order. We sort them before picking one so that
+// a game always plays out the same way.
+fn sort_positions(positions: &mut [Pos]) {
+    positions.sort_by_key(|p| (p.x, p.y, p.z));
+}
+
+/// Units with less HP than this retreat instead of looking for a fight.
+const RETREAT_HP: u8 = 40;
+/// Strength our army needs, in percent of the threat we perceive, before we build anything else.
+const THREAT_MARGIN: u32 = 150;
+/// Enemy units within that distance of our cities threaten them.
+const THREAT_DISTANCE: i32 = 6;
+/// Settlers look for a city site that far around them at most.
+const SETTLE_DISTANCE: i32 = 6;
+/// What a site next to water is worth on top of its yield, for its sea tiles and its harbor.
+const COAST_BONUS: u32 = 4;
+/// What each known resource around a site is worth on top of its yield.
+const RESOURCE_BONUS: u32 = 3;
+/// Every that many turns of war, we consider offering peace.
+const PEACE_OFFER_TURNS: u16 = 10;
+
+/// Rates the attack of `unit_id` on `defender_id`. The higher, the better for us.
+///
+/// Killing the defender is what matters most, then not dying, then the damage trade. Anything
+/// above zero is a favorable attack.
+fn rate_attack(unit_id: UnitID, defender_id: UnitID, map: &LiveMap) -> f32 {
+    fn avg((min, max): DmgRange) -> f32 {
+        (min as f32 + max as f32) / 2.0
+    }
+
+    let stats = map.get_combat_stats(unit_id, defender_id);
+    let odds = stats.outcome_probabilities();
+    let dmg_trade = avg(stats.dmgrange_to_defender()) - avg(stats.dmgrange_to_attacker());
+    odds.defender_dies * 2.0 - odds.attacker_dies * 2.0 + dmg_trade / MAX_HP as f32
+}
+
+/// Returns the position of the most promising enemy `unit_id` can attack this turn.
+///
+/// Targets are rated with `rate_attack()`, in parallel. We only consider favorable attacks.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::terrain::TerrainMap;
+/// use civng_core::map::LiveMap;
+/// use civng_core::unit::{Unit, UnitType, Player};
+/// use civng_core::hexpos::{OffsetPos, Direction};
+/// use civng_core::ai::best_attack_target;
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(10, 10));
+/// let pos = OffsetPos::new(5, 5).to_pos();
+/// map.add_unit(Unit::with_state(UnitType::Melee, Player::AI(0), pos, 2, 100));
+/// map.add_unit(Unit::new(UnitType::Swordsman, Player::Me, pos.neighbor(Direction::North)));
+/// // Attacking a healthy swordsman would hurt us more than it.
+/// assert_eq!(best_attack_target(1, &map), None);
+/// let weak = pos.neighbor(Direction::South);
+/// map.add_unit(Unit::with_state(UnitType::Melee, Player::Me, weak, 2, 20));
+/// assert_eq!(best_attack_target(1, &map), Some(weak));
+/// ```
+pub fn best_attack_target(unit_id: UnitID, map: &LiveMap) -> Option<Pos> {
+    let unit = map.units().get(unit_id);
+    let mut candidates: Vec<Pos> = if unit.type_().is_ranged() {
+        map.bombardable_pos(unit_id).keys().cloned().collect()
+    } else {
+        map.reachable_pos(unit_id)
+           .values()
+           .filter(|p| LivePath::new(p, map).is_attack())
+           .map(|p| p.to())
+           .collect()
+    };
+    sort_positions(&mut candidates);
+    let at_war = |u: &Unit| map.diplomacy().at_war(u.owner(), unit.owner());
+    let targets: Vec<(Pos, UnitID)> = candidates.into_iter()
+                                                .filter_map(|pos| map.defender_at(pos))
+                                                .filter(|u| at_war(u))
+                                                .map(|u| (u.pos(), u.id()))
+                                                .collect();
+    // Rating only reads the map, so it can run in parallel. Picking the best one stays
+    // sequential so that ties are always broken the same way.
+    let ratings: Vec<(Pos, f32)> = targets.par_iter()
+                                          .map(|&(pos, defender_id)| {
+                                              (pos, rate_attack(unit_id, defender_id, map))
+                                          })
+                                          .collect();
+    let mut result = None;
+    let mut best_rating = 0.0;
+    for (pos, rating) in ratings {
+        if rating > best_rating {
+            best_rating = rating;
+            result = Some(pos);
+        }
+    }
+    result
+}
+
+/// Returns the distance between `pos` and the closest unit matching `pred`, if any.
+fn distance_to_closest<F: Fn(&Unit) -> bool>(pos: Pos, map: &LiveMap, pred: F) -> Option<i32> {
+    map.units().all_units().filter(|u| pred(u)).map(|u| u.pos().distance(pos)).min()
+}
+
+/// Returns the reachable position of `unit_id` with the lowest `score`, if it's lower than the
+/// score of its current position.
+fn best_destination<F: Fn(Pos) -> i32>(unit_id: UnitID, map: &LiveMap, score: F) -> Option<Pos> {
+    let mut choices: Vec<Pos> = map.reachable_pos(unit_id)
+                                   .values()
+                                   .filter(|p| !LivePath::new(p, map).is_attack())
+                                   .map(|p| p.to())
+                                   .collect();
+    sort_positions(&mut choices);
+    let current = map.units().get(unit_id).pos();
+    let mut best = (score(current), None);
+    for pos in choices {
+        let s = score(pos);
+        if s < best.0 {
+            best = (s, Some(pos));
+        }
+    }
+    best.1
+}
+
+/// Moves `unit_id` as close as possible to the closest enemy.
+///
+/// Our units can't cross the sea, so enemies on our continent come first. Enemies elsewhere are
+/// only approached when there are none left here. Between positions as close to enemies, we pick
+/// the one that's easiest to defend.
+pub fn advance(unit_id: UnitID, map: &mut LiveMap) {
+    if let Some(pos) = advance_destination(unit_id, map) {
+        map.moveunit_to(unit_id, pos);
+    }
+}
+
+/// Returns where `unit_id` moves to when it advances, if it moves at all. See `advance()`.
+fn advance_destination(unit_id: UnitID, map: &LiveMap) -> Option<Pos> {
+    let owner = map.units().get(unit_id).owner();
+    let distances: Vec<(Pos, i32)> = {
+        let reachable = map.reachable_pos(unit_id);
+        let current = map.units().get(unit_id).pos();
+        let terrain = map.terrain();
+        let continent = terrain.continent_at(current);
+        let is_enemy = |u: &Unit| map.diplomacy().at_war(u.owner(), owner);
+        let enemy_here = map.units()
+                            .all_units()
+                            .any(|u| is_enemy(u) && terrain.continent_at(u.pos()) == continent);
+        let is_target = |u: &Unit| {
+            is_enemy(u) && (!enemy_here || terrain.continent_at(u.pos()) == continent)
+        };
+        let positions: Vec<Pos> = Some(current)
+                                      .into_iter()
+                                      .chain(reachable.keys().cloned())
+                                      .collect();
+        positions
+            .into_par_iter()
+            .filter_map(|p| {
+                // Defense scores stay under 100, so they only break ties between distances.
+                distance_to_closest(p, map, &is_target)
+                    .map(|d| (p, d * 100 - defense_score(terrain, p)))
+            })
+            .collect()
+    };
+    best_destination(unit_id, map, |pos| {
+        distances.iter().find(|&&(p, _)| p == pos).map_or(i32::max_value(), |&(_, d)| d)
+    })
+}
+
+/// Moves `unit_id` away from enemies, towards friendly units.
+///
+/// Being far from enemies matters more than being close to friends.
+pub fn retreat(unit_id: UnitID, map: &mut LiveMap) {
+    if let Some(pos) = retreat_destination(unit_id, map) {
+        map.moveunit_to(unit_id, pos);
+    }
+}
+
+/// Returns where `unit_id` moves to when it retreats, if it moves at all. See `retreat()`.
+fn retreat_destination(unit_id: UnitID, map: &LiveMap) -> Option<Pos> {
+    let owner = map.units().get(unit_id).owner();
+    let scores: Vec<(Pos, i32)> = {
+        let reachable = map.reachable_pos(unit_id);
+        let current = map.units().get(unit_id).pos();
+        let positions: Vec<Pos> = Some(current)
+                                      .into_iter()
+                                      .chain(reachable.keys().cloned())
+                                      .collect();
+        positions
+            .into_par_iter()
+            .map(|p| {
+                let enemy = distance_to_closest(p, map, |u| {
+                                map.diplomacy().at_war(u.owner(), owner)
+                            })
+                                .unwrap_or(0);
+                let friend = distance_to_closest(p, map, |u| {
+                                 u.owner() == owner && u.id() != unit_id
+                             })
+                                 .unwrap_or(0);
+                (p, friend - enemy * 100)
+            })
+            .collect()
+    };
+    best_destination(unit_id, map, |pos| {
+        scores.iter().find(|&&(p, _)| p == pos).map_or(i32::max_value(), |&(_, s)| s)
+    })
+}
+
+/// Rates `pos` as a site for a new city of `player`, or returns `None` if no city can be founded
+/// there.
+///
+/// A site is worth what the tiles a city there would own yield, the ones of other cities aside,
+/// with a bonus for each resource `player` knows of and one for being on the coast.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::terrain::{TerrainMap, Terrain};
+/// use civng_core::map::LiveMap;
+/// use civng_core::unit::Player;
+/// use civng_core::hexpos::{OffsetPos, Direction};
+/// use civng_core::ai::city_site_score;
+///
+/// let mut terrain = TerrainMap::empty_map(20, 20);
+/// let inland = OffsetPos::new(5, 5).to_pos();
+/// let coast = OffsetPos::new(14, 14).to_pos();
+/// terrain.set_terrain(coast.neighbor(Direction::North), Terrain::Coast);
+/// let map = LiveMap::new(terrain);
+/// // Coast tiles yield less than grassland, but the coast bonus makes up for it.
+/// assert!(city_site_score(coast, Player::Me, &map) > city_site_score(inland, Player::Me, &map));
+/// assert_eq!(city_site_score(coast.neighbor(Direction::North), Player::Me, &map), None);
+/// ```
+pub fn city_site_score(pos: Pos, player: Player, map: &LiveMap) -> Option<u32> {
+    let terrain = map.terrain();
+    let cities = map.cities();
+    if !cities.can_found_city(pos, terrain) {
+        return None;
+    }
+    let known = |p: Pos| {
+        terrain.get_resource(p).map_or(false, |r| cities.science(player) >= r.revealed_at())
+    };
+    let tiles = pos.within(CITY_RADIUS)
+                   .into_iter()
+                   .filter(|&p| terrain.get_terrain(p) != Terrain::OutOfBounds)
+                   .filter(|&p| cities.tile_owner(p).is_none());
+    let mut result = 0;
+    for p in tiles {
+        let y = map.yield_at(player, p);
+        result += y.food as u32 + y.production as u32 + y.gold as u32;
+        if known(p) {
+            result += RESOURCE_BONUS;
+        }
+    }
+    if pos.around().iter().any(|&p| terrain.get_terrain(p).is_water()) {
+        result += COAST_BONUS;
+    }
+    Some(result)
+}
+
+/// Returns the best site for a city of `player` within `SETTLE_DISTANCE` of `from`, on the same
+/// continent, if there's any.
+///
+/// Farther sites are worth a bit less, so that settlers don't walk for long for little.
+fn best_city_site(from: Pos, player: Player, map: &LiveMap) -> Option<Pos> {
+    let continent = map.terrain().continent_at(from);
+    let mut candidates: Vec<Pos> = from.within(SETTLE_DISTANCE)
+                                       .into_iter()
+                                       .filter(|&p| map.terrain().continent_at(p) == continent)
+                                       .collect();
+    sort_positions(&mut candidates);
+    let scores: Vec<(Pos, u32)> = candidates.into_par_iter()
+                                            .filter_map(|p| {
+                                                city_site_score(p, player, map).map(|s| (p, s))
+                                            })
+                                            .collect();
+    let mut result = None;
+    let mut best_score = 0;
+    for (pos, score) in scores {
+        let score = (score * 10).saturating_sub(pos.distance(from) as u32 * 5);
+        if score > best_score {
+            best_score = score;
+            result = Some(pos);
+        }
+    }
+    result
+}
+
+/// What a unit decided to do with its turn, before doing it.
+#[derive(Clone, Copy)]
+enum Decision {
+    /// Attack the enemy at that position, then any other one worth it.
+    Attack(Pos),
+    /// Move towards enemies, to that position if any.
+    Advance(Option<Pos>),
+    /// Move away from enemies, to that position if any.
+    Retreat(Option<Pos>),
+    /// Move towards that city site and found a city there once on it.
+    Settle(Pos),
+}
+
+/// Returns what `unit_id` does this turn. See `play_unit()`.
+///
+/// Deciding only reads the map, so that units can decide at the same time.
+fn decide(unit_id: UnitID, map: &LiveMap) -> Decision {
+    let unit = map.units().get(unit_id);
+    if unit.type_() == UnitType::Settler {
+        let owner = unit.owner();
+        let at_war = |u: &Unit| map.diplomacy().at_war(u.owner(), owner);
+        let safe = distance_to_closest(unit.pos(), map, at_war)
+                       .map_or(true, |d| d > unit.type_().sight() as i32 + 1);
+        if let Some(site) = best_city_site(unit.pos(), owner, map).filter(|_| safe) {
+            return Decision::Settle(site);
+        }
+    }
+    if unit.hp() < RETREAT_HP || unit.type_().is_civilian() {
+        return Decision::Retreat(retreat_destination(unit_id, map));
+    }
+    match best_attack_target(unit_id, map) {
+        Some(pos) => Decision::Attack(pos),
+        None => Decision::Advance(advance_destination(unit_id, map)),
+    }
+}
+
+/// Makes `unit_id` do what it decided.
+fn carry_out(unit_id: UnitID, decision: Decision, map: &mut LiveMap) {
+    let first_target = match decision {
+        Decision::Attack(pos) => pos,
+        Decision::Settle(site) => {
+            if map.units().get(unit_id).pos() != site {
+                if let Some(pos) = best_destination(unit_id, map, |p| p.distance(site)) {
+                    map.moveunit_to(unit_id, pos);
+                }
+            }
+            if map.units().get(unit_id).pos() == site {
+                map.found_city(unit_id);
+            }
+            return;
+        }
+        Decision::Advance(pos) | Decision::Retreat(pos) => {
+            if let Some(pos) = pos {
+                map.moveunit_to(unit_id, pos);
+            }
+            return;
+        }
+    };
+    let unit_type = map.units().get(unit_id).type_();
+    let mut target = Some(first_target);
+    let mut attacked = false;
+    while let Some(target_pos) = target {
+        let combat_stats = if unit_type.is_ranged() {
+            map.bombard_at(unit_id, target_pos)
+        } else {
+            map.moveunit_to(unit_id, target_pos)
+        };
+        match combat_stats {
+            Some(mut combat_stats) => map.attack(&mut combat_stats),
+            None => break,
+        }
+        attacked = true;
+        let unit = map.units().get(unit_id);
+        if unit.is_dead() || unit.is_exhausted() || !unit.can_attack() ||
+           unit.hp() < RETREAT_HP {
+            break;
+        }
+        target = best_attack_target(unit_id, map);
+    }
+    if !attacked {
+        advance(unit_id, map);
+    }
+}
+
+/// Plays `unit_id` for this turn.
+///
+/// Settlers away from enemies head for the best city site around, see `city_site_score()`, and
+/// found a city once there. Badly damaged units and other civilians retreat. Other units attack
+/// their best target if they have a favorable one, as many times as they can, or advance towards
+/// the enemy otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::terrain::TerrainMap;
+/// use civng_core::map::LiveMap;
+/// use civng_core::unit::{Unit, UnitType, Player};
+/// use civng_core::hexpos::{OffsetPos, Direction};
+/// use civng_core::ai::play_unit;
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(10, 10));
+/// let pos = OffsetPos::new(5, 5).to_pos();
+/// let enemy = pos.neighbor(Direction::North);
+/// map.add_unit(Unit::new(UnitType::Melee, Player::Me, enemy));
+/// map.add_unit(Unit::with_state(UnitType::Melee, Player::AI(0), pos, 2, 30));
+/// play_unit(2, &mut map);
+/// assert!(map.units().get(2).pos().distance(enemy) > 1);
+/// assert_eq!(map.units().get(1).hp(), 100);
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(10, 10));
+/// map.add_unit(Unit::new(UnitType::Settler, Player::AI(0), pos));
+/// for _ in 0..5 {
+///     map.refresh();
+///     if map.units().find(1).is_some() {
+///         play_unit(1, &mut map);
+///     }
+/// }
+/// assert!(map.cities().all_cities()[0].owner() == Player::AI(0));
+/// ```
+pub fn play_unit(unit_id: UnitID, map: &mut LiveMap) {
+    let decision = decide(unit_id, map);
+    carry_out(unit_id, decision, map);
+}
+
+/// What changed on the map since units decided what to do, which tells whose decisions still hold.
+struct Changes {
+    /// Position, HP and owner of every living unit, as we last saw them.
+    units: HashMap<UnitID, (Pos, u8, Player)>,
+    /// Owner and status of every city, as we last saw them.
+    cities: Vec<(CityID, Player, CityStatus)>,
+    /// Whether a change any decision might depend on happened, such as a unit dying.
+    global: bool,
+    /// Owners and positions of the units that moved or were hurt, before and after.
+    tiles: Vec<(Player, Pos)>,
+}
+
+impl Changes {
+    fn new(map: &LiveMap) -> Changes {
+        Changes {
+            units: Changes::unit_states(map),
+            cities: Changes::city_states(map),
+            global: false,
+            tiles: Vec::new(),
+        }
+    }
+
+    fn unit_states(map: &LiveMap) -> HashMap<UnitID, (Pos, u8, Player)> {
+        map.units()
+           .all_units()
+           .filter(|u| !u.is_dead())
+           .map(|u| (u.id(), (u.pos(), u.hp(), u.owner())))
+           .collect()
+    }
+
+    fn city_states(map: &LiveMap) -> Vec<(CityID, Player, CityStatus)> {
+        map.cities().all_cities().iter().map(|c| (c.id(), c.owner(), c.status())).collect()
+    }
+
+    /// Notes what changed since we last looked at `map`.
+    fn record(&mut self, map: &LiveMap) {
+        let units = Changes::unit_states(map);
+        for (unit_id, &(pos, hp, owner)) in self.units.iter() {
+            match units.get(unit_id) {
+                Some(&state) if state == (pos, hp, owner) => (),
+                Some(&(new_pos, _, new_owner)) => {
+                    self.tiles.push((owner, pos));
+                    self.tiles.push((new_owner, new_pos));
+                }
+                None => self.global = true,
+            }
+        }
+        let cities = Changes::city_states(map);
+        if units.len() != self.units.len() || cities.len() != self.cities.len() ||
+           cities.iter().zip(self.cities.iter()).any(|(a, b)| a != b) {
+            self.global = true;
+        }
+        self.units = units;
+        self.cities = cities;
+    }
+
+    /// Whether what changed might make `unit_id` decide otherwise than it did with `decision`.
+    ///
+    /// A decision depends on the units around: where the unit can go, the enemies it can attack
+    /// from there, what flanks them and what the unit's friends see of them. Advancing also
+    /// depends on where enemies are across the map, and retreating on where friends are too.
+    fn affect(&self, unit_id: UnitID, decision: Decision, map: &LiveMap) -> bool {
+        if self.global {
+            return true;
+        }
+        let unit = map.units().get(unit_id);
+        let radius = unit.movements() as i32 + max(unit.type_().range(), 1) as i32 +
+                     unit.type_().sight() as i32 + 2;
+        self.tiles.iter().any(|&(player, pos)| {
+            pos.distance(unit.pos()) <= radius ||
+            match decision {
+                Decision::Attack(_) | Decision::Settle(_) => false,
+                Decision::Advance(_) => map.diplomacy().at_war(player, unit.owner()),
+                Decision::Retreat(_) => true,
+            }
+        })
+    }
+}
+
+/// Returns the strength of `unit`, damaged units counting less.
+fn unit_strength(unit: &Unit) -> u32 {
+    unit.strength() as u32 * unit.hp() as u32 / MAX_HP as u32
+}
+
+/// Returns the strength of the army of `player`, its damaged units counting less.
+fn army_strength(player: Player, map: &LiveMap) -> u32 {
+    map.units()
+       .all_units()
+       .filter(|u| u.owner() == player && !u.is_dead())
+       .map(unit_strength)
+       .sum()
+}
+
+/// Returns the strength of the enemy units `player` sees within `THREAT_DISTANCE` of its cities,
+/// damaged units counting less.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::terrain::TerrainMap;
+/// use civng_core::map::LiveMap;
+/// use civng_core::city::City;
+/// use civng_core::unit::{Unit, UnitType, Player};
+/// use civng_core::hexpos::{OffsetPos, Direction};
+/// use civng_core::ai::perceived_threat;
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(30, 10));
+/// let pos = OffsetPos::new(5, 5).to_pos();
+/// map.add_city(City::new("Rome", Player::AI(0), pos));
+/// map.add_unit(Unit::new(UnitType::Ranged, Player::AI(0), pos));
+/// let near = pos.neighbor(Direction::South).neighbor(Direction::South);
+/// map.add_unit(Unit::with_state(UnitType::Melee, Player::Me, near, 2, 50));
+/// // Too far for our archer to see, and for the city to worry about.
+/// map.add_unit(Unit::new(UnitType::Swordsman, Player::Me, OffsetPos::new(25, 5).to_pos()));
+/// map.refresh();
+/// assert_eq!(perceived_threat(Player::AI(0), &map), 4);
+/// ```
+pub fn perceived_threat(player: Player, map: &LiveMap) -> u32 {
+    let cities: Vec<Pos> = map.cities()
+                              .all_cities()
+                              .into_iter()
+                              .filter(|c| c.owner() == player)
+                              .map(|c| c.pos())
+                              .collect();
+    let fog = map.fog(player);
+    map.units()
+       .all_units()
+       .filter(|u| map.diplomacy().at_war(u.owner(), player))
+       .filter(|u| fog.map_or(true, |f| f.is_visible(u.pos())))
+       .filter(|u| cities.iter().any(|&c| c.distance(u.pos()) <= THREAT_DISTANCE))
+       .map(unit_strength)
+       .sum()
+}
+
+/// Returns what `building` is worth to a city, threatened or not, for its cost.
+///
+/// Yields and science always count, defense and experience only under threat.
+fn building_value(building: &Building, threatened: bool) -> u32 {
+    let y = building.yields;
+    let mut value = y.food as u32 + y.production as u32 + y.gold as u32 + building.science as u32;
+    if threatened {
+        value += building.defense as u32 / 25 + building.xp as u32 / 15;
+    }
+    value * 1000 / max(building.cost, 1) as u32
+}
+
+/// Returns what `city_id` should build.
+///
+/// We want an army first: one unit per city at least, and as many as it takes to be
+/// `THREAT_MARGIN` percent as strong as the enemies we see near our cities. We want as many ranged
+/// units as melee ones, and the strongest units our resources allow, as long as our supply cap
+/// allows. When nothing threatens us, cities with 2 citizens or more train a settler if there's a
+/// site nearby and none of our settlers is on its way. Otherwise, we build the building worth the
+/// most for its cost, defenses only counting under threat.
+fn choose_production(city_id: CityID, map: &LiveMap) -> Production {
+    let city = map.cities().get(city_id);
+    let owner = city.owner();
+    let cities = map.cities().all_cities();
+    let city_count = cities.iter().filter(|c| c.owner() == owner).count();
+    let army: Vec<&Unit> = map.units()
+                              .all_units()
+                              .filter(|u| u.owner() == owner && !u.type_().is_civilian())
+                              .collect();
+    let ranged_count = army.iter().filter(|u| u.type_().is_ranged()).count();
+    let ranged = ranged_count * 2 < army.len();
+    let wanted = |t: UnitType| !t.is_civilian() && t.is_ranged() == ranged;
+    let choices = map.production_choices(city_id);
+    let unit = choices.iter()
+                      .filter_map(|&p| {
+                          match p {
+                              Production::Unit(t) if wanted(t) => Some(t),
+                              _ => None,
+                          }
+                      })
+                      .max_by_key(|t| (t.strength(), t.ranged_strength()))
+                      .map_or(Production::Unit(UnitType::Melee), Production::Unit);
+    let threat = perceived_threat(owner, map);
+    let strength = army.iter().map(|u| unit_strength(u)).sum::<u32>();
+    let supplied = map.supply_used(owner) < map.cities().supply_cap(owner);
+    if (army.len() < city_count || strength * 100 < threat * THREAT_MARGIN) && supplied {
+        return unit;
+    }
+    let settler = Production::Unit(UnitType::Settler);
+    let settling = map.units()
+                      .all_units()
+                      .any(|u| u.owner() == owner && u.type_() == UnitType::Settler) ||
+                   cities.iter().any(|c| c.owner() == owner && c.producing() == Some(settler));
+    if threat == 0 && !settling && city.population() >= 2 && choices.contains(&settler) &&
+       best_city_site(city.pos(), owner, map).is_some() {
+        return settler;
+    }
+    let buildings = map.cities().buildings();
+    let value = |p: &Production| {
+        match *p {
+            Production::Building(b) => building_value(buildings.get(b), threat > 0),
+            Production::Unit(_) => 0,
+        }
+    };
+    choices.iter()
+           .filter(|p| {
+               match **p {
+                   Production::Building(_) => true,
+                   Production::Unit(_) => false,
+               }
+           })
+           .max_by_key(|p| (value(p), -(map.cities().production_cost(**p) as i32)))
+           .cloned()
+           .unwrap_or(unit)
+}
+
+/// Returns whether `player` accepts to make peace with `with`.
+///
+/// We make peace when our army isn't stronger than theirs. The more the war wears us out, the
+/// stronger we can be and still want peace: each happiness it costs us is worth 20% of their army.
+pub fn accepts_peace(player: Player, with: Player, map: &LiveMap) -> bool {
+    let weariness = map.diplomacy().war(player, with).map_or(0, |w| w.weariness(player)) as u32;
+    army_strength(player, map) * 100 <= army_strength(with, map) * (100 + 20 * weariness)
+}
+
+/// Makes `player` offer peace to the players it has been at war with for a while, if it wants
+/// peace, and withdraws the offers it doesn't stand by anymore.
+///
+/// Other AI players accept right away if they want peace too.
+fn offer_peace(player: Player, map: &mut LiveMap) {
+    let offers: Vec<Player> = map.diplomacy()
+                                 .peace_offers()
+                                 .iter()
+                                 .filter(|&&(from, _)| from == player)
+                                 .map(|&(_, to)| to)
+                                 .collect();
+    for to in offers {
+        if !accepts_peace(player, to, map) {
+            map.diplomacy_mut().withdraw_peace_offer(player, to);
+        }
+    }
+    let wars: Vec<(Player, u16)> = map.diplomacy()
+                                      .wars()
+                                      .iter()
+                                      .filter_map(|w| {
+                                          match w.players {
+                                              (a, b) if a == player => Some((b, w.turns)),
+                                              (a, b) if b == player => Some((a, w.turns)),
+                                              _ => None,
+                                          }
+                                      })
+                                      .collect();
+    for (with, turns) in wars {
+        if turns == 0 || turns % PEACE_OFFER_TURNS != 0 || !accepts_peace(player, with, map) {
+            continue;
+        }
+        match with {
+            Player::Me => map.offer_peace(player, with),
+            Player::AI(_) if accepts_peace(with, player, map) => {
+                map.diplomacy_mut().make_peace(player, with)
+            }
+            Player::AI(_) => (),
+        }
+    }
+}
+
+/// Returns whether `player` accepts to open its borders to `with`, which it must be at peace with.
+pub fn accepts_open_borders(player: Player, with: Player, map: &LiveMap) -> bool {
+    !map.diplomacy().at_war(player, with)
+}
+
+/// Returns what `player` should do with `city_id`, which it has just captured.
+///
+/// We keep the city if we can afford the unhappiness. Otherwise, we make a puppet of it if it's
+/// worth it and raze it if it isn't.
+fn choose_city_status(player: Player, city_id: CityID, map: &LiveMap) -> CityStatus {
+    let cities = map.cities();
+    if cities.happiness(player) >= 0 {
+        CityStatus::Annexed
+    } else if cities.get(city_id).population() > 1 {
+        CityStatus::Puppet
+    } else {
+        CityStatus::Razing
+    }
+}
+
+/// Returns the belief `player` should adopt to found its pantheon, if there's any left.
+///
+/// We pick the belief adding the most to the tiles of our cities. When none of them helps, we
+/// pick the one that comes last, which is `Belief::GodOfWar` unless somebody adopted it.
+fn choose_belief(player: Player, map: &LiveMap) -> Option<Belief> {
+    let cities = map.cities();
+    let tiles: Vec<Pos> = cities.all_cities()
+                                .into_iter()
+                                .filter(|c| c.owner() == player)
+                                .flat_map(|c| cities.city_tiles(c.id(), map.terrain()))
+                                .collect();
+    cities.available_beliefs().into_iter().max_by_key(|b| {
+        tiles.iter()
+             .map(|&p| b.tile_bonus(map.terrain(), p))
+             .fold(0, |acc, y| acc + y.food as u32 + y.production as u32 + y.gold as u32)
+    })
+}
+
+/// Returns the promotion `unit` picks, if it can be promoted.
+///
+/// Ranged units stay behind the lines where enemy archers shoot at them, so they want cover.
+/// Other units pick the bonus for the terrain they stand on. Healing promotions come last.
+fn choose_promotion(unit: &Unit, map: &LiveMap) -> Option<Promotion> {
+    let terrain_promotion = if map.terrain().is_rough(unit.pos()) {
+        Promotion::Drill
+    } else {
+        Promotion::Shock
+    };
+    let preferred = if unit.type_().is_ranged() {
+        Promotion::Cover
+    } else {
+        terrain_promotion
+    };
+    let available = unit.available_promotions();
+    if available.contains(&preferred) {
+        Some(preferred)
+    } else {
+        available.first().cloned()
+    }
+}
+
+/// Plays the turn of every unit belonging to `player` and picks what its idle cities build.
+///
+/// Long wars first make us offer peace and a pantheon is founded as soon as we can. Cities strike
+/// first, the enemy with the least HP in their range. Units then pick their promotions and decide
+/// what to do all at once, in parallel. Then they play, ranged units first so that they soften up
+/// enemies before melee units commit, and otherwise in creation order. A unit whose decision
+/// might not hold anymore, because of what the units before it did, decides again when its turn
+/// comes. Cities captured during the turn are then annexed, puppeted or razed.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::terrain::TerrainMap;
+/// use civng_core::map::LiveMap;
+/// use civng_core::unit::{Unit, UnitType, Player};
+/// use civng_core::hexpos::{OffsetPos, Direction};
+/// use civng_core::ai::play_turn;
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(10, 10));
+/// let pos = OffsetPos::new(5, 5).to_pos();
+/// map.add_unit(Unit::with_state(UnitType::Melee, Player::Me, pos, 2, 10));
+/// let (north, south) = (pos.neighbor(Direction::North), pos.neighbor(Direction::South));
+/// map.add_unit(Unit::with_state(UnitType::Melee, Player::AI(0), north, 2, 100));
+/// map.add_unit(Unit::with_state(UnitType::Ranged, Player::AI(0), south, 2, 100));
+/// play_turn(Player::AI(0), &mut map);
+/// // The archer got to shoot before the warrior finished our unit off.
+/// assert!(map.units().get(3).xp() > 0);
+/// ```
+pub fn play_turn(player: Player, map: &mut LiveMap) {
+    offer_peace(player, map);
+    if map.cities().can_found_pantheon(player) {
+        if let Some(belief) = choose_belief(player, map) {
+            map.cities_mut().found_pantheon(player, belief);
+        }
+    }
+    let cities: Vec<CityID> = map.cities()
+                                 .all_cities()
+                                 .into_iter()
+                                 .filter(|c| c.owner() == player)
+                                 .map(|c| c.id())
+                                 .collect();
+    for &city_id in cities.iter() {
+        let city_status = map.cities().get(city_id).status();
+        if city_status.is_managed() && map.cities().get(city_id).producing().is_none() {
+            let production = choose_production(city_id, map);
+            map.set_production(city_id, Some(production));
+        }
+        if let Some(&target_id) = map.city_strike_targets(city_id).first() {
+            let mut stats = map.get_city_strike_stats(city_id, target_id);
+            map.city_strike(&mut stats);
+        }
+    }
+    let mut units: Vec<(bool, UnitID)> = map.units()
+                                            .all_units()
+                                            .filter(|u| u.owner() == player && !u.is_dead())
+                                            .map(|u| (!u.type_().is_ranged(), u.id()))
+                                            .collect();
+    units.sort();
+    let units: Vec<UnitID> = units.into_iter().map(|(_, unit_id)| unit_id).collect();
+    for &unit_id in units.iter() {
+        if let Some(promotion) = choose_promotion(map.units().get(unit_id), map) {
+            map.promote(unit_id, promotion);
+        }
+    }
+    let decisions: Vec<Decision> = {
+        let map = &*map;
+        units.par_iter().map(|&unit_id| decide(unit_id, map)).collect()
+    };
+    let mut changes = Changes::new(map);
+    for (unit_id, decision) in units.into_iter().zip(decisions) {
+        // The unit might have died during the turn of a previous unit.
+        if !map.units().find(unit_id).map_or(false, |u| !u.is_dead()) {
+            continue;
+        }
+        let decision = if changes.affect(unit_id, decision, map) {
+            decide(unit_id, map)
+        } else {
+            decision
+        };
+        carry_out(unit_id, decision, map);
+        changes.record(map);
+    }
+    let captured: Vec<CityID> = map.cities()
+                                   .all_cities()
+                                   .into_iter()
+                                   .filter(|c| c.owner() == player)
+                                   .filter(|c| c.status() == CityStatus::Captured)
+                                   .map(|c| c.id())
+                                   .collect();
+    for city_id in captured {
+        let status = choose_city_status(player, city_id, map);
+        map.set_city_status(city_id, status);
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Fixed-size sets of tile indices, with one bit per tile.
+//!
+//! Indices are those of `TerrainMap` tiles, as given by `HexGrid`, so testing whether a tile is in
+//! the set is a single bit test.
+
+const WORD_BITS: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitSet {
+    /// Creates an empty set of indices going from `0` to `len - 1`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::bitset::BitSet;
+    ///
+    /// let mut set = BitSet::new(100);
+    /// set.insert(3);
+    /// set.insert(70);
+    /// assert!(set.contains(70));
+    /// assert!(!set.contains(4));
+    /// // Indices out of the set are never in it.
+    /// assert!(!set.contains(1000));
+    /// assert_eq!(set.count(), 2);
+    /// set.remove(3);
+    /// assert_eq!(set.iter().collect::<Vec<usize>>(), vec![70]);
+    /// set.clear();
+    /// assert_eq!(set.count(), 0);
+    /// ```
+    pub fn new(len: usize) -> BitSet {
+        BitSet {
+            words: vec![0; (len + WORD_BITS - 1) / WORD_BITS],
+            len: len,
+        }
+    }
+
+    /// Number of indices the set can hold.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        index < self.len && self.words[index / WORD_BITS] & (1 << (index % WORD_BITS)) != 0
+    }
+
+    /// Adds `index` to the set. Panics if it's out of bounds.
+    pub fn insert(&mut self, index: usize) {
+        assert!(index < self.len);
+        self.words[index / WORD_BITS] |= 1 << (index % WORD_BITS);
+    }
+
+    /// Removes `index` from the set. Does nothing if it's out of bounds.
+    pub fn remove(&mut self, index: usize) {
+        if index < self.len {
+            self.words[index / WORD_BITS] &= !(1 << (index % WORD_BITS));
+        }
+    }
+
+    /// Adds `index` to the set if `value` is true, removes it otherwise.
+    pub fn set(&mut self, index: usize, value: bool) {
+        if value {
+            self.insert(index);
+        } else {
+            self.remove(index);
+        }
+    }
+
+    /// Removes all indices from the set.
+    pub fn clear(&mut self) {
+        for word in self.words.iter_mut() {
+            *word = 0;
+        }
+    }
+
+    /// Returns the number of indices in the set.
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Returns the indices in the set, in increasing order.
+    pub fn iter(&self) -> BitSetIter {
+        BitSetIter {
+            set: self,
+            index: 0,
+        }
+    }
+}
+
+pub struct BitSetIter<'a> {
+    set: &'a BitSet,
+    index: usize,
+}
+
+impl<'a> Iterator for BitSetIter<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.index < self.set.len {
+            let index = self.index;
+            // Empty words are skipped all at once.
+            if index % WORD_BITS == 0 && self.set.words[index / WORD_BITS] == 0 {
+                self.index += WORD_BITS;
+                continue;
+            }
+            self.index += 1;
+            if self.set.contains(index) {
+                return Some(index);
+            }
+        }
+        None
+    }
+}
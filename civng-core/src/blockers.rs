@@ -0,0 +1,87 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Choices a player is left with before ending its turn.
+//!
+//! AI players make them as they play. We're taken through ours when we try to end our turn, which
+//! we can still force.
+
+use map::LiveMap;
+use unit::{Unit, UnitID, Player};
+use city::CityID;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TurnBlocker {
+    /// A unit waits for orders.
+    IdleUnit(UnitID),
+    /// A city we manage builds nothing, while it could build something.
+    NoProduction(CityID),
+    /// A unit can pick a promotion.
+    Promotion(UnitID),
+}
+
+impl TurnBlocker {
+    /// Describes the blocker, naming the unit or city of `map` it's about.
+    pub fn description(&self, map: &LiveMap) -> String {
+        match *self {
+            TurnBlocker::IdleUnit(unit_id) => {
+                format!("{} waits for orders.", map.units().get(unit_id).name())
+            }
+            TurnBlocker::NoProduction(city_id) => {
+                format!("{} builds nothing.", map.cities().get(city_id).name())
+            }
+            TurnBlocker::Promotion(unit_id) => {
+                format!("{} can be promoted.", map.units().get(unit_id).name())
+            }
+        }
+    }
+}
+
+/// Returns the choices `player` is left with this turn: units waiting for orders first, then
+/// cities building nothing and finally units to promote, each in the order they were created.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::blockers::{turn_blockers, TurnBlocker};
+/// use civng_core::map::LiveMap;
+/// use civng_core::terrain::TerrainMap;
+/// use civng_core::unit::{Unit, UnitType, Player, Order};
+/// use civng_core::city::City;
+/// use civng_core::hexpos::{Pos, Direction};
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(4, 4));
+/// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+/// map.add_city(City::new("Paris", Player::Me, Pos::vector(Direction::South)));
+/// map.refresh();
+/// map.add_xp(1, 10);
+/// let blockers = turn_blockers(&map, Player::Me);
+/// assert_eq!(blockers,
+///            vec![TurnBlocker::IdleUnit(1),
+///                 TurnBlocker::NoProduction(1),
+///                 TurnBlocker::Promotion(1)]);
+/// assert_eq!(blockers[0].description(&map), "Melee waits for orders.");
+/// map.set_order(1, Some(Order::Fortify));
+/// assert_eq!(turn_blockers(&map, Player::Me).len(), 2);
+/// assert!(turn_blockers(&map, Player::AI(0)).is_empty());
+/// ```
+pub fn turn_blockers(map: &LiveMap, player: Player) -> Vec<TurnBlocker> {
+    let mut units: Vec<&Unit> = map.units().all_units().filter(|u| u.owner() == player).collect();
+    units.sort_by_key(|u| u.id());
+    let mut result: Vec<TurnBlocker> = units.iter()
+                                            .filter(|u| u.needs_orders())
+                                            .map(|u| TurnBlocker::IdleUnit(u.id()))
+                                            .collect();
+    for city in map.cities().all_cities() {
+        if city.owner() == player && city.status().is_managed() && city.producing().is_none() &&
+           !map.production_choices(city.id()).is_empty() {
+            result.push(TurnBlocker::NoProduction(city.id()));
+        }
+    }
+    result.extend(units.iter().filter(|u| u.can_promote()).map(|u| TurnBlocker::Promotion(u.id())));
+    result
+}
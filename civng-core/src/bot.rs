@@ -0,0 +1,179 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Line-based protocol letting external programs play a player, for bots written in any language.
+//!
+//! At the start of each of its turns, the bot receives the game state as known by its player, on a
+//! single line, in the JSON format described in `export`. It then sends commands, one per line:
+//!
+//! ```text
+//! move ID X Y      Moves unit ID to the tile at offset position X Y, attacking what's there
+//! bombard ID X Y   Makes ranged unit ID bombard the tile at offset position X Y
+//! skip ID          Makes unit ID do nothing for the rest of the turn
+//! end              Ends the turn
+//! ```
+//!
+//! Every command but `end` is answered with a line, either `ok` or `error` followed by the reason
+//! the command was rejected. The turn also ends when the bot closes its end of the connection.
+//!
+//! `Bot` works with any reader and writer, so bots can be connected through pipes or sockets.
+
+use std::io::{self, BufRead, Write};
+
+use hexpos::{Pos, OffsetPos};
+use map::LiveMap;
+use terrain::Terrain;
+use unit::{UnitID, Player};
+use export::game_state_json;
+
+enum Command {
+    Move(UnitID, Pos),
+    Bombard(UnitID, Pos),
+    Skip(UnitID),
+    End,
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Command, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |i: usize| -> Result<i32, String> {
+            match words.get(i) {
+                Some(s) => s.parse().map_err(|_| format!("invalid number: {}", s)),
+                None => Err("missing argument".to_owned()),
+            }
+        };
+        let unit_id = || number(1).map(|id| id as UnitID);
+        let pos = || -> Result<Pos, String> {
+            Ok(OffsetPos::new(try!(number(2)), try!(number(3))).to_pos())
+        };
+        match words.first() {
+            Some(&"move") => Ok(Command::Move(try!(unit_id()), try!(pos()))),
+            Some(&"bombard") => Ok(Command::Bombard(try!(unit_id()), try!(pos()))),
+            Some(&"skip") => Ok(Command::Skip(try!(unit_id()))),
+            Some(&"end") => Ok(Command::End),
+            Some(s) => Err(format!("unknown command: {}", s)),
+            None => Err("empty command".to_owned()),
+        }
+    }
+}
+
+/// Checks that `unit_id` is a live unit of `player`.
+fn check_unit(map: &LiveMap, player: Player, unit_id: UnitID) -> Result<(), String> {
+    match map.units().find(unit_id) {
+        Some(u) if u.owner() == player && !u.is_dead() => Ok(()),
+        _ => Err(format!("no unit {} of ours", unit_id)),
+    }
+}
+
+fn check_pos(map: &LiveMap, pos: Pos) -> Result<(), String> {
+    if map.terrain().get_terrain(pos) == Terrain::OutOfBounds {
+        Err("position out of the map".to_owned())
+    } else {
+        Ok(())
+    }
+}
+
+/// Carries out `command` for `player`.
+fn execute(command: Command, map: &mut LiveMap, player: Player) -> Result<(), String> {
+    match command {
+        Command::Move(unit_id, pos) => {
+            try!(check_unit(map, player, unit_id));
+            try!(check_pos(map, pos));
+            let from = map.units().get(unit_id).pos();
+            match map.moveunit_to(unit_id, pos) {
+                Some(mut combat_stats) => map.attack(&mut combat_stats),
+                None if map.units().get(unit_id).pos() == from => {
+                    return Err("can't reach that position".to_owned());
+                }
+                None => (),
+            }
+        }
+        Command::Bombard(unit_id, pos) => {
+            try!(check_unit(map, player, unit_id));
+            try!(check_pos(map, pos));
+            match map.bombard_at(unit_id, pos) {
+                Some(mut combat_stats) => map.attack(&mut combat_stats),
+                None => return Err("can't bombard that position".to_owned()),
+            }
+        }
+        Command::Skip(unit_id) => {
+            try!(check_unit(map, player, unit_id));
+            map.skip_turn(unit_id);
+        }
+        Command::End => (),
+    }
+    Ok(())
+}
+
+/// An external program playing a player through the bot protocol.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use civng_core::terrain::TerrainMap;
+/// use civng_core::map::LiveMap;
+/// use civng_core::unit::{Unit, UnitType, Player};
+/// use civng_core::hexpos::{Pos, OffsetPos};
+/// use civng_core::bot::Bot;
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(5, 5));
+/// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+/// map.refresh();
+/// let commands = "move 1 1 0\nmove 2 1 1\nfly 1\nend\n";
+/// let mut bot = Bot::new(Cursor::new(commands), Vec::new());
+/// bot.play_turn(&mut map, Player::Me, 1).unwrap();
+/// assert_eq!(map.units().get(1).pos(), OffsetPos::new(1, 0).to_pos());
+/// let output = String::from_utf8(bot.into_output()).unwrap();
+/// let lines: Vec<&str> = output.lines().collect();
+/// assert!(lines[0].starts_with("{\"turn\":1"));
+/// assert_eq!(&lines[1..], ["ok", "error no unit 2 of ours", "error unknown command: fly"]);
+/// ```
+pub struct Bot<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Bot<R, W> {
+    /// Creates a bot whose commands are read from `input` and to which we write on `output`.
+    pub fn new(input: R, output: W) -> Bot<R, W> {
+        Bot {
+            input: input,
+            output: output,
+        }
+    }
+
+    /// Returns the writer we send game states and replies to.
+    pub fn into_output(self) -> W {
+        self.output
+    }
+
+    /// Sends the game state at turn `turn` to the bot and carries out its commands for `player`
+    /// until it ends its turn.
+    pub fn play_turn(&mut self, map: &mut LiveMap, player: Player, turn: u16) -> io::Result<()> {
+        try!(writeln!(self.output, "{}", game_state_json(map, turn, player)));
+        try!(self.output.flush());
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if try!(self.input.read_line(&mut line)) == 0 {
+                return Ok(());
+            }
+            let result = match Command::parse(&line) {
+                Ok(Command::End) => return Ok(()),
+                Ok(command) => execute(command, map, player),
+                Err(msg) => Err(msg),
+            };
+            let reply = match result {
+                Ok(()) => "ok".to_owned(),
+                Err(msg) => format!("error {}", msg),
+            };
+            try!(writeln!(self.output, "{}", reply));
+            try!(self.output.flush());
+        }
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Buildings and wonders cities construct.
+//!
+//! They're defined in a content file, `resources/buildings.txt` for the built-in ones. Like
+//! scenario files, content files are a series of `kind = value` lines, with empty lines and lines
+//! starting with `#` being ignored. `kind` is `building` or `wonder`, a wonder being a building
+//! that only one city in the world can have. The value is made of comma-separated fields: the name
+//! of the building, its production cost and its effects, each being a stat and an amount.
+//!
+//! ```text
+//! building = Granary, 60, food 2
+//! building = Walls, 75, defense 50
+//! wonder = Great Library, 185, science 3, gold 1
+//! ```
+//!
+//! `food`, `production`, `gold` and `science` are added to the yield of the city. `defense` is a
+//! bonus, in percents, for units defending the city and `xp` is the experience units trained in
+//! the city start with.
+
+use std::io;
+
+use terrain::Yield;
+
+/// Index of a building in its `Buildings`.
+pub type BuildingID = usize;
+
+/// Built-in content file.
+const BUILTIN_BUILDINGS: &'static str = include_str!("../../resources/buildings.txt");
+
+#[derive(Clone)]
+pub struct Building {
+    pub name: String,
+    /// Production it takes to build it.
+    pub cost: u16,
+    /// Whether only one city in the world can have it.
+    pub wonder: bool,
+    /// Added to the yield of the city.
+    pub yields: Yield,
+    /// Added to the science of the city.
+    pub science: u8,
+    /// Defense bonus, in percents, of units defending the city.
+    pub defense: u8,
+    /// Experience of the units trained in the city.
+    pub xp: u16,
+}
+
+impl Building {
+    /// Describes what the building does, for example `+2 food, +50% defense`.
+    pub fn effects(&self) -> String {
+        let effects = [(self.yields.food as u16, " food"),
+                       (self.yields.production as u16, " production"),
+                       (self.yields.gold as u16, " gold"),
+                       (self.science as u16, " science"),
+                       (self.defense as u16, "% defense"),
+                       (self.xp, " xp")];
+        let result: Vec<String> = effects.iter()
+                                         .filter(|&&(amount, _)| amount > 0)
+                                         .map(|&(amount, suffix)| format!("+{}{}", amount, suffix))
+                                         .collect();
+        result.join(", ")
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Buildings of a content file, in the order they're defined.
+#[derive(Clone)]
+pub struct Buildings {
+    buildings: Vec<Building>,
+}
+
+impl Buildings {
+    /// Parses content file `contents`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::building::Buildings;
+    ///
+    /// let contents = "building = Granary, 60, food 2\nwonder = Great Wall, 185, defense 25\n";
+    /// let buildings = Buildings::parse(contents).unwrap();
+    /// assert_eq!(buildings.find("Great Wall"), Some(1));
+    /// assert!(buildings.get(1).wonder);
+    /// assert_eq!(buildings.get(1).effects(), "+25% defense");
+    /// assert!(Buildings::parse("building = Granary, 60, charisma 2\n").is_err());
+    /// assert!(Buildings::parse("building = Granary\n").is_err());
+    /// ```
+    pub fn parse(contents: &str) -> io::Result<Buildings> {
+        let mut buildings = Vec::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let kind = parts.next().unwrap().trim();
+            let value = match parts.next() {
+                Some(v) => v.trim(),
+                None => return Err(invalid_data(&format!("Invalid building line: {}", line))),
+            };
+            let invalid_value = || invalid_data(&format!("Invalid building: {}", value));
+            let wonder = match kind {
+                "building" => false,
+                "wonder" => true,
+                _ => return Err(invalid_data(&format!("Unknown building kind: {}", kind))),
+            };
+            let fields: Vec<&str> = value.split(',').map(|f| f.trim()).collect();
+            if fields.len() < 2 || fields[0].is_empty() {
+                return Err(invalid_value());
+            }
+            let mut building = Building {
+                name: fields[0].to_owned(),
+                cost: try!(fields[1].parse().map_err(|_| invalid_value())),
+                wonder: wonder,
+                yields: Yield::default(),
+                science: 0,
+                defense: 0,
+                xp: 0,
+            };
+            for effect in fields[2..].iter() {
+                let effect: Vec<&str> = effect.split_whitespace().collect();
+                if effect.len() != 2 {
+                    return Err(invalid_value());
+                }
+                let amount: u8 = try!(effect[1].parse().map_err(|_| invalid_value()));
+                match effect[0] {
+                    "food" => building.yields.food = amount,
+                    "production" => building.yields.production = amount,
+                    "gold" => building.yields.gold = amount,
+                    "science" => building.science = amount,
+                    "defense" => building.defense = amount,
+                    "xp" => building.xp = amount as u16,
+                    _ => return Err(invalid_value()),
+                }
+            }
+            buildings.push(building);
+        }
+        Ok(Buildings { buildings: buildings })
+    }
+
+    /// Returns the buildings of the built-in content file.
+    pub fn builtin() -> Buildings {
+        Buildings::parse(BUILTIN_BUILDINGS).unwrap()
+    }
+
+    pub fn all(&self) -> &[Building] {
+        &self.buildings[..]
+    }
+
+    pub fn get(&self, building_id: BuildingID) -> &Building {
+        &self.buildings[building_id]
+    }
+
+    /// Returns the building named `name`, if any.
+    pub fn find(&self, name: &str) -> Option<BuildingID> {
+        self.buildings.iter().position(|b| b.name == name)
+    }
+}
@@ -0,0 +1,1121 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Cities and their economy.
+//!
+//! Settlers found cities at least `MIN_CITY_DISTANCE` away from each other, on passable land.
+//! A city owns the tiles within `CITY_RADIUS` of its center that are closer to it than to any
+//! other city. Its center is always worked and each citizen works one more of its tiles. Unless
+//! the player locked tiles for citizens to work, they pick the tiles yielding the most, food
+//! first.
+//!
+//! Every turn, citizens eat `FOOD_PER_CITIZEN` food each. Food left over is stocked until the city
+//! grows by one citizen, after which its stock starts over. A city running out of food starves and
+//! loses a citizen. Each citizen produces one science for the owner of the city.
+//!
+//! Production accumulates for what the city builds, a unit or a building (see `building`), and
+//! what's left over once it's built goes to the next thing. Without anything to build, production
+//! keeps accumulating. Buildings add to the yield and science of their city and wonders can only
+//! be built once in the whole world.
+//!
+//! Each city also yields gold to its owner's treasury and faith, which founds pantheons (see
+//! `religion`). Conquests make a player unhappy (see
+//! `CityStatus`), and so do long wars (see `diplomacy`). While a player's happiness is below zero,
+//! its cities don't grow.
+//!
+//! A player can have as many units as its supply cap allows, `BASE_SUPPLY` plus one per city and
+//! one per two citizens. Above that, the production of its cities suffers (see
+//! `supply_penalty()`).
+//!
+//! Cities defend themselves: once per turn, a city can strike an enemy unit within
+//! `CITY_STRIKE_RANGE` of its center, harder when it's big or garrisoned (see
+//! `LiveMap::city_strength()`).
+
+use std::cmp::min;
+use std::collections::HashMap;
+
+use hexpos::Pos;
+use terrain::{Terrain, TerrainMap, Yield};
+use unit::{Player, UnitType};
+use building::{Buildings, BuildingID};
+use events::GameEvent;
+use religion::{Belief, PANTHEON_FAITH};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::ser::SerializeStruct;
+
+pub type CityID = usize;
+
+/// Distance up to which a city can work tiles.
+pub const CITY_RADIUS: i32 = 2;
+/// Distance under which no city can be founded next to another one.
+pub const MIN_CITY_DISTANCE: i32 = 3;
+/// Food each citizen eats every turn.
+pub const FOOD_PER_CITIZEN: u16 = 2;
+/// Ranged strength of a city without citizens or garrison.
+pub const CITY_BASE_STRENGTH: u8 = 5;
+/// Distance up to which a city can strike enemies.
+pub const CITY_STRIKE_RANGE: i32 = 2;
+/// Names of the cities founded by settlers, in the order they're given out.
+const CITY_NAMES: [&'static str; 12] = ["Alexandria", "Carthage", "Kyoto", "Delhi", "Babylon",
+                                        "Athens", "Sparta", "Thebes", "Memphis", "Nineveh",
+                                        "Persepolis", "Antioch"];
+/// Happiness of a player before the unhappiness of its conquests.
+pub const BASE_HAPPINESS: i16 = 4;
+/// Gold plundered for each citizen of a captured city.
+pub const PLUNDER_PER_CITIZEN: u32 = 10;
+/// Units a player can have for free, before its cities and citizens add to its supply cap.
+pub const BASE_SUPPLY: u16 = 4;
+/// Production penalty, in percents, for each unit above the supply cap.
+const SUPPLY_PENALTY_PER_UNIT: u16 = 10;
+/// Highest production penalty, in percents, of players above their supply cap.
+const MAX_SUPPLY_PENALTY: u16 = 50;
+
+/// Returns how much food a city with `population` citizens has to stock to grow, like in Civ 5.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::city::growth_threshold;
+///
+/// assert_eq!(growth_threshold(1), 15);
+/// assert_eq!(growth_threshold(2), 22);
+/// assert_eq!(growth_threshold(5), 51);
+/// ```
+pub fn growth_threshold(population: u8) -> u16 {
+    let grown = population.saturating_sub(1) as f32;
+    (15.0 + 6.0 * grown + grown.powf(1.8)).floor() as u16
+}
+
+/// Returns how much citizens value working a tile yielding `tile_yield`.
+fn tile_score(tile_yield: Yield) -> u16 {
+    tile_yield.food as u16 * 4 + tile_yield.production as u16 * 2 + tile_yield.gold as u16
+}
+
+/// What a city builds.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Production {
+    Unit(UnitType),
+    Building(BuildingID),
+}
+
+/// How a city came to belong to its owner and what its owner does with it.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum CityStatus {
+    /// Founded by its owner.
+    Founded,
+    /// Just captured: its owner has yet to decide what to do with it. Until then, it's annexed.
+    Captured,
+    /// Captured and run by its owner like any other city.
+    Annexed,
+    /// Captured and left to run itself: it builds nothing and turns its production into gold.
+    Puppet,
+    /// Captured and being burnt down: it loses a citizen every turn until it's gone.
+    Razing,
+}
+
+impl CityStatus {
+    pub fn all() -> &'static [CityStatus] {
+        const ALL: [CityStatus; 5] = [CityStatus::Founded,
+                                      CityStatus::Captured,
+                                      CityStatus::Annexed,
+                                      CityStatus::Puppet,
+                                      CityStatus::Razing];
+        &ALL
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            CityStatus::Founded => "founded",
+            CityStatus::Captured => "captured",
+            CityStatus::Annexed => "annexed",
+            CityStatus::Puppet => "puppet",
+            CityStatus::Razing => "razing",
+        }
+    }
+
+    /// Happiness the owner of a city with this status loses.
+    pub fn unhappiness(&self) -> i16 {
+        match *self {
+            CityStatus::Founded => 0,
+            CityStatus::Captured | CityStatus::Annexed => 3,
+            CityStatus::Puppet => 1,
+            CityStatus::Razing => 2,
+        }
+    }
+
+    /// Whether the owner of a city with this status chooses what it builds.
+    pub fn is_managed(&self) -> bool {
+        match *self {
+            CityStatus::Puppet | CityStatus::Razing => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct City {
+    id: CityID,
+    name: String,
+    owner: Player,
+    pos: Pos,
+    population: u8,
+    /// Food stocked towards the next citizen
+    food: u16,
+    /// Production accumulated towards what the city builds
+    production: u16,
+    /// Tiles the player wants worked, whatever they yield, oldest first.
+    locked_tiles: Vec<Pos>,
+    /// Tiles worked by citizens, the center excluded.
+    worked_tiles: Vec<Pos>,
+    /// What the city builds, if anything
+    producing: Option<Production>,
+    /// Buildings of the city, in the order they were built
+    buildings: Vec<BuildingID>,
+    /// Whether the city has struck an enemy this turn
+    has_struck: bool,
+    status: CityStatus,
+}
+
+impl City {
+    pub fn new(name: &str, owner: Player, pos: Pos) -> City {
+        City::with_state(name, owner, pos, 1, 0, 0)
+    }
+
+    /// Creates a city with specific population and stocks, for example when loading a saved game.
+    pub fn with_state(name: &str,
+                      owner: Player,
+                      pos: Pos,
+                      population: u8,
+                      food: u16,
+                      production: u16)
+                      -> City {
+        City {
+            id: 0, // set in Cities::add_city()
+            name: name.to_owned(),
+            owner: owner,
+            pos: pos,
+            population: population,
+            food: food,
+            production: production,
+            locked_tiles: Vec::new(),
+            worked_tiles: Vec::new(),
+            producing: None,
+            buildings: Vec::new(),
+            has_struck: false,
+            status: CityStatus::Founded,
+        }
+    }
+
+    pub fn id(&self) -> CityID {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn owner(&self) -> Player {
+        self.owner
+    }
+
+    pub fn pos(&self) -> Pos {
+        self.pos
+    }
+
+    pub fn population(&self) -> u8 {
+        self.population
+    }
+
+    pub fn food(&self) -> u16 {
+        self.food
+    }
+
+    pub fn production(&self) -> u16 {
+        self.production
+    }
+
+    /// Adds `amount` to the production the city accumulated.
+    pub fn add_production(&mut self, amount: u16) {
+        self.production += amount;
+    }
+
+    pub fn locked_tiles(&self) -> &[Pos] {
+        &self.locked_tiles[..]
+    }
+
+    /// Tiles worked by citizens, as of the last time they were assigned. The center isn't included.
+    pub fn worked_tiles(&self) -> &[Pos] {
+        &self.worked_tiles[..]
+    }
+
+    pub fn producing(&self) -> Option<Production> {
+        self.producing
+    }
+
+    pub fn buildings(&self) -> &[BuildingID] {
+        &self.buildings[..]
+    }
+
+    pub fn status(&self) -> CityStatus {
+        self.status
+    }
+
+    pub fn has_struck(&self) -> bool {
+        self.has_struck
+    }
+
+    /// Marks the city as having used its strike for this turn.
+    pub fn set_struck(&mut self) {
+        self.has_struck = true;
+    }
+
+    pub fn has_building(&self, building_id: BuildingID) -> bool {
+        self.buildings.contains(&building_id)
+    }
+
+    /// Adds `building_id` to the city, for example when loading a saved game.
+    pub fn add_building(&mut self, building_id: BuildingID) {
+        if !self.has_building(building_id) {
+            self.buildings.push(building_id);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Cities {
+    maxid: CityID,
+    cities: HashMap<CityID, City>,
+    /// Science accumulated by each player
+    science: HashMap<Player, u32>,
+    /// Gold in the treasury of each player
+    gold: HashMap<Player, u32>,
+    /// Faith accumulated by each player, minus what its pantheon cost
+    faith: HashMap<Player, u32>,
+    /// Belief of the pantheon of each player having founded one
+    pantheons: HashMap<Player, Belief>,
+    /// Happiness each player loses to its wars (see `Diplomacy::war_weariness()`)
+    war_weariness: HashMap<Player, u16>,
+    /// Production penalty, in percents, of each player above its supply cap
+    supply_penalties: HashMap<Player, u16>,
+    /// Production modifier, in percents, each player gets from the difficulty level
+    production_bonuses: HashMap<Player, i8>,
+    /// Buildings cities can build
+    buildings: Buildings,
+}
+
+impl Cities {
+    pub fn new() -> Cities {
+        Cities {
+            maxid: 0,
+            cities: HashMap::new(),
+            science: HashMap::new(),
+            gold: HashMap::new(),
+            faith: HashMap::new(),
+            pantheons: HashMap::new(),
+            war_weariness: HashMap::new(),
+            supply_penalties: HashMap::new(),
+            production_bonuses: HashMap::new(),
+            buildings: Buildings::builtin(),
+        }
+    }
+
+    /// Buildings cities can build.
+    pub fn buildings(&self) -> &Buildings {
+        &self.buildings
+    }
+
+    /// All cities, in the order they were founded.
+    pub fn all_cities(&self) -> Vec<&City> {
+        let mut result: Vec<&City> = self.cities.values().collect();
+        result.sort_by_key(|c| c.id());
+        result
+    }
+
+    pub fn add_city(&mut self, mut city: City) -> CityID {
+        self.maxid += 1;
+        city.id = self.maxid;
+        self.cities.insert(city.id, city);
+        self.maxid
+    }
+
+    pub fn get(&self, city_id: CityID) -> &City {
+        self.cities.get(&city_id).unwrap()
+    }
+
+    pub fn get_mut(&mut self, city_id: CityID) -> &mut City {
+        self.cities.get_mut(&city_id).unwrap()
+    }
+
+    pub fn get_at_pos(&self, pos: Pos) -> Option<&City> {
+        self.cities.values().find(|c| c.pos() == pos)
+    }
+
+    /// Whether a city can be founded at `pos`: on passable land, at least `MIN_CITY_DISTANCE`
+    /// away from other cities.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::city::{City, Cities};
+    /// use civng_core::terrain::{TerrainMap, Terrain};
+    /// use civng_core::unit::Player;
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut terrain = TerrainMap::empty_map(10, 10);
+    /// let east = Pos::vector(Direction::SouthEast).amplify(3);
+    /// terrain.set_terrain(east, Terrain::Ocean);
+    /// let mut cities = Cities::new();
+    /// cities.add_city(City::new("Rome", Player::Me, Pos::origin()));
+    /// let south = Pos::vector(Direction::South);
+    /// assert!(!cities.can_found_city(south.amplify(2), &terrain));
+    /// assert!(cities.can_found_city(south.amplify(3), &terrain));
+    /// assert!(!cities.can_found_city(east, &terrain));
+    /// ```
+    pub fn can_found_city(&self, pos: Pos, terrain: &TerrainMap) -> bool {
+        terrain.is_passable(pos) && !terrain.get_terrain(pos).is_water() &&
+        self.cities.values().all(|c| c.pos().distance(pos) >= MIN_CITY_DISTANCE)
+    }
+
+    /// Returns the name of the next city founded by a settler, the first of `CITY_NAMES` no city
+    /// has.
+    pub fn new_city_name(&self) -> String {
+        let taken = |name: &str| self.cities.values().any(|c| c.name() == name);
+        match CITY_NAMES.iter().find(|&&name| !taken(name)) {
+            Some(name) => name.to_string(),
+            None => format!("City {}", self.maxid + 1),
+        }
+    }
+
+    /// Science `player` accumulated so far.
+    pub fn science(&self, player: Player) -> u32 {
+        self.science.get(&player).cloned().unwrap_or(0)
+    }
+
+    pub fn set_science(&mut self, player: Player, science: u32) {
+        self.science.insert(player, science);
+    }
+
+    /// Gold in the treasury of `player`.
+    pub fn gold(&self, player: Player) -> u32 {
+        self.gold.get(&player).cloned().unwrap_or(0)
+    }
+
+    pub fn set_gold(&mut self, player: Player, gold: u32) {
+        self.gold.insert(player, gold);
+    }
+
+    /// Faith `player` accumulated so far.
+    pub fn faith(&self, player: Player) -> u32 {
+        self.faith.get(&player).cloned().unwrap_or(0)
+    }
+
+    pub fn set_faith(&mut self, player: Player, faith: u32) {
+        self.faith.insert(player, faith);
+    }
+
+    /// Belief of the pantheon of `player`, if it founded one.
+    pub fn pantheon(&self, player: Player) -> Option<Belief> {
+        self.pantheons.get(&player).cloned()
+    }
+
+    /// Restores the pantheon of `player`, for example when loading a saved game.
+    pub fn restore_pantheon(&mut self, player: Player, belief: Belief) {
+        self.pantheons.insert(player, belief);
+    }
+
+    /// Beliefs nobody adopted yet.
+    pub fn available_beliefs(&self) -> Vec<Belief> {
+        Belief::all().iter().cloned().filter(|b| !self.pantheons.values().any(|x| x == b)).collect()
+    }
+
+    /// Returns whether `player` has enough faith to found a pantheon and hasn't founded one yet.
+    pub fn can_found_pantheon(&self, player: Player) -> bool {
+        self.pantheon(player).is_none() && self.faith(player) >= PANTHEON_FAITH &&
+        !self.available_beliefs().is_empty()
+    }
+
+    /// Makes `player` found a pantheon adopting `belief`, which costs `PANTHEON_FAITH`.
+    ///
+    /// Returns `false`, without changing anything, if `player` can't found a pantheon or if
+    /// somebody else adopted `belief`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::city::{City, Cities};
+    /// use civng_core::religion::Belief;
+    /// use civng_core::terrain::{Terrain, TerrainMap, Yield};
+    /// use civng_core::unit::Player;
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let terrain = TerrainMap::empty_map(5, 5);
+    /// let mut cities = Cities::new();
+    /// cities.add_city(City::new("Rome", Player::Me, Pos::origin()));
+    /// assert!(!cities.found_pantheon(Player::Me, Belief::GoddessOfTheFields));
+    /// cities.set_faith(Player::Me, 25);
+    /// cities.set_faith(Player::AI(0), 25);
+    /// assert!(cities.found_pantheon(Player::Me, Belief::GoddessOfTheFields));
+    /// assert_eq!(cities.faith(Player::Me), 5);
+    /// assert!(!cities.found_pantheon(Player::AI(0), Belief::GoddessOfTheFields));
+    /// assert_eq!(cities.tile_yield(Player::Me, Pos::origin(), &terrain), Yield::new(3, 0, 0));
+    /// assert_eq!(cities.tile_yield(Player::AI(0), Pos::origin(), &terrain), Yield::new(2, 0, 0));
+    /// ```
+    pub fn found_pantheon(&mut self, player: Player, belief: Belief) -> bool {
+        if !self.can_found_pantheon(player) || !self.available_beliefs().contains(&belief) {
+            return false;
+        }
+        *self.faith.entry(player).or_insert(0) -= PANTHEON_FAITH;
+        self.pantheons.insert(player, belief);
+        true
+    }
+
+    /// Happiness `player` loses to its wars.
+    pub fn war_weariness(&self, player: Player) -> u16 {
+        self.war_weariness.get(&player).cloned().unwrap_or(0)
+    }
+
+    /// Sets the happiness `player` loses to its wars, which the map updates every turn.
+    pub fn set_war_weariness(&mut self, player: Player, weariness: u16) {
+        self.war_weariness.insert(player, weariness);
+    }
+
+    /// Number of units `player` can have without its production suffering.
+    pub fn supply_cap(&self, player: Player) -> u16 {
+        let cities: Vec<&City> = self.cities.values().filter(|c| c.owner() == player).collect();
+        let population = cities.iter().map(|c| c.population() as u16).sum::<u16>();
+        BASE_SUPPLY + cities.len() as u16 + population / 2
+    }
+
+    /// Production penalty, in percents, of `player` if it has `units` units.
+    ///
+    /// Each unit above the supply cap costs `SUPPLY_PENALTY_PER_UNIT`, up to `MAX_SUPPLY_PENALTY`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::city::{City, Cities};
+    /// use civng_core::terrain::{TerrainMap, Yield};
+    /// use civng_core::unit::Player;
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let terrain = TerrainMap::empty_map(5, 5);
+    /// let mut cities = Cities::new();
+    /// cities.add_city(City::with_state("Rome", Player::Me, Pos::origin(), 4, 0, 0));
+    /// assert_eq!(cities.supply_cap(Player::Me), 7);
+    /// assert_eq!(cities.supply_penalty(Player::Me, 7), 0);
+    /// assert_eq!(cities.supply_penalty(Player::Me, 9), 20);
+    /// assert_eq!(cities.supply_penalty(Player::Me, 20), 50);
+    /// cities.assign_citizens(1, &terrain);
+    /// assert_eq!(cities.city_yield(1, &terrain).production, 1);
+    /// cities.set_supply_penalty(Player::Me, 50);
+    /// assert_eq!(cities.city_yield(1, &terrain).production, 0);
+    /// ```
+    pub fn supply_penalty(&self, player: Player, units: u16) -> u16 {
+        let over = units.saturating_sub(self.supply_cap(player));
+        min(over * SUPPLY_PENALTY_PER_UNIT, MAX_SUPPLY_PENALTY)
+    }
+
+    /// Sets the production penalty `player` suffers for being above its supply cap, which the map
+    /// updates every turn.
+    pub fn set_supply_penalty(&mut self, player: Player, penalty: u16) {
+        self.supply_penalties.insert(player, penalty);
+    }
+
+    /// Sets the production modifier `player` gets from the difficulty level (see
+    /// `Difficulty::ai_production_bonus()`), which the map updates every turn.
+    pub fn set_production_bonus(&mut self, player: Player, bonus: i8) {
+        self.production_bonuses.insert(player, bonus);
+    }
+
+    /// Happiness of `player`: `BASE_HAPPINESS` minus the unhappiness of the cities it captured and
+    /// its war weariness.
+    pub fn happiness(&self, player: Player) -> i16 {
+        self.cities
+            .values()
+            .filter(|c| c.owner() == player)
+            .fold(BASE_HAPPINESS - self.war_weariness(player) as i16,
+                  |acc, c| acc - c.status().unhappiness())
+    }
+
+    /// Gives `city_id` to `new_owner`, who plunders `PLUNDER_PER_CITIZEN` gold per citizen.
+    ///
+    /// The city loses a citizen, what it stocked for its production and the tiles its former owner
+    /// locked. Its new owner has to decide what to do with it with `set_status()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::city::{City, Cities, CityStatus};
+    /// use civng_core::unit::Player;
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut cities = Cities::new();
+    /// cities.add_city(City::with_state("Rome", Player::AI(0), Pos::origin(), 3, 0, 40));
+    /// cities.capture(1, Player::Me);
+    /// assert!(cities.get(1).owner() == Player::Me);
+    /// assert_eq!(cities.get(1).population(), 2);
+    /// assert_eq!(cities.gold(Player::Me), 30);
+    /// assert_eq!(cities.happiness(Player::Me), 1);
+    /// assert!(cities.set_status(1, CityStatus::Puppet));
+    /// assert_eq!(cities.happiness(Player::Me), 3);
+    /// // The decision is final.
+    /// assert!(!cities.set_status(1, CityStatus::Annexed));
+    /// ```
+    pub fn capture(&mut self, city_id: CityID, new_owner: Player) {
+        let plunder = {
+            let city = self.get_mut(city_id);
+            let plunder = city.population as u32 * PLUNDER_PER_CITIZEN;
+            city.owner = new_owner;
+            city.status = CityStatus::Captured;
+            city.population = if city.population > 1 {
+                city.population - 1
+            } else {
+                1
+            };
+            city.production = 0;
+            city.producing = None;
+            city.locked_tiles.clear();
+            plunder
+        };
+        *self.gold.entry(new_owner).or_insert(0) += plunder;
+    }
+
+    /// Decides what happens to `city_id`, which has just been captured.
+    ///
+    /// Returns `false`, without changing anything, if the city wasn't just captured or if `status`
+    /// isn't a decision about a captured city.
+    pub fn set_status(&mut self, city_id: CityID, status: CityStatus) -> bool {
+        let city = self.get_mut(city_id);
+        match status {
+            CityStatus::Annexed | CityStatus::Puppet | CityStatus::Razing
+                if city.status == CityStatus::Captured => {
+                city.status = status;
+                if !status.is_managed() {
+                    city.producing = None;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Restores the status of `city_id`, for example when loading a saved game.
+    pub fn restore_status(&mut self, city_id: CityID, status: CityStatus) {
+        self.get_mut(city_id).status = status;
+    }
+
+    /// Returns the city owning the tile at `pos`, if any.
+    ///
+    /// That's the closest city within `CITY_RADIUS`. When two cities are as close, the oldest one
+    /// wins.
+    pub fn tile_owner(&self, pos: Pos) -> Option<CityID> {
+        self.all_cities()
+            .into_iter()
+            .filter(|c| c.pos().distance(pos) <= CITY_RADIUS)
+            .min_by_key(|c| (c.pos().distance(pos), c.id()))
+            .map(|c| c.id())
+    }
+
+    /// Returns the tiles `city_id` owns on `terrain`, its center excluded.
+    pub fn city_tiles(&self, city_id: CityID, terrain: &TerrainMap) -> Vec<Pos> {
+        let center = self.get(city_id).pos();
+        let in_bounds = |p: Pos| terrain.get_terrain(p) != Terrain::OutOfBounds;
+        let mut result: Vec<Pos> = center.within(CITY_RADIUS)
+                                         .into_iter()
+                                         .filter(|&p| p != center && in_bounds(p))
+                                         .filter(|&p| self.tile_owner(p) == Some(city_id))
+                                         .collect();
+        result.sort_by_key(|p| {
+            let opos = p.to_offset_pos();
+            (opos.y, opos.x)
+        });
+        result
+    }
+
+    /// Returns the city of `player` closest to `pos`, if `player` has any.
+    pub fn nearest_city(&self, player: Player, pos: Pos) -> Option<CityID> {
+        self.all_cities()
+            .into_iter()
+            .filter(|c| c.owner() == player)
+            .min_by_key(|c| (c.pos().distance(pos), c.id()))
+            .map(|c| c.id())
+    }
+
+    /// Returns what the tile at `pos` yields to a city of `player`.
+    ///
+    /// That's what the tile yields (see `TerrainMap::yield_at()`) plus what its resource yields, if
+    /// `player` accumulated enough science to know about it, and the bonus of the pantheon of
+    /// `player`, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::city::Cities;
+    /// use civng_core::terrain::{Resource, TerrainMap, Yield};
+    /// use civng_core::unit::Player;
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut terrain = TerrainMap::empty_map(5, 5);
+    /// terrain.set_resource(Pos::origin(), Some(Resource::Horses));
+    /// let mut cities = Cities::new();
+    /// assert_eq!(cities.tile_yield(Player::Me, Pos::origin(), &terrain), Yield::new(2, 0, 0));
+    /// cities.set_science(Player::Me, Resource::Horses.revealed_at());
+    /// assert_eq!(cities.tile_yield(Player::Me, Pos::origin(), &terrain), Yield::new(2, 1, 0));
+    /// assert_eq!(cities.tile_yield(Player::AI(0), Pos::origin(), &terrain), Yield::new(2, 0, 0));
+    /// ```
+    pub fn tile_yield(&self, player: Player, pos: Pos, terrain: &TerrainMap) -> Yield {
+        let base = match terrain.get_resource(pos) {
+            Some(resource) if self.science(player) >= resource.revealed_at() => {
+                terrain.yield_at(pos) + resource.yields()
+            }
+            _ => terrain.yield_at(pos),
+        };
+        match self.pantheon(player) {
+            Some(belief) => base + belief.tile_bonus(terrain, pos),
+            None => base,
+        }
+    }
+
+    /// Returns what the center of `city_id` yields. City centers yield at least 1 production.
+    pub fn center_yield(&self, city_id: CityID, terrain: &TerrainMap) -> Yield {
+        let city = self.get(city_id);
+        let mut result = self.tile_yield(city.owner(), city.pos(), terrain);
+        if result.production == 0 {
+            result.production = 1;
+        }
+        result
+    }
+
+    /// Returns what `city_id` yields every turn from its center, the tiles its citizens work and
+    /// its buildings.
+    ///
+    /// Its production suffers from the supply penalty of its owner, if any, and gets the production
+    /// modifier of its owner's difficulty level.
+    pub fn city_yield(&self, city_id: CityID, terrain: &TerrainMap) -> Yield {
+        let city = self.get(city_id);
+        let center = self.center_yield(city_id, terrain);
+        let tiles = city.worked_tiles()
+                        .iter()
+                        .fold(center, |acc, &p| acc + self.tile_yield(city.owner(), p, terrain));
+        let mut result = city.buildings()
+                             .iter()
+                             .fold(tiles, |acc, &b| acc + self.buildings.get(b).yields);
+        let penalty = self.supply_penalties.get(&city.owner()).cloned().unwrap_or(0) as i32;
+        let bonus = self.production_bonuses.get(&city.owner()).cloned().unwrap_or(0) as i32;
+        let percent = (100 + bonus - penalty).max(0);
+        let production = result.production as i32 * percent / 100;
+        result.production = min(production, u8::max_value() as i32) as u8;
+        result
+    }
+
+    /// Faith `city_id` produces every turn: one, plus what the features of its worked tiles give.
+    pub fn city_faith(&self, city_id: CityID, terrain: &TerrainMap) -> u16 {
+        let city = self.get(city_id);
+        city.worked_tiles()
+            .iter()
+            .filter_map(|&p| terrain.get_feature(p))
+            .fold(1, |acc, f| acc + f.faith() as u16)
+    }
+
+    /// Science `city_id` produces every turn: one per citizen plus what its buildings give.
+    pub fn city_science(&self, city_id: CityID) -> u16 {
+        let city = self.get(city_id);
+        city.buildings()
+            .iter()
+            .fold(city.population() as u16,
+                  |acc, &b| acc + self.buildings.get(b).science as u16)
+    }
+
+    /// Defense bonus, in percents, the buildings of `city_id` give to the units defending it.
+    pub fn defense_bonus(&self, city_id: CityID) -> u16 {
+        self.get(city_id).buildings().iter().map(|&b| self.buildings.get(b).defense as u16).sum()
+    }
+
+    /// Experience the buildings of `city_id` give to the units it trains.
+    pub fn training_xp(&self, city_id: CityID) -> u16 {
+        self.get(city_id).buildings().iter().map(|&b| self.buildings.get(b).xp).sum()
+    }
+
+    /// Returns whether a city, any city, has `building_id`.
+    pub fn is_built(&self, building_id: BuildingID) -> bool {
+        self.cities.values().any(|c| c.has_building(building_id))
+    }
+
+    /// Returns the production it takes to build `production`.
+    pub fn production_cost(&self, production: Production) -> u16 {
+        match production {
+            Production::Unit(unit_type) => unit_type.cost(),
+            Production::Building(building_id) => self.buildings.get(building_id).cost,
+        }
+    }
+
+    /// Returns the name of what `production` builds.
+    pub fn production_name(&self, production: Production) -> String {
+        match production {
+            Production::Unit(unit_type) => unit_type.name().to_owned(),
+            Production::Building(building_id) => self.buildings.get(building_id).name.clone(),
+        }
+    }
+
+    /// Returns whether `city_id` can build `production`.
+    ///
+    /// Cities can train any unit, but they can't build a building twice nor a wonder another
+    /// city has.
+    pub fn can_produce(&self, city_id: CityID, production: Production) -> bool {
+        match production {
+            Production::Unit(_) => true,
+            Production::Building(building_id) => {
+                if building_id >= self.buildings.all().len() {
+                    false
+                } else if self.buildings.get(building_id).wonder {
+                    !self.is_built(building_id)
+                } else {
+                    !self.get(city_id).has_building(building_id)
+                }
+            }
+        }
+    }
+
+    /// Returns what `city_id` can build, units first.
+    pub fn production_choices(&self, city_id: CityID) -> Vec<Production> {
+        let units = UnitType::all().iter().map(|&t| Production::Unit(t)).collect::<Vec<_>>();
+        let buildings = (0..self.buildings.all().len()).map(|b| Production::Building(b));
+        units.into_iter()
+             .chain(buildings)
+             .filter(|&p| self.can_produce(city_id, p))
+             .collect()
+    }
+
+    /// Makes `city_id` build `production`, or nothing if `None`.
+    ///
+    /// Returns `false`, without changing anything, if the city can't build it or if its owner
+    /// doesn't manage it, like puppets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::city::{City, Cities, Production};
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::unit::Player;
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let terrain = TerrainMap::empty_map(5, 5);
+    /// let mut cities = Cities::new();
+    /// cities.add_city(City::with_state("Rome", Player::Me, Pos::origin(), 1, 0, 59));
+    /// cities.add_city(City::with_state("Paris", Player::AI(0), Pos::new(4, 0, -4), 1, 0, 0));
+    /// let granary_id = cities.buildings().find("Granary").unwrap();
+    /// let granary = Production::Building(granary_id);
+    /// let pyramids = Production::Building(cities.buildings().find("Pyramids").unwrap());
+    /// assert!(cities.set_production(1, Some(granary)));
+    /// assert!(cities.set_production(2, Some(pyramids)));
+    /// cities.refresh(&terrain);
+    /// assert!(cities.get(1).producing().is_none());
+    /// assert_eq!(cities.get(1).production(), 0);
+    /// assert!(cities.get(1).has_building(granary_id));
+    /// // Rome can't build a second granary, but it can build the pyramids until Paris has them.
+    /// assert!(!cities.set_production(1, Some(granary)));
+    /// assert!(cities.production_choices(1).contains(&pyramids));
+    /// ```
+    pub fn set_production(&mut self, city_id: CityID, production: Option<Production>) -> bool {
+        if let Some(p) = production {
+            if !self.can_produce(city_id, p) || !self.get(city_id).status().is_managed() {
+                return false;
+            }
+        }
+        self.get_mut(city_id).producing = production;
+        true
+    }
+
+    /// Puts the citizens of `city_id` to work: locked tiles first, then the best tiles left.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::city::{City, Cities};
+    /// use civng_core::terrain::{Terrain, TerrainMap};
+    /// use civng_core::unit::Player;
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut terrain = TerrainMap::empty_map(5, 5);
+    /// let hill = Pos::vector(Direction::South);
+    /// terrain.set_terrain(hill, Terrain::Hill);
+    /// let mut cities = Cities::new();
+    /// cities.add_city(City::new("Rome", Player::Me, Pos::origin()));
+    /// cities.assign_citizens(1, &terrain);
+    /// // Grassland yields more food than the hill.
+    /// assert!(!cities.get(1).worked_tiles().contains(&hill));
+    /// cities.toggle_locked_tile(1, hill, &terrain);
+    /// assert_eq!(cities.get(1).worked_tiles(), &[hill]);
+    /// ```
+    pub fn assign_citizens(&mut self, city_id: CityID, terrain: &TerrainMap) {
+        let tiles = self.city_tiles(city_id, terrain);
+        let (owner, population, locked) = {
+            let city = self.get(city_id);
+            (city.owner(), city.population() as usize, city.locked_tiles().to_vec())
+        };
+        // Tiles can be lost to a newer city closer to them.
+        let locked: Vec<Pos> = locked.into_iter().filter(|p| tiles.contains(p)).collect();
+        let mut candidates: Vec<Pos> = tiles.into_iter().filter(|p| !locked.contains(p)).collect();
+        // Sorting is stable, so equally good tiles keep their order.
+        candidates.sort_by_key(|&p| {
+            u16::max_value() - tile_score(self.tile_yield(owner, p, terrain))
+        });
+        let mut worked = locked.clone();
+        worked.extend(candidates);
+        worked.truncate(population);
+        let city = self.get_mut(city_id);
+        city.locked_tiles = locked;
+        city.worked_tiles = worked;
+    }
+
+    /// Locks the tile at `pos` so that a citizen of `city_id` always works it, or unlocks it if it
+    /// was locked.
+    ///
+    /// There can't be more locked tiles than citizens: the oldest lock goes away if needed.
+    /// Returns `false` if `city_id` doesn't own the tile.
+    pub fn toggle_locked_tile(&mut self, city_id: CityID, pos: Pos, terrain: &TerrainMap) -> bool {
+        if !self.city_tiles(city_id, terrain).contains(&pos) {
+            return false;
+        }
+        {
+            let city = self.get_mut(city_id);
+            if let Some(index) = city.locked_tiles.iter().position(|&p| p == pos) {
+                city.locked_tiles.remove(index);
+            } else {
+                city.locked_tiles.push(pos);
+                if city.locked_tiles.len() > city.population as usize {
+                    city.locked_tiles.remove(0);
+                }
+            }
+        }
+        self.assign_citizens(city_id, terrain);
+        true
+    }
+
+    /// Restores the locked tiles of `city_id`, for example when loading a saved game.
+    pub fn set_locked_tiles(&mut self, city_id: CityID, tiles: Vec<Pos>) {
+        self.get_mut(city_id).locked_tiles = tiles;
+    }
+
+    /// Makes cities on `terrain` feed their citizens, grow or starve, accumulate production,
+    /// science and faith and build what they're building for a new turn.
+    ///
+    /// Cities being razed lose a citizen instead and are removed once they have none left. Puppets
+    /// turn their production into gold.
+    ///
+    /// Returns the events of cities growing, starving, being razed or having built something. Units
+    /// are only announced: it's up to the caller to put them on the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::city::{City, Cities};
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::unit::Player;
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let terrain = TerrainMap::empty_map(5, 5);
+    /// let mut cities = Cities::new();
+    /// cities.add_city(City::with_state("Rome", Player::Me, Pos::origin(), 1, 13, 0));
+    /// // The center and a grassland tile yield 4 food and 1 production. The citizen eats 2.
+    /// cities.refresh(&terrain);
+    /// assert_eq!(cities.get(1).population(), 2);
+    /// assert_eq!(cities.get(1).food(), 0);
+    /// assert_eq!(cities.get(1).production(), 1);
+    /// assert_eq!(cities.get(1).worked_tiles().len(), 2);
+    /// assert_eq!(cities.science(Player::Me), 1);
+    /// assert_eq!(cities.faith(Player::Me), 1);
+    /// ```
+    pub fn refresh(&mut self, terrain: &TerrainMap) -> Vec<GameEvent> {
+        let mut result = Vec::new();
+        let ids: Vec<CityID> = self.all_cities().iter().map(|c| c.id()).collect();
+        let unhappy: Vec<Player> = self.cities
+                                       .values()
+                                       .map(|c| c.owner())
+                                       .filter(|&p| self.happiness(p) < 0)
+                                       .collect();
+        for city_id in ids {
+            if self.get(city_id).status() == CityStatus::Razing {
+                if self.get(city_id).population > 1 {
+                    self.get_mut(city_id).population -= 1;
+                } else {
+                    let city = self.cities.remove(&city_id).unwrap();
+                    result.push(GameEvent::CityRazed(city.name, city.owner, city.pos));
+                }
+                continue;
+            }
+            self.assign_citizens(city_id, terrain);
+            let city_yield = self.city_yield(city_id, terrain);
+            let science = self.city_science(city_id);
+            let faith = self.city_faith(city_id, terrain);
+            let (owner, gold) = {
+                let city = self.get_mut(city_id);
+                city.has_struck = false;
+                let eaten = city.population as u16 * FOOD_PER_CITIZEN;
+                let food = city.food + city_yield.food as u16;
+                let mut gold = city_yield.gold as u32;
+                if city.status == CityStatus::Puppet {
+                    gold += city_yield.production as u32;
+                } else {
+                    city.production += city_yield.production as u16;
+                }
+                if food < eaten {
+                    city.food = 0;
+                    if city.population > 1 {
+                        city.population -= 1;
+                        result.push(GameEvent::CityStarved(city_id, city.population));
+                    }
+                } else if food - eaten >= growth_threshold(city.population) &&
+                   !unhappy.contains(&city.owner) {
+                    city.food = 0;
+                    city.population += 1;
+                    result.push(GameEvent::CityGrew(city_id, city.population));
+                } else {
+                    // Unhappy cities keep their stock until they can grow again.
+                    city.food = min(food - eaten, growth_threshold(city.population));
+                }
+                (city.owner, gold)
+            };
+            *self.science.entry(owner).or_insert(0) += science as u32;
+            *self.gold.entry(owner).or_insert(0) += gold;
+            *self.faith.entry(owner).or_insert(0) += faith as u32;
+            if let Some(production) = self.get(city_id).producing() {
+                if self.get(city_id).production() >= self.production_cost(production) {
+                    self.complete_production(city_id, production);
+                    result.push(GameEvent::CityBuilt(city_id, production));
+                }
+            }
+            self.assign_citizens(city_id, terrain);
+        }
+        result
+    }
+
+    /// Makes `city_id` pay for `production` and, if it's a building, adds it to the city.
+    ///
+    /// Other cities building the same wonder have to build something else.
+    fn complete_production(&mut self, city_id: CityID, production: Production) {
+        let cost = self.production_cost(production);
+        {
+            let city = self.get_mut(city_id);
+            city.production -= cost;
+            city.producing = None;
+        }
+        if let Production::Building(building_id) = production {
+            self.get_mut(city_id).buildings.push(building_id);
+            if self.buildings.get(building_id).wonder {
+                for city in self.cities.values_mut() {
+                    if city.producing == Some(production) {
+                        city.producing = None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Gives the cities, stocks and pantheon of `a` to `b` and those of `b` to `a`.
+    pub fn swap_players(&mut self, a: Player, b: Player) {
+        fn swap_keys<T>(map: &mut HashMap<Player, T>, a: Player, b: Player) {
+            let (of_a, of_b) = (map.remove(&a), map.remove(&b));
+            if let Some(value) = of_a {
+                map.insert(b, value);
+            }
+            if let Some(value) = of_b {
+                map.insert(a, value);
+            }
+        }
+
+        for city in self.cities.values_mut() {
+            city.owner = city.owner.swapped(a, b);
+        }
+        swap_keys(&mut self.science, a, b);
+        swap_keys(&mut self.gold, a, b);
+        swap_keys(&mut self.faith, a, b);
+        swap_keys(&mut self.pantheons, a, b);
+        swap_keys(&mut self.war_weariness, a, b);
+        swap_keys(&mut self.supply_penalties, a, b);
+        swap_keys(&mut self.production_bonuses, a, b);
+    }
+}
+
+/// Cities are serialized as a list sorted by id, and stocks and pantheons as `(player, value)`
+/// pairs sorted by player. Happiness and production modifiers are computed again on the next
+/// `LiveMap::refresh()` and the buildings cities can build are the builtin ones.
+///
+/// # Examples
+///
+/// ```
+/// extern crate serde_json;
+/// extern crate civng_core;
+///
+/// use civng_core::city::{Cities, City, CityStatus, Production};
+/// use civng_core::unit::{Player, UnitType};
+/// use civng_core::religion::Belief;
+/// use civng_core::hexpos::{Pos, Direction};
+///
+/// # fn main() {
+/// let mut cities = Cities::new();
+/// cities.add_city(City::with_state("Paris", Player::Me, Pos::origin(), 3, 5, 8));
+/// let south = Pos::vector(Direction::South).amplify(4);
+/// cities.add_city(City::new("Rome", Player::AI(1), south));
+/// cities.set_production(2, Some(Production::Unit(UnitType::Melee)));
+/// cities.restore_status(2, CityStatus::Puppet);
+/// cities.set_gold(Player::AI(1), 40);
+/// cities.restore_pantheon(Player::Me, Belief::SacredWaters);
+/// let json = serde_json::to_string(&cities).unwrap();
+/// assert!(json.starts_with("{\"maxid\":2,\"cities\":[{\"id\":1,\"name\":\"Paris\""));
+/// let copy: Cities = serde_json::from_str(&json).unwrap();
+/// let city = copy.get(1);
+/// assert_eq!((city.population(), city.food(), city.production()), (3, 5, 8));
+/// assert!(copy.get(2).producing() == Some(Production::Unit(UnitType::Melee)));
+/// assert_eq!(copy.get(2).status(), CityStatus::Puppet);
+/// assert_eq!(copy.gold(Player::AI(1)), 40);
+/// assert_eq!(copy.pantheon(Player::Me), Some(Belief::SacredWaters));
+/// assert_eq!(serde_json::to_string(&copy).unwrap(), json);
+/// # }
+/// ```
+impl Serialize for Cities {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        fn sorted<T: Clone>(map: &HashMap<Player, T>) -> Vec<(Player, T)> {
+            let mut result: Vec<(Player, T)> = map.iter().map(|(&p, v)| (p, v.clone())).collect();
+            result.sort_by_key(|&(p, _)| p.name());
+            result
+        }
+
+        let mut state = try!(serializer.serialize_struct("Cities", 6));
+        try!(state.serialize_field("maxid", &self.maxid));
+        try!(state.serialize_field("cities", &self.all_cities()));
+        try!(state.serialize_field("science", &sorted(&self.science)));
+        try!(state.serialize_field("gold", &sorted(&self.gold)));
+        try!(state.serialize_field("faith", &sorted(&self.faith)));
+        try!(state.serialize_field("pantheons", &sorted(&self.pantheons)));
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Cities {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Cities, D::Error> {
+        #[derive(Deserialize)]
+        struct CityList {
+            maxid: CityID,
+            cities: Vec<City>,
+            science: Vec<(Player, u32)>,
+            gold: Vec<(Player, u32)>,
+            faith: Vec<(Player, u32)>,
+            pantheons: Vec<(Player, Belief)>,
+        }
+
+        let list = try!(CityList::deserialize(deserializer));
+        let mut result = Cities::new();
+        result.maxid = list.maxid;
+        result.cities = list.cities.into_iter().map(|c| (c.id, c)).collect();
+        result.science = list.science.into_iter().collect();
+        result.gold = list.gold.into_iter().collect();
+        result.faith = list.faith.into_iter().collect();
+        result.pantheons = list.pantheons.into_iter().collect();
+        Ok(result)
+    }
+}
@@ -0,0 +1,853 @@
+// Copyright 2015 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::fs::{File, read_dir};
+use std::io::prelude::*;
+use std::collections::HashMap;
+use std::iter::FromIterator;
+
+use byteorder;
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
+
+use hexpos::{Pos, OffsetPos};
+use terrain::{Terrain, TerrainMap, Feature, Resource};
+use unit::UnitType;
+
+/// Latest map format version we know how to read.
+const MAX_SUPPORTED_VERSION: u8 = 12;
+
+/// Bits of the first byte of a map holding its format version. The others are flags.
+const VERSION_MASK: u8 = 0x0f;
+
+/// Flag of the first byte of a map telling that scenario data follows its tiles.
+const SCENARIO_FLAG: u8 = 0x80;
+
+/// First map format version (0xB) with a world size string in its header.
+const WORLD_SIZE_VERSION: u8 = 11;
+
+/// Size of the unknown block starting the scenario section of version 12 maps.
+const SCENARIO_PADDING_LEN: usize = 68;
+
+/// Length of the null-padded name field of scenario cities.
+const CITY_NAME_LEN: usize = 64;
+
+/// Bits of tile river flags standing for the edges of `RIVER_EDGES`.
+const RIVER_FLAGS_MASK: u8 = 0b111;
+
+/// Value of `TileImprovement::city_id` for tiles without a city.
+const NO_CITY: u16 = 0xffff;
+
+/// Value of `TileImprovement::unit_id` for tiles without a unit.
+const NO_UNIT: u16 = 0xffff;
+
+/// Length of scenario unit records.
+///
+/// Unknown (2 bytes), name index (2 bytes), experience and health (4 bytes each), unit type,
+/// owner, facing and status (1 byte each) and promotions (32 bytes).
+const UNIT_RECORD_LEN: usize = 48;
+
+/// Offset of the index of the unit type, in the unit type list, in unit records.
+const UNIT_TYPE_OFFSET: usize = 12;
+
+/// Offset of the owner in unit records.
+const UNIT_OWNER_OFFSET: usize = 13;
+
+/// Length of the team name records following tile improvements.
+const TEAM_RECORD_LEN: usize = 64;
+
+/// Length of player records, which follow team names.
+///
+/// Policies (32 bytes), then leader name, civ name, civ type, team color, era and handicap (64
+/// bytes each), culture and gold (4 bytes each), start position (2 * 4 bytes), team, playable flag
+/// and 2 unknown bytes.
+const PLAYER_RECORD_LEN: usize = 436;
+
+/// Offset of the start position in player records.
+const PLAYER_START_POS_OFFSET: usize = 424;
+
+/// Why a Civ5Map file couldn't be loaded.
+#[derive(Debug)]
+pub enum MapLoadError {
+    /// The file couldn't be opened or read.
+    Io(io::Error),
+    /// The file ended before all the map data could be read.
+    UnexpectedEof,
+    /// The file uses a map format version we don't know about.
+    UnsupportedVersion(u8),
+    /// A string in the header isn't valid UTF-8.
+    InvalidUtf8,
+    /// A tile refers to a terrain type that isn't in the header.
+    InvalidTerrainId(u8),
+}
+
+impl fmt::Display for MapLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MapLoadError::Io(ref err) => write!(f, "Can't read map file: {}", err),
+            MapLoadError::UnexpectedEof => write!(f, "Map file is truncated"),
+            MapLoadError::UnsupportedVersion(v) => write!(f, "Unsupported map version {}", v),
+            MapLoadError::InvalidUtf8 => write!(f, "Map header contains invalid text"),
+            MapLoadError::InvalidTerrainId(id) => write!(f, "Map tile has unknown terrain {}", id),
+        }
+    }
+}
+
+impl Error for MapLoadError {
+    fn description(&self) -> &str {
+        match *self {
+            MapLoadError::Io(_) => "I/O error",
+            MapLoadError::UnexpectedEof => "unexpected end of file",
+            MapLoadError::UnsupportedVersion(_) => "unsupported map version",
+            MapLoadError::InvalidUtf8 => "invalid UTF-8 in map header",
+            MapLoadError::InvalidTerrainId(_) => "invalid terrain id",
+        }
+    }
+}
+
+impl From<io::Error> for MapLoadError {
+    fn from(err: io::Error) -> MapLoadError {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            MapLoadError::UnexpectedEof
+        } else {
+            MapLoadError::Io(err)
+        }
+    }
+}
+
+impl From<byteorder::Error> for MapLoadError {
+    fn from(err: byteorder::Error) -> MapLoadError {
+        match err {
+            byteorder::Error::UnexpectedEOF => MapLoadError::UnexpectedEof,
+            byteorder::Error::Io(err) => MapLoadError::from(err),
+        }
+    }
+}
+
+#[allow(dead_code)]
+struct MapHeader {
+    version: u8,
+    /// Whether scenario data follows the tiles.
+    is_scenario: bool,
+    width: u32,
+    height: u32,
+    playercount: u8,
+    flags: u32,
+    terrain: Vec<String>,
+    features1: Vec<String>,
+    features2: Vec<String>,
+    resources: Vec<String>,
+    name: String,
+    description: String,
+    world_size: String,
+}
+
+#[allow(dead_code)]
+struct MapTile {
+    terrain_id: u8,
+    resource_id: u8,
+    feature1_id: u8,
+    river_flags: u8,
+    elevation: u8, // 0 = flat, 1 = hill, 2 = mountain
+    continent: u8,
+    feature2_id: u8,
+    resource_amount: u8,
+}
+
+/// Sizes of the scenario sections following the tiles.
+struct ScenarioHeader {
+    max_turns: u32,
+    start_year: i32,
+    player_count: u8,
+    city_state_count: u8,
+    team_count: u8,
+    improvement_len: u32,
+    unit_type_len: u32,
+    tech_len: u32,
+    policy_len: u32,
+    building_len: u32,
+    promotion_len: u32,
+    unit_data_len: u32,
+    unit_name_len: u32,
+    city_data_len: u32,
+    victory_len: u32,
+    game_option_len: u32,
+}
+
+/// Per-tile scenario data.
+#[allow(dead_code)]
+struct TileImprovement {
+    city_id: u16,
+    unit_id: u16,
+    owner: u8,
+    improvement_id: u8,
+    route_type: u8,
+    route_owner: u8,
+}
+
+/// A city placed by the author of a scenario map.
+pub struct ScenarioCity {
+    pub name: String,
+    pub owner: u8,
+    pub population: u16,
+    pub pos: Pos,
+}
+
+/// A unit placed by the author of a scenario map.
+pub struct ScenarioUnit {
+    pub type_: UnitType,
+    pub owner: u8,
+    pub pos: Pos,
+}
+
+/// Scenario data that maps saved from the WorldBuilder as scenarios have after their tiles.
+pub struct Scenario {
+    pub max_turns: u32,
+    pub start_year: i32,
+    pub player_count: u8,
+    pub city_state_count: u8,
+    pub team_count: u8,
+    /// Names of the improvement types referred to by the scenario.
+    pub improvements: Vec<String>,
+    /// Names of the unit types referred to by the scenario's units.
+    pub unit_types: Vec<String>,
+    /// Units of the scenario, leaving out those of a type we don't have.
+    pub units: Vec<ScenarioUnit>,
+    pub cities: Vec<ScenarioCity>,
+    /// Start position of each player, in player order, or `None` for players the map doesn't
+    /// place.
+    pub start_positions: Vec<Option<Pos>>,
+}
+
+fn read_str(fp: &mut File, len: u32) -> Result<String, MapLoadError> {
+    let bytes = try!(read_bytes(fp, len));
+    String::from_utf8(bytes).map_err(|_| MapLoadError::InvalidUtf8)
+}
+
+/// Decodes free text such as map and city names.
+///
+/// Those are UTF-8 in recent maps, but older tools saved them in Latin-1, which we fall back to.
+fn decode_text(bytes: &[u8]) -> String {
+    match String::from_utf8(bytes.to_vec()) {
+        Ok(s) => s,
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+fn read_text(fp: &mut File, len: u32) -> Result<String, MapLoadError> {
+    let bytes = try!(read_bytes(fp, len));
+    Ok(decode_text(&bytes))
+}
+
+fn read_bytes(fp: &mut File, len: u32) -> Result<Vec<u8>, MapLoadError> {
+    let mut bytes = Vec::new();
+    try!(fp.take(len as u64).read_to_end(&mut bytes));
+    if bytes.len() < len as usize {
+        return Err(MapLoadError::UnexpectedEof);
+    }
+    Ok(bytes)
+}
+
+fn read_str_list(fp: &mut File, len: u32) -> Result<Vec<String>, MapLoadError> {
+    let s = try!(read_str(fp, len));
+    let mut result: Vec<String> = s.split('\0').map(|s| s.to_string()).collect();
+    // Lists are null-terminated, which leaves us with an empty string at the end.
+    if result.last().map_or(false, |s| s.is_empty()) {
+        result.pop();
+    }
+    Ok(result)
+}
+
+fn load_map_header(fp: &mut File) -> Result<MapHeader, MapLoadError> {
+    let version_byte = try!(fp.read_u8());
+    let version = version_byte & VERSION_MASK;
+    if version == 0 || version > MAX_SUPPORTED_VERSION {
+        return Err(MapLoadError::UnsupportedVersion(version));
+    }
+    let width = try!(fp.read_u32::<LittleEndian>());
+    let height = try!(fp.read_u32::<LittleEndian>());
+    let playercount = try!(fp.read_u8());
+    let flags = try!(fp.read_u32::<LittleEndian>());
+    let terrain_len = try!(fp.read_u32::<LittleEndian>());
+    let feature1_len = try!(fp.read_u32::<LittleEndian>());
+    let feature2_len = try!(fp.read_u32::<LittleEndian>());
+    let resource_len = try!(fp.read_u32::<LittleEndian>());
+    let _ = try!(fp.read_u32::<LittleEndian>());
+    let mapname_len = try!(fp.read_u32::<LittleEndian>());
+    let mapdesc_len = try!(fp.read_u32::<LittleEndian>());
+    let terrain_list = try!(read_str_list(fp, terrain_len));
+    let feature1_list = try!(read_str_list(fp, feature1_len));
+    let feature2_list = try!(read_str_list(fp, feature2_len));
+    let resource_list = try!(read_str_list(fp, resource_len));
+    let mapname = try!(read_text(fp, mapname_len));
+    let mapdesc = try!(read_text(fp, mapdesc_len));
+    let world_size = if version >= WORLD_SIZE_VERSION {
+        let world_size_len = try!(fp.read_u32::<LittleEndian>());
+        try!(read_str(fp, world_size_len))
+    } else {
+        String::new()
+    };
+    Ok(MapHeader {
+        version: version,
+        is_scenario: version_byte & SCENARIO_FLAG != 0,
+        width: width,
+        height: height,
+        playercount: playercount,
+        flags: flags,
+        terrain: terrain_list,
+        features1: feature1_list,
+        features2: feature2_list,
+        resources: resource_list,
+        name: mapname,
+        description: mapdesc,
+        world_size: world_size,
+    })
+}
+
+fn load_map_tiles(fp: &mut File, len: u32) -> Result<Vec<MapTile>, MapLoadError> {
+    let mut result: Vec<MapTile> = Vec::new();
+    for _ in 0..len {
+        let mut bytes: [u8; 8] = [0; 8];
+        try!(fp.read_exact(&mut bytes));
+        result.push(MapTile {
+            terrain_id: bytes[0],
+            resource_id: bytes[1],
+            feature1_id: bytes[2],
+            river_flags: bytes[3],
+            elevation: bytes[4],
+            continent: bytes[5],
+            feature2_id: bytes[6],
+            resource_amount: bytes[7],
+        });
+    }
+    Ok(result)
+}
+
+fn load_scenario_header(fp: &mut File) -> Result<ScenarioHeader, MapLoadError> {
+    let max_turns = try!(fp.read_u32::<LittleEndian>());
+    let _ = try!(fp.read_u32::<LittleEndian>());
+    let start_year = try!(fp.read_i32::<LittleEndian>());
+    let player_count = try!(fp.read_u8());
+    let city_state_count = try!(fp.read_u8());
+    let team_count = try!(fp.read_u8());
+    let _ = try!(fp.read_u8());
+    let mut lens = [0u32; 11];
+    for len in lens.iter_mut() {
+        *len = try!(fp.read_u32::<LittleEndian>());
+    }
+    Ok(ScenarioHeader {
+        max_turns: max_turns,
+        start_year: start_year,
+        player_count: player_count,
+        city_state_count: city_state_count,
+        team_count: team_count,
+        improvement_len: lens[0],
+        unit_type_len: lens[1],
+        tech_len: lens[2],
+        policy_len: lens[3],
+        building_len: lens[4],
+        promotion_len: lens[5],
+        unit_data_len: lens[6],
+        unit_name_len: lens[7],
+        city_data_len: lens[8],
+        victory_len: lens[9],
+        game_option_len: lens[10],
+    })
+}
+
+fn load_tile_improvements(fp: &mut File, len: u32) -> Result<Vec<TileImprovement>, MapLoadError> {
+    let mut result = Vec::new();
+    for _ in 0..len {
+        let mut bytes: [u8; 8] = [0; 8];
+        try!(fp.read_exact(&mut bytes));
+        result.push(TileImprovement {
+            city_id: bytes[0] as u16 | (bytes[1] as u16) << 8,
+            unit_id: bytes[2] as u16 | (bytes[3] as u16) << 8,
+            owner: bytes[4],
+            improvement_id: bytes[5],
+            route_type: bytes[6],
+            route_owner: bytes[7],
+        });
+    }
+    Ok(result)
+}
+
+/// Returns our unit type closest to the Civ5 unit type `name`, if we have one.
+fn civ5_unit_type(name: &str) -> Option<UnitType> {
+    match name {
+        "UNIT_WARRIOR" | "UNIT_SPEARMAN" => Some(UnitType::Melee),
+        "UNIT_ARCHER" => Some(UnitType::Ranged),
+        "UNIT_WORKER" => Some(UnitType::Worker),
+        "UNIT_HORSEMAN" => Some(UnitType::Horseman),
+        "UNIT_SWORDSMAN" => Some(UnitType::Swordsman),
+        "UNIT_SCOUT" => Some(UnitType::Scout),
+        "UNIT_SETTLER" => Some(UnitType::Settler),
+        _ => None,
+    }
+}
+
+/// Reads the scenario sections following the tiles of a map of `mh`.
+///
+/// Returns `None` if the map isn't flagged as a scenario. We only know the layout of version 12
+/// scenarios, so scenario data from older versions is ignored.
+fn load_scenario(fp: &mut File, mh: &MapHeader) -> Result<Option<Scenario>, MapLoadError> {
+    if !mh.is_scenario || mh.version < MAX_SUPPORTED_VERSION {
+        return Ok(None);
+    }
+    try!(read_bytes(fp, SCENARIO_PADDING_LEN as u32));
+    let sh = try!(load_scenario_header(fp));
+    let improvements = try!(read_str_list(fp, sh.improvement_len));
+    let unit_types = try!(read_str_list(fp, sh.unit_type_len));
+    // Techs, policies, buildings and promotions are skipped: our game doesn't have any use for
+    // them yet.
+    for len in &[sh.tech_len, sh.policy_len, sh.building_len, sh.promotion_len] {
+        try!(read_bytes(fp, *len));
+    }
+    let unit_data = try!(read_bytes(fp, sh.unit_data_len));
+    try!(read_bytes(fp, sh.unit_name_len));
+    let city_data = try!(read_bytes(fp, sh.city_data_len));
+    try!(read_bytes(fp, sh.victory_len));
+    try!(read_bytes(fp, sh.game_option_len));
+    let tile_improvements = try!(load_tile_improvements(fp,
+                                                        mh.width.saturating_mul(mh.height)));
+    try!(read_bytes(fp, (sh.team_count as usize * TEAM_RECORD_LEN) as u32));
+    let mut start_positions = Vec::new();
+    for _ in 0..sh.player_count {
+        let record = try!(read_bytes(fp, PLAYER_RECORD_LEN as u32));
+        let mut posdata = &record[PLAYER_START_POS_OFFSET..];
+        let x = try!(posdata.read_u32::<LittleEndian>());
+        let y = try!(posdata.read_u32::<LittleEndian>());
+        // Unplaced players have their coordinates set to -1. Anything outside the map would
+        // wrap around when cast, so we can't take it either.
+        let pos = if x < mh.width && y < mh.height {
+            Some(OffsetPos::new(x as i32, y as i32).to_pos())
+        } else {
+            None
+        };
+        start_positions.push(pos);
+    }
+    // City and unit records don't contain their position, tiles refer to their city and unit
+    // instead.
+    let mut city_positions: HashMap<u16, Pos> = HashMap::new();
+    let mut unit_positions: HashMap<u16, Pos> = HashMap::new();
+    for (i, ti) in tile_improvements.iter().enumerate() {
+        let opos = OffsetPos::new(i as i32 % mh.width as i32, i as i32 / mh.width as i32);
+        if ti.city_id != NO_CITY {
+            city_positions.insert(ti.city_id, opos.to_pos());
+        }
+        if ti.unit_id != NO_UNIT {
+            unit_positions.insert(ti.unit_id, opos.to_pos());
+        }
+    }
+    let mut units = Vec::new();
+    for (unit_id, record) in unit_data.chunks(UNIT_RECORD_LEN).enumerate() {
+        let pos = match unit_positions.get(&(unit_id as u16)) {
+            Some(pos) if record.len() == UNIT_RECORD_LEN => *pos,
+            _ => continue,
+        };
+        let type_name = unit_types.get(record[UNIT_TYPE_OFFSET] as usize);
+        let type_ = match type_name.and_then(|name| civ5_unit_type(name)) {
+            Some(type_) => type_,
+            None => continue,
+        };
+        units.push(ScenarioUnit {
+            type_: type_,
+            owner: record[UNIT_OWNER_OFFSET],
+            pos: pos,
+        });
+    }
+    let mut cities = Vec::new();
+    if !city_positions.is_empty() {
+        let city_count = *city_positions.keys().max().unwrap() as usize + 1;
+        let record_len = city_data.len() / city_count;
+        if record_len < CITY_NAME_LEN + 4 {
+            return Err(MapLoadError::UnexpectedEof);
+        }
+        for (city_id, record) in city_data.chunks(record_len).take(city_count).enumerate() {
+            let pos = match city_positions.get(&(city_id as u16)) {
+                Some(pos) if record.len() >= CITY_NAME_LEN + 4 => *pos,
+                _ => continue,
+            };
+            let name: Vec<u8> = record[..CITY_NAME_LEN]
+                                    .iter()
+                                    .cloned()
+                                    .take_while(|&b| b != 0)
+                                    .collect();
+            cities.push(ScenarioCity {
+                name: decode_text(&name),
+                owner: record[CITY_NAME_LEN],
+                population: record[CITY_NAME_LEN + 2] as u16 |
+                            (record[CITY_NAME_LEN + 3] as u16) << 8,
+                pos: pos,
+            });
+        }
+    }
+    Ok(Some(Scenario {
+        max_turns: sh.max_turns,
+        start_year: sh.start_year,
+        player_count: sh.player_count,
+        city_state_count: sh.city_state_count,
+        team_count: sh.team_count,
+        improvements: improvements,
+        unit_types: unit_types,
+        units: units,
+        cities: cities,
+        start_positions: start_positions,
+    }))
+}
+
+/// Summary of a Civ5Map file, read from its header.
+pub struct MapInfo {
+    pub path: PathBuf,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub playercount: u8,
+}
+
+/// Reads the header of the Civ5Map file at `path`, without loading its tiles.
+pub fn read_civ5map_info(path: &Path) -> Result<MapInfo, MapLoadError> {
+    let mut fp = try!(File::open(path));
+    let mh = try!(load_map_header(&mut fp));
+    Ok(MapInfo {
+        path: path.to_path_buf(),
+        // Header strings are null-terminated.
+        name: mh.name.trim_end_matches('\0').to_owned(),
+        width: mh.width,
+        height: mh.height,
+        playercount: mh.playercount,
+    })
+}
+
+/// Returns info about all Civ5Map files in directory `dir`, sorted by file name.
+///
+/// Files with an unreadable header are left out. If `dir` can't be read, returns an empty list.
+pub fn list_civ5maps(dir: &Path) -> Vec<MapInfo> {
+    let mut paths: Vec<PathBuf> = match read_dir(dir) {
+        Ok(entries) => {
+            entries.filter_map(|e| e.ok())
+                   .map(|e| e.path())
+                   .filter(|p| p.extension().map_or(false, |ext| ext == "Civ5Map"))
+                   .collect()
+        }
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    paths.iter().filter_map(|p| read_civ5map_info(p).ok()).collect()
+}
+
+/// Loads the terrain of the Civ5Map file at `path`.
+///
+/// # Examples
+///
+/// ```
+/// use std::env;
+/// use std::fs::File;
+/// use std::io::{Read, Write};
+/// use civng_core::civ5map::{save_civ5map, load_civ5map, MapLoadError};
+/// use civng_core::terrain::{Terrain, TerrainMap};
+///
+/// let path = env::temp_dir().join("civng-doctest-errors.Civ5Map");
+/// save_civ5map(&TerrainMap::new(2, 2, vec![Terrain::Grassland; 4]), &path).unwrap();
+/// let mut bytes = Vec::new();
+/// File::open(&path).unwrap().read_to_end(&mut bytes).unwrap();
+/// let load = |bytes: &[u8]| {
+///     File::create(&path).unwrap().write_all(bytes).unwrap();
+///     load_civ5map(&path)
+/// };
+/// match load(&bytes[..bytes.len() - 1]) {
+///     Err(MapLoadError::UnexpectedEof) => (),
+///     _ => panic!("A truncated map loads"),
+/// }
+/// let mut unknown_version = bytes.clone();
+/// unknown_version[0] = 0x0d;
+/// match load(&unknown_version) {
+///     Err(MapLoadError::UnsupportedVersion(13)) => (),
+///     _ => panic!("A version 13 map loads"),
+/// }
+/// // The high bit of the version byte flags scenario maps.
+/// let mut scenario = bytes.clone();
+/// scenario[0] = 0x8c;
+/// assert!(load(&scenario).is_ok());
+/// // The terrain list follows the 42 bytes of the fixed-size part of the header.
+/// let mut invalid_text = bytes.clone();
+/// invalid_text[42] = 0xff;
+/// match load(&invalid_text) {
+///     Err(MapLoadError::InvalidUtf8) => (),
+///     _ => panic!("A map with an invalid terrain name loads"),
+/// }
+/// ```
+pub fn load_civ5map(path: &Path) -> Result<TerrainMap, MapLoadError> {
+    let mut fp = try!(File::open(path));
+    let mh = try!(load_map_header(&mut fp));
+    let tiles = try!(load_map_tiles(&mut fp, mh.width.saturating_mul(mh.height)));
+    build_terrain_map(&mh, &tiles)
+}
+
+/// Loads the terrain of the Civ5Map file at `path` along with its scenario data, if any.
+///
+/// # Examples
+///
+/// ```
+/// extern crate byteorder;
+/// extern crate civng_core;
+///
+/// use std::env;
+/// use std::fs::File;
+/// use std::io::{Read, Write};
+/// use std::path::Path;
+/// use byteorder::{ByteOrder, LittleEndian};
+/// use civng_core::civ5map::load_civ5map_scenario;
+/// use civng_core::hexpos::OffsetPos;
+/// use civng_core::unit::UnitType;
+///
+/// let path = Path::new("../resources/duel-scenario.Civ5Map");
+/// let (terrain, scenario) = load_civ5map_scenario(path).unwrap();
+/// assert_eq!(terrain.size(), (40, 24));
+/// let scenario = scenario.unwrap();
+/// assert_eq!(scenario.start_positions,
+///            vec![Some(OffsetPos::new(8, 9).to_pos()), Some(OffsetPos::new(16, 10).to_pos())]);
+/// let cities: Vec<_> = scenario.cities.iter().map(|c| (&c.name[..], c.owner)).collect();
+/// assert_eq!(cities, vec![("Rome", 0), ("Carthage", 1)]);
+/// // We have no great generals, so the scenario's one is left out.
+/// assert_eq!(scenario.units.len(), 4);
+/// let horseman = &scenario.units[3];
+/// assert!(horseman.type_ == UnitType::Horseman);
+/// assert_eq!((horseman.owner, horseman.pos), (1, OffsetPos::new(17, 10).to_pos()));
+/// // Maps without the scenario flag have no scenario data.
+/// let path = Path::new("../resources/pangea-duel.Civ5Map");
+/// assert!(load_civ5map_scenario(path).unwrap().1.is_none());
+///
+/// // The file ends with the record of the last player, whose start position is 12 bytes from the
+/// // end. Start positions outside the map are left out.
+/// let mut bytes = Vec::new();
+/// File::open("../resources/duel-scenario.Civ5Map").unwrap().read_to_end(&mut bytes).unwrap();
+/// let path = env::temp_dir().join("civng-doctest-start-positions.Civ5Map");
+/// let start_positions = |x: u32, y: u32| {
+///     let offset = bytes.len() - 12;
+///     let mut bytes = bytes.clone();
+///     LittleEndian::write_u32(&mut bytes[offset..], x);
+///     LittleEndian::write_u32(&mut bytes[offset + 4..], y);
+///     File::create(&path).unwrap().write_all(&bytes).unwrap();
+///     load_civ5map_scenario(&path).unwrap().1.unwrap().start_positions
+/// };
+/// assert_eq!(start_positions(39, 23)[1], Some(OffsetPos::new(39, 23).to_pos()));
+/// assert_eq!(start_positions(40, 10)[1], None);
+/// assert_eq!(start_positions(16, 24)[1], None);
+/// // Unplaced players have -1 coordinates, which would wrap around into the map if we cast them.
+/// assert_eq!(start_positions(0xffffffff, 0xffffffff)[1], None);
+/// assert_eq!(start_positions(0x80000000, 10)[1], None);
+/// ```
+pub fn load_civ5map_scenario(path: &Path)
+                             -> Result<(TerrainMap, Option<Scenario>), MapLoadError> {
+    let mut fp = try!(File::open(path));
+    let mh = try!(load_map_header(&mut fp));
+    let tiles = try!(load_map_tiles(&mut fp, mh.width.saturating_mul(mh.height)));
+    let terrainmap = try!(build_terrain_map(&mh, &tiles));
+    let scenario = try!(load_scenario(&mut fp, &mh));
+    Ok((terrainmap, scenario))
+}
+
+/// Natural wonders which are mountain ranges. They're in the second feature list of maps.
+const MOUNTAIN_WONDERS: [&'static str; 8] = ["FEATURE_FUJI",
+                                             "FEATURE_MESA",
+                                             "FEATURE_VOLCANO",
+                                             "FEATURE_GIBRALTAR",
+                                             "FEATURE_SRI_PADA",
+                                             "FEATURE_MT_SINAI",
+                                             "FEATURE_MT_KAILASH",
+                                             "FEATURE_ULURU"];
+
+fn build_terrain_map(mh: &MapHeader, tiles: &[MapTile]) -> Result<TerrainMap, MapLoadError> {
+    let mut mapdata: Vec<Terrain> = Vec::new();
+    let name2terrain = HashMap::<&str, Terrain>::from_iter(vec![
+            ("TERRAIN_COAST", Terrain::Coast),
+            ("TERRAIN_OCEAN", Terrain::Ocean),
+            ("TERRAIN_GRASS", Terrain::Grassland),
+            ("TERRAIN_PLAINS", Terrain::Plain),
+            ("TERRAIN_DESERT", Terrain::Desert),
+        ]);
+    for tile in tiles.iter() {
+        let name = match mh.terrain.get(tile.terrain_id as usize) {
+            Some(name) => name,
+            None => return Err(MapLoadError::InvalidTerrainId(tile.terrain_id)),
+        };
+        let terrain = match tile.elevation {
+            1 => Terrain::Hill,
+            2 => Terrain::Mountain,
+            _ => {
+                match name2terrain.get(&name[..]) {
+                    Some(t) => *t,
+                    None => Terrain::Desert,
+                }
+            }
+        };
+        mapdata.push(terrain);
+    }
+    let mut result = TerrainMap::new(mh.width as i32, mh.height as i32, mapdata);
+    // Civ5 maps have no lake terrain: lakes are small bodies of coast.
+    result.mark_lakes();
+    for (index, tile) in tiles.iter().enumerate() {
+        let feature1 = mh.features1.get(tile.feature1_id as usize).map(|s| &s[..]);
+        let feature2 = mh.features2.get(tile.feature2_id as usize).map(|s| &s[..]);
+        let feature = if feature1 == Some("FEATURE_ICE") {
+            Some(Feature::Ice)
+        } else if feature1 == Some("FEATURE_FOREST") {
+            Some(Feature::Forest)
+        } else if feature1 == Some("FEATURE_JUNGLE") {
+            Some(Feature::Jungle)
+        } else if feature1 == Some("FEATURE_MARSH") {
+            Some(Feature::Marsh)
+        } else if feature2.map_or(false, |name| MOUNTAIN_WONDERS.contains(&name)) {
+            Some(Feature::MountainRange)
+        } else {
+            None
+        };
+        if feature.is_some() {
+            let (x, y) = (index as i32 % result.size().0, index as i32 / result.size().0);
+            result.set_feature(OffsetPos::new(x, y).to_pos(), feature);
+        }
+    }
+    // We only have the strategic resources needed to train units.
+    for (index, tile) in tiles.iter().enumerate() {
+        let resource = match mh.resources.get(tile.resource_id as usize).map(|s| &s[..]) {
+            Some("RESOURCE_HORSE") => Resource::Horses,
+            Some("RESOURCE_IRON") => Resource::Iron,
+            _ => continue,
+        };
+        let (x, y) = (index as i32 % result.size().0, index as i32 / result.size().0);
+        result.set_resource(OffsetPos::new(x, y).to_pos(), Some(resource));
+    }
+    // River flags tell along which of the edges of `RIVER_EDGES` rivers run.
+    for (index, tile) in tiles.iter().enumerate() {
+        let (x, y) = (index as i32 % result.size().0, index as i32 / result.size().0);
+        result.set_river_flags(OffsetPos::new(x, y).to_pos(), tile.river_flags & RIVER_FLAGS_MASK);
+    }
+    Ok(result)
+}
+
+fn push_u32(buf: &mut Vec<u8>, n: u32) {
+    let mut bytes = [0; 4];
+    LittleEndian::write_u32(&mut bytes, n);
+    buf.extend_from_slice(&bytes);
+}
+
+/// Returns `strings` as a null-terminated string list.
+fn str_list_bytes(strings: &[&str]) -> Vec<u8> {
+    let mut result = Vec::new();
+    for s in strings {
+        result.extend_from_slice(s.as_bytes());
+        result.push(0);
+    }
+    result
+}
+
+/// Saves `map` as a minimal Civ5Map file at `path`.
+///
+/// The file has no resources or scenario data and our terrain types are mapped to their closest
+/// Civ5 equivalent: lakes are coast, hills are grassland hills and mountains, as well as mountain
+/// ranges, are `TERRAIN_MOUNTAIN`. The map is named after the file.
+///
+/// # Examples
+///
+/// ```
+/// use std::env;
+/// use civng_core::civ5map::{save_civ5map, load_civ5map};
+/// use civng_core::terrain::{Terrain, TerrainMap, Feature};
+/// use civng_core::hexpos::{Pos, Direction};
+///
+/// let mut map = TerrainMap::new(2, 2, vec![Terrain::Lake, Terrain::Hill,
+///                                          Terrain::Mountain, Terrain::Desert]);
+/// map.set_feature(Pos::origin(), Some(Feature::Ice));
+/// map.set_river(Pos::origin(), Direction::South, true);
+/// let path = env::temp_dir().join("civng-doctest.Civ5Map");
+/// save_civ5map(&map, &path).unwrap();
+/// let loaded = load_civ5map(&path).unwrap();
+/// assert_eq!(loaded.size(), (2, 2));
+/// assert!(loaded.tiles().map(|(_, t)| t).eq(map.tiles().map(|(_, t)| t)));
+/// assert!(loaded.get_feature(Pos::origin()) == Some(Feature::Ice));
+/// let south = Pos::origin().neighbor(Direction::South);
+/// assert!(loaded.has_river_between(Pos::origin(), south));
+/// assert!(!loaded.has_river_between(Pos::origin(), Pos::origin().neighbor(Direction::SouthEast)));
+/// ```
+pub fn save_civ5map(map: &TerrainMap, path: &Path) -> io::Result<()> {
+    const TERRAIN_NAMES: [&'static str; 6] = ["TERRAIN_GRASS",
+                                              "TERRAIN_PLAINS",
+                                              "TERRAIN_DESERT",
+                                              "TERRAIN_COAST",
+                                              "TERRAIN_MOUNTAIN",
+                                              "TERRAIN_OCEAN"];
+    // No resource, feature or wonder on a tile.
+    const NONE_ID: u8 = 0xff;
+    // World size strings are padded to this length.
+    const WORLD_SIZE_LEN: usize = 64;
+
+    let (width, height) = map.size();
+    let terrain_list = str_list_bytes(&TERRAIN_NAMES);
+    let feature_list = str_list_bytes(&["FEATURE_ICE",
+                                        "FEATURE_FOREST",
+                                        "FEATURE_JUNGLE",
+                                        "FEATURE_MARSH"]);
+    let name = path.file_name().map_or(String::new(), |s| s.to_string_lossy().into_owned());
+    let name = str_list_bytes(&[&name]);
+    let description = str_list_bytes(&[""]);
+    let mut world_size = "WORLDSIZE_STANDARD".as_bytes().to_vec();
+    world_size.resize(WORLD_SIZE_LEN, 0);
+
+    let mut buf = Vec::new();
+    buf.push(MAX_SUPPORTED_VERSION);
+    push_u32(&mut buf, width as u32);
+    push_u32(&mut buf, height as u32);
+    buf.push(0); // playercount
+    push_u32(&mut buf, 0); // flags
+    push_u32(&mut buf, terrain_list.len() as u32);
+    push_u32(&mut buf, feature_list.len() as u32); // features1
+    push_u32(&mut buf, 0); // features2
+    push_u32(&mut buf, 0); // resources
+    push_u32(&mut buf, 0); // mod data
+    push_u32(&mut buf, name.len() as u32);
+    push_u32(&mut buf, description.len() as u32);
+    buf.extend_from_slice(&terrain_list);
+    buf.extend_from_slice(&feature_list);
+    buf.extend_from_slice(&name);
+    buf.extend_from_slice(&description);
+    push_u32(&mut buf, world_size.len() as u32);
+    buf.extend_from_slice(&world_size);
+    for (pos, terrain) in map.tiles() {
+        let (terrain_id, elevation) = match terrain {
+            Terrain::Grassland => (0, 0),
+            Terrain::Plain => (1, 0),
+            Terrain::Desert => (2, 0),
+            Terrain::Coast | Terrain::Lake | Terrain::OutOfBounds => (3, 0),
+            Terrain::Ocean => (5, 0),
+            Terrain::Hill => (0, 1),
+            Terrain::Mountain => (4, 2),
+        };
+        let (terrain_id, elevation, feature_id) = match map.get_feature(pos) {
+            Some(Feature::Ice) => (terrain_id, elevation, 0),
+            Some(Feature::Forest) => (terrain_id, elevation, 1),
+            Some(Feature::Jungle) => (terrain_id, elevation, 2),
+            Some(Feature::Marsh) => (terrain_id, elevation, 3),
+            Some(Feature::MountainRange) => (4, 2, NONE_ID),
+            None => (terrain_id, elevation, NONE_ID),
+        };
+        let river_flags = map.river_flags(pos);
+        buf.extend_from_slice(&[terrain_id,
+                                NONE_ID,
+                                feature_id,
+                                river_flags,
+                                elevation,
+                                0,
+                                NONE_ID,
+                                0]);
+    }
+    let mut fp = try!(File::create(path));
+    fp.write_all(&buf)
+}
@@ -0,0 +1,584 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+use std::cmp::{min, max};
+
+use num;
+
+use unit::{Unit, UnitID, UnitType, MAX_HP};
+use city::{City, CityID};
+use hexpos::Pos;
+use rng;
+
+// See http://forums.civfanatics.com/showthread.php?t=432238
+
+pub type DmgRange = (u8, u8);
+
+/// Chance, in percents, of a defender to withdraw when it's as fast as its attacker.
+const WITHDRAW_BASE_CHANCE: i16 = 50;
+/// How much the withdraw chance rises, in percents, per movement point the defender has over its
+/// attacker, and drops per movement point it lacks.
+const WITHDRAW_CHANCE_PER_MOVE: i16 = 20;
+/// Highest withdraw chance, in percents. Even the fastest skirmisher can be caught.
+const MAX_WITHDRAW_CHANCE: i16 = 90;
+/// Spread of damage ranges, before the strength multiplier and the damage penalty.
+const BASE_DMG_SPREAD: f32 = 30.0;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CombatStats {
+    pub ranged: bool,
+    /// City striking the defender, in which case `attacker_id` isn't a unit.
+    pub attacker_city: Option<CityID>,
+    pub attacker_id: UnitID,
+    pub defender_id: UnitID,
+    /// Tile the attacker, unit or city, attacks from.
+    pub attacker_pos: Pos,
+    /// Tile the defender stands on when attacked.
+    pub defender_pos: Pos,
+    pub attacker_name: String,
+    pub defender_name: String,
+    pub attacker_base_strength: u8,
+    pub defender_base_strength: u8,
+    pub attacker_starting_hp: u8,
+    pub defender_starting_hp: u8,
+    pub dmg_to_attacker: u8,
+    pub dmg_to_defender: u8,
+    pub attacker_modifiers: Vec<Modifier>,
+    pub defender_modifiers: Vec<Modifier>,
+    /// Chance, in percents, of the defender to withdraw instead of fighting. See
+    /// `withdraw_chance()`.
+    pub withdraw_chance: u8,
+    /// Free tile the defender withdraws to. Without one, it can't withdraw.
+    pub withdraw_to: Option<Pos>,
+    /// Whether the defender withdrew when the combat was rolled.
+    pub withdrew: bool,
+    /// Whether the defender of a ranged attack strikes back, as a house rule.
+    pub retaliates: bool,
+}
+
+/// How one side of a combat gets to its strength and to the damage it deals.
+///
+/// A damage range starts at a base minimum damage and spreads over a base spread. Both are scaled
+/// by a multiplier depending on how much stronger one side is than the other, then reduced by a
+/// penalty for the damage the dealing side already took.
+#[derive(Clone)]
+pub struct StrengthBreakdown {
+    pub base_strength: u8,
+    /// Each modifier, with the strength it adds to the base strength, or removes from it.
+    pub modifiers: Vec<(Modifier, f32)>,
+    /// Strength after modifiers.
+    pub strength: f32,
+    pub starting_hp: u8,
+    /// Minimum damage before the multiplier and the penalty. Ranged attacks deal less.
+    pub base_min_dmg: f32,
+    /// Spread of damage before the multiplier and the penalty.
+    pub base_dmg_spread: f32,
+    /// Multiplier given by the ratio of this side's strength to the other side's.
+    pub dmg_multiplier: f32,
+    /// Fraction of its damage this side loses to its own wounds.
+    pub hp_penalty: f32,
+    /// Range of damage this side deals. The defender of a ranged attack deals none.
+    pub dmg_range: DmgRange,
+}
+
+/// Returns the chance, in percents, of `defender` to withdraw when `attacker` attacks it in melee.
+///
+/// Only units that `can_withdraw()` do. The faster the defender compared to its attacker, the
+/// likelier it gets away.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::unit::{Unit, UnitType, Player};
+/// use civng_core::combat::withdraw_chance;
+/// use civng_core::hexpos::Pos;
+///
+/// let horseman = Unit::new(UnitType::Horseman, Player::AI(0), Pos::origin());
+/// assert_eq!(withdraw_chance(UnitType::Melee, &horseman), 70);
+/// assert_eq!(withdraw_chance(UnitType::Horseman, &horseman), 50);
+/// let melee = Unit::new(UnitType::Melee, Player::AI(0), Pos::origin());
+/// assert_eq!(withdraw_chance(UnitType::Melee, &melee), 0);
+/// ```
+pub fn withdraw_chance(attacker: UnitType, defender: &Unit) -> u8 {
+    if attacker.is_ranged() || !defender.can_withdraw() {
+        return 0;
+    }
+    let mobility = defender.type_().movements_per_turn() as i16 -
+                   attacker.movements_per_turn() as i16;
+    let chance = WITHDRAW_BASE_CHANCE + mobility * WITHDRAW_CHANCE_PER_MOVE;
+    max(min(chance, MAX_WITHDRAW_CHANCE), 0) as u8
+}
+
+/// Returns the base strengths of `attacker` and `defender` when `attacker` attacks `defender`.
+///
+/// Ranged units attack with their ranged strength. Defenders always defend with their melee
+/// strength: a ranged unit attacked in melee can't bombard back and fights like a (weak) melee
+/// unit, only dealing damage to its attacker in return. Civilians have no strength but defend
+/// with a token strength of 1 so that damage can still be computed: it's always fatal.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::unit::UnitType;
+/// use civng_core::combat::combat_strengths;
+///
+/// assert_eq!(combat_strengths(UnitType::Melee, UnitType::Melee), (8, 8));
+/// assert_eq!(combat_strengths(UnitType::Melee, UnitType::Ranged), (8, 5));
+/// assert_eq!(combat_strengths(UnitType::Ranged, UnitType::Melee), (7, 8));
+/// assert_eq!(combat_strengths(UnitType::Ranged, UnitType::Ranged), (7, 5));
+/// assert_eq!(combat_strengths(UnitType::Melee, UnitType::Worker), (8, 1));
+/// ```
+pub fn combat_strengths(attacker: UnitType, defender: UnitType) -> (u8, u8) {
+    let astrength = if attacker.is_ranged() {
+        attacker.ranged_strength()
+    } else {
+        attacker.strength()
+    };
+    (astrength, max(defender.strength(), 1))
+}
+
+impl CombatStats {
+    /// Creates stats for a combat of `attacker` against `defender`.
+    ///
+    /// Modifiers add up: a fortified unit on a hill gets both bonuses.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::combat::{CombatStats, Modifier, ModifierType};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let melee = Unit::new(UnitType::Melee, Player::Me, Pos::origin());
+    /// let ranged = Unit::new(UnitType::Ranged, Player::AI(0), Pos::origin());
+    /// let modifiers = vec![Modifier::new(25, ModifierType::Terrain),
+    ///                      Modifier::new(25, ModifierType::Fortification)];
+    /// let stats = CombatStats::new(&melee, vec![], &ranged, modifiers);
+    /// assert!(!stats.ranged);
+    /// assert_eq!(stats.defender_strength(), 7.5);
+    /// // A ranged defender strikes back in melee.
+    /// assert!(stats.dmgrange_to_attacker().1 > 0);
+    /// let stats = CombatStats::new(&ranged, vec![], &melee, vec![]);
+    /// assert!(stats.ranged);
+    /// assert_eq!(stats.dmgrange_to_attacker(), (0, 0));
+    /// ```
+    pub fn new(attacker: &Unit,
+               attacker_modifiers: Vec<Modifier>,
+               defender: &Unit,
+               defender_modifiers: Vec<Modifier>)
+               -> CombatStats {
+        let ranged = attacker.type_().is_ranged();
+        let (astrength, dstrength) = combat_strengths(attacker.type_(), defender.type_());
+        CombatStats {
+            ranged: ranged,
+            attacker_city: None,
+            attacker_id: attacker.id(),
+            defender_id: defender.id(),
+            attacker_pos: attacker.pos(),
+            defender_pos: defender.pos(),
+            attacker_name: attacker.name().to_owned(),
+            defender_name: defender.name().to_owned(),
+            attacker_base_strength: astrength,
+            defender_base_strength: dstrength,
+            attacker_starting_hp: attacker.hp(),
+            defender_starting_hp: defender.hp(),
+            dmg_to_attacker: 0,
+            dmg_to_defender: 0,
+            attacker_modifiers: attacker_modifiers,
+            defender_modifiers: defender_modifiers,
+            withdraw_chance: withdraw_chance(attacker.type_(), defender),
+            withdraw_to: None,
+            withdrew: false,
+            retaliates: false,
+        }
+    }
+
+    /// Creates stats for `city`, with a ranged strength of `strength`, striking `defender`.
+    ///
+    /// Cities always strike at full health and never take damage in return.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::city::City;
+    /// use civng_core::combat::CombatStats;
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let city = City::new("Rome", Player::Me, Pos::origin());
+    /// let melee = Unit::new(UnitType::Melee, Player::AI(0), Pos::origin());
+    /// let stats = CombatStats::city_strike(&city, 8, &melee, vec![]);
+    /// assert!(stats.ranged);
+    /// assert_eq!(stats.attacker_name, "Rome");
+    /// assert_eq!(stats.dmgrange_to_attacker(), (0, 0));
+    /// assert!(stats.dmgrange_to_defender().0 > 0);
+    /// ```
+    pub fn city_strike(city: &City,
+                       strength: u8,
+                       defender: &Unit,
+                       defender_modifiers: Vec<Modifier>)
+                       -> CombatStats {
+        CombatStats {
+            ranged: true,
+            attacker_city: Some(city.id()),
+            attacker_id: 0,
+            defender_id: defender.id(),
+            attacker_pos: city.pos(),
+            defender_pos: defender.pos(),
+            attacker_name: city.name().to_owned(),
+            defender_name: defender.name().to_owned(),
+            attacker_base_strength: strength,
+            defender_base_strength: max(defender.strength(), 1),
+            attacker_starting_hp: MAX_HP,
+            defender_starting_hp: defender.hp(),
+            dmg_to_attacker: 0,
+            dmg_to_defender: 0,
+            attacker_modifiers: Vec::new(),
+            defender_modifiers: defender_modifiers,
+            withdraw_chance: 0,
+            withdraw_to: None,
+            withdrew: false,
+            retaliates: false,
+        }
+    }
+
+    pub fn attacker_strength(&self) -> f32 {
+        apply_modifier(self.attacker_base_strength as f32,
+                       self.attacker_modifiers_total())
+    }
+
+    pub fn defender_strength(&self) -> f32 {
+        apply_modifier(self.defender_base_strength as f32,
+                       self.defender_modifiers_total())
+    }
+
+    pub fn attacker_modifiers_total(&self) -> i16 {
+        sum_modifiers(&self.attacker_modifiers)
+    }
+
+    pub fn defender_modifiers_total(&self) -> i16 {
+        sum_modifiers(&self.defender_modifiers)
+    }
+
+    pub fn dmgrange_to_attacker(&self) -> DmgRange {
+        if self.ranged && !self.retaliates {
+            (0, 0)
+        } else {
+            compute_dmg_range(self.defender_strength(),
+                              self.defender_starting_hp,
+                              self.attacker_strength(),
+                              self.ranged)
+        }
+    }
+
+    pub fn dmgrange_to_defender(&self) -> DmgRange {
+        compute_dmg_range(self.attacker_strength(),
+                          self.attacker_starting_hp,
+                          self.defender_strength(),
+                          self.ranged)
+    }
+
+    pub fn attacker_remaining_hp(&self) -> u8 {
+        if self.dmg_to_attacker > self.attacker_starting_hp {
+            0
+        } else {
+            self.attacker_starting_hp - self.dmg_to_attacker
+        }
+    }
+
+    pub fn defender_remaining_hp(&self) -> u8 {
+        if self.dmg_to_defender > self.defender_starting_hp {
+            0
+        } else {
+            self.defender_starting_hp - self.dmg_to_defender
+        }
+    }
+
+    /// Adjusts raw dice results so that they respect our combat rules.
+    ///
+    /// Only one unit can die in a combat. If both rolls are deadly, the "less dead" unit is
+    /// revived with 1 HP. Returns `(dmg_to_attacker, dmg_to_defender)`.
+    fn resolve_dmg(&self, dmg_to_attacker: u8, dmg_to_defender: u8) -> (u8, u8) {
+        let defender_hp = self.defender_starting_hp as i16 - dmg_to_defender as i16;
+        let attacker_hp = self.attacker_starting_hp as i16 - dmg_to_attacker as i16;
+        if defender_hp <= 0 && attacker_hp <= 0 {
+            if attacker_hp > defender_hp {
+                (self.attacker_starting_hp - 1, dmg_to_defender)
+            } else {
+                (dmg_to_attacker, self.defender_starting_hp - 1)
+            }
+        } else {
+            (dmg_to_attacker, dmg_to_defender)
+        }
+    }
+
+    /// Returns how the attacker gets to its strength and to the damage it deals.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::combat::{CombatStats, Modifier, ModifierType};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let attacker = Unit::new(UnitType::Melee, Player::Me, Pos::origin());
+    /// let defender = Unit::new(UnitType::Melee, Player::AI(0), Pos::origin());
+    /// let modifiers = vec![Modifier::new(25, ModifierType::Flanking)];
+    /// let stats = CombatStats::new(&attacker, modifiers, &defender, vec![]);
+    /// let breakdown = stats.attacker_breakdown();
+    /// assert_eq!(breakdown.base_strength, 8);
+    /// assert_eq!(breakdown.modifiers[0].1, 2.0);
+    /// assert_eq!(breakdown.strength, 10.0);
+    /// assert!(breakdown.dmg_multiplier > 1.0);
+    /// assert_eq!(breakdown.dmg_range, stats.dmgrange_to_defender());
+    /// assert!(stats.defender_breakdown().dmg_multiplier < 1.0);
+    /// ```
+    pub fn attacker_breakdown(&self) -> StrengthBreakdown {
+        breakdown(self.attacker_base_strength,
+                  &self.attacker_modifiers,
+                  self.attacker_starting_hp,
+                  self.defender_strength(),
+                  self.ranged,
+                  self.dmgrange_to_defender())
+    }
+
+    /// Returns how the defender gets to its strength and to the damage it deals back.
+    pub fn defender_breakdown(&self) -> StrengthBreakdown {
+        breakdown(self.defender_base_strength,
+                  &self.defender_modifiers,
+                  self.defender_starting_hp,
+                  self.attacker_strength(),
+                  self.ranged,
+                  self.dmgrange_to_attacker())
+    }
+
+    /// Chance, between `0.0` and `1.0`, of the defender to withdraw instead of fighting.
+    pub fn withdraw_odds(&self) -> f32 {
+        if self.withdraw_to.is_some() {
+            self.withdraw_chance as f32 / 100.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Computes the odds of each possible outcome of the combat.
+    ///
+    /// Damage rolls are uniformly distributed in their `DmgRange`, independently from each
+    /// other. We go through all possible roll combinations and resolve them the same way
+    /// `roll()` does. A defender that withdraws survives, and so does its attacker.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::combat::CombatStats;
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let attacker = Unit::new(UnitType::Melee, Player::Me, Pos::origin());
+    /// let defender = Unit::new(UnitType::Melee, Player::AI(0), Pos::origin());
+    /// let stats = CombatStats::new(&attacker, vec![], &defender, vec![]);
+    /// // Two fresh units of equal strength can't kill each other in a single fight.
+    /// assert_eq!(stats.outcome_probabilities().both_survive, 1.0);
+    /// ```
+    pub fn outcome_probabilities(&self) -> OutcomeProbabilities {
+        let (amin, amax) = self.dmgrange_to_attacker();
+        let (dmin, dmax) = self.dmgrange_to_defender();
+        let mut attacker_dies = 0;
+        let mut defender_dies = 0;
+        let mut total = 0;
+        for raw_to_attacker in amin..amax + 1 {
+            for raw_to_defender in dmin..dmax + 1 {
+                let (to_attacker, to_defender) = self.resolve_dmg(raw_to_attacker,
+                                                                  raw_to_defender);
+                if to_attacker >= self.attacker_starting_hp {
+                    attacker_dies += 1;
+                }
+                if to_defender >= self.defender_starting_hp {
+                    defender_dies += 1;
+                }
+                total += 1;
+            }
+        }
+        let fights = 1.0 - self.withdraw_odds();
+        let attacker_dies = fights * attacker_dies as f32 / total as f32;
+        let defender_dies = fights * defender_dies as f32 / total as f32;
+        OutcomeProbabilities {
+            attacker_dies: attacker_dies,
+            defender_dies: defender_dies,
+            both_survive: 1.0 - attacker_dies - defender_dies,
+        }
+    }
+
+    /// Rolls the outcome of the combat: whether the defender withdraws and otherwise the damage
+    /// each side takes.
+    pub fn roll(&mut self) {
+        if self.withdraw_to.is_some() && self.withdraw_chance > 0 {
+            self.withdrew = rng::roll_dice(1, 100) <= self.withdraw_chance;
+            if self.withdrew {
+                self.dmg_to_attacker = 0;
+                self.dmg_to_defender = 0;
+                return;
+            }
+        }
+        let dmg_to_attacker = roll_dice(self.dmgrange_to_attacker());
+        let dmg_to_defender = roll_dice(self.dmgrange_to_defender());
+        let (dmg_to_attacker, dmg_to_defender) = self.resolve_dmg(dmg_to_attacker,
+                                                                  dmg_to_defender);
+        self.dmg_to_attacker = dmg_to_attacker;
+        self.dmg_to_defender = dmg_to_defender;
+    }
+}
+
+/// Odds, between `0.0` and `1.0`, of each possible outcome of a combat.
+#[derive(Clone, Copy)]
+pub struct OutcomeProbabilities {
+    pub attacker_dies: f32,
+    pub defender_dies: f32,
+    pub both_survive: f32,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum ModifierType {
+    Terrain,
+    Flanking,
+    Fortification,
+    RiverCrossing,
+    CityDefense,
+    Difficulty,
+    ResourceDeficit,
+    Pantheon,
+    Promotion,
+    HomeContinent,
+    Counter,
+}
+
+impl ModifierType {
+    pub fn description(&self) -> &str {
+        match *self {
+            ModifierType::Terrain => "Terrain",
+            ModifierType::Flanking => "Flanking",
+            ModifierType::Fortification => "Fortification",
+            ModifierType::RiverCrossing => "River crossing",
+            ModifierType::CityDefense => "City defense",
+            ModifierType::Difficulty => "Difficulty",
+            ModifierType::ResourceDeficit => "Resource deficit",
+            ModifierType::Pantheon => "Pantheon",
+            ModifierType::Promotion => "Promotion",
+            ModifierType::HomeContinent => "Home continent",
+            ModifierType::Counter => "Counter",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Modifier {
+    amount: i8, // 20 == +20%
+    modtype: ModifierType,
+}
+
+impl Modifier {
+    pub fn new(amount: i8, modtype: ModifierType) -> Modifier {
+        Modifier {
+            amount: amount,
+            modtype: modtype,
+        }
+    }
+
+    pub fn amount(&self) -> i8 {
+        self.amount
+    }
+
+    pub fn description(&self) -> String {
+        format!("{:+}% {}", self.amount, self.modtype.description())
+    }
+}
+
+fn sum_modifiers(modifiers: &Vec<Modifier>) -> i16 {
+    modifiers.iter().fold(0, |acc, m| acc + m.amount as i16)
+}
+
+fn apply_modifier(strength: f32, modifier: i16) -> f32 {
+    let fmodifier = 1.0 + (modifier as f32 / 100.0);
+    strength * fmodifier
+}
+
+fn breakdown(base_strength: u8,
+             modifiers: &Vec<Modifier>,
+             hp: u8,
+             opponent_strength: f32,
+             ranged: bool,
+             dmg_range: DmgRange)
+             -> StrengthBreakdown {
+    let strength = apply_modifier(base_strength as f32, sum_modifiers(modifiers));
+    StrengthBreakdown {
+        base_strength: base_strength,
+        modifiers: modifiers.iter()
+                            .map(|m| (*m, base_strength as f32 * m.amount as f32 / 100.0))
+                            .collect(),
+        strength: strength,
+        starting_hp: hp,
+        base_min_dmg: base_min_dmg(ranged),
+        base_dmg_spread: BASE_DMG_SPREAD,
+        dmg_multiplier: dmg_multiplier(strength, opponent_strength),
+        hp_penalty: hp_penalty(hp),
+        dmg_range: dmg_range,
+    }
+}
+
+fn roll_dice(range: DmgRange) -> u8 {
+    let (min, max) = range;
+    rng::roll_dice(min, max)
+}
+
+/// Returns how much the damage dealt by a side of strength `source_strength` is scaled against a
+/// side of strength `target_strength`.
+fn dmg_multiplier(source_strength: f32, target_strength: f32) -> f32 {
+    let target_is_weak = source_strength > target_strength;
+    let (strong_strength, weak_strength) = if target_is_weak {
+        (source_strength, target_strength)
+    } else {
+        (target_strength, source_strength)
+    };
+    let r = strong_strength / weak_strength;
+    let mut m = 0.5 + num::pow(r + 3.0, 4) / 512.0;
+    if !target_is_weak {
+        m = 1.0 / m;
+    }
+    m
+}
+
+fn base_min_dmg(ranged: bool) -> f32 {
+    if ranged {
+        20.0
+    } else {
+        40.0
+    }
+}
+
+fn compute_dmg_range(source_strength: f32,
+                     source_hp: u8,
+                     target_strength: f32,
+                     ranged: bool)
+                     -> DmgRange {
+    let m = dmg_multiplier(source_strength, target_strength);
+    let mut min = apply_penalty_for_damaged_unit(base_min_dmg(ranged) * m, source_hp);
+    if min < 1.0 {
+        min = 1.0;
+    }
+    let spread = apply_penalty_for_damaged_unit(BASE_DMG_SPREAD * m, source_hp);
+    (min.floor() as u8, (min + spread).floor() as u8)
+}
+
+/// Returns the fraction of its damage a unit with `hp` hit points loses to its wounds.
+fn hp_penalty(hp: u8) -> f32 {
+    ((100 - hp) / 20) as f32 * 0.1
+}
+
+fn apply_penalty_for_damaged_unit(dealt_dmg: f32, dealer_hp: u8) -> f32 {
+    let penalty = hp_penalty(dealer_hp);
+    dealt_dmg - (dealt_dmg * penalty)
+}
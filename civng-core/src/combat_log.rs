@@ -0,0 +1,181 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! History of the combats of a game.
+//!
+//! Each combat is recorded with the damage we expected from its damage ranges and the damage that
+//! was actually rolled. Over many combats, how far rolls are from expectations tells us how lucky
+//! a player was and whether our dice behave like Civ5's.
+
+use combat::CombatStats;
+use unit::Player;
+
+/// Outcome of a single combat.
+#[derive(Clone)]
+pub struct CombatRecord {
+    pub attacker: Player,
+    pub defender: Player,
+    /// Average of the damage range to the attacker.
+    pub expected_dmg_to_attacker: f32,
+    /// Average of the damage range to the defender.
+    pub expected_dmg_to_defender: f32,
+    pub dmg_to_attacker: u8,
+    pub dmg_to_defender: u8,
+}
+
+impl CombatRecord {
+    /// Records `stats`, which have been rolled, of a combat between `attacker` and `defender`.
+    pub fn new(stats: &CombatStats, attacker: Player, defender: Player) -> CombatRecord {
+        let average = |(min, max): (u8, u8)| (min as f32 + max as f32) / 2.0;
+        CombatRecord {
+            attacker: attacker,
+            defender: defender,
+            expected_dmg_to_attacker: average(stats.dmgrange_to_attacker()),
+            expected_dmg_to_defender: average(stats.dmgrange_to_defender()),
+            dmg_to_attacker: stats.dmg_to_attacker,
+            dmg_to_defender: stats.dmg_to_defender,
+        }
+    }
+
+    /// Returns whether `player` dealt more damage than it received in this combat.
+    ///
+    /// Returns `false` if `player` wasn't part of the combat.
+    pub fn is_won_by(&self, player: Player) -> bool {
+        if self.attacker == player {
+            self.dmg_to_defender > self.dmg_to_attacker
+        } else if self.defender == player {
+            self.dmg_to_attacker > self.dmg_to_defender
+        } else {
+            false
+        }
+    }
+
+    /// Returns how much more damage `player` dealt, and how much less it received, than expected.
+    ///
+    /// A positive value means that `player` was lucky. Returns `None` if `player` wasn't part of
+    /// the combat.
+    pub fn luck_of(&self, player: Player) -> Option<f32> {
+        let to_attacker = self.dmg_to_attacker as f32 - self.expected_dmg_to_attacker;
+        let to_defender = self.dmg_to_defender as f32 - self.expected_dmg_to_defender;
+        if self.attacker == player {
+            Some(to_defender - to_attacker)
+        } else if self.defender == player {
+            Some(to_attacker - to_defender)
+        } else {
+            None
+        }
+    }
+}
+
+/// Statistics about the combats of a player.
+pub struct CombatSummary {
+    /// Number of combats the player took part in.
+    pub combats: usize,
+    /// Number of combats in which the player dealt more damage than it received.
+    pub won: usize,
+    /// Average of `CombatRecord::luck_of()` over the player's combats.
+    pub average_luck: f32,
+    /// Average difference between rolled and expected damage, over all combats of the game.
+    ///
+    /// Dice that behave should keep it close to zero.
+    pub average_deviation: f32,
+}
+
+impl CombatSummary {
+    /// Returns the percentage of combats won, or zero if there weren't any.
+    pub fn win_rate(&self) -> f32 {
+        if self.combats == 0 {
+            0.0
+        } else {
+            self.won as f32 * 100.0 / self.combats as f32
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CombatLog {
+    records: Vec<CombatRecord>,
+}
+
+impl CombatLog {
+    pub fn new() -> CombatLog {
+        CombatLog { records: Vec::new() }
+    }
+
+    pub fn records(&self) -> &[CombatRecord] {
+        &self.records[..]
+    }
+
+    pub fn record(&mut self, record: CombatRecord) {
+        self.records.push(record);
+    }
+
+    /// Summarizes the combats of `player`.
+    ///
+    /// Ranged attackers can't be damaged: they don't count in the average deviation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::combat::CombatStats;
+    /// use civng_core::combat_log::{CombatLog, CombatRecord};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let attacker = Unit::new(UnitType::Melee, Player::Me, Pos::origin());
+    /// let defender = Unit::new(UnitType::Melee, Player::AI(0), Pos::origin());
+    /// let mut stats = CombatStats::new(&attacker, vec![], &defender, vec![]);
+    /// // Both units deal between 40 and 70 damage.
+    /// stats.dmg_to_attacker = 40;
+    /// stats.dmg_to_defender = 60;
+    /// let mut log = CombatLog::new();
+    /// log.record(CombatRecord::new(&stats, Player::Me, Player::AI(0)));
+    /// let summary = log.summary(Player::Me);
+    /// assert_eq!(summary.won, 1);
+    /// assert_eq!(summary.win_rate(), 100.0);
+    /// assert_eq!(summary.average_luck, 20.0);
+    /// assert_eq!(summary.average_deviation, -5.0);
+    /// assert_eq!(log.summary(Player::AI(0)).average_luck, -20.0);
+    /// assert_eq!(log.summary(Player::AI(1)).combats, 0);
+    /// ```
+    pub fn summary(&self, player: Player) -> CombatSummary {
+        let mut combats = 0;
+        let mut won = 0;
+        let mut total_luck = 0.0;
+        let mut total_deviation = 0.0;
+        let mut rolls = 0;
+        for record in self.records.iter() {
+            if let Some(luck) = record.luck_of(player) {
+                combats += 1;
+                total_luck += luck;
+                if record.is_won_by(player) {
+                    won += 1;
+                }
+            }
+            total_deviation += record.dmg_to_defender as f32 - record.expected_dmg_to_defender;
+            rolls += 1;
+            if record.expected_dmg_to_attacker > 0.0 {
+                total_deviation += record.dmg_to_attacker as f32 - record.expected_dmg_to_attacker;
+                rolls += 1;
+            }
+        }
+        CombatSummary {
+            combats: combats,
+            won: won,
+            average_luck: if combats > 0 {
+                total_luck / combats as f32
+            } else {
+                0.0
+            },
+            average_deviation: if rolls > 0 {
+                total_deviation / rolls as f32
+            } else {
+                0.0
+            },
+        }
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Combat bonuses of units against others, such as spearmen against mounted units.
+//!
+//! They're defined in a content file, `resources/counters.txt` for the built-in ones, which works
+//! like the content file of buildings. Each line is `bonus = unit, opponent, amount`: units
+//! matching `unit` get a bonus of `amount` percents, which can be negative, when they fight units
+//! matching `opponent`, whether they attack or defend.
+//!
+//! ```text
+//! bonus = Melee, Mounted, 50
+//! bonus = Archery, City, 100
+//! ```
+//!
+//! Units are matched by type, such as `Horseman`, or by class, such as `Mounted`. `City` matches
+//! opponents standing in a city, which only happens when we attack them. When several bonuses
+//! apply, they add up.
+
+use std::cmp::{min, max};
+use std::io;
+
+use unit::{Unit, UnitType, UnitClass};
+
+/// Built-in content file.
+const BUILTIN_COUNTERS: &'static str = include_str!("../../resources/counters.txt");
+
+/// Units a bonus applies to, or against.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CounterTarget {
+    Type(UnitType),
+    Class(UnitClass),
+    /// Units standing in a city.
+    City,
+}
+
+impl CounterTarget {
+    /// Returns the target named `name` in content files.
+    pub fn from_name(name: &str) -> Option<CounterTarget> {
+        if name == "City" {
+            return Some(CounterTarget::City);
+        }
+        match UnitType::all().iter().find(|t| t.name() == name) {
+            Some(&t) => Some(CounterTarget::Type(t)),
+            None => UnitClass::from_name(name).map(CounterTarget::Class),
+        }
+    }
+
+    /// Returns whether `unit`, which stands in a city if `in_city`, is targeted.
+    fn matches(&self, unit: &Unit, in_city: bool) -> bool {
+        match *self {
+            CounterTarget::Type(t) => unit.type_() == t,
+            CounterTarget::Class(c) => unit.type_().class() == c,
+            CounterTarget::City => in_city,
+        }
+    }
+}
+
+/// A bonus of some units against others.
+#[derive(Clone, Copy)]
+pub struct Counter {
+    pub unit: CounterTarget,
+    pub opponent: CounterTarget,
+    /// Combat bonus, in percents.
+    pub bonus: i8,
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Bonuses of a content file, in the order they're defined.
+#[derive(Clone)]
+pub struct Counters {
+    counters: Vec<Counter>,
+}
+
+impl Counters {
+    /// Parses content file `contents`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::counters::{Counters, CounterTarget};
+    /// use civng_core::unit::UnitType;
+    ///
+    /// let counters = Counters::parse("bonus = Horseman, Archery, 25\n").unwrap();
+    /// assert!(counters.all()[0].unit == CounterTarget::Type(UnitType::Horseman));
+    /// assert_eq!(counters.all()[0].bonus, 25);
+    /// assert!(Counters::parse("bonus = Horseman, Dragon, 25\n").is_err());
+    /// // Only opponents can stand in a city.
+    /// assert!(Counters::parse("bonus = City, Mounted, 25\n").is_err());
+    /// assert!(Counters::parse("bonus = Horseman, Archery, 200\n").is_err());
+    /// ```
+    pub fn parse(contents: &str) -> io::Result<Counters> {
+        let mut counters = Vec::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let kind = parts.next().unwrap().trim();
+            let value = match parts.next() {
+                Some(v) => v.trim(),
+                None => return Err(invalid_data(&format!("Invalid bonus line: {}", line))),
+            };
+            if kind != "bonus" {
+                return Err(invalid_data(&format!("Unknown content kind: {}", kind)));
+            }
+            let invalid_value = || invalid_data(&format!("Invalid bonus: {}", value));
+            let fields: Vec<&str> = value.split(',').map(|f| f.trim()).collect();
+            if fields.len() != 3 {
+                return Err(invalid_value());
+            }
+            let unit = match CounterTarget::from_name(fields[0]) {
+                Some(CounterTarget::City) | None => return Err(invalid_value()),
+                Some(t) => t,
+            };
+            let opponent = try!(CounterTarget::from_name(fields[1]).ok_or_else(&invalid_value));
+            let bonus = try!(fields[2].parse().map_err(|_| invalid_value()));
+            counters.push(Counter {
+                unit: unit,
+                opponent: opponent,
+                bonus: bonus,
+            });
+        }
+        Ok(Counters { counters: counters })
+    }
+
+    /// Returns the bonuses of the built-in content file.
+    pub fn builtin() -> Counters {
+        Counters::parse(BUILTIN_COUNTERS).unwrap()
+    }
+
+    pub fn all(&self) -> &[Counter] {
+        &self.counters[..]
+    }
+
+    /// Returns the bonus, in percents, of `unit` fighting `opponent`, which stands in a city if
+    /// `opponent_in_city`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::counters::Counters;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let counters = Counters::parse("bonus = Melee, Mounted, 50\nbonus = Infantry, City, -20\n")
+    ///                    .unwrap();
+    /// let melee = Unit::new(UnitType::Melee, Player::Me, Pos::origin());
+    /// let horseman = Unit::new(UnitType::Horseman, Player::AI(0), Pos::origin());
+    /// assert_eq!(counters.bonus(&melee, &horseman, false), 50);
+    /// assert_eq!(counters.bonus(&melee, &horseman, true), 30);
+    /// assert_eq!(counters.bonus(&horseman, &melee, false), 0);
+    /// ```
+    pub fn bonus(&self, unit: &Unit, opponent: &Unit, opponent_in_city: bool) -> i8 {
+        let total: i16 = self.counters
+                             .iter()
+                             .filter(|c| {
+                                 c.unit.matches(unit, false) &&
+                                 c.opponent.matches(opponent, opponent_in_city)
+                             })
+                             .map(|c| c.bonus as i16)
+                             .sum();
+        max(min(total, i8::max_value() as i16), i8::min_value() as i16) as i8
+    }
+}
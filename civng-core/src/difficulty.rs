@@ -0,0 +1,157 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Difficulty levels, named after Civ5's.
+//!
+//! Difficulty doesn't make the AI smarter. It gives AI players a combat modifier, a production
+//! modifier and, on higher levels, extra starting units. `Prince` is the level where the AI gets
+//! no advantage at all.
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Difficulty {
+    Settler,
+    Chieftain,
+    Warlord,
+    Prince,
+    King,
+    Emperor,
+    Immortal,
+    Deity,
+}
+
+impl Difficulty {
+    pub fn all() -> [Difficulty; 8] {
+        [Difficulty::Settler,
+         Difficulty::Chieftain,
+         Difficulty::Warlord,
+         Difficulty::Prince,
+         Difficulty::King,
+         Difficulty::Emperor,
+         Difficulty::Immortal,
+         Difficulty::Deity]
+    }
+
+    /// Name of the level in config, scenario and save files as well as on the command line.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Difficulty::Settler => "settler",
+            Difficulty::Chieftain => "chieftain",
+            Difficulty::Warlord => "warlord",
+            Difficulty::Prince => "prince",
+            Difficulty::King => "king",
+            Difficulty::Emperor => "emperor",
+            Difficulty::Immortal => "immortal",
+            Difficulty::Deity => "deity",
+        }
+    }
+
+    /// Returns the level named `name`, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::difficulty::Difficulty;
+    ///
+    /// assert_eq!(Difficulty::from_name("king"), Some(Difficulty::King));
+    /// assert_eq!(Difficulty::from_name("easy"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        Difficulty::all().iter().find(|d| d.name() == name).cloned()
+    }
+
+    /// Combat modifier, in percents, AI units get in all their combats.
+    pub fn ai_combat_bonus(&self) -> i8 {
+        match *self {
+            Difficulty::Settler => -30,
+            Difficulty::Chieftain => -20,
+            Difficulty::Warlord => -10,
+            Difficulty::Prince => 0,
+            Difficulty::King => 10,
+            Difficulty::Emperor => 20,
+            Difficulty::Immortal => 30,
+            Difficulty::Deity => 40,
+        }
+    }
+
+    /// Production modifier, in percents, of the cities of AI players.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::city::{City, Cities};
+    /// use civng_core::difficulty::Difficulty;
+    /// use civng_core::terrain::{Terrain, TerrainMap};
+    /// use civng_core::unit::Player;
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// assert_eq!(Difficulty::Prince.ai_production_bonus(), 0);
+    /// let terrain = TerrainMap::new(1, 1, vec![Terrain::Hill]);
+    /// let mut cities = Cities::new();
+    /// cities.add_city(City::with_state("Carthage", Player::AI(0), Pos::origin(), 1, 0, 0));
+    /// assert_eq!(cities.city_yield(1, &terrain).production, 2);
+    /// cities.set_production_bonus(Player::AI(0), Difficulty::Deity.ai_production_bonus());
+    /// assert_eq!(cities.city_yield(1, &terrain).production, 3);
+    /// cities.set_production_bonus(Player::AI(0), Difficulty::Settler.ai_production_bonus());
+    /// assert_eq!(cities.city_yield(1, &terrain).production, 1);
+    /// ```
+    pub fn ai_production_bonus(&self) -> i8 {
+        match *self {
+            Difficulty::Settler => -50,
+            Difficulty::Chieftain => -30,
+            Difficulty::Warlord => -15,
+            Difficulty::Prince => 0,
+            Difficulty::King => 15,
+            Difficulty::Emperor => 25,
+            Difficulty::Immortal => 40,
+            Difficulty::Deity => 50,
+        }
+    }
+
+    /// Number of units each AI player gets on top of the default ones.
+    pub fn ai_extra_units(&self) -> u8 {
+        match *self {
+            Difficulty::Settler | Difficulty::Chieftain | Difficulty::Warlord |
+            Difficulty::Prince => 0,
+            Difficulty::King | Difficulty::Emperor => 1,
+            Difficulty::Immortal => 2,
+            Difficulty::Deity => 3,
+        }
+    }
+}
+
+/// Levels are serialized by name.
+///
+/// # Examples
+///
+/// ```
+/// extern crate serde_json;
+/// extern crate civng_core;
+///
+/// use civng_core::difficulty::Difficulty;
+///
+/// # fn main() {
+/// assert_eq!(serde_json::to_string(&Difficulty::King).unwrap(), "\"king\"");
+/// assert_eq!(serde_json::from_str::<Difficulty>("\"deity\"").unwrap(), Difficulty::Deity);
+/// assert!(serde_json::from_str::<Difficulty>("\"easy\"").is_err());
+/// # }
+/// ```
+impl Serialize for Difficulty {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Difficulty {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Difficulty, D::Error> {
+        let name = try!(String::deserialize(deserializer));
+        Difficulty::from_name(&name)
+            .ok_or_else(|| de::Error::custom(format!("Invalid difficulty: {}", name)))
+    }
+}
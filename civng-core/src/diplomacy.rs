@@ -0,0 +1,279 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Relations between players.
+//!
+//! Players start at war with each other. At war, they fight and can go anywhere. Once they made
+//! peace, they can't attack each other anymore and their units can't enter each other's territory,
+//! the tiles their cities own, unless they agreed to open their borders to each other.
+//!
+//! Wars wear players out. Once the fighting started, or once war was declared, every turn of war
+//! and every unit lost to it cost happiness (see `war_weariness()`), so long and bloody wars end
+//! up hurting the growth of cities.
+
+use std::cmp::min;
+
+use unit::Player;
+
+/// Every that many turns of war cost one happiness.
+const WEARINESS_TURNS: u16 = 10;
+/// Every that many units lost to a war cost one happiness.
+const WEARINESS_LOSSES: u16 = 2;
+/// Most happiness a single war can cost a player.
+const MAX_WAR_WEARINESS: u16 = 5;
+
+/// How long a war between two players has been going on and what it cost each of them.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct War {
+    pub players: (Player, Player),
+    /// Turns since the war started.
+    pub turns: u16,
+    /// Units each of `players` lost to the war, in the same order.
+    pub losses: (u16, u16),
+}
+
+impl War {
+    fn new(a: Player, b: Player) -> War {
+        War {
+            players: (a, b),
+            turns: 0,
+            losses: (0, 0),
+        }
+    }
+
+    fn involves(&self, a: Player, b: Player) -> bool {
+        self.players == (a, b) || self.players == (b, a)
+    }
+
+    /// Units `player`, who takes part in the war, lost to it.
+    pub fn losses_of(&self, player: Player) -> u16 {
+        if self.players.0 == player {
+            self.losses.0
+        } else {
+            self.losses.1
+        }
+    }
+
+    /// Happiness the war costs `player`, who takes part in it.
+    pub fn weariness(&self, player: Player) -> u16 {
+        let weariness = self.turns / WEARINESS_TURNS + self.losses_of(player) / WEARINESS_LOSSES;
+        min(weariness, MAX_WAR_WEARINESS)
+    }
+}
+
+/// Whether each pair of players is at peace and has open borders.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Diplomacy {
+    /// Pairs of players at peace, each pair being listed once.
+    peace: Vec<(Player, Player)>,
+    /// Pairs of players who opened their borders to each other, each pair being listed once.
+    open_borders: Vec<(Player, Player)>,
+    /// Wars that started, by a declaration or by a first fight. Players start at war without
+    /// having fought, which doesn't wear them out.
+    wars: Vec<War>,
+    /// Peace offers that weren't answered yet, the player making the offer coming first.
+    peace_offers: Vec<(Player, Player)>,
+}
+
+fn find_pair(pairs: &[(Player, Player)], a: Player, b: Player) -> Option<usize> {
+    pairs.iter().position(|&pair| pair == (a, b) || pair == (b, a))
+}
+
+impl Diplomacy {
+    pub fn new() -> Diplomacy {
+        Diplomacy {
+            peace: Vec::new(),
+            open_borders: Vec::new(),
+            wars: Vec::new(),
+            peace_offers: Vec::new(),
+        }
+    }
+
+    /// Returns whether `a` and `b` are at war. Nobody is at war with themselves.
+    pub fn at_war(&self, a: Player, b: Player) -> bool {
+        a != b && find_pair(&self.peace, a, b).is_none()
+    }
+
+    pub fn has_open_borders(&self, a: Player, b: Player) -> bool {
+        find_pair(&self.open_borders, a, b).is_some()
+    }
+
+    /// Returns whether units of `player` can enter the territory of `owner`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::diplomacy::Diplomacy;
+    /// use civng_core::unit::Player;
+    ///
+    /// let mut diplomacy = Diplomacy::new();
+    /// assert!(diplomacy.can_enter(Player::Me, Player::AI(0)));
+    /// diplomacy.make_peace(Player::Me, Player::AI(0));
+    /// assert!(!diplomacy.can_enter(Player::AI(0), Player::Me));
+    /// assert!(diplomacy.set_open_borders(Player::AI(0), Player::Me, true));
+    /// assert!(diplomacy.can_enter(Player::Me, Player::AI(0)));
+    /// // Declaring war closes borders, but it doesn't matter anymore.
+    /// diplomacy.declare_war(Player::Me, Player::AI(0));
+    /// assert!(!diplomacy.has_open_borders(Player::Me, Player::AI(0)));
+    /// assert!(!diplomacy.set_open_borders(Player::Me, Player::AI(0), true));
+    /// ```
+    pub fn can_enter(&self, player: Player, owner: Player) -> bool {
+        player == owner || self.at_war(player, owner) || self.has_open_borders(player, owner)
+    }
+
+    /// Makes `a` and `b` make peace, which ends their war and the peace offers between them.
+    pub fn make_peace(&mut self, a: Player, b: Player) {
+        if self.at_war(a, b) {
+            self.peace.push((a, b));
+        }
+        self.wars.retain(|w| !w.involves(a, b));
+        self.peace_offers.retain(|&(x, y)| (x, y) != (a, b) && (x, y) != (b, a));
+    }
+
+    /// Makes `a` and `b` go to war, which ends their open borders agreement if they had one.
+    pub fn declare_war(&mut self, a: Player, b: Player) {
+        if let Some(index) = find_pair(&self.peace, a, b) {
+            self.peace.remove(index);
+            self.wars.push(War::new(a, b));
+        }
+        self.set_open_borders(a, b, false);
+    }
+
+    /// Returns the war between `a` and `b`, if it started.
+    pub fn war(&self, a: Player, b: Player) -> Option<&War> {
+        self.wars.iter().find(|w| w.involves(a, b))
+    }
+
+    /// Wars that started.
+    pub fn wars(&self) -> &[War] {
+        &self.wars[..]
+    }
+
+    /// Restores a war, for example when loading a saved game.
+    pub fn restore_war(&mut self, war: War) {
+        let (a, b) = war.players;
+        self.wars.retain(|w| !w.involves(a, b));
+        self.wars.push(war);
+    }
+
+    /// Records that `loser` lost a unit to `winner`, starting their war if it didn't yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::diplomacy::Diplomacy;
+    /// use civng_core::unit::Player;
+    ///
+    /// let mut diplomacy = Diplomacy::new();
+    /// assert!(diplomacy.war(Player::Me, Player::AI(0)).is_none());
+    /// diplomacy.add_loss(Player::Me, Player::AI(0));
+    /// diplomacy.add_loss(Player::Me, Player::AI(0));
+    /// assert_eq!(diplomacy.war_weariness(Player::Me), 1);
+    /// assert_eq!(diplomacy.war_weariness(Player::AI(0)), 0);
+    /// for _ in 0..20 {
+    ///     diplomacy.pass_turn();
+    /// }
+    /// assert_eq!(diplomacy.war(Player::AI(0), Player::Me).unwrap().turns, 20);
+    /// assert_eq!(diplomacy.war_weariness(Player::Me), 3);
+    /// assert_eq!(diplomacy.war_weariness(Player::AI(0)), 2);
+    /// diplomacy.make_peace(Player::Me, Player::AI(0));
+    /// assert_eq!(diplomacy.war_weariness(Player::Me), 0);
+    /// ```
+    pub fn add_loss(&mut self, loser: Player, winner: Player) {
+        if !self.at_war(loser, winner) {
+            return;
+        }
+        if self.war(loser, winner).is_none() {
+            self.wars.push(War::new(loser, winner));
+        }
+        let war = self.wars.iter_mut().find(|w| w.involves(loser, winner)).unwrap();
+        if war.players.0 == loser {
+            war.losses.0 += 1;
+        } else {
+            war.losses.1 += 1;
+        }
+    }
+
+    /// Makes every war last one more turn.
+    pub fn pass_turn(&mut self) {
+        for war in self.wars.iter_mut() {
+            war.turns += 1;
+        }
+    }
+
+    /// Happiness `player` loses to all the wars it takes part in.
+    pub fn war_weariness(&self, player: Player) -> u16 {
+        self.wars
+            .iter()
+            .filter(|w| w.players.0 == player || w.players.1 == player)
+            .map(|w| w.weariness(player))
+            .sum()
+    }
+
+    /// Records that `from` offers peace to `to`, who can accept it with `make_peace()`.
+    pub fn offer_peace(&mut self, from: Player, to: Player) {
+        if self.at_war(from, to) && !self.has_peace_offer(from, to) {
+            self.peace_offers.push((from, to));
+        }
+    }
+
+    /// Returns whether `from` offered peace to `to`, who didn't answer yet.
+    pub fn has_peace_offer(&self, from: Player, to: Player) -> bool {
+        self.peace_offers.contains(&(from, to))
+    }
+
+    /// Withdraws the peace offer of `from` to `to`, if there's one.
+    pub fn withdraw_peace_offer(&mut self, from: Player, to: Player) {
+        self.peace_offers.retain(|&pair| pair != (from, to));
+    }
+
+    /// Peace offers that weren't answered yet, the player making the offer coming first.
+    pub fn peace_offers(&self) -> &[(Player, Player)] {
+        &self.peace_offers[..]
+    }
+
+    /// Opens or closes the borders of `a` and `b` to each other.
+    ///
+    /// Returns `false`, without changing anything, if we're opening borders of players at war.
+    pub fn set_open_borders(&mut self, a: Player, b: Player, open: bool) -> bool {
+        match find_pair(&self.open_borders, a, b) {
+            Some(index) if !open => {
+                self.open_borders.remove(index);
+            }
+            None if open => {
+                if self.at_war(a, b) {
+                    return false;
+                }
+                self.open_borders.push((a, b));
+            }
+            _ => (),
+        }
+        true
+    }
+
+    /// Pairs of players at peace.
+    pub fn peace_pairs(&self) -> &[(Player, Player)] {
+        &self.peace[..]
+    }
+
+    /// Pairs of players with open borders.
+    pub fn open_borders_pairs(&self) -> &[(Player, Player)] {
+        &self.open_borders[..]
+    }
+
+    /// Gives the relations of `a` to `b` and those of `b` to `a`.
+    pub fn swap_players(&mut self, a: Player, b: Player) {
+        let pairs = self.peace
+                        .iter_mut()
+                        .chain(self.open_borders.iter_mut())
+                        .chain(self.peace_offers.iter_mut())
+                        .chain(self.wars.iter_mut().map(|w| &mut w.players));
+        for pair in pairs {
+            *pair = (pair.0.swapped(a, b), pair.1.swapped(a, b));
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Events happening on a `LiveMap`.
+//!
+//! The map records events as they happen and `LiveMap::take_events()` hands them over, in the order
+//! they happened. This is how the UI, scripted triggers and anything else interested learn about
+//! what happened outside of their control, such as during AI turns.
+
+use hexpos::{Pos, PosPath};
+use unit::{UnitID, Player};
+use combat::CombatStats;
+use terrain::Feature;
+use city::{CityID, Production};
+
+#[derive(Clone)]
+pub enum GameEvent {
+    /// Units were refreshed for a new turn.
+    TurnStarted,
+    /// A unit followed a path.
+    UnitMoved(UnitID, PosPath),
+    /// A combat took place. Its stats hold the outcome.
+    CombatResolved(CombatStats),
+    /// A unit of a player, on the given tile, was attacked.
+    UnitAttacked(UnitID, Player, Pos),
+    /// A unit of a player died on the given tile. It stays on the map until the next turn starts.
+    UnitKilled(UnitID, Player, Pos),
+    /// A unit of a player, healing on the given tile, is back to full health.
+    UnitHealed(UnitID, Player, Pos),
+    /// A player explored a tile for the first time.
+    TileRevealed(Player, Pos),
+    /// A worker removed a feature from the given tile.
+    FeatureRemoved(UnitID, Pos, Feature),
+    /// A city of the given player was founded on the given tile.
+    CityFounded(CityID, Player, Pos),
+    /// A city grew to the given population.
+    CityGrew(CityID, u8),
+    /// A city starved down to the given population.
+    CityStarved(CityID, u8),
+    /// A city built a unit or a building.
+    CityBuilt(CityID, Production),
+    /// A city of the given player, on the given tile, has enemy units next to it or was attacked.
+    /// It's raised at most once per turn for each city.
+    CityBesieged(CityID, Player, Pos),
+    /// A city was captured from the first player by the second.
+    CityCaptured(CityID, Player, Player),
+    /// A city with the given name was razed to the ground by the given player on the given tile.
+    CityRazed(String, Player, Pos),
+    /// The first player offered peace to the second.
+    PeaceOffered(Player, Player),
+}
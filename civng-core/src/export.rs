@@ -0,0 +1,432 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Game state export, for external tools.
+//!
+//! The state is a JSON object holding what a player knows about the game:
+//!
+//! ```text
+//! {"turn":3,"player":"Me","width":3,"height":2,
+//!  "terrain":["Hill","Plain",null,"Plain","Plain","Coast"],
+//!  "units":[{"id":1,"type":"Melee","owner":"Me","x":0,"y":1,"hp":100,"movements":2,
+//!            "orders":["goto"]}],
+//!  "cities":[{"id":1,"name":"Rome","owner":"Me","x":1,"y":1,"population":3}]}
+//! ```
+//!
+//! `terrain` lists tiles in rows, like `TerrainMap::tiles()`, with `null` for tiles the player
+//! hasn't explored. `units` lists the player's units and the enemy units it sees, with their
+//! offset positions, and `cities` lists the cities on tiles the player explored. The object is
+//! written on a single line so that a turn-by-turn dump is made of one state per line.
+//!
+//! The full state of the game, as returned by `full_state_json()`, has the same format, but its
+//! `player` is `null` and it has all terrain and units.
+//!
+//! Game events are JSON objects as well, with an `event` field holding the name of the event, as
+//! in `GameEvent`, and fields depending on the event:
+//!
+//! ```text
+//! {"event":"UnitMoved","unit":3,"path":[[0,1],[1,1],[2,1]]}
+//! {"event":"CombatResolved","attacker":3,"defender":4,"ranged":false,
+//!  "dmg_to_attacker":12,"dmg_to_defender":30}
+//! {"event":"CombatResolved","city":2,"defender":4,"ranged":true,
+//!  "dmg_to_attacker":0,"dmg_to_defender":25}
+//! {"event":"UnitKilled","unit":4,"owner":"AI0","x":3,"y":1}
+//! {"event":"UnitHealed","unit":3,"owner":"Me","x":2,"y":1}
+//! {"event":"FeatureRemoved","unit":5,"feature":"Forest","x":2,"y":0}
+//! {"event":"CityFounded","city":1,"owner":"Me","x":1,"y":1}
+//! {"event":"CityGrew","city":1,"population":3}
+//! {"event":"CityBuilt","city":1,"unit":"Ranged"}
+//! {"event":"CityBuilt","city":2,"building":0}
+//! {"event":"CityBesieged","city":1,"owner":"Me","x":1,"y":1}
+//! {"event":"CityCaptured","city":2,"from":"AI0","by":"Me"}
+//! {"event":"CityRazed","name":"Paris","owner":"Me","x":4,"y":2}
+//! {"event":"PeaceOffered","from":"AI0","to":"Me"}
+//! ```
+//!
+//! A combat in which a city strikes a unit has the id of the city instead of an attacker.
+//! Buildings are identified by their index in the content file defining them (see `building`).
+//!
+//! Terrain, features, unit types and players are written the way their own `Serialize`
+//! implementation writes them, so that the export matches saved maps and units.
+
+use serde::Serialize;
+use serde_json;
+
+use hexpos::OffsetPos;
+use terrain::{Terrain, Feature};
+use map::LiveMap;
+use unit::{Unit, UnitID, UnitType, Player};
+use city::{City, CityID, Production};
+use building::BuildingID;
+use events::GameEvent;
+
+#[derive(Serialize)]
+struct UnitState {
+    id: UnitID,
+    #[serde(rename = "type")]
+    type_: UnitType,
+    owner: Player,
+    #[serde(flatten)]
+    pos: OffsetPos,
+    hp: u8,
+    movements: u8,
+    orders: Vec<&'static str>,
+}
+
+impl UnitState {
+    fn new(unit: &Unit) -> UnitState {
+        UnitState {
+            id: unit.id(),
+            type_: unit.type_(),
+            owner: unit.owner(),
+            pos: unit.pos().to_offset_pos(),
+            hp: unit.hp(),
+            movements: unit.movements(),
+            orders: unit.orders().iter().map(|o| o.name()).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CityState<'a> {
+    id: CityID,
+    name: &'a str,
+    owner: Player,
+    #[serde(flatten)]
+    pos: OffsetPos,
+    population: u8,
+}
+
+impl<'a> CityState<'a> {
+    fn new(city: &'a City) -> CityState<'a> {
+        CityState {
+            id: city.id(),
+            name: city.name(),
+            owner: city.owner(),
+            pos: city.pos().to_offset_pos(),
+            population: city.population(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct GameState<'a> {
+    turn: u16,
+    player: Option<Player>,
+    width: i32,
+    height: i32,
+    terrain: Vec<Option<Terrain>>,
+    units: Vec<UnitState>,
+    cities: Vec<CityState<'a>>,
+}
+
+/// Returns `value` as a single line of JSON.
+fn to_json<T: Serialize>(value: &T) -> String {
+    // Our values only have string keys, which is the only way serializing them could fail.
+    serde_json::to_string(value).expect("Can't export to JSON")
+}
+
+/// Returns the state of the game at turn `turn`, as known by `player`, as a JSON object.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::terrain::TerrainMap;
+/// use civng_core::map::LiveMap;
+/// use civng_core::unit::{Unit, UnitType, Player};
+/// use civng_core::hexpos::{Pos, OffsetPos};
+/// use civng_core::export::game_state_json;
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(8, 1));
+/// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+/// let far = OffsetPos::new(7, 0).to_pos();
+/// map.add_unit(Unit::new(UnitType::Ranged, Player::AI(0), far));
+/// let json = game_state_json(&map, 1, Player::Me);
+/// assert!(json.starts_with("{\"turn\":1,\"player\":\"Me\",\"width\":8,\"height\":1,\
+///                           \"terrain\":[\"Grassland\",\"Grassland\",\"Grassland\",null,"));
+/// assert!(json.contains("{\"id\":1,\"type\":\"Melee\",\"owner\":\"Me\",\"x\":0,\"y\":0,"));
+/// // The enemy unit is out of sight.
+/// assert!(!json.contains("AI0"));
+/// assert!(game_state_json(&map, 1, Player::AI(0)).contains("AI0"));
+/// ```
+pub fn game_state_json(map: &LiveMap, turn: u16, player: Player) -> String {
+    state_json(map, turn, Some(player))
+}
+
+/// Returns the full state of the game at turn `turn`, regardless of what players know.
+pub fn full_state_json(map: &LiveMap, turn: u16) -> String {
+    state_json(map, turn, None)
+}
+
+fn state_json(map: &LiveMap, turn: u16, player: Option<Player>) -> String {
+    let (width, height) = map.terrain().size();
+    let fog = player.and_then(|p| map.fog(p));
+    let terrain = map.terrain()
+                     .tiles()
+                     .map(|(pos, terrain)| {
+                         if fog.map_or(true, |f| f.is_explored(pos)) {
+                             Some(terrain)
+                         } else {
+                             None
+                         }
+                     })
+                     .collect();
+    let mut units: Vec<&Unit> = map.units()
+                                   .all_units()
+                                   .filter(|u| {
+                                       Some(u.owner()) == player ||
+                                       fog.map_or(true, |f| f.is_visible(u.pos()))
+                                   })
+                                   .collect();
+    units.sort_by_key(|u| u.id());
+    let cities = map.cities()
+                    .all_cities()
+                    .into_iter()
+                    .filter(|c| fog.map_or(true, |f| f.is_explored(c.pos())))
+                    .map(CityState::new)
+                    .collect();
+    to_json(&GameState {
+        turn: turn,
+        player: player,
+        width: width,
+        height: height,
+        terrain: terrain,
+        units: units.into_iter().map(UnitState::new).collect(),
+        cities: cities,
+    })
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event")]
+enum EventState<'a> {
+    TurnStarted,
+    UnitMoved {
+        unit: UnitID,
+        path: Vec<(i32, i32)>,
+    },
+    CombatResolved {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        city: Option<CityID>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        attacker: Option<UnitID>,
+        defender: UnitID,
+        ranged: bool,
+        dmg_to_attacker: u8,
+        dmg_to_defender: u8,
+    },
+    UnitAttacked {
+        unit: UnitID,
+        owner: Player,
+        #[serde(flatten)]
+        pos: OffsetPos,
+    },
+    UnitKilled {
+        unit: UnitID,
+        owner: Player,
+        #[serde(flatten)]
+        pos: OffsetPos,
+    },
+    UnitHealed {
+        unit: UnitID,
+        owner: Player,
+        #[serde(flatten)]
+        pos: OffsetPos,
+    },
+    TileRevealed {
+        player: Player,
+        #[serde(flatten)]
+        pos: OffsetPos,
+    },
+    FeatureRemoved {
+        unit: UnitID,
+        feature: Feature,
+        #[serde(flatten)]
+        pos: OffsetPos,
+    },
+    CityFounded {
+        city: CityID,
+        owner: Player,
+        #[serde(flatten)]
+        pos: OffsetPos,
+    },
+    CityGrew {
+        city: CityID,
+        population: u8,
+    },
+    CityStarved {
+        city: CityID,
+        population: u8,
+    },
+    CityBuilt {
+        city: CityID,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        unit: Option<UnitType>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        building: Option<BuildingID>,
+    },
+    CityBesieged {
+        city: CityID,
+        owner: Player,
+        #[serde(flatten)]
+        pos: OffsetPos,
+    },
+    CityCaptured {
+        city: CityID,
+        from: Player,
+        by: Player,
+    },
+    CityRazed {
+        name: &'a str,
+        owner: Player,
+        #[serde(flatten)]
+        pos: OffsetPos,
+    },
+    PeaceOffered {
+        from: Player,
+        to: Player,
+    },
+}
+
+/// Returns `event` as a JSON object.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::hexpos::{Pos, Direction, PosPath};
+/// use civng_core::unit::Player;
+/// use civng_core::events::GameEvent;
+/// use civng_core::export::event_json;
+///
+/// let mut path = PosPath::new(Pos::origin());
+/// path.push(Pos::vector(Direction::South));
+/// assert_eq!(event_json(&GameEvent::UnitMoved(2, path)),
+///            "{\"event\":\"UnitMoved\",\"unit\":2,\"path\":[[0,0],[0,1]]}");
+/// assert_eq!(event_json(&GameEvent::TileRevealed(Player::AI(1), Pos::origin())),
+///            "{\"event\":\"TileRevealed\",\"player\":\"AI1\",\"x\":0,\"y\":0}");
+/// ```
+pub fn event_json(event: &GameEvent) -> String {
+    let state = match *event {
+        GameEvent::TurnStarted => EventState::TurnStarted,
+        GameEvent::UnitMoved(unit_id, ref path) => {
+            EventState::UnitMoved {
+                unit: unit_id,
+                path: path.stack()
+                          .iter()
+                          .map(|&p| {
+                              let opos = p.to_offset_pos();
+                              (opos.x, opos.y)
+                          })
+                          .collect(),
+            }
+        }
+        GameEvent::CombatResolved(ref stats) => {
+            EventState::CombatResolved {
+                city: stats.attacker_city,
+                attacker: match stats.attacker_city {
+                    Some(_) => None,
+                    None => Some(stats.attacker_id),
+                },
+                defender: stats.defender_id,
+                ranged: stats.ranged,
+                dmg_to_attacker: stats.dmg_to_attacker,
+                dmg_to_defender: stats.dmg_to_defender,
+            }
+        }
+        GameEvent::UnitAttacked(unit_id, owner, pos) => {
+            EventState::UnitAttacked {
+                unit: unit_id,
+                owner: owner,
+                pos: pos.to_offset_pos(),
+            }
+        }
+        GameEvent::UnitKilled(unit_id, owner, pos) => {
+            EventState::UnitKilled {
+                unit: unit_id,
+                owner: owner,
+                pos: pos.to_offset_pos(),
+            }
+        }
+        GameEvent::UnitHealed(unit_id, owner, pos) => {
+            EventState::UnitHealed {
+                unit: unit_id,
+                owner: owner,
+                pos: pos.to_offset_pos(),
+            }
+        }
+        GameEvent::TileRevealed(player, pos) => {
+            EventState::TileRevealed {
+                player: player,
+                pos: pos.to_offset_pos(),
+            }
+        }
+        GameEvent::CityFounded(city_id, owner, pos) => {
+            EventState::CityFounded {
+                city: city_id,
+                owner: owner,
+                pos: pos.to_offset_pos(),
+            }
+        }
+        GameEvent::CityGrew(city_id, population) => {
+            EventState::CityGrew {
+                city: city_id,
+                population: population,
+            }
+        }
+        GameEvent::CityStarved(city_id, population) => {
+            EventState::CityStarved {
+                city: city_id,
+                population: population,
+            }
+        }
+        GameEvent::CityBuilt(city_id, production) => {
+            let (unit, building) = match production {
+                Production::Unit(unit_type) => (Some(unit_type), None),
+                Production::Building(building_id) => (None, Some(building_id)),
+            };
+            EventState::CityBuilt {
+                city: city_id,
+                unit: unit,
+                building: building,
+            }
+        }
+        GameEvent::CityBesieged(city_id, owner, pos) => {
+            EventState::CityBesieged {
+                city: city_id,
+                owner: owner,
+                pos: pos.to_offset_pos(),
+            }
+        }
+        GameEvent::CityCaptured(city_id, from, by) => {
+            EventState::CityCaptured {
+                city: city_id,
+                from: from,
+                by: by,
+            }
+        }
+        GameEvent::CityRazed(ref name, owner, pos) => {
+            EventState::CityRazed {
+                name: name,
+                owner: owner,
+                pos: pos.to_offset_pos(),
+            }
+        }
+        GameEvent::PeaceOffered(from, to) => {
+            EventState::PeaceOffered {
+                from: from,
+                to: to,
+            }
+        }
+        GameEvent::FeatureRemoved(unit_id, pos, feature) => {
+            EventState::FeatureRemoved {
+                unit: unit_id,
+                feature: feature,
+                pos: pos.to_offset_pos(),
+            }
+        }
+    };
+    to_json(&state)
+}
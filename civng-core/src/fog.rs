@@ -0,0 +1,174 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Fog of war: what a player knows about the map.
+//!
+//! A tile is *explored* once one of the player's units has seen it and it stays explored forever.
+//! A tile is *visible* while one of the player's units sees it.
+
+use hexpos::{Pos, HexGrid};
+use bitset::BitSet;
+
+/// Returns the tiles within `sight` of `center` which aren't hidden behind a tile blocking sight.
+///
+/// `blocks` tells whether sight stops at a tile. Such tiles can be seen, but not what's behind
+/// them.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::fog::field_of_view;
+/// use civng_core::hexpos::{Pos, Direction};
+///
+/// let south = Pos::vector(Direction::South);
+/// let tiles = field_of_view(Pos::origin(), 2, |p| p == south);
+/// assert!(tiles.contains(&south));
+/// assert!(!tiles.contains(&south.amplify(2)));
+/// assert!(tiles.contains(&Pos::vector(Direction::North).amplify(2)));
+/// ```
+pub fn field_of_view<F: Fn(Pos) -> bool>(center: Pos, sight: u8, blocks: F) -> Vec<Pos> {
+    center.within(sight as i32)
+          .into_iter()
+          .filter(|&pos| {
+              let line = center.line_to(pos);
+              line.len() < 3 || !line[1..line.len() - 1].iter().any(|&p| blocks(p))
+          })
+          .collect()
+}
+
+/// Explored and visible tiles of a single player.
+#[derive(Clone)]
+pub struct FogOfWar {
+    /// Grid of the map, giving the index of each tile.
+    grid: HexGrid,
+    /// Explored tiles, in `TerrainMap` order.
+    explored: BitSet,
+    /// Visible tiles, in `TerrainMap` order.
+    visible: BitSet,
+    /// How many units see each tile, so that a tile only gets hidden when none of them does.
+    seen_by: Vec<u16>,
+}
+
+impl FogOfWar {
+    /// Creates the fog of a `width` x `height` map where nothing is explored yet.
+    pub fn new(width: i32, height: i32) -> FogOfWar {
+        let len = (width * height) as usize;
+        FogOfWar {
+            grid: HexGrid::new(width, height),
+            explored: BitSet::new(len),
+            visible: BitSet::new(len),
+            seen_by: vec![0; len],
+        }
+    }
+
+    pub fn is_explored(&self, pos: Pos) -> bool {
+        self.grid.index(pos).map_or(false, |i| self.explored.contains(i))
+    }
+
+    pub fn is_visible(&self, pos: Pos) -> bool {
+        self.grid.index(pos).map_or(false, |i| self.visible.contains(i))
+    }
+
+    /// Returns explored tiles, indexed like `TerrainMap` tiles.
+    pub fn explored(&self) -> &BitSet {
+        &self.explored
+    }
+
+    /// Returns visible tiles, indexed like `TerrainMap` tiles.
+    pub fn visible(&self) -> &BitSet {
+        &self.visible
+    }
+
+    /// Marks `pos` as explored. Does nothing if `pos` is out of bounds.
+    pub fn explore(&mut self, pos: Pos) {
+        if let Some(i) = self.grid.index(pos) {
+            self.explored.insert(i);
+        }
+    }
+
+    /// Forgets what is visible, without forgetting what was explored.
+    pub fn hide_all(&mut self) {
+        self.visible.clear();
+        for count in self.seen_by.iter_mut() {
+            *count = 0;
+        }
+    }
+
+    /// Makes `tiles`, such as those returned by `field_of_view()`, visible and explored.
+    ///
+    /// Each call counts as one more unit seeing `tiles`, until `hide()` is called with them.
+    ///
+    /// Returns the tiles that weren't explored before.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::fog::{FogOfWar, field_of_view};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut fog = FogOfWar::new(10, 10);
+    /// let far = Pos::vector(Direction::South).amplify(3);
+    /// let tiles = field_of_view(Pos::origin(), 2, |_| false);
+    /// fog.reveal(&tiles);
+    /// assert!(fog.is_visible(Pos::vector(Direction::South).amplify(2)));
+    /// assert!(!fog.is_explored(far));
+    /// fog.hide_all();
+    /// assert!(!fog.is_visible(Pos::origin()));
+    /// assert!(fog.is_explored(Pos::origin()));
+    /// // Nothing new to explore there.
+    /// assert!(fog.reveal(&tiles).is_empty());
+    /// ```
+    pub fn reveal(&mut self, tiles: &[Pos]) -> Vec<Pos> {
+        let mut result = Vec::new();
+        for &pos in tiles {
+            if let Some(i) = self.grid.index(pos) {
+                if !self.explored.contains(i) {
+                    self.explored.insert(i);
+                    result.push(pos);
+                }
+                self.visible.insert(i);
+                self.seen_by[i] += 1;
+            }
+        }
+        result
+    }
+
+    /// Takes back a call to `reveal()` with `tiles`, when the unit that saw them moved or died.
+    ///
+    /// Tiles stay visible as long as other units see them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::fog::{FogOfWar, field_of_view};
+    /// use civng_core::hexpos::OffsetPos;
+    ///
+    /// let mut fog = FogOfWar::new(10, 10);
+    /// let (pos1, pos2) = (OffsetPos::new(2, 2).to_pos(), OffsetPos::new(3, 3).to_pos());
+    /// let first = field_of_view(pos1, 1, |_| false);
+    /// let second = field_of_view(pos2, 1, |_| false);
+    /// fog.reveal(&first);
+    /// fog.reveal(&second);
+    /// fog.hide(&first);
+    /// assert!(!fog.is_visible(pos1));
+    /// assert!(fog.is_explored(pos1));
+    /// assert!(fog.is_visible(pos2));
+    /// assert_eq!(fog.visible().count(), second.len());
+    /// ```
+    pub fn hide(&mut self, tiles: &[Pos]) {
+        for &pos in tiles {
+            if let Some(i) = self.grid.index(pos) {
+                if self.seen_by[i] > 0 {
+                    self.seen_by[i] -= 1;
+                    if self.seen_by[i] == 0 {
+                        self.visible.remove(i);
+                    }
+                }
+            }
+        }
+    }
+}
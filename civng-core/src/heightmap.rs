@@ -0,0 +1,284 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Maps made from grayscale images.
+//!
+//! A heightmap image gives the elevation of each tile, from black (lowest) to white (highest), and
+//! an optional moisture image tells how wet each tile is. Each pixel is a tile. Tiles below sea
+//! level are water and the highest ones are hills and mountains. Other tiles are deserts, plains
+//! or grassland, from driest to wettest. Water is coast near land, ocean away from it, or lake
+//! when the body of water is small.
+//!
+//! Images are read in the PNG or PGM (binary or plain) format. Color images are turned to
+//! grayscale by their luminance.
+
+use std::cmp::min;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+use std::str;
+
+use png;
+
+use terrain::{Terrain, TerrainMap};
+use mapgen::find_landmasses;
+
+/// Tiles lower than this are water.
+pub const SEA_LEVEL: u8 = 96;
+/// Tiles at least this high are hills.
+pub const HILL_LEVEL: u8 = 176;
+/// Tiles at least this high are mountains.
+pub const MOUNTAIN_LEVEL: u8 = 224;
+/// Land tiles drier than this are deserts.
+pub const DESERT_MOISTURE: u8 = 64;
+/// Land tiles drier than this, but not deserts, are plains. Wetter ones are grassland.
+pub const PLAIN_MOISTURE: u8 = 144;
+
+/// Bytes every PNG file starts with.
+const PNG_SIGNATURE: &'static [u8] = b"\x89PNG\r\n\x1a\n";
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Returns the whitespace-separated field of PGM `data` starting at or after `pos`, skipping
+/// comments, and moves `pos` past it.
+fn next_field<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    loop {
+        while *pos < data.len() && (data[*pos] as char).is_whitespace() {
+            *pos += 1;
+        }
+        if *pos < data.len() && data[*pos] == b'#' {
+            while *pos < data.len() && data[*pos] != b'\n' {
+                *pos += 1;
+            }
+        } else {
+            break;
+        }
+    }
+    let start = *pos;
+    while *pos < data.len() && !(data[*pos] as char).is_whitespace() {
+        *pos += 1;
+    }
+    if *pos > start {
+        Some(&data[start..*pos])
+    } else {
+        None
+    }
+}
+
+fn next_number(data: &[u8], pos: &mut usize) -> Option<usize> {
+    next_field(data, pos).and_then(|f| str::from_utf8(f).ok()).and_then(|f| f.parse().ok())
+}
+
+/// A grayscale image, with pixels going from 0 (black) to 255 (white).
+pub struct GrayImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl GrayImage {
+    /// Parses `data` as a PGM image.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::heightmap::GrayImage;
+    ///
+    /// let image = GrayImage::parse_pgm(b"P2\n# A comment\n3 1\n10\n0 5 10\n").unwrap();
+    /// assert_eq!(image.size(), (3, 1));
+    /// assert_eq!(image.get(1, 0), 127);
+    /// assert_eq!(image.get(2, 0), 255);
+    /// assert!(GrayImage::parse_pgm(b"P2\n3 1\n10\n0 5\n").is_err());
+    /// ```
+    pub fn parse_pgm(data: &[u8]) -> io::Result<GrayImage> {
+        let mut pos = 0;
+        let binary = match next_field(data, &mut pos) {
+            Some(b"P5") => true,
+            Some(b"P2") => false,
+            _ => return Err(invalid_data("Not a PGM image")),
+        };
+        let mut header = [0usize; 3];
+        for value in header.iter_mut() {
+            match next_number(data, &mut pos) {
+                Some(n) => *value = n,
+                None => return Err(invalid_data("Invalid PGM header")),
+            }
+        }
+        let (width, height, maxval) = (header[0], header[1], header[2]);
+        if maxval == 0 || maxval > 65535 {
+            return Err(invalid_data("Invalid PGM maximum value"));
+        }
+        let count = width * height;
+        let mut values = Vec::with_capacity(count);
+        if binary {
+            // A single whitespace separates the header from pixels.
+            let body = &data[min(pos + 1, data.len())..];
+            let bytes_per_value = if maxval < 256 { 1 } else { 2 };
+            if body.len() < count * bytes_per_value {
+                return Err(invalid_data("Truncated PGM image"));
+            }
+            for chunk in body.chunks(bytes_per_value).take(count) {
+                values.push(chunk.iter().fold(0, |acc, &b| acc << 8 | b as usize));
+            }
+        } else {
+            for _ in 0..count {
+                match next_number(data, &mut pos) {
+                    Some(n) => values.push(n),
+                    None => return Err(invalid_data("Truncated PGM image")),
+                }
+            }
+        }
+        let pixels = values.into_iter().map(|v| (min(v, maxval) * 255 / maxval) as u8).collect();
+        Ok(GrayImage {
+            width: width,
+            height: height,
+            pixels: pixels,
+        })
+    }
+
+    /// Parses `data` as a PNG image, turning colors to gray.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate png;
+    /// extern crate civng_core;
+    ///
+    /// use civng_core::heightmap::GrayImage;
+    ///
+    /// # fn main() {
+    /// let mut data = Vec::new();
+    /// {
+    ///     let mut encoder = png::Encoder::new(&mut data, 2, 1);
+    ///     encoder.set_color(png::ColorType::Rgb);
+    ///     let mut writer = encoder.write_header().unwrap();
+    ///     writer.write_image_data(&[255, 255, 255, 0, 0, 255]).unwrap();
+    /// }
+    /// let image = GrayImage::parse_png(&data).unwrap();
+    /// assert_eq!(image.size(), (2, 1));
+    /// assert_eq!(image.get(0, 0), 255);
+    /// // Blue is the darkest color.
+    /// assert_eq!(image.get(1, 0), 29);
+    /// assert!(GrayImage::parse_png(&data[..40]).is_err());
+    /// # }
+    /// ```
+    pub fn parse_png(data: &[u8]) -> io::Result<GrayImage> {
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = try!(decoder.read_info());
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = try!(reader.next_frame(&mut buffer));
+        let (width, height) = (info.width as usize, info.height as usize);
+        let samples = info.color_type.samples();
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let line = &buffer[y * info.line_size..];
+            for x in 0..width {
+                let pixel = &line[x * samples..];
+                let gray = match info.color_type {
+                    png::ColorType::Rgb | png::ColorType::Rgba => {
+                        // ITU-R BT.601 luma
+                        (pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) /
+                        1000
+                    }
+                    _ => pixel[0] as u32,
+                };
+                pixels.push(gray as u8);
+            }
+        }
+        Ok(GrayImage {
+            width: width,
+            height: height,
+            pixels: pixels,
+        })
+    }
+
+    /// Loads the PNG or PGM image at `path`, telling one from the other by its contents.
+    pub fn load(path: &Path) -> io::Result<GrayImage> {
+        let mut fp = try!(File::open(path));
+        let mut data = Vec::new();
+        try!(fp.read_to_end(&mut data));
+        if data.starts_with(PNG_SIGNATURE) {
+            GrayImage::parse_png(&data)
+        } else {
+            GrayImage::parse_pgm(&data)
+        }
+    }
+
+    /// Returns the width and height of the image.
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Returns the pixel at column `x` and row `y`.
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Returns the terrain of a tile at `elevation` with `moisture`.
+fn terrain_at(elevation: u8, moisture: u8) -> Terrain {
+    if elevation < SEA_LEVEL {
+        Terrain::Ocean
+    } else if elevation >= MOUNTAIN_LEVEL {
+        Terrain::Mountain
+    } else if elevation >= HILL_LEVEL {
+        Terrain::Hill
+    } else if moisture < DESERT_MOISTURE {
+        Terrain::Desert
+    } else if moisture < PLAIN_MOISTURE {
+        Terrain::Plain
+    } else {
+        Terrain::Grassland
+    }
+}
+
+/// Returns the map described by `heightmap` and `moisture`, which must have the same size.
+///
+/// Without a moisture image, land is evenly wet and lowlands are all plains. Heightmaps without any
+/// land are refused.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::heightmap::{GrayImage, import_heightmap};
+///
+/// let heightmap = GrayImage::parse_pgm(b"P2 4 1 255 0 128 128 255").unwrap();
+/// let moisture = GrayImage::parse_pgm(b"P2 4 1 255 0 0 255 0").unwrap();
+/// let map = import_heightmap(&heightmap, Some(&moisture)).unwrap();
+/// // A single water tile is a lake.
+/// assert_eq!(map.to_text(), "_ \"A\n");
+/// let sea = GrayImage::parse_pgm(b"P2 2 1 255 0 0").unwrap();
+/// assert!(import_heightmap(&sea, None).is_err());
+/// ```
+pub fn import_heightmap(heightmap: &GrayImage,
+                        moisture: Option<&GrayImage>)
+                        -> io::Result<TerrainMap> {
+    let (width, height) = heightmap.size();
+    if moisture.map_or(false, |m| m.size() != (width, height)) {
+        return Err(invalid_data("Heightmap and moisture images have different sizes"));
+    }
+    if width == 0 || height == 0 {
+        return Err(invalid_data("Empty heightmap"));
+    }
+    let mut data = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let wetness = moisture.map_or(DESERT_MOISTURE, |m| m.get(x, y));
+            data.push(terrain_at(heightmap.get(x, y), wetness));
+        }
+    }
+    let mut result = TerrainMap::new(width as i32, height as i32, data);
+    result.classify_water();
+    if find_landmasses(&result).is_empty() {
+        return Err(invalid_data("Heightmap has no land"));
+    }
+    Ok(result)
+}
@@ -0,0 +1,836 @@
+// Copyright 2015 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Pure (UI-less) hex cell positioning logic. Inspired from this
+//! [awesome resrouce on the subject](http://www.redblobgames.com/grids/hexagons/).
+//!
+//! The vocabulary here is entirely borrowed from that referenced article, so you can lookup there
+//! for reference.
+//!
+//! Note that this module assumes a "flat-topped" hex grid.
+//!
+//! `i32` is chosen as a base integer type because positions in hex grids often have to go negative
+//! even with a top-left origin.
+
+use std::cmp::{Ordering, min, max};
+use std::collections::{BinaryHeap, HashMap};
+
+use num::integer::Integer;
+
+const DIRECTION_COUNT: usize = 6;
+
+/// Possible move directions in a flat-topped hex grid
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Direction {
+    North,
+    NorthEast,
+    SouthEast,
+    South,
+    SouthWest,
+    NorthWest,
+}
+
+impl Direction {
+    pub fn all() -> [Direction; DIRECTION_COUNT] {
+        [Direction::North,
+         Direction::NorthEast,
+         Direction::SouthEast,
+         Direction::South,
+         Direction::SouthWest,
+         Direction::NorthWest]
+    }
+
+    /// Returns the direction pointing the other way.
+    pub fn opposite(&self) -> Direction {
+        match *self {
+            Direction::North => Direction::South,
+            Direction::NorthEast => Direction::SouthWest,
+            Direction::SouthEast => Direction::NorthWest,
+            Direction::South => Direction::North,
+            Direction::SouthWest => Direction::NorthEast,
+            Direction::NorthWest => Direction::SouthEast,
+        }
+    }
+}
+
+/// "Cube"-type position. We simply call it `Pos` for conciseness because that's our "official" pos.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Pos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl Pos {
+    pub fn new(x: i32, y: i32, z: i32) -> Pos {
+        Pos { x: x, y: y, z: z }
+    }
+
+    /// Returns pos `(0, 0, 0)`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// assert_eq!(Pos::origin(), Pos::new(0, 0, 0));
+    /// ```
+    pub fn origin() -> Pos {
+        Pos::new(0, 0, 0)
+    }
+
+    /// Returns a position of size `1` in the specified direction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let pos1 = Pos::origin().neighbor(Direction::North);
+    /// let pos2 = Pos::vector(Direction::North);
+    /// assert_eq!(pos1, pos2);
+    /// ```
+    pub fn vector(direction: Direction) -> Pos {
+        Pos::origin().neighbor(direction)
+    }
+
+    pub fn to_axialpos(&self) -> AxialPos {
+        AxialPos::new(self.x, self.z)
+    }
+
+    pub fn to_offset_pos(&self) -> OffsetPos {
+        // Each x means +1x, -½y, -½z. y goes first.
+        // Each y means -1y, +1z.
+        let x = self.x;
+        let y = self.z + self.x / 2;
+        OffsetPos::new(x, y)
+    }
+
+    /// Translates `self` by `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let pos1 = Pos::origin().neighbor(Direction::South);
+    /// let pos2 = Pos::origin().neighbor(Direction::SouthWest);
+    /// let pos3 = Pos::origin().neighbor(Direction::South).neighbor(Direction::SouthWest);
+    /// assert_eq!(pos1.translate(pos2), pos3);
+    /// ```
+    pub fn translate(&self, other: Pos) -> Pos {
+        Pos::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+
+    /// Multiplies `self` by `factor`.
+    ///
+    /// This increases the distance to our origin by that factor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut pos1 = Pos::origin();
+    /// for _ in 0..3 {
+    ///     pos1 = pos1.neighbor(Direction::South);
+    /// }
+    /// let pos2 = Pos::origin().neighbor(Direction::South).amplify(3);
+    /// assert_eq!(pos1, pos2);
+    /// ```
+    pub fn amplify(&self, factor: i32) -> Pos {
+        Pos::new(self.x * factor, self.y * factor, self.z * factor)
+    }
+
+    /// Returns cell at the opposite side of the origin.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let pos1 = Pos::origin().neighbor(Direction::South).neighbor(Direction::SouthWest);
+    /// let pos2 = Pos::origin().neighbor(Direction::North).neighbor(Direction::NorthEast);
+    /// assert_eq!(pos1, pos2.neg());
+    /// ```
+    pub fn neg(&self) -> Pos {
+        Pos::new(-self.x, -self.y, -self.z)
+    }
+
+    /// Returns a pos relative to `self` when moving in the specified `direction`.
+    ///
+    /// By "moving", we mean moving a distance of a single cell.
+    pub fn neighbor(&self, direction: Direction) -> Pos {
+        let mut p = *self;
+        match direction {
+            Direction::North => {
+                p.y += 1;
+                p.z -= 1
+            }
+            Direction::NorthEast => {
+                p.x += 1;
+                p.z -= 1
+            }
+            Direction::SouthEast => {
+                p.x += 1;
+                p.y -= 1
+            }
+            Direction::South => {
+                p.z += 1;
+                p.y -= 1
+            }
+            Direction::SouthWest => {
+                p.z += 1;
+                p.x -= 1
+            }
+            Direction::NorthWest => {
+                p.y += 1;
+                p.x -= 1
+            }
+        }
+        p
+    }
+
+    /// Returns the number of cells separating `self` from `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let pos = Pos::origin().neighbor(Direction::South).neighbor(Direction::SouthWest);
+    /// assert_eq!(pos.distance(Pos::origin()), 2);
+    /// assert_eq!(Pos::origin().distance(Pos::origin()), 0);
+    /// ```
+    pub fn distance(&self, other: Pos) -> i32 {
+        let (dx, dy, dz) = ((self.x - other.x).abs(),
+                            (self.y - other.y).abs(),
+                            (self.z - other.z).abs());
+        max(dx, max(dy, dz))
+    }
+
+    /// Returns all positions at a distance of `radius` or less from `self`, `self` included.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// assert_eq!(Pos::origin().within(0), vec![Pos::origin()]);
+    /// assert_eq!(Pos::origin().within(2).len(), 19);
+    /// assert!(Pos::origin().within(2).iter().all(|p| p.distance(Pos::origin()) <= 2));
+    /// ```
+    pub fn within(&self, radius: i32) -> Vec<Pos> {
+        let mut result = Vec::new();
+        for dx in -radius..radius + 1 {
+            for dy in max(-radius, -dx - radius)..min(radius, -dx + radius) + 1 {
+                result.push(self.translate(Pos::new(dx, dy, -dx - dy)));
+            }
+        }
+        result
+    }
+
+    /// Returns the positions on the straight line going from `self` to `other`, both included.
+    ///
+    /// When the line runs exactly between two cells, we consistently pick the same side.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let south = Pos::vector(Direction::South);
+    /// assert_eq!(Pos::origin().line_to(south.amplify(2)),
+    ///            vec![Pos::origin(), south, south.amplify(2)]);
+    /// assert_eq!(Pos::origin().line_to(Pos::origin()), vec![Pos::origin()]);
+    /// let far = Pos::new(3, -1, -2);
+    /// let line = Pos::origin().line_to(far);
+    /// assert_eq!(line.len(), 4);
+    /// assert!(line.windows(2).all(|w| w[0].distance(w[1]) == 1));
+    /// ```
+    pub fn line_to(&self, other: Pos) -> Vec<Pos> {
+        fn round(x: f64, y: f64, z: f64) -> Pos {
+            let (mut rx, mut ry, mut rz) = (x.round(), y.round(), z.round());
+            let (dx, dy, dz) = ((rx - x).abs(), (ry - y).abs(), (rz - z).abs());
+            if dx > dy && dx > dz {
+                rx = -ry - rz;
+            } else if dy > dz {
+                ry = -rx - rz;
+            } else {
+                rz = -rx - ry;
+            }
+            Pos::new(rx as i32, ry as i32, rz as i32)
+        }
+
+        let count = self.distance(other);
+        if count == 0 {
+            return vec![*self];
+        }
+        // The nudge keeps us from landing exactly between two cells.
+        let (x, y, z) = (self.x as f64 + 1e-6, self.y as f64 + 2e-6, self.z as f64 - 3e-6);
+        let (dx, dy, dz) = ((other.x - self.x) as f64,
+                            (other.y - self.y) as f64,
+                            (other.z - self.z) as f64);
+        (0..count + 1)
+            .map(|i| {
+                let t = i as f64 / count as f64;
+                round(x + dx * t, y + dy * t, z + dz * t)
+            })
+            .collect()
+    }
+
+    /// Returns an array of all neighbors around `self`.
+    pub fn around(&self) -> [Pos; DIRECTION_COUNT] {
+        let mut result = [Pos::origin(); DIRECTION_COUNT];
+        for (i, d) in Direction::all().into_iter().enumerate() {
+            result[i] = self.neighbor(*d);
+        }
+        result
+    }
+
+    pub fn fmt(&self) -> String {
+        format!("{},{},{}", self.x, self.y, self.z)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct AxialPos {
+    pub q: i32,
+    pub r: i32,
+}
+
+impl AxialPos {
+    pub fn new(q: i32, r: i32) -> AxialPos {
+        AxialPos { q: q, r: r }
+    }
+
+    pub fn to_pos(&self) -> Pos {
+        Pos::new(self.q, self.r - self.q, self.r)
+    }
+
+    pub fn fmt(&self) -> String {
+        format!("{},{}", self.q, self.r)
+    }
+}
+
+/// "odd-q" type of Offset position.
+///
+/// Origin is top-left. `(1, 0)` is SouthEast of origin. `(0, 1)` is South.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OffsetPos {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl OffsetPos {
+    pub fn new(x: i32, y: i32) -> OffsetPos {
+        OffsetPos { x: x, y: y }
+    }
+
+    pub fn to_pos(&self) -> Pos {
+        // Each x means +1x, -½y, -½z. y goes first.
+        // Each y means -1y, +1z.
+        let (halfx, remx) = self.x.div_rem(&2);
+        let x = self.x;
+        let y = (-self.y) + (-halfx - remx);
+        let z = self.y + (-halfx);
+        Pos::new(x, y, z)
+    }
+
+    pub fn fmt(&self) -> String {
+        format!("{},{}", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PosPath {
+    stack: Vec<Pos>,
+}
+
+impl PosPath {
+    pub fn new(origin: Pos) -> PosPath {
+        PosPath { stack: vec![origin] }
+    }
+
+    pub fn stack(&self) -> &[Pos] {
+        &self.stack[..]
+    }
+
+    pub fn from(&self) -> Pos {
+        *self.stack.first().unwrap()
+    }
+
+    pub fn to(&self) -> Pos {
+        *self.stack.last().unwrap()
+    }
+
+    /// Returns the position before the last in the path.
+    pub fn before_last(&self) -> Option<Pos> {
+        if self.stack.len() > 1 {
+            Some(self.stack[self.stack.len() - 2])
+        } else {
+            None
+        }
+    }
+
+    pub fn steps(&self) -> usize {
+        // We don't count origin, we're already there.
+        self.stack.len() - 1
+    }
+
+    pub fn push(&mut self, pos: Pos) {
+        self.stack.push(pos);
+    }
+
+    pub fn go(&mut self, towards: Direction) {
+        let pos = self.to().neighbor(towards);
+        self.push(pos);
+    }
+
+    pub fn pop(&mut self) -> Option<Pos> {
+        // We never pop origin
+        if self.stack.len() > 1 {
+            self.stack.pop()
+        } else {
+            None
+        }
+    }
+}
+
+pub struct PathWalker {
+    origin: Pos,
+    max_depth: usize,
+    backing_off: bool,
+    current_path: Vec<Direction>,
+}
+
+impl PathWalker {
+    pub fn new(origin: Pos, max_depth: usize) -> PathWalker {
+        PathWalker {
+            origin: origin,
+            max_depth: max_depth,
+            backing_off: false,
+            current_path: Vec::new(),
+        }
+    }
+
+    fn nextdir(dir: Direction) -> Option<Direction> {
+        match dir {
+            Direction::North => Some(Direction::NorthEast),
+            Direction::NorthEast => Some(Direction::SouthEast),
+            Direction::SouthEast => Some(Direction::South),
+            Direction::South => Some(Direction::SouthWest),
+            Direction::SouthWest => Some(Direction::NorthWest),
+            Direction::NorthWest => None,
+        }
+    }
+
+    pub fn get_current_path(&self) -> PosPath {
+        let mut result = PosPath::new(self.origin);
+        for d in self.current_path.iter() {
+            result.go(*d);
+        }
+        result
+    }
+
+    fn tick(&mut self) -> Option<PosPath> {
+        if self.current_path.is_empty() {
+            return None;
+        }
+        let current = *self.current_path.last().unwrap();
+        match Self::nextdir(current) {
+            Some(d) => {
+                {
+                    let md = self.current_path.last_mut().unwrap();
+                    *md = d;
+                }
+                Some(self.get_current_path())
+            }
+            None => None,
+        }
+    }
+
+    pub fn next(&mut self) -> Option<PosPath> {
+        if self.max_depth == 0 {
+            None
+        } else if !self.backing_off && self.current_path.len() < self.max_depth {
+            self.current_path.push(Direction::North);
+            Some(self.get_current_path())
+        } else {
+            self.backing_off = false;
+            if self.current_path.is_empty() {
+                None
+            } else {
+                match self.tick() {
+                    Some(p) => Some(p),
+                    None => {
+                        self.backoff();
+                        let _ = self.current_path.pop();
+                        self.next()
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn backoff(&mut self) {
+        self.backing_off = true;
+    }
+}
+
+/// Paths going out of a single origin, each node pointing to the node of the path it extends.
+///
+/// Extending a path only adds a node, so we can go through many paths and only build the
+/// `PosPath` of those we keep.
+pub struct PathTree {
+    /// Position and parent of each node. The origin is node 0 and has no parent.
+    nodes: Vec<(Pos, Option<usize>)>,
+}
+
+impl PathTree {
+    pub fn new(origin: Pos) -> PathTree {
+        PathTree { nodes: vec![(origin, None)] }
+    }
+
+    /// Adds the path going from `node` to `pos` and returns its node.
+    pub fn extend(&mut self, node: usize, pos: Pos) -> usize {
+        self.nodes.push((pos, Some(node)));
+        self.nodes.len() - 1
+    }
+
+    /// Returns the position where the path of `node` ends.
+    pub fn pos(&self, node: usize) -> Pos {
+        self.nodes[node].0
+    }
+
+    /// Returns the whole path of `node`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::hexpos::{Pos, Direction, PathTree};
+    ///
+    /// let south = Pos::vector(Direction::South);
+    /// let mut tree = PathTree::new(Pos::origin());
+    /// let node = tree.extend(0, south);
+    /// tree.extend(0, Pos::vector(Direction::North));
+    /// let node = tree.extend(node, south.amplify(2));
+    /// assert_eq!(tree.path(node).stack(), &[Pos::origin(), south, south.amplify(2)]);
+    /// assert_eq!(tree.path(0).steps(), 0);
+    /// ```
+    pub fn path(&self, node: usize) -> PosPath {
+        let mut stack = vec![];
+        let mut current = Some(node);
+        while let Some(n) = current {
+            stack.push(self.nodes[n].0);
+            current = self.nodes[n].1;
+        }
+        stack.reverse();
+        PosPath { stack: stack }
+    }
+}
+
+/// Goes through the paths of up to `max_depth` steps going out of `origin`, in the same order as
+/// `PathWalker`, and returns the tree of the paths it went through.
+///
+/// `visit` gets the tree, the node of each path and the state `visit` returned for the path it
+/// extends, `start` for the origin. It returns the state of the new path, or `None` if paths
+/// extending it aren't worth going through, which is `PathWalker::backoff()`.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::hexpos::{Pos, walk_paths};
+///
+/// let mut count = 0;
+/// let tree = walk_paths(Pos::origin(), 2, 0, |_, _, &depth| {
+///     count += 1;
+///     // We only go on from the first direction.
+///     if count == 1 { Some(depth + 1) } else { None }
+/// });
+/// assert_eq!(count, 12);
+/// assert_eq!(tree.path(7).steps(), 2);
+/// assert_eq!(tree.path(8).steps(), 1);
+/// ```
+pub fn walk_paths<S, F: FnMut(&PathTree, usize, &S) -> Option<S>>(origin: Pos,
+                                                                  max_depth: usize,
+                                                                  start: S,
+                                                                  mut visit: F)
+                                                                  -> PathTree {
+    fn walk<S, F: FnMut(&PathTree, usize, &S) -> Option<S>>(tree: &mut PathTree,
+                                                            node: usize,
+                                                            depth: usize,
+                                                            state: &S,
+                                                            max_depth: usize,
+                                                            visit: &mut F) {
+        if depth >= max_depth {
+            return;
+        }
+        let from = tree.pos(node);
+        for d in Direction::all().iter() {
+            let child = tree.extend(node, from.neighbor(*d));
+            if let Some(child_state) = visit(tree, child, state) {
+                walk(tree, child, depth + 1, &child_state, max_depth, visit);
+            }
+        }
+    }
+
+    let mut tree = PathTree::new(origin);
+    walk(&mut tree, 0, 0, &start, max_depth, &mut visit);
+    tree
+}
+
+/// A position, or a tile index, waiting to be visited by `find_path()`, ordered so that the
+/// lowest `priority` comes out of a `BinaryHeap` first.
+struct Candidate<T> {
+    priority: u32,
+    pos: T,
+}
+
+impl<T> PartialEq for Candidate<T> {
+    fn eq(&self, other: &Candidate<T>) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl<T> Eq for Candidate<T> {}
+
+impl<T> PartialOrd for Candidate<T> {
+    fn partial_cmp(&self, other: &Candidate<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Candidate<T> {
+    fn cmp(&self, other: &Candidate<T>) -> Ordering {
+        other.priority.cmp(&self.priority)
+    }
+}
+
+/// Returns the cheapest path going from `from` to `to`, if there's one, using A*.
+///
+/// `cost` returns how much entering a position costs, or `None` if it can't be entered, such as
+/// when it's out of the map. Costs are expected to be at least 1, which is what makes the
+/// distance between two positions a suitable A* heuristic.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::hexpos::{Pos, Direction, find_path};
+///
+/// let to = Pos::vector(Direction::South).amplify(3);
+/// let wall = Pos::vector(Direction::South);
+/// // The search area has to be bounded, or failed searches never end.
+/// let cost = |p: Pos| if p == wall || p.distance(Pos::origin()) > 5 { None } else { Some(1) };
+/// let path = find_path(Pos::origin(), to, &cost).unwrap();
+/// assert_eq!(path.steps(), 4);
+/// assert!(!path.stack().contains(&wall));
+/// assert!(find_path(Pos::origin(), to, |p| if p == to { None } else { cost(p) }).is_none());
+/// ```
+pub fn find_path<F: Fn(Pos) -> Option<u8>>(from: Pos, to: Pos, cost: F) -> Option<PosPath> {
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<Pos, Pos> = HashMap::new();
+    let mut costs: HashMap<Pos, u32> = HashMap::new();
+    open.push(Candidate {
+        priority: 0,
+        pos: from,
+    });
+    costs.insert(from, 0);
+    while let Some(Candidate { pos, .. }) = open.pop() {
+        if pos == to {
+            let mut stack = vec![to];
+            let mut current = to;
+            while let Some(&previous) = came_from.get(&current) {
+                stack.push(previous);
+                current = previous;
+            }
+            let mut result = PosPath::new(from);
+            for &p in stack.iter().rev().skip(1) {
+                result.push(p);
+            }
+            return Some(result);
+        }
+        let pos_cost = costs[&pos];
+        for &neighbor in pos.around().iter() {
+            let step_cost = match cost(neighbor) {
+                Some(c) => c as u32,
+                None => continue,
+            };
+            let new_cost = pos_cost + step_cost;
+            if costs.get(&neighbor).map_or(true, |&c| new_cost < c) {
+                costs.insert(neighbor, new_cost);
+                came_from.insert(neighbor, pos);
+                open.push(Candidate {
+                    priority: new_cost + neighbor.distance(to) as u32,
+                    pos: neighbor,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Tiles of a `width` x `height` map, as flat indices in the row order `TerrainMap` uses.
+///
+/// The position and the neighbors of each index are computed once, so that code visiting tiles
+/// over and over, such as pathfinding, doesn't convert between `Pos` and `OffsetPos` at each step.
+#[derive(Debug, Clone)]
+pub struct HexGrid {
+    width: i32,
+    height: i32,
+    positions: Vec<Pos>,
+    /// Indices of the neighbors of each tile, in `Direction::all()` order. `None` when the
+    /// neighbor is out of the map.
+    neighbors: Vec<[Option<usize>; DIRECTION_COUNT]>,
+}
+
+impl HexGrid {
+    /// Creates the grid of a `width` x `height` map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::hexpos::{HexGrid, Direction, OffsetPos};
+    ///
+    /// let grid = HexGrid::new(4, 3);
+    /// assert_eq!(grid.len(), 12);
+    /// let pos = OffsetPos::new(1, 1).to_pos();
+    /// let index = grid.index(pos).unwrap();
+    /// assert_eq!(grid.pos(index), pos);
+    /// let south = grid.neighbors(index)[3].unwrap();
+    /// assert_eq!(grid.pos(south), pos.neighbor(Direction::South));
+    /// // The top left corner only has neighbors to the east and the south.
+    /// assert_eq!(grid.neighbors(0).iter().filter(|n| n.is_some()).count(), 2);
+    /// assert!(grid.index(OffsetPos::new(4, 0).to_pos()).is_none());
+    /// ```
+    pub fn new(width: i32, height: i32) -> HexGrid {
+        let len = (width * height) as usize;
+        let mut result = HexGrid {
+            width: width,
+            height: height,
+            positions: Vec::with_capacity(len),
+            neighbors: Vec::with_capacity(len),
+        };
+        for index in 0..len {
+            let (y, x) = (index as i32).div_rem(&width);
+            result.positions.push(OffsetPos::new(x, y).to_pos());
+        }
+        for index in 0..len {
+            let mut neighbors = [None; DIRECTION_COUNT];
+            for (i, npos) in result.positions[index].around().iter().enumerate() {
+                neighbors[i] = result.index(*npos);
+            }
+            result.neighbors.push(neighbors);
+        }
+        result
+    }
+
+    pub fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    /// Returns the number of tiles in the grid.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns the index of `pos`, or `None` if it's out of the map.
+    pub fn index(&self, pos: Pos) -> Option<usize> {
+        let opos = pos.to_offset_pos();
+        if opos.x < 0 || opos.y < 0 || opos.x >= self.width || opos.y >= self.height {
+            None
+        } else {
+            Some((opos.y * self.width + opos.x) as usize)
+        }
+    }
+
+    /// Returns the position of the tile at `index`.
+    pub fn pos(&self, index: usize) -> Pos {
+        self.positions[index]
+    }
+
+    /// Returns the indices of the neighbors of the tile at `index`, in `Direction::all()` order.
+    pub fn neighbors(&self, index: usize) -> &[Option<usize>; DIRECTION_COUNT] {
+        &self.neighbors[index]
+    }
+
+    /// Returns the cheapest path going from `from` to `to`, if there's one, using A*.
+    ///
+    /// It's `find_path()` on the tiles of the grid, which saves it from hashing positions. `cost`
+    /// gets tile indices and the search never leaves the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::hexpos::{HexGrid, Direction, OffsetPos};
+    ///
+    /// let grid = HexGrid::new(5, 5);
+    /// let from = OffsetPos::new(2, 0).to_pos();
+    /// let to = OffsetPos::new(2, 4).to_pos();
+    /// let wall = grid.index(from.neighbor(Direction::South)).unwrap();
+    /// let cost = |i: usize| if i == wall { None } else { Some(1) };
+    /// let path = grid.find_path(from, to, &cost).unwrap();
+    /// assert_eq!(path.steps(), 5);
+    /// assert_eq!(path.to(), to);
+    /// assert!(grid.find_path(from, to, |i| if grid.pos(i) == to { None } else { cost(i) })
+    ///             .is_none());
+    /// ```
+    pub fn find_path<F: Fn(usize) -> Option<u8>>(&self,
+                                                 from: Pos,
+                                                 to: Pos,
+                                                 cost: F)
+                                                 -> Option<PosPath> {
+        let (start, goal) = match (self.index(from), self.index(to)) {
+            (Some(start), Some(goal)) => (start, goal),
+            _ => return None,
+        };
+        let mut open = BinaryHeap::new();
+        let mut came_from: Vec<Option<usize>> = vec![None; self.len()];
+        let mut costs: Vec<Option<u32>> = vec![None; self.len()];
+        open.push(Candidate {
+            priority: 0,
+            pos: start,
+        });
+        costs[start] = Some(0);
+        while let Some(Candidate { pos: index, .. }) = open.pop() {
+            if index == goal {
+                let mut stack = vec![goal];
+                let mut current = goal;
+                while let Some(previous) = came_from[current] {
+                    stack.push(previous);
+                    current = previous;
+                }
+                let mut result = PosPath::new(from);
+                for &i in stack.iter().rev().skip(1) {
+                    result.push(self.positions[i]);
+                }
+                return Some(result);
+            }
+            let index_cost = costs[index].unwrap();
+            for &neighbor in self.neighbors[index].iter() {
+                let neighbor = match neighbor {
+                    Some(n) => n,
+                    None => continue,
+                };
+                let step_cost = match cost(neighbor) {
+                    Some(c) => c as u32,
+                    None => continue,
+                };
+                let new_cost = index_cost + step_cost;
+                if costs[neighbor].map_or(true, |c| new_cost < c) {
+                    costs[neighbor] = Some(new_cost);
+                    came_from[neighbor] = Some(index);
+                    open.push(Candidate {
+                        priority: new_cost + self.positions[neighbor].distance(to) as u32,
+                        pos: neighbor,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,58 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! # civng-core
+//!
+//! The game engine of civng: map, units, combat, AI, map files and saved games.
+//!
+//! It doesn't know anything about terminals, so it can be used by any frontend. `civng-tui` is
+//! the text-based one.
+
+extern crate num;
+extern crate byteorder;
+extern crate rand;
+#[macro_use]
+extern crate bitflags;
+extern crate rayon;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate toml;
+extern crate png;
+extern crate rhai;
+
+pub mod hexpos;
+pub mod bitset;
+pub mod fog;
+pub mod terrain;
+pub mod map;
+pub mod events;
+pub mod unit;
+pub mod city;
+pub mod building;
+pub mod counters;
+pub mod combat;
+pub mod combat_log;
+pub mod modifiers;
+pub mod civ5map;
+pub mod ai;
+pub mod rng;
+pub mod mapgen;
+pub mod quickstart;
+pub mod heightmap;
+pub mod savegame;
+pub mod pbem;
+pub mod export;
+pub mod bot;
+pub mod scenario;
+pub mod script;
+pub mod difficulty;
+pub mod diplomacy;
+pub mod religion;
+pub mod blockers;
+pub mod rules;
@@ -0,0 +1,2421 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+use std::collections::HashSet;
+use std::collections::hash_map::{HashMap, Entry};
+use std::sync::Arc;
+
+use hexpos::{Pos, PosPath, walk_paths};
+use unit::{Unit, Units, UnitID, UnitType, Player, Order, Promotion, HealingGround};
+use city::{City, Cities, CityID, CityStatus, Production, CITY_BASE_STRENGTH, CITY_STRIKE_RANGE};
+use fog::{FogOfWar, field_of_view};
+use bitset::BitSet;
+use terrain::{TerrainMap, Terrain, Yield, Resource, RESOURCE_SUPPLY};
+use combat::{CombatStats, Modifier};
+use combat_log::{CombatLog, CombatRecord};
+use modifiers::{ModifierProvider, CombatSide, default_providers};
+use difficulty::Difficulty;
+use diplomacy::Diplomacy;
+use events::GameEvent;
+use rules::Ruleset;
+
+/// A map with everything living on it: units, cities and what players know and agreed on.
+///
+/// Maps can be sent to another thread, for example to play the AI turn in the background, and
+/// cloned, for example to keep showing the map as it was while that happens. Clones share their
+/// modifier providers.
+#[derive(Clone)]
+pub struct LiveMap {
+    terrain: TerrainMap,
+    units: Units,
+    cities: Cities,
+    /// Events that happened since the last call to `take_events()`.
+    events: Vec<GameEvent>,
+    /// House rules the game is played with.
+    rules: Ruleset,
+    /// Combat rules giving modifiers to units.
+    modifier_providers: Vec<Arc<ModifierProvider>>,
+    difficulty: Difficulty,
+    /// What each player having had units on the map knows about it.
+    fogs: HashMap<Player, FogOfWar>,
+    /// What each unit saw when the fog was last updated.
+    sights: HashMap<UnitID, UnitSight>,
+    /// Whether the terrain changed since the fog was last updated, which can change what any
+    /// unit sees.
+    terrain_changed: bool,
+    /// How many live units stand on each tile, in `TerrainMap` order.
+    occupants: Vec<u8>,
+    /// Tiles with live units on them, in `TerrainMap` order.
+    occupied: BitSet,
+    /// Every combat that took place on the map.
+    combat_log: CombatLog,
+    diplomacy: Diplomacy,
+    /// Cities we raised `GameEvent::CityBesieged` for this turn.
+    besieged: HashSet<CityID>,
+}
+
+impl LiveMap {
+    pub fn new(terrain: TerrainMap) -> LiveMap {
+        let len = terrain.grid().len();
+        LiveMap {
+            terrain: terrain,
+            units: Units::new(),
+            cities: Cities::new(),
+            events: Vec::new(),
+            rules: Ruleset::new(),
+            modifier_providers: default_providers().into_iter().map(Arc::from).collect(),
+            difficulty: Difficulty::Prince,
+            fogs: HashMap::new(),
+            sights: HashMap::new(),
+            terrain_changed: false,
+            occupants: vec![0; len],
+            occupied: BitSet::new(len),
+            combat_log: CombatLog::new(),
+            diplomacy: Diplomacy::new(),
+            besieged: HashSet::new(),
+        }
+    }
+
+    pub fn terrain(&self) -> &TerrainMap {
+        &self.terrain
+    }
+
+    pub fn units(&self) -> &Units {
+        &self.units
+    }
+
+    pub fn cities(&self) -> &Cities {
+        &self.cities
+    }
+
+    /// Returns what the tile at `pos` yields when it's worked by a city of `player`.
+    ///
+    /// This is the yield of its terrain, feature and river, plus that of its resource if `player`
+    /// knows about it and the bonus of its pantheon (see `Cities::tile_yield()`). Tiles have no
+    /// improvements yet.
+    pub fn yield_at(&self, player: Player, pos: Pos) -> Yield {
+        self.cities.tile_yield(player, pos, &self.terrain)
+    }
+
+    pub fn combat_log(&self) -> &CombatLog {
+        &self.combat_log
+    }
+
+    /// Returns AI players that still have units or cities, in order.
+    pub fn ai_players(&self) -> Vec<Player> {
+        let mut result = self.units.ai_players();
+        for city in self.cities.all_cities() {
+            if city.owner() != Player::Me && !result.contains(&city.owner()) {
+                result.push(city.owner());
+            }
+        }
+        result.sort_by_key(|p| {
+            match *p {
+                Player::AI(n) => n,
+                Player::Me => 0,
+            }
+        });
+        result
+    }
+
+    pub fn diplomacy(&self) -> &Diplomacy {
+        &self.diplomacy
+    }
+
+    pub fn diplomacy_mut(&mut self) -> &mut Diplomacy {
+        &mut self.diplomacy
+    }
+
+    /// Makes `from` offer peace to `to`, who can accept it with `Diplomacy::make_peace()`.
+    pub fn offer_peace(&mut self, from: Player, to: Player) {
+        if self.diplomacy.at_war(from, to) && !self.diplomacy.has_peace_offer(from, to) {
+            self.diplomacy.offer_peace(from, to);
+            self.events.push(GameEvent::PeaceOffered(from, to));
+        }
+    }
+
+    /// Returns the player whose territory the tile at `pos` is in, if any.
+    ///
+    /// A player's territory is made of the tiles its cities own (see `Cities::tile_owner()`).
+    pub fn territory_owner(&self, pos: Pos) -> Option<Player> {
+        self.cities.tile_owner(pos).map(|city_id| self.cities.get(city_id).owner())
+    }
+
+    /// Returns whether units of `player` can enter the tile at `pos`, as far as borders go.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::city::City;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(8, 8));
+    /// let east = Pos::vector(Direction::SouthEast);
+    /// map.add_city(City::new("Paris", Player::AI(0), east.amplify(4)));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.refresh();
+    /// let border = east.amplify(2);
+    /// assert!(map.reachable_pos(1).contains_key(&border));
+    /// map.diplomacy_mut().make_peace(Player::Me, Player::AI(0));
+    /// assert!(!map.can_enter(Player::Me, border));
+    /// assert!(!map.reachable_pos(1).contains_key(&border));
+    /// map.diplomacy_mut().set_open_borders(Player::Me, Player::AI(0), true);
+    /// assert!(map.reachable_pos(1).contains_key(&border));
+    /// ```
+    pub fn can_enter(&self, player: Player, pos: Pos) -> bool {
+        self.territory_owner(pos).map_or(true, |owner| self.diplomacy.can_enter(player, owner))
+    }
+
+    /// Number of units of `player`, which count against its supply cap.
+    pub fn supply_used(&self, player: Player) -> u16 {
+        self.units.all_units().filter(|u| u.owner() == player).count() as u16
+    }
+
+    /// Production penalty, in percents, `player` suffers for its units above its supply cap. See
+    /// `Cities::supply_penalty()`.
+    pub fn supply_penalty(&self, player: Player) -> u16 {
+        self.cities.supply_penalty(player, self.supply_used(player))
+    }
+
+    /// Returns whether `player` accumulated enough science to see and use `resource`.
+    pub fn knows_resource(&self, player: Player, resource: Resource) -> bool {
+        self.cities.science(player) >= resource.revealed_at()
+    }
+
+    /// Returns how many units needing `resource` `player` can maintain.
+    ///
+    /// Each tile of the resource in the territory of `player` supplies `RESOURCE_SUPPLY` units,
+    /// once the player knows about it.
+    pub fn resource_supply(&self, player: Player, resource: Resource) -> u16 {
+        if !self.knows_resource(player, resource) {
+            return 0;
+        }
+        let mut count = 0;
+        for city in self.cities.all_cities().into_iter().filter(|c| c.owner() == player) {
+            let mut tiles = self.cities.city_tiles(city.id(), &self.terrain);
+            tiles.push(city.pos());
+            count += tiles.into_iter()
+                          .filter(|&p| self.terrain.get_resource(p) == Some(resource))
+                          .count() as u16;
+        }
+        count * RESOURCE_SUPPLY
+    }
+
+    /// Returns how many units of `player` need `resource`.
+    pub fn resource_usage(&self, player: Player, resource: Resource) -> u16 {
+        self.units
+            .all_units()
+            .filter(|u| u.owner() == player && u.type_().required_resource() == Some(resource))
+            .count() as u16
+    }
+
+    /// Returns whether `player` has more units needing `resource` than it can maintain.
+    ///
+    /// Those units fight with a penalty until the deficit is made up for.
+    pub fn has_resource_deficit(&self, player: Player, resource: Resource) -> bool {
+        self.resource_usage(player, resource) > self.resource_supply(player, resource)
+    }
+
+    /// Returns whether `city_id` can build `production`.
+    ///
+    /// On top of what `Cities::can_produce()` checks, units needing a strategic resource can only
+    /// be trained when the owner of the city has some of it to spare.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{TerrainMap, Resource};
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::city::{City, Production};
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut terrain = TerrainMap::empty_map(5, 5);
+    /// terrain.set_resource(Pos::vector(Direction::South), Some(Resource::Iron));
+    /// let mut map = LiveMap::new(terrain);
+    /// map.add_city(City::new("Rome", Player::Me, Pos::origin()));
+    /// let swordsman = Production::Unit(UnitType::Swordsman);
+    /// // We don't know about iron yet.
+    /// assert!(!map.can_produce(1, swordsman));
+    /// map.cities_mut().set_science(Player::Me, 100);
+    /// assert_eq!(map.resource_supply(Player::Me, Resource::Iron), 2);
+    /// assert!(map.production_choices(1).iter().any(|&p| p == swordsman));
+    /// map.add_unit(Unit::new(UnitType::Swordsman, Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Swordsman, Player::Me, Pos::origin()));
+    /// assert!(!map.set_production(1, Some(swordsman)));
+    /// assert!(!map.has_resource_deficit(Player::Me, Resource::Iron));
+    /// map.add_unit(Unit::new(UnitType::Swordsman, Player::Me, Pos::origin()));
+    /// assert!(map.has_resource_deficit(Player::Me, Resource::Iron));
+    /// ```
+    pub fn can_produce(&self, city_id: CityID, production: Production) -> bool {
+        let owner = self.cities.get(city_id).owner();
+        let has_resource = match production {
+            Production::Unit(unit_type) => {
+                unit_type.required_resource().map_or(true, |r| {
+                    self.resource_usage(owner, r) < self.resource_supply(owner, r)
+                })
+            }
+            Production::Building(_) => true,
+        };
+        has_resource && self.cities.can_produce(city_id, production)
+    }
+
+    /// Returns what `city_id` can build, units first. See `can_produce()`.
+    pub fn production_choices(&self, city_id: CityID) -> Vec<Production> {
+        self.cities
+            .production_choices(city_id)
+            .into_iter()
+            .filter(|&p| self.can_produce(city_id, p))
+            .collect()
+    }
+
+    /// Returns the fog of war of `player`, or `None` if `player` never had units on the map.
+    pub fn fog(&self, player: Player) -> Option<&FogOfWar> {
+        self.fogs.get(&player)
+    }
+
+    /// Mutable access to the fog of war of `player`, for restoring explored tiles.
+    pub fn fog_mut(&mut self, player: Player) -> &mut FogOfWar {
+        let (width, height) = self.terrain.size();
+        self.fogs.entry(player).or_insert_with(|| FogOfWar::new(width, height))
+    }
+
+    /// Gives everything `a` has and knows to `b` and everything of `b` to `a`: units, cities,
+    /// stocks, relations and fog of war.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::city::City;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(10, 10));
+    /// let far = Pos::vector(Direction::South).amplify(8);
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.add_city(City::new("Rome", Player::AI(0), far));
+    /// map.cities_mut().set_gold(Player::AI(0), 40);
+    /// map.diplomacy_mut().make_peace(Player::Me, Player::AI(1));
+    /// map.swap_players(Player::Me, Player::AI(0));
+    /// assert!(map.units().get(1).owner() == Player::AI(0));
+    /// assert!(map.cities().get(1).owner() == Player::Me);
+    /// assert_eq!(map.cities().gold(Player::Me), 40);
+    /// assert!(!map.diplomacy().at_war(Player::AI(0), Player::AI(1)));
+    /// assert!(map.diplomacy().at_war(Player::Me, Player::AI(1)));
+    /// assert!(map.fog(Player::AI(0)).unwrap().is_visible(Pos::origin()));
+    /// assert!(map.fog(Player::Me).is_none());
+    /// ```
+    pub fn swap_players(&mut self, a: Player, b: Player) {
+        self.units.swap_players(a, b);
+        self.cities.swap_players(a, b);
+        self.diplomacy.swap_players(a, b);
+        let (fog_a, fog_b) = (self.fogs.remove(&a), self.fogs.remove(&b));
+        if let Some(fog) = fog_a {
+            self.fogs.insert(b, fog);
+        }
+        if let Some(fog) = fog_b {
+            self.fogs.insert(a, fog);
+        }
+        for sight in self.sights.values_mut() {
+            sight.owner = sight.owner.swapped(a, b);
+        }
+    }
+
+    /// Returns the tiles `unit_id` sees.
+    ///
+    /// Units on hills see one tile farther. Hills, mountains, forests and jungles hide what's
+    /// behind them from units standing lower than them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{Terrain, TerrainMap, Feature};
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let south = Pos::vector(Direction::South);
+    /// let mut terrain = TerrainMap::empty_map(10, 10);
+    /// terrain.set_feature(south, Some(Feature::Forest));
+    /// let mut map = LiveMap::new(terrain);
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// let tiles = map.visible_tiles(1);
+    /// assert!(tiles.contains(&south));
+    /// assert!(!tiles.contains(&south.amplify(2)));
+    /// assert!(!tiles.contains(&Pos::vector(Direction::SouthEast).amplify(3)));
+    /// // From a hill, we see over the forest and farther.
+    /// map.terrain_mut().set_terrain(Pos::origin(), Terrain::Hill);
+    /// let tiles = map.visible_tiles(1);
+    /// assert!(tiles.contains(&south.amplify(3)));
+    /// assert!(tiles.contains(&Pos::vector(Direction::SouthEast).amplify(3)));
+    /// ```
+    pub fn visible_tiles(&self, unit_id: UnitID) -> Vec<Pos> {
+        let unit = self.units.get(unit_id);
+        let terrain = self.terrain.get_terrain(unit.pos());
+        let sight = unit.type_().sight() + terrain.sight_bonus();
+        let height = terrain.height();
+        field_of_view(unit.pos(), sight, |p| self.terrain.sight_height(p) > height)
+    }
+
+    /// Updates what we keep per tile about units: the tiles each player sees from the position of
+    /// its units and the tiles units stand on.
+    ///
+    /// Only units that moved, died or appeared since the last update are looked at: what they
+    /// saw is hidden and what they see now is revealed. When the terrain changed, all fogs are
+    /// recomputed.
+    fn update_unit_tiles(&mut self) {
+        if self.terrain_changed {
+            for fog in self.fogs.values_mut() {
+                fog.hide_all();
+            }
+            self.sights.clear();
+            for count in self.occupants.iter_mut() {
+                *count = 0;
+            }
+            self.occupied.clear();
+            self.terrain_changed = false;
+        }
+        let stale: Vec<UnitID> = {
+            let units = &self.units;
+            self.sights
+                .iter()
+                .filter(|&(unit_id, sight)| {
+                    units.find(*unit_id).map_or(true, |u| u.is_dead() || !sight.is_of(u))
+                })
+                .map(|(&unit_id, _)| unit_id)
+                .collect()
+        };
+        for unit_id in stale {
+            let sight = self.sights.remove(&unit_id).unwrap();
+            if let Some(fog) = self.fogs.get_mut(&sight.owner) {
+                fog.hide(&sight.tiles);
+            }
+            self.set_occupied(sight.pos, false);
+        }
+        let (width, height) = self.terrain.size();
+        let sights = &self.sights;
+        let unseen: Vec<UnitID> = self.units
+                                      .all_units()
+                                      .filter(|u| !u.is_dead() && !sights.contains_key(&u.id()))
+                                      .map(|u| u.id())
+                                      .collect();
+        for unit_id in unseen {
+            let tiles = self.visible_tiles(unit_id);
+            let (owner, pos, unit_type) = {
+                let unit = self.units.get(unit_id);
+                (unit.owner(), unit.pos(), unit.type_())
+            };
+            let revealed = self.fogs
+                               .entry(owner)
+                               .or_insert_with(|| FogOfWar::new(width, height))
+                               .reveal(&tiles);
+            for pos in revealed {
+                self.events.push(GameEvent::TileRevealed(owner, pos));
+            }
+            self.set_occupied(pos, true);
+            self.sights.insert(unit_id,
+                               UnitSight {
+                                   owner: owner,
+                                   pos: pos,
+                                   unit_type: unit_type,
+                                   tiles: tiles,
+                               });
+        }
+    }
+
+    /// Counts one more, or one less, unit standing at `pos`.
+    fn set_occupied(&mut self, pos: Pos, occupied: bool) {
+        if let Some(i) = self.terrain.grid().index(pos) {
+            if occupied {
+                self.occupants[i] += 1;
+            } else {
+                self.occupants[i] -= 1;
+            }
+            self.occupied.set(i, self.occupants[i] > 0);
+        }
+    }
+
+    /// Returns whether a live unit stands at `pos`.
+    pub fn is_occupied(&self, pos: Pos) -> bool {
+        self.terrain.grid().index(pos).map_or(false, |i| self.occupied.contains(i))
+    }
+
+    /// Returns how many live units stand at `pos`.
+    pub fn occupants(&self, pos: Pos) -> u8 {
+        self.terrain.grid().index(pos).map_or(0, |i| self.occupants[i])
+    }
+
+    /// Returns tiles with live units on them, indexed like `TerrainMap` tiles.
+    pub fn occupied(&self) -> &BitSet {
+        &self.occupied
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// Sets the difficulty level, which determines the combat modifier of AI units and the
+    /// production modifier of AI cities.
+    pub fn set_difficulty(&mut self, difficulty: Difficulty) {
+        self.difficulty = difficulty;
+    }
+
+    pub fn rules(&self) -> &Ruleset {
+        &self.rules
+    }
+
+    /// Whether `unit` moves through zones of control as if they weren't there on this map.
+    ///
+    /// Scouts and units with `Promotion::Mobility` do, unless `zoc_exemptions` is off in our
+    /// rules.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::{LiveMap, MovePlan};
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(5, 5));
+    /// let pos1 = Pos::vector(Direction::South);
+    /// let pos3 = pos1.amplify(3);
+    /// map.add_unit(Unit::new(UnitType::Scout, Player::Me, pos1));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), pos3));
+    /// map.refresh();
+    /// assert!(map.ignores_zoc(map.units().get(1)));
+    /// match map.plan_move(1, pos3) {
+    ///     Some(MovePlan::Attack(_, 3)) => (),
+    ///     _ => panic!("Our scout goes from a ZOC to another and attacks"),
+    /// }
+    /// let mut rules = *map.rules();
+    /// rules.zoc_exemptions = false;
+    /// map.set_rules(rules);
+    /// assert!(!map.ignores_zoc(map.units().get(1)));
+    /// assert!(map.plan_move(1, pos3).is_none());
+    /// ```
+    pub fn ignores_zoc(&self, unit: &Unit) -> bool {
+        self.rules.zoc_exemptions && unit.ignores_zoc()
+    }
+
+    /// Sets the house rules the game is played with, Civ5's by default.
+    ///
+    /// Under Civ5's "last hex" rule, for example, a unit with movements left can always enter an
+    /// adjacent tile, even if the tile costs more movements than what the unit has left. Without
+    /// it, units can't enter tiles they can't afford.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{TerrainMap, Terrain};
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    /// use civng_core::rules::Ruleset;
+    ///
+    /// let mut terrain = TerrainMap::empty_map(3, 3);
+    /// let hill = Pos::origin().neighbor(Direction::South).neighbor(Direction::South);
+    /// terrain.set_terrain(hill, Terrain::Hill);
+    /// let mut map = LiveMap::new(terrain);
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.refresh();
+    /// assert!(map.reachable_pos(1).contains_key(&hill));
+    /// let mut rules = Ruleset::new();
+    /// rules.last_hex_rule = false;
+    /// map.set_rules(rules);
+    /// assert!(!map.reachable_pos(1).contains_key(&hill));
+    /// ```
+    pub fn set_rules(&mut self, rules: Ruleset) {
+        self.rules = rules;
+    }
+
+    pub fn is_pos_passable(&self, pos: Pos) -> bool {
+        self.terrain.is_passable(pos) && !self.is_occupied(pos)
+    }
+
+    /// Returns the first passable tile after `from`.
+    ///
+    /// Iterates all tiles from left to right, from the position `pos`. As soon as a tile is
+    /// passable (terrain-wise and unit-wise), we return its position.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let map = LiveMap::new(TerrainMap::empty_map(2, 2));
+    /// assert_eq!(map.first_passable(Pos::origin()), Pos::origin());
+    /// ```
+    pub fn first_passable(&self, from: Pos) -> Pos {
+        self.find_passable(from).expect("No tile is passable!")
+    }
+
+    /// Returns the first passable tile after `from`, like `first_passable()`, or `None` if there
+    /// isn't any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{TerrainMap, Terrain};
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::OffsetPos;
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(4, 4));
+    /// let last = OffsetPos::new(3, 3).to_pos();
+    /// assert_eq!(map.find_passable(last), Some(last));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, last));
+    /// assert_eq!(map.find_passable(last), None);
+    /// map.terrain_mut().set_terrain(last, Terrain::Ocean);
+    /// assert_eq!(map.find_passable(last), None);
+    /// ```
+    pub fn find_passable(&self, from: Pos) -> Option<Pos> {
+        self.terrain
+            .tiles()
+            .skip_while(|&(p, _)| p != from)
+            .map(|(pos, _)| pos)
+            .find(|&pos| self.is_pos_passable(pos))
+    }
+
+    pub fn add_unit(&mut self, unit: Unit) {
+        self.units.add_unit(unit);
+        self.update_unit_tiles();
+    }
+
+    /// Replaces our units with `units`, for example when loading a saved game.
+    pub fn set_units(&mut self, units: Units) {
+        self.units = units;
+        self.update_unit_tiles();
+    }
+
+    /// Puts `unit_id` on `pos` right away, wherever it is, without following a path nor spending
+    /// movements.
+    ///
+    /// This is meant for debugging. Returns false, leaving the unit where it was, if `pos` is
+    /// impassable or its units would keep the unit from stopping there.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{TerrainMap, Terrain};
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(8, 8));
+    /// let far = Pos::vector(Direction::South).amplify(6);
+    /// map.terrain_mut().set_terrain(far.neighbor(Direction::South), Terrain::Mountain);
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), far.neighbor(Direction::North)));
+    /// map.refresh();
+    /// assert!(map.teleport_unit(1, far));
+    /// assert_eq!(map.units().get(1).pos(), far);
+    /// assert_eq!(map.units().get(1).movements(), 2);
+    /// assert!(!map.teleport_unit(1, far.neighbor(Direction::South)));
+    /// assert!(!map.teleport_unit(1, far.neighbor(Direction::North)));
+    /// assert_eq!(map.units().get(1).pos(), far);
+    /// ```
+    pub fn teleport_unit(&mut self, unit_id: UnitID, pos: Pos) -> bool {
+        let owner = self.units.get(unit_id).owner();
+        if !self.terrain.is_passable(pos) ||
+           hindrances_at(self, pos, Some(owner)).contains(HINDRANCE_UNIT) {
+            return false;
+        }
+        self.units.get_mut(unit_id).move_to(pos, 0);
+        self.update_unit_tiles();
+        true
+    }
+
+    /// Hands `unit_id` over to the first AI player if it's ours and to us otherwise.
+    ///
+    /// This is meant for debugging, to set up fights and zones of control as we need them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(4, 4));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(1), Pos::origin()));
+    /// map.toggle_unit_owner(1);
+    /// assert!(map.units().get(1).owner() == Player::Me);
+    /// map.toggle_unit_owner(1);
+    /// assert!(map.units().get(1).owner() == Player::AI(0));
+    /// ```
+    pub fn toggle_unit_owner(&mut self, unit_id: UnitID) {
+        let owner = match self.units.get(unit_id).owner() {
+            Player::Me => Player::AI(0),
+            Player::AI(_) => Player::Me,
+        };
+        self.units.get_mut(unit_id).set_owner(owner);
+        self.update_unit_tiles();
+    }
+
+    /// Adds `city` to the map and puts its citizens to work.
+    pub fn add_city(&mut self, city: City) -> CityID {
+        let (owner, pos) = (city.owner(), city.pos());
+        let city_id = self.cities.add_city(city);
+        self.events.push(GameEvent::CityFounded(city_id, owner, pos));
+        // The new city might take tiles from its neighbors.
+        self.assign_citizens();
+        city_id
+    }
+
+    /// Makes the settler `unit_id` found a city where it stands, which uses the settler up.
+    ///
+    /// Returns the new city, or `None` if the unit isn't a settler or if no city can be founded
+    /// there (see `Cities::can_found_city()`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(10, 10));
+    /// let south = Pos::vector(Direction::South);
+    /// map.add_unit(Unit::new(UnitType::Settler, Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Settler, Player::Me, south));
+    /// map.add_unit(Unit::new(UnitType::Worker, Player::Me, south.amplify(5)));
+    /// assert_eq!(map.found_city(1), Some(1));
+    /// assert_eq!(map.cities().get(1).name(), "Alexandria");
+    /// assert!(map.units().top_at_pos(Pos::origin()).is_none());
+    /// // Too close to our new city.
+    /// assert_eq!(map.found_city(2), None);
+    /// assert_eq!(map.found_city(3), None);
+    /// ```
+    pub fn found_city(&mut self, unit_id: UnitID) -> Option<CityID> {
+        let (owner, pos) = {
+            let unit = self.units.get(unit_id);
+            if unit.type_() != UnitType::Settler || unit.is_dead() ||
+               !self.cities.can_found_city(unit.pos(), &self.terrain) {
+                return None;
+            }
+            (unit.owner(), unit.pos())
+        };
+        self.units.get_mut(unit_id).use_up();
+        let name = self.cities.new_city_name();
+        let city_id = self.add_city(City::new(&name, owner, pos));
+        self.update_unit_tiles();
+        Some(city_id)
+    }
+
+    /// Puts the citizens of every city back to work, for example after their tiles changed.
+    pub fn assign_citizens(&mut self) {
+        let ids: Vec<CityID> = self.cities.all_cities().iter().map(|c| c.id()).collect();
+        for city_id in ids {
+            self.cities.assign_citizens(city_id, &self.terrain);
+        }
+    }
+
+    /// Locks or unlocks the tile at `pos` for citizens of `city_id` to work.
+    ///
+    /// See `Cities::toggle_locked_tile()`.
+    pub fn toggle_locked_tile(&mut self, city_id: CityID, pos: Pos) -> bool {
+        self.cities.toggle_locked_tile(city_id, pos, &self.terrain)
+    }
+
+    /// Makes `city_id` build `production`, or nothing if `None`. See `Cities::set_production()`.
+    ///
+    /// Returns `false`, without changing anything, if the city can't build it (see
+    /// `can_produce()`).
+    pub fn set_production(&mut self, city_id: CityID, production: Option<Production>) -> bool {
+        if let Some(p) = production {
+            if !self.can_produce(city_id, p) {
+                return false;
+            }
+        }
+        self.cities.set_production(city_id, production)
+    }
+
+    /// Mutable access to cities, for example to restore them when loading a saved game.
+    pub fn cities_mut(&mut self) -> &mut Cities {
+        &mut self.cities
+    }
+
+    /// Adds a combat rule to the ones applied to combats on this map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    /// use civng_core::combat::{Modifier, ModifierType};
+    /// use civng_core::modifiers::{ModifierProvider, CombatSide};
+    ///
+    /// /// Attackers are always a bit weaker.
+    /// struct AttackPenalty;
+    ///
+    /// impl ModifierProvider for AttackPenalty {
+    ///     fn modifier(&self, _: &LiveMap, side: CombatSide) -> Option<Modifier> {
+    ///         if side.defends { None } else { Some(Modifier::new(-10, ModifierType::Terrain)) }
+    ///     }
+    /// }
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(3, 3));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), Pos::vector(Direction::South)));
+    /// assert_eq!(map.get_combat_stats(1, 2).attacker_modifiers_total(), 0);
+    /// map.add_modifier_provider(Box::new(AttackPenalty));
+    /// assert_eq!(map.get_combat_stats(1, 2).attacker_modifiers_total(), -10);
+    /// ```
+    pub fn add_modifier_provider(&mut self, provider: Box<ModifierProvider>) {
+        self.modifier_providers.push(Arc::from(provider));
+    }
+
+    fn get_unit_modifiers(&self, side: CombatSide) -> Vec<Modifier> {
+        self.modifier_providers.iter().filter_map(|p| p.modifier(self, side)).collect()
+    }
+
+    /// Returns the modifiers `unit_id` would defend with against a melee attack right now.
+    ///
+    /// Modifiers depending on who attacks, such as flanking, are left out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{Terrain, TerrainMap};
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut terrain = TerrainMap::empty_map(3, 3);
+    /// terrain.set_terrain(Pos::origin(), Terrain::Hill);
+    /// let mut map = LiveMap::new(terrain);
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// let modifiers = map.defense_modifiers(1);
+    /// assert_eq!(modifiers.len(), 1);
+    /// assert_eq!(modifiers[0].description(), "+25% Terrain");
+    /// ```
+    pub fn defense_modifiers(&self, unit_id: UnitID) -> Vec<Modifier> {
+        self.get_unit_modifiers(CombatSide {
+            unit_id: unit_id,
+            against_id: None,
+            defends: true,
+            ranged: false,
+        })
+    }
+
+    /// Returns the combat stats `attacker_id` would have if it attacked `defender_id` right now.
+    pub fn get_combat_stats(&self, attacker_id: UnitID, defender_id: UnitID) -> CombatStats {
+        let attacker = self.units.get(attacker_id);
+        let defender = self.units.get(defender_id);
+        let ranged = attacker.type_().is_ranged();
+        let attacker_modifiers = self.get_unit_modifiers(CombatSide {
+            unit_id: attacker_id,
+            against_id: Some(defender_id),
+            defends: false,
+            ranged: ranged,
+        });
+        let defender_modifiers = self.get_unit_modifiers(CombatSide {
+            unit_id: defender_id,
+            against_id: Some(attacker_id),
+            defends: true,
+            ranged: ranged,
+        });
+        let mut stats = CombatStats::new(attacker,
+                                         attacker_modifiers,
+                                         defender,
+                                         defender_modifiers);
+        if stats.withdraw_chance > 0 {
+            stats.withdraw_to = self.withdraw_pos(defender_id, attacker.pos());
+        }
+        stats.retaliates = ranged && self.rules.ranged_retaliation;
+        stats
+    }
+
+    /// Returns the free tile next to `unit_id` it can withdraw to when attacked from `from`.
+    ///
+    /// Units withdraw to a passable tile they can enter, without units nor a city of another
+    /// player, as far from their attacker as possible.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(6, 6));
+    /// let south = Pos::vector(Direction::South);
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Horseman, Player::AI(0), south));
+    /// let stats = map.get_combat_stats(1, 2);
+    /// assert_eq!(stats.withdraw_chance, 70);
+    /// assert_eq!(stats.withdraw_to, Some(south.amplify(2)));
+    /// ```
+    pub fn withdraw_pos(&self, unit_id: UnitID, from: Pos) -> Option<Pos> {
+        let unit = self.units.get(unit_id);
+        unit.pos()
+            .around()
+            .iter()
+            .cloned()
+            .filter(|&p| self.terrain.is_passable(p) && self.can_enter(unit.owner(), p))
+            .filter(|&p| self.units.top_at_pos(p).is_none())
+            .filter(|&p| self.cities.get_at_pos(p).map_or(true, |c| c.owner() == unit.owner()))
+            .max_by_key(|&p| p.distance(from))
+    }
+
+    /// Returns the friendly unit `unit_id` would swap places with by moving to `pos`, if any, along
+    /// with the movement cost of the swap for `unit_id`.
+    ///
+    /// Units can swap places with a friendly unit on an adjacent tile if they have enough movements
+    /// to enter that tile. The other unit doesn't spend movements.
+    fn swap_candidate(&self, unit_id: UnitID, pos: Pos) -> Option<(UnitID, u8)> {
+        let unit = self.units.get(unit_id);
+        if unit.pos().distance(pos) != 1 {
+            return None;
+        }
+        let other_id = match self.units.top_at_pos(pos) {
+            Some(u) if u.owner() == unit.owner() => u.id(),
+            _ => return None,
+        };
+        // Units only swap places when they can't stack.
+        if self.occupants(pos) < self.rules.stacking_limit {
+            return None;
+        }
+        let mut path = PosPath::new(unit.pos());
+        path.push(pos);
+        let livepath = LivePath::new(&path, self);
+        if !livepath.could_be_reachable() ||
+           !livepath.is_affordable(unit.movements(), self.rules.last_hex_rule) {
+            None
+        } else if livepath.is_exhausting() {
+            Some((other_id, unit.movements()))
+        } else {
+            Some((other_id, livepath.cost()))
+        }
+    }
+
+    /// Moves `unit_id` to `pos`, or initiates a melee attack if an enemy is there.
+    ///
+    /// If a friendly unit is on `pos` and it's adjacent, both units swap places. Melee units moving
+    /// into an enemy city capture it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(3, 3));
+    /// let pos1 = Pos::origin();
+    /// let pos2 = pos1.neighbor(Direction::South);
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, pos1));
+    /// map.add_unit(Unit::new(UnitType::Ranged, Player::Me, pos2));
+    /// map.refresh();
+    /// map.moveunit_to(1, pos2);
+    /// assert_eq!(map.units().get(1).pos(), pos2);
+    /// assert_eq!(map.units().get(2).pos(), pos1);
+    /// assert_eq!(map.units().get(2).movements(), 2);
+    /// ```
+    pub fn moveunit_to(&mut self, unit_id: UnitID, pos: Pos) -> Option<CombatStats> {
+        if let Some((other_id, cost)) = self.swap_candidate(unit_id, pos) {
+            let from = self.units.get(unit_id).pos();
+            let mut path = PosPath::new(from);
+            path.push(pos);
+            let mut other_path = PosPath::new(pos);
+            other_path.push(from);
+            self.units.get_mut(unit_id).move_to(pos, cost);
+            self.units.get_mut(other_id).move_to(from, 0);
+            self.events.push(GameEvent::UnitMoved(unit_id, path));
+            self.events.push(GameEvent::UnitMoved(other_id, other_path));
+            self.update_unit_tiles();
+            self.move_followers(unit_id);
+            return None;
+        }
+        match self.plan_move(unit_id, pos) {
+            Some(MovePlan::Move(path)) => {
+                self.walk(unit_id, path);
+                None
+            }
+            Some(MovePlan::Attack(approach, defender_id)) => {
+                if approach.steps() > 0 {
+                    self.walk(unit_id, approach);
+                }
+                Some(self.get_combat_stats(unit_id, defender_id))
+            }
+            None => None,
+        }
+    }
+
+    /// Returns how `unit_id` gets to `pos` this turn, if it can.
+    ///
+    /// Attacks are planned as a walk to the tile next to the defender, followed by the attack. The
+    /// unit has to be able to stop on that tile and to have movements left once there, so it can't
+    /// approach through a tile full of our units nor by moving from a ZOC to another.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::{LiveMap, MovePlan};
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(5, 5));
+    /// let pos1 = Pos::vector(Direction::South);
+    /// let pos2 = pos1.neighbor(Direction::South);
+    /// let pos3 = pos2.neighbor(Direction::South);
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, pos1));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), pos3));
+    /// map.refresh();
+    /// match map.plan_move(1, Pos::origin()) {
+    ///     Some(MovePlan::Attack(ref approach, 2)) if approach.steps() == 0 => (),
+    ///     _ => panic!("We attack adjacent enemies from where we stand"),
+    /// }
+    /// // Going next to our second target would move us from a ZOC to another.
+    /// assert!(map.plan_move(1, pos3).is_none());
+    /// let mut rules = *map.rules();
+    /// rules.zoc = false;
+    /// map.set_rules(rules);
+    /// match map.plan_move(1, pos3) {
+    ///     Some(MovePlan::Attack(ref approach, 3)) if approach.to() == pos2 => (),
+    ///     _ => panic!("Without ZOC, we can get next to our second target"),
+    /// }
+    /// // We can't stop on a tile already full of our units to attack from there.
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, pos2));
+    /// map.refresh();
+    /// assert!(map.plan_move(1, pos3).is_none());
+    /// ```
+    pub fn plan_move(&self, unit_id: UnitID, pos: Pos) -> Option<MovePlan> {
+        let path = match self.reachable_pos(unit_id).remove(&pos) {
+            Some(path) => path,
+            None => return None,
+        };
+        let unit = self.units.get(unit_id);
+        // Our own units there are ones we stack with.
+        let defender_id = self.defender_at(pos).and_then(|u| {
+            if u.owner() != unit.owner() { Some(u.id()) } else { None }
+        });
+        let defender_id = match defender_id {
+            Some(defender_id) => defender_id,
+            None => {
+                return if LivePath::new(&path, self).is_reachable() {
+                    Some(MovePlan::Move(path))
+                } else {
+                    None
+                };
+            }
+        };
+        if unit.type_().is_civilian() || !unit.can_attack() {
+            return None;
+        }
+        let stack = path.stack();
+        let mut approach = PosPath::new(stack[0]);
+        for &p in stack[1..stack.len() - 1].iter() {
+            approach.push(p);
+        }
+        if approach.steps() > 0 {
+            let livepath = LivePath::new(&approach, self);
+            if !livepath.is_reachable() || livepath.is_exhausting() ||
+               livepath.cost() >= unit.movements() {
+                return None;
+            }
+        }
+        Some(MovePlan::Attack(approach, defender_id))
+    }
+
+    /// Moves `unit_id` along `path`, which `reachable_pos()` returned.
+    fn walk(&mut self, unit_id: UnitID, path: PosPath) {
+        let cost = {
+            let livepath = LivePath::new(&path, self);
+            if livepath.is_exhausting() {
+                self.units.get(unit_id).movements()
+            } else {
+                livepath.cost()
+            }
+        };
+        self.units.get_mut(unit_id).move_to(path.to(), cost);
+        self.events.push(GameEvent::UnitMoved(unit_id, path));
+        self.capture_city(unit_id);
+        self.update_unit_tiles();
+        self.move_followers(unit_id);
+        self.check_sieges();
+    }
+
+    /// Raises `GameEvent::CityBesieged` for `city_id`, unless we already did this turn.
+    fn besiege(&mut self, city_id: CityID) {
+        if self.besieged.insert(city_id) {
+            let city = self.cities.get(city_id);
+            self.events.push(GameEvent::CityBesieged(city_id, city.owner(), city.pos()));
+        }
+    }
+
+    /// Besieges the cities having enemy military units next to them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::city::City;
+    /// use civng_core::events::GameEvent;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(6, 6));
+    /// let south = Pos::vector(Direction::South);
+    /// let southeast = Pos::vector(Direction::SouthEast);
+    /// map.add_city(City::new("Rome", Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Worker, Player::AI(0), south.amplify(2)));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), southeast.amplify(2)));
+    /// map.refresh();
+    /// let is_siege = |e: &GameEvent| match *e {
+    ///     GameEvent::CityBesieged(1, Player::Me, pos) => pos == Pos::origin(),
+    ///     _ => false,
+    /// };
+    /// map.take_events();
+    /// // Civilians don't threaten cities.
+    /// map.moveunit_to(1, south);
+    /// assert!(!map.take_events().iter().any(&is_siege));
+    /// map.moveunit_to(2, southeast);
+    /// assert_eq!(map.take_events().iter().filter(|e| is_siege(e)).count(), 1);
+    /// // The siege is only raised once per turn, but again on the next turns.
+    /// map.moveunit_to(2, Pos::vector(Direction::NorthEast));
+    /// assert!(!map.take_events().iter().any(&is_siege));
+    /// map.refresh();
+    /// assert!(map.take_events().iter().any(&is_siege));
+    /// ```
+    fn check_sieges(&mut self) {
+        let besieged: Vec<CityID> = self.cities
+                                        .all_cities()
+                                        .into_iter()
+                                        .filter(|c| {
+                                            c.pos().around().iter().any(|&p| {
+                                                self.units.military_at_pos(p).map_or(false, |u| {
+                                                    !u.is_dead() &&
+                                                    self.diplomacy.at_war(u.owner(), c.owner())
+                                                })
+                                            })
+                                        })
+                                        .map(|c| c.id())
+                                        .collect();
+        for city_id in besieged {
+            self.besiege(city_id);
+        }
+    }
+
+    /// Makes `unit_id` capture the enemy city it stands in, if any.
+    ///
+    /// Only melee units capture cities. See `Cities::capture()`.
+    fn capture_city(&mut self, unit_id: UnitID) {
+        let (owner, pos) = {
+            let unit = self.units.get(unit_id);
+            if unit.is_dead() || unit.type_().is_civilian() || unit.type_().is_ranged() {
+                return;
+            }
+            (unit.owner(), unit.pos())
+        };
+        let (city_id, former_owner) = match self.cities.get_at_pos(pos) {
+            Some(city) if self.diplomacy.at_war(city.owner(), owner) => (city.id(), city.owner()),
+            _ => return,
+        };
+        self.cities.capture(city_id, owner);
+        self.cities.assign_citizens(city_id, &self.terrain);
+        self.events.push(GameEvent::CityCaptured(city_id, former_owner, owner));
+    }
+
+    /// Decides what happens to `city_id`, which has just been captured. See
+    /// `Cities::set_status()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::city::{City, CityStatus};
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(5, 5));
+    /// let south = Pos::vector(Direction::South);
+    /// map.add_city(City::new("Paris", Player::AI(0), south));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.refresh();
+    /// map.moveunit_to(1, south);
+    /// assert!(map.cities().get(1).owner() == Player::Me);
+    /// assert_eq!(map.cities().get(1).status(), CityStatus::Captured);
+    /// assert!(map.set_city_status(1, CityStatus::Razing));
+    /// map.refresh();
+    /// assert!(map.cities().all_cities().is_empty());
+    /// ```
+    pub fn set_city_status(&mut self, city_id: CityID, status: CityStatus) -> bool {
+        self.cities.set_status(city_id, status)
+    }
+
+    /// Returns and forgets events that happened since the last call, in the order they happened.
+    ///
+    /// This lets the UI know about what happened outside of its control, such as AI movements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    /// use civng_core::events::GameEvent;
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(5, 5));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.refresh();
+    /// let _ = map.take_events();
+    /// map.moveunit_to(1, Pos::vector(Direction::South));
+    /// let events = map.take_events();
+    /// match events[0] {
+    ///     GameEvent::UnitMoved(unit_id, _) => assert_eq!(unit_id, 1),
+    ///     _ => panic!("expected a movement"),
+    /// }
+    /// // Moving south revealed new tiles.
+    /// assert!(events.iter().any(|e| match *e {
+    ///     GameEvent::TileRevealed(Player::Me, _) => true,
+    ///     _ => false,
+    /// }));
+    /// assert!(map.take_events().is_empty());
+    /// ```
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        self.events.drain(..).collect()
+    }
+
+    /// Returns the stats of a ranged attack of `unit_id` on the enemy at `pos`, to give to
+    /// `attack()`.
+    ///
+    /// The unit needs `pos` in range and line of sight, see `bombardable_pos()`, and movements
+    /// left. A unit that moved can still shoot with its leftover movements, but only as many
+    /// times per turn as it can attack. Unlike melee attacks, bombarding ignores zones of control.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(6, 6));
+    /// let south = Pos::vector(Direction::South);
+    /// map.add_unit(Unit::new(UnitType::Ranged, Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), south.amplify(3)));
+    /// map.refresh();
+    /// // Out of range, until we move closer.
+    /// assert!(map.bombard_at(1, south.amplify(3)).is_none());
+    /// map.moveunit_to(1, south);
+    /// let mut stats = map.bombard_at(1, south.amplify(3)).unwrap();
+    /// map.attack(&mut stats);
+    /// assert_eq!(map.units().get(1).attacks(), 1);
+    /// assert!(map.bombard_at(1, south.amplify(3)).is_none());
+    /// ```
+    pub fn bombard_at(&mut self, unit_id: UnitID, pos: Pos) -> Option<CombatStats> {
+        {
+            let unit = self.units.get(unit_id);
+            if unit.is_exhausted() || !unit.can_attack() {
+                return None;
+            }
+        }
+        if !self.bombardable_pos(unit_id).contains_key(&pos) {
+            return None;
+        }
+        if let Some(defender_id) = self.defender_at(pos).map(|u| u.id()) {
+            let defender = self.units.get(defender_id);
+            if !self.diplomacy.at_war(defender.owner(), self.units.get(unit_id).owner()) {
+                return None;
+            }
+            let combat_result = self.get_combat_stats(unit_id, defender_id);
+            return Some(combat_result);
+        }
+        None
+    }
+
+    pub fn attack(&mut self, combat_stats: &mut CombatStats) {
+        self.units.attack(&self.rules, combat_stats);
+        if combat_stats.withdrew {
+            // Nobody fought, so there's nothing to log, but the withdrawal is reported.
+            self.events.push(GameEvent::CombatResolved(combat_stats.clone()));
+            self.update_unit_tiles();
+            return;
+        }
+        let record = CombatRecord::new(combat_stats,
+                                       self.units.get(combat_stats.attacker_id).owner(),
+                                       self.units.get(combat_stats.defender_id).owner());
+        self.combat_log.record(record);
+        self.events.push(GameEvent::CombatResolved(combat_stats.clone()));
+        let besieged = {
+            let defender = self.units.get(combat_stats.defender_id);
+            self.events.push(GameEvent::UnitAttacked(defender.id(),
+                                                     defender.owner(),
+                                                     defender.pos()));
+            self.cities
+                .get_at_pos(defender.pos())
+                .and_then(|c| if c.owner() == defender.owner() { Some(c.id()) } else { None })
+        };
+        if let Some(city_id) = besieged {
+            self.besiege(city_id);
+        }
+        let owners = (self.units.get(combat_stats.attacker_id).owner(),
+                      self.units.get(combat_stats.defender_id).owner());
+        for &(unit_id, enemy) in [(combat_stats.attacker_id, owners.1),
+                                  (combat_stats.defender_id, owners.0)]
+                                     .iter() {
+            let unit = self.units.get(unit_id);
+            if unit.is_dead() {
+                self.events.push(GameEvent::UnitKilled(unit_id, unit.owner(), unit.pos()));
+                self.diplomacy.add_loss(unit.owner(), enemy);
+            }
+        }
+        // A melee attacker killing its defender moves to its tile, possibly into a city.
+        self.capture_city(combat_stats.attacker_id);
+        self.update_unit_tiles();
+    }
+
+    /// Returns the military unit garrisoned in `city_id`, the strongest if there are several.
+    pub fn garrison(&self, city_id: CityID) -> Option<UnitID> {
+        let city = self.cities.get(city_id);
+        self.units
+            .all_units()
+            .filter(|u| {
+                u.pos() == city.pos() && u.owner() == city.owner() && !u.is_dead() &&
+                !u.type_().is_civilian()
+            })
+            .max_by_key(|u| (u.strength(), u.id()))
+            .map(|u| u.id())
+    }
+
+    /// Returns the ranged strength `city_id` strikes with.
+    ///
+    /// Cities start at `CITY_BASE_STRENGTH`, get one more per citizen and half of the strength of
+    /// their garrison.
+    pub fn city_strength(&self, city_id: CityID) -> u8 {
+        let population = self.cities.get(city_id).population();
+        let garrison = self.garrison(city_id).map_or(0, |u| self.units.get(u).strength() / 2);
+        CITY_BASE_STRENGTH.saturating_add(population).saturating_add(garrison)
+    }
+
+    /// Returns the enemy units `city_id` can strike right now, from the weakest in HP.
+    ///
+    /// A city can strike once per turn any enemy within `CITY_STRIKE_RANGE` of its center.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::city::City;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(6, 6));
+    /// map.add_city(City::new("Rome", Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// let south = Pos::vector(Direction::South);
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), south.amplify(2)));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), south.amplify(3)));
+    /// assert_eq!(map.garrison(1), Some(1));
+    /// // 5, plus 1 citizen and half of the melee garrison.
+    /// assert_eq!(map.city_strength(1), 10);
+    /// assert_eq!(map.city_strike_targets(1), vec![2]);
+    /// let mut stats = map.get_city_strike_stats(1, 2);
+    /// map.city_strike(&mut stats);
+    /// assert!(map.units().get(2).hp() < 100);
+    /// assert!(map.city_strike_targets(1).is_empty());
+    /// ```
+    pub fn city_strike_targets(&self, city_id: CityID) -> Vec<UnitID> {
+        let city = self.cities.get(city_id);
+        if city.has_struck() {
+            return Vec::new();
+        }
+        let mut targets: Vec<&Unit> = self.units
+                                          .all_units()
+                                          .filter(|u| {
+                                              self.diplomacy.at_war(u.owner(), city.owner()) &&
+                                              !u.is_dead() &&
+                                              u.pos().distance(city.pos()) <= CITY_STRIKE_RANGE
+                                          })
+                                          .collect();
+        targets.sort_by_key(|u| (u.hp(), u.id()));
+        targets.into_iter().map(|u| u.id()).collect()
+    }
+
+    /// Returns the combat stats `city_id` would have if it struck `defender_id` right now.
+    pub fn get_city_strike_stats(&self, city_id: CityID, defender_id: UnitID) -> CombatStats {
+        let defender_modifiers = self.get_unit_modifiers(CombatSide {
+            unit_id: defender_id,
+            against_id: None,
+            defends: true,
+            ranged: true,
+        });
+        CombatStats::city_strike(self.cities.get(city_id),
+                                 self.city_strength(city_id),
+                                 self.units.get(defender_id),
+                                 defender_modifiers)
+    }
+
+    /// Makes the city of `combat_stats` strike its defender, using its strike for this turn.
+    pub fn city_strike(&mut self, combat_stats: &mut CombatStats) {
+        let city_id = combat_stats.attacker_city.unwrap();
+        self.units.city_strike(combat_stats);
+        self.cities.get_mut(city_id).set_struck();
+        let record = CombatRecord::new(combat_stats,
+                                       self.cities.get(city_id).owner(),
+                                       self.units.get(combat_stats.defender_id).owner());
+        self.combat_log.record(record);
+        self.events.push(GameEvent::CombatResolved(combat_stats.clone()));
+        {
+            let defender = self.units.get(combat_stats.defender_id);
+            let (unit_id, owner, pos) = (defender.id(), defender.owner(), defender.pos());
+            self.events.push(GameEvent::UnitAttacked(unit_id, owner, pos));
+            if defender.is_dead() {
+                self.events.push(GameEvent::UnitKilled(unit_id, owner, pos));
+                self.diplomacy.add_loss(owner, self.cities.get(city_id).owner());
+            }
+        }
+        self.update_unit_tiles();
+    }
+
+    /// Mutable access to terrain, for scripted map changes.
+    pub fn terrain_mut(&mut self) -> &mut TerrainMap {
+        self.terrain_changed = true;
+        &mut self.terrain
+    }
+
+    /// Prepares units and cities for a new turn.
+    ///
+    /// Sentries with an enemy in sight are woken up so that they ask for orders again. Cities grow
+    /// or starve, accumulate production and science and build what they're building. Units they
+    /// train appear on their tile with the experience their buildings give. Wars last one more
+    /// turn, and the war weariness and supply penalty of players are updated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::city::{City, Production};
+    /// use civng_core::unit::{UnitType, Player};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(5, 5));
+    /// map.add_city(City::with_state("Sparta", Player::Me, Pos::origin(), 1, 0, 40));
+    /// let barracks = map.cities().buildings().find("Barracks").unwrap();
+    /// map.cities_mut().get_mut(1).add_building(barracks);
+    /// map.set_production(1, Some(Production::Unit(UnitType::Melee)));
+    /// map.refresh();
+    /// let unit = map.units().top_at_pos(Pos::origin()).unwrap();
+    /// assert_eq!((unit.name(), unit.xp()), ("Melee", 15));
+    /// ```
+    pub fn refresh(&mut self) {
+        self.diplomacy.pass_turn();
+        let players: Vec<Player> = Some(Player::Me).into_iter().chain(self.ai_players()).collect();
+        for player in players {
+            let weariness = self.diplomacy.war_weariness(player);
+            self.cities.set_war_weariness(player, weariness);
+            let penalty = self.supply_penalty(player);
+            self.cities.set_supply_penalty(player, penalty);
+            let bonus = match player {
+                Player::Me => 0,
+                Player::AI(_) => self.difficulty.ai_production_bonus(),
+            };
+            self.cities.set_production_bonus(player, bonus);
+        }
+        let city_events = self.cities.refresh(&self.terrain);
+        for event in city_events.iter() {
+            if let GameEvent::CityBuilt(city_id, Production::Unit(unit_type)) = *event {
+                let (owner, pos, xp) = {
+                    let city = self.cities.get(city_id);
+                    (city.owner(), city.pos(), self.cities.training_xp(city_id))
+                };
+                let mut unit = Unit::new(unit_type, owner, pos);
+                unit.add_xp(xp);
+                self.units.add_unit(unit);
+            }
+        }
+        self.events.extend(city_events);
+        let grounds = self.units
+                          .all_units()
+                          .map(|u| (u.id(), self.healing_ground(u.id())))
+                          .collect();
+        self.units.refresh(&self.rules, &grounds);
+        self.units.wake_sentries();
+        self.update_unit_tiles();
+        self.events.push(GameEvent::TurnStarted);
+        self.besieged.clear();
+        self.check_sieges();
+    }
+
+    /// Gives `amount` experience to `unit_id`.
+    pub fn add_xp(&mut self, unit_id: UnitID, amount: u16) {
+        self.units.get_mut(unit_id).add_xp(amount);
+    }
+
+    /// Gives `promotion` to `unit_id` if it can pick it. Returns whether the unit was promoted.
+    pub fn promote(&mut self, unit_id: UnitID, promotion: Promotion) -> bool {
+        self.units.get_mut(unit_id).promote(promotion)
+    }
+
+    /// Sets the number of attacks `unit_id` made this turn, as `Unit::set_attacks()` does.
+    pub fn set_attacks(&mut self, unit_id: UnitID, attacks: u8) {
+        self.units.get_mut(unit_id).set_attacks(attacks);
+    }
+
+    /// Gives `unit_id` a name of its own, as `Unit::set_name()` does.
+    pub fn rename_unit(&mut self, unit_id: UnitID, name: &str) {
+        self.units.get_mut(unit_id).set_name(name);
+    }
+
+    /// Returns where `unit_id` heals, given the city or the territory it stands in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player, HealingGround};
+    /// use civng_core::city::City;
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(8, 8));
+    /// map.add_city(City::new("Rome", Player::AI(0), Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::vector(Direction::South)));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), Pos::origin()));
+    /// assert_eq!(map.healing_ground(1), HealingGround::Enemy);
+    /// assert_eq!(map.healing_ground(2), HealingGround::City);
+    /// assert_eq!(map.heal_rate(1), 0);
+    /// assert_eq!(map.heal_rate(2), 20);
+    /// ```
+    pub fn healing_ground(&self, unit_id: UnitID) -> HealingGround {
+        let unit = self.units.get(unit_id);
+        match self.cities.get_at_pos(unit.pos()) {
+            Some(city) if city.owner() == unit.owner() => return HealingGround::City,
+            _ => (),
+        }
+        match self.territory_owner(unit.pos()) {
+            Some(owner) if owner == unit.owner() => HealingGround::Friendly,
+            Some(owner) if self.diplomacy.at_war(owner, unit.owner()) => HealingGround::Enemy,
+            _ => HealingGround::Neutral,
+        }
+    }
+
+    /// Returns the HP `unit_id` heals at the start of next turn if it rests until then.
+    pub fn heal_rate(&self, unit_id: UnitID) -> u8 {
+        self.units.heal_rate(unit_id, self.healing_ground(unit_id), &self.rules)
+    }
+
+    /// Puts `unit_id` on top of the units sharing its tile. See `Units::bring_to_top()`.
+    pub fn bring_to_top(&mut self, unit_id: UnitID) {
+        self.units.bring_to_top(unit_id);
+    }
+
+    /// Returns the unit defending the tile at `pos`: its military unit or, without one, the
+    /// civilian on top.
+    pub fn defender_at(&self, pos: Pos) -> Option<&Unit> {
+        self.units.military_at_pos(pos).or_else(|| self.units.top_at_pos(pos))
+    }
+
+    /// Makes `unit_id` do nothing for the rest of the turn.
+    pub fn skip_turn(&mut self, unit_id: UnitID) {
+        self.units.get_mut(unit_id).skip_turn();
+    }
+
+    /// Pushes `unit_id` to the end of the activation queue for this turn.
+    pub fn wait(&mut self, unit_id: UnitID) {
+        self.units.get_mut(unit_id).wait();
+    }
+
+    /// Gives `order` to `unit_id`, or cancels its orders if `None`.
+    pub fn set_order(&mut self, unit_id: UnitID, order: Option<Order>) {
+        self.units.get_mut(unit_id).set_order(order);
+    }
+
+    /// Adds `order` at the end of the order queue of `unit_id`.
+    pub fn queue_order(&mut self, unit_id: UnitID, order: Order) {
+        self.units.get_mut(unit_id).queue_order(order);
+    }
+
+    /// Moves `unit_id` on to its next queued order.
+    fn complete_order(&mut self, unit_id: UnitID) {
+        self.units.get_mut(unit_id).complete_order();
+    }
+
+    /// Returns the cheapest path `unit_id` can follow to `to`, as far as its owner knows.
+    ///
+    /// Tiles the owner didn't explore are assumed to be passable and to cost 1 movement. The path
+    /// goes around units and ignores zones of control, so the unit may have to stop before it
+    /// gets to the end of it.
+    pub fn find_path(&self, unit_id: UnitID, to: Pos) -> Option<PosPath> {
+        let unit = self.units.get(unit_id);
+        let fog = match self.fog(unit.owner()) {
+            Some(fog) => fog,
+            None => return None,
+        };
+        let grid = self.terrain.grid();
+        let impassable = self.terrain.impassable();
+        grid.find_path(unit.pos(), to, |index| {
+            let pos = grid.pos(index);
+            if !fog.explored().contains(index) {
+                Some(1)
+            } else if impassable.contains(index) || self.occupied.contains(index) ||
+               !self.can_enter(unit.owner(), pos) {
+                None
+            } else {
+                Some(self.terrain.get_terrain(pos).movement_cost())
+            }
+        })
+    }
+
+    /// Returns the path to the nearest tile `unit_id` can reach that borders unexplored tiles.
+    fn path_to_frontier(&self, unit_id: UnitID) -> Option<PosPath> {
+        let unit = self.units.get(unit_id);
+        let fog = match self.fog(unit.owner()) {
+            Some(fog) => fog,
+            None => return None,
+        };
+        let mut frontier: Vec<Pos> = self.terrain
+                                         .tiles()
+                                         .filter(|&(p, _)| {
+                                             self.terrain.is_passable(p) && fog.is_explored(p)
+                                         })
+                                         .filter(|&(p, _)| {
+                                             p.around().iter().any(|n| {
+                                                 self.terrain.get_terrain(*n) !=
+                                                 Terrain::OutOfBounds &&
+                                                 !fog.is_explored(*n)
+                                             })
+                                         })
+                                         .map(|(p, _)| p)
+                                         .collect();
+        frontier.sort_by_key(|p| p.distance(unit.pos()));
+        frontier.iter().filter_map(|&p| self.find_path(unit_id, p)).next()
+    }
+
+    /// Moves `unit_id` toward unexplored tiles for as long as it has movements.
+    ///
+    /// The unit keeps its explore order, unless an enemy unit is in sight or there's nothing left
+    /// it can explore.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player, Order};
+    /// use civng_core::hexpos::{Pos, OffsetPos};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(10, 3));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.refresh();
+    /// let far = OffsetPos::new(9, 1).to_pos();
+    /// assert!(!map.fog(Player::Me).unwrap().is_explored(far));
+    /// for _ in 0..5 {
+    ///     map.set_order(1, Some(Order::Explore));
+    ///     map.explore(1);
+    ///     map.refresh();
+    /// }
+    /// assert!(map.fog(Player::Me).unwrap().is_explored(far));
+    /// assert_eq!(map.units().get(1).order(), None);
+    /// ```
+    pub fn explore(&mut self, unit_id: UnitID) {
+        while !self.units.get(unit_id).is_exhausted() {
+            if self.enemy_in_sight(unit_id) {
+                self.set_order(unit_id, None);
+                return;
+            }
+            let next = match self.path_to_frontier(unit_id) {
+                Some(ref path) if path.steps() > 0 => path.stack()[1],
+                _ => {
+                    self.complete_order(unit_id);
+                    return;
+                }
+            };
+            if !self.step_keeping_order(unit_id, next) {
+                // Blocked, by a zone of control for example. We'll try again next turn.
+                return;
+            }
+        }
+    }
+
+    /// Moves `unit_id` toward `to` for as long as it has movements.
+    ///
+    /// The unit keeps its go-to order until it gets to `to` or there's no path to it anymore.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player, Order};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(5, 5));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.refresh();
+    /// let target = Pos::vector(Direction::South).amplify(3);
+    /// map.set_order(1, Some(Order::GoTo(target)));
+    /// map.go_to(1, target);
+    /// assert_eq!(map.units().get(1).order(), Some(Order::GoTo(target)));
+    /// map.refresh();
+    /// map.go_to(1, target);
+    /// assert_eq!(map.units().get(1).pos(), target);
+    /// assert_eq!(map.units().get(1).order(), None);
+    /// ```
+    pub fn go_to(&mut self, unit_id: UnitID, to: Pos) {
+        while !self.units.get(unit_id).is_exhausted() {
+            if self.units.get(unit_id).pos() == to {
+                self.complete_order(unit_id);
+                return;
+            }
+            let next = match self.find_path(unit_id, to) {
+                Some(path) => path.stack()[1],
+                None => {
+                    self.set_order(unit_id, None);
+                    return;
+                }
+            };
+            if !self.step_keeping_order(unit_id, next) {
+                return;
+            }
+        }
+        if self.units.get(unit_id).pos() == to {
+            self.complete_order(unit_id);
+        }
+    }
+
+    /// Moves `unit_id` next to `leader_id`, if it isn't already and has movements for it.
+    ///
+    /// If the leader is dead, the unit stops following it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player, Order};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(5, 5));
+    /// let pos1 = Pos::origin();
+    /// let pos2 = pos1.neighbor(Direction::South);
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, pos1));
+    /// map.add_unit(Unit::new(UnitType::Ranged, Player::Me, pos2));
+    /// map.refresh();
+    /// map.set_order(2, Some(Order::Follow(1)));
+    /// // Moving the leader away brings its escort along.
+    /// map.moveunit_to(1, Pos::vector(Direction::SouthEast));
+    /// map.moveunit_to(1, Pos::vector(Direction::SouthEast).amplify(2));
+    /// assert_eq!(map.units().get(2).pos().distance(map.units().get(1).pos()), 1);
+    /// assert_eq!(map.units().get(2).order(), Some(Order::Follow(1)));
+    /// ```
+    pub fn follow(&mut self, unit_id: UnitID, leader_id: UnitID) {
+        while !self.units.get(unit_id).is_exhausted() {
+            let leader_pos = self.units
+                                 .all_units()
+                                 .find(|u| u.id() == leader_id && !u.is_dead())
+                                 .map(|u| u.pos());
+            let leader_pos = match leader_pos {
+                Some(p) => p,
+                None => {
+                    self.complete_order(unit_id);
+                    return;
+                }
+            };
+            let pos = self.units.get(unit_id).pos();
+            if pos.distance(leader_pos) <= 1 {
+                return;
+            }
+            let mut targets: Vec<Pos> = leader_pos.around()
+                                                  .iter()
+                                                  .cloned()
+                                                  .filter(|&p| self.is_pos_passable(p))
+                                                  .collect();
+            targets.sort_by_key(|p| p.distance(pos));
+            let next = match targets.iter().filter_map(|&p| self.find_path(unit_id, p)).next() {
+                Some(path) => path.stack()[1],
+                None => return,
+            };
+            if !self.step_keeping_order(unit_id, next) {
+                return;
+            }
+        }
+    }
+
+    /// Moves `unit_id` along its patrol route for as long as it has movements.
+    ///
+    /// When the unit gets to the end of its route, it heads back to where it started. It stops
+    /// patrolling when an enemy unit comes in sight or the route is blocked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player, Order};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(5, 5));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.refresh();
+    /// let end = Pos::vector(Direction::South);
+    /// map.set_order(1, Some(Order::Patrol(Pos::origin(), end)));
+    /// map.patrol(1);
+    /// // The unit went to the end of its route and came back.
+    /// assert_eq!(map.units().get(1).pos(), Pos::origin());
+    /// assert_eq!(map.units().get(1).order(), Some(Order::Patrol(end, Pos::origin())));
+    /// ```
+    pub fn patrol(&mut self, unit_id: UnitID) {
+        while !self.units.get(unit_id).is_exhausted() {
+            let (from, to) = match self.units.get(unit_id).order() {
+                Some(Order::Patrol(from, to)) if from != to => (from, to),
+                _ => return,
+            };
+            if self.enemy_in_sight(unit_id) {
+                self.set_order(unit_id, None);
+                return;
+            }
+            if self.units.get(unit_id).pos() == to {
+                self.set_order(unit_id, Some(Order::Patrol(to, from)));
+                continue;
+            }
+            let next = match self.find_path(unit_id, to) {
+                Some(path) => path.stack()[1],
+                None => {
+                    self.set_order(unit_id, None);
+                    return;
+                }
+            };
+            if !self.step_keeping_order(unit_id, next) {
+                return;
+            }
+        }
+    }
+
+    /// Makes worker `unit_id` spend its turn removing the feature of its tile.
+    ///
+    /// `Order::Clear` counts the turns the worker spent on the tile. The feature is removed once
+    /// that count reaches `Feature::removal_turns()`, which changes what the tile yields, and the
+    /// nearest city of the worker's owner gets `Feature::removal_production()`. The order is
+    /// completed right away if there's nothing the worker can clear. Moving the worker or
+    /// giving it another order before then cancels the work.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{TerrainMap, Feature};
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player, Order};
+    /// use civng_core::city::City;
+    /// use civng_core::hexpos::{Pos, OffsetPos};
+    ///
+    /// let mut terrain = TerrainMap::empty_map(8, 3);
+    /// terrain.set_feature(Pos::origin(), Some(Feature::Forest));
+    /// let mut map = LiveMap::new(terrain);
+    /// map.add_city(City::new("Rome", Player::Me, OffsetPos::new(7, 0).to_pos()));
+    /// map.add_unit(Unit::new(UnitType::Worker, Player::Me, Pos::origin()));
+    /// map.set_order(1, Some(Order::Clear(0)));
+    /// for _ in 0..2 {
+    ///     map.refresh();
+    ///     map.clear(1);
+    /// }
+    /// assert_eq!(map.units().get(1).order(), Some(Order::Clear(2)));
+    /// // Only one turn of work per turn.
+    /// map.clear(1);
+    /// assert_eq!(map.units().get(1).order(), Some(Order::Clear(2)));
+    /// map.refresh();
+    /// map.clear(1);
+    /// assert!(map.terrain().get_feature(Pos::origin()).is_none());
+    /// assert_eq!(map.units().get(1).order(), None);
+    /// // The city produced 1 production per turn on its own.
+    /// assert_eq!(map.cities().get(1).production(), 3 + 20);
+    /// ```
+    pub fn clear(&mut self, unit_id: UnitID) {
+        let (pos, worked, orders) = {
+            let unit = self.units.get(unit_id);
+            match unit.order() {
+                Some(Order::Clear(worked)) => (unit.pos(), worked, unit.orders().to_vec()),
+                _ => return,
+            }
+        };
+        let feature = self.terrain.get_feature(pos);
+        let turns = match feature.and_then(|f| f.removal_turns()) {
+            Some(turns) if self.units.get(unit_id).type_() == UnitType::Worker => turns,
+            _ => {
+                self.complete_order(unit_id);
+                return;
+            }
+        };
+        if self.units.get(unit_id).is_exhausted() {
+            return;
+        }
+        self.units.get_mut(unit_id).exhaust();
+        if worked + 1 < turns {
+            let mut orders = orders;
+            orders[0] = Order::Clear(worked + 1);
+            self.units.get_mut(unit_id).set_orders(orders);
+            return;
+        }
+        let feature = feature.unwrap();
+        self.terrain.set_feature(pos, None);
+        self.terrain_changed = true;
+        let owner = self.units.get(unit_id).owner();
+        if let Some(city_id) = self.cities.nearest_city(owner, pos) {
+            self.cities.get_mut(city_id).add_production(feature.removal_production() as u16);
+        }
+        self.events.push(GameEvent::FeatureRemoved(unit_id, pos, feature));
+        self.complete_order(unit_id);
+        // Forests and jungles hide what's behind them.
+        self.update_unit_tiles();
+    }
+
+    /// Moves the escorts of `leader_id` along with it.
+    fn move_followers(&mut self, leader_id: UnitID) {
+        let followers: Vec<UnitID> = self.units
+                                         .all_units()
+                                         .filter(|u| u.order() == Some(Order::Follow(leader_id)))
+                                         .map(|u| u.id())
+                                         .collect();
+        for unit_id in followers {
+            self.follow(unit_id, leader_id);
+        }
+    }
+
+    /// Returns whether an enemy unit is within sight of `unit_id`.
+    fn enemy_in_sight(&self, unit_id: UnitID) -> bool {
+        let unit = self.units.get(unit_id);
+        let tiles = self.visible_tiles(unit_id);
+        self.units
+            .all_units()
+            .any(|u| u.owner() != unit.owner() && tiles.contains(&u.pos()))
+    }
+
+    /// Moves `unit_id` to the adjacent `next` position without cancelling its standing orders.
+    ///
+    /// Returns whether the unit could move.
+    fn step_keeping_order(&mut self, unit_id: UnitID, next: Pos) -> bool {
+        let (pos, orders) = {
+            let unit = self.units.get(unit_id);
+            (unit.pos(), unit.orders().to_vec())
+        };
+        self.moveunit_to(unit_id, next);
+        if self.units.get(unit_id).pos() == pos {
+            false
+        } else {
+            self.units.get_mut(unit_id).set_orders(orders);
+            true
+        }
+    }
+
+    /// Completes the heal order of `unit_id` once the unit is back to full health.
+    ///
+    /// Until then, the unit stays fortified. Completing the order sends a `UnitHealed` event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player, Order};
+    /// use civng_core::hexpos::Pos;
+    /// use civng_core::events::GameEvent;
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(5, 5));
+    /// map.add_unit(Unit::with_state(UnitType::Melee, Player::Me, Pos::origin(), 2, 95));
+    /// map.set_order(1, Some(Order::Heal));
+    /// map.heal(1);
+    /// assert_eq!(map.units().get(1).order(), Some(Order::Heal));
+    /// map.refresh();
+    /// map.take_events();
+    /// map.heal(1);
+    /// assert_eq!(map.units().get(1).order(), None);
+    /// match map.take_events().pop() {
+    ///     Some(GameEvent::UnitHealed(1, Player::Me, _)) => (),
+    ///     _ => panic!("We're told the unit healed"),
+    /// }
+    /// ```
+    pub fn heal(&mut self, unit_id: UnitID) {
+        let (owner, pos) = {
+            let unit = self.units.get(unit_id);
+            if unit.order() != Some(Order::Heal) || unit.is_damaged() {
+                return;
+            }
+            (unit.owner(), unit.pos())
+        };
+        self.complete_order(unit_id);
+        self.events.push(GameEvent::UnitHealed(unit_id, owner, pos));
+    }
+
+    /// Carries out the current order of `unit_id`, moving on to the next queued order each time
+    /// one is completed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player, Order};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(5, 5));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.refresh();
+    /// let target = Pos::vector(Direction::South);
+    /// map.queue_order(1, Order::GoTo(target));
+    /// map.queue_order(1, Order::Fortify);
+    /// map.carry_out_unit_orders(1);
+    /// assert_eq!(map.units().get(1).pos(), target);
+    /// assert_eq!(map.units().get(1).order(), Some(Order::Fortify));
+    /// ```
+    pub fn carry_out_unit_orders(&mut self, unit_id: UnitID) {
+        loop {
+            let (order, count) = {
+                let unit = self.units.get(unit_id);
+                (unit.order(), unit.orders().len())
+            };
+            match order {
+                Some(Order::Explore) => self.explore(unit_id),
+                Some(Order::GoTo(to)) => self.go_to(unit_id, to),
+                Some(Order::Patrol(_, _)) => self.patrol(unit_id),
+                Some(Order::Follow(leader_id)) => self.follow(unit_id, leader_id),
+                Some(Order::Clear(_)) => self.clear(unit_id),
+                Some(Order::Heal) => self.heal(unit_id),
+                _ => return,
+            }
+            if self.units.get(unit_id).orders().len() >= count {
+                // The order isn't done yet.
+                return;
+            }
+        }
+    }
+
+    /// Carries out the orders of `player`'s units at the start of a turn.
+    ///
+    /// Units on the move stop and ask for orders again if an enemy unit is in sight. Escorts are
+    /// moved last, to catch up with units that moved.
+    pub fn carry_out_orders(&mut self, player: Player) {
+        let units: Vec<(UnitID, Order)> = self.units
+                                              .all_units()
+                                              .filter(|u| u.owner() == player)
+                                              .filter_map(|u| u.order().map(|o| (u.id(), o)))
+                                              .collect();
+        for &(unit_id, order) in units.iter() {
+            match order {
+                Order::Follow(_) => continue,
+                Order::GoTo(_) if self.enemy_in_sight(unit_id) => self.set_order(unit_id, None),
+                _ => self.carry_out_unit_orders(unit_id),
+            }
+        }
+        for &(unit_id, order) in units.iter() {
+            if let Order::Follow(_) = order {
+                self.carry_out_unit_orders(unit_id);
+            }
+        }
+    }
+
+    /// Returns the positions `unit_id` can move to or attack this turn, with the cheapest path
+    /// getting there.
+    ///
+    /// Paths are followed one step at a time, keeping what `LivePath` would tell about them, and
+    /// only those of the positions we return are built in the end.
+    pub fn reachable_pos(&self, unit_id: UnitID) -> HashMap<Pos, PosPath> {
+        let unit = self.units.get(unit_id);
+        let owner = unit.owner();
+        let movements = unit.movements();
+        let ignores_zoc = self.ignores_zoc(unit);
+        let start_territory = self.territory_owner(unit.pos());
+        let passable = |pos: Pos| {
+            self.terrain.is_passable(pos) &&
+            (self.can_enter(owner, pos) || self.territory_owner(pos) == start_territory)
+        };
+        if !passable(unit.pos()) {
+            return HashMap::new();
+        }
+        let start = PathStep {
+            cost: 0,
+            zoc: !ignores_zoc &&
+                 hindrances_at(self, unit.pos(), Some(owner)).contains(HINDRANCE_ZOC),
+            through_zoc: false,
+            can_stop: true,
+        };
+        // The cost and the node of the cheapest path to each position.
+        let mut best: HashMap<Pos, (u8, usize)> = HashMap::new();
+        let tree = walk_paths(unit.pos(), movements as usize, start, |tree, node, previous| {
+            let pos = tree.pos(node);
+            // Moving from a cell in a ZOC to another cell in a ZOC ends the path.
+            if !passable(pos) || previous.through_zoc {
+                return None;
+            }
+            let is_attack = self.units
+                                .top_at_pos(pos)
+                                .map_or(false, |u| self.diplomacy.at_war(u.owner(), owner));
+            if is_attack && unit.type_().is_ranged() {
+                return None;
+            }
+            let hindrances = hindrances_at(self, pos, Some(owner));
+            let zoc = !ignores_zoc && hindrances.contains(HINDRANCE_ZOC);
+            let cost = previous.cost + self.terrain.get_terrain(pos).movement_cost();
+            let affordable = if self.rules.last_hex_rule {
+                previous.cost < movements
+            } else {
+                cost <= movements
+            };
+            // Attacks are made from a tile we stop on, and we don't go any further.
+            let reachable = if is_attack {
+                previous.can_stop
+            } else {
+                !hindrances.contains(HINDRANCE_UNIT)
+            };
+            if reachable && affordable {
+                match best.entry(pos) {
+                    Entry::Occupied(mut e) => {
+                        // We replace the path only if the cost of the newer path is lower.
+                        if cost < e.get().0 {
+                            e.insert((cost, node));
+                        }
+                    }
+                    Entry::Vacant(e) => {
+                        e.insert((cost, node));
+                    }
+                }
+            }
+            if cost >= movements || is_attack {
+                None
+            } else {
+                Some(PathStep {
+                    cost: cost,
+                    zoc: zoc,
+                    through_zoc: previous.zoc && zoc,
+                    can_stop: !hindrances.contains(HINDRANCE_UNIT),
+                })
+            }
+        });
+        best.into_iter().map(|(pos, (_, node))| (pos, tree.path(node))).collect()
+    }
+
+    /// Returns the tiles with an enemy that `unit_id` can bombard and its owner sees, nearest
+    /// first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(6, 6));
+    /// let south = Pos::vector(Direction::South);
+    /// map.add_unit(Unit::new(UnitType::Ranged, Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, south));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), south.amplify(2)));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), south.amplify(3)));
+    /// map.refresh();
+    /// // Our own unit isn't a target, and the farthest enemy is out of range.
+    /// assert_eq!(map.bombard_targets(1), vec![south.amplify(2)]);
+    /// ```
+    pub fn bombard_targets(&self, unit_id: UnitID) -> Vec<Pos> {
+        self.visible_enemies_among(unit_id, self.bombardable_pos(unit_id).keys().cloned())
+    }
+
+    /// Returns the tiles with an enemy that `unit_id` can attack in melee this turn and its owner
+    /// sees, nearest first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(6, 6));
+    /// let south = Pos::vector(Direction::South);
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), south.amplify(2)));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), south));
+    /// map.refresh();
+    /// // We can't walk past the nearest enemy to attack the other one.
+    /// assert_eq!(map.attack_targets(1), vec![south]);
+    /// ```
+    pub fn attack_targets(&self, unit_id: UnitID) -> Vec<Pos> {
+        let unit = self.units.get(unit_id);
+        if unit.type_().is_civilian() || !unit.can_attack() {
+            return Vec::new();
+        }
+        self.visible_enemies_among(unit_id, self.reachable_pos(unit_id).keys().cloned())
+    }
+
+    /// Returns the tiles among `positions` with an enemy of `unit_id` that its owner sees, nearest
+    /// first.
+    fn visible_enemies_among<I: Iterator<Item = Pos>>(&self,
+                                                      unit_id: UnitID,
+                                                      positions: I)
+                                                      -> Vec<Pos> {
+        let unit = self.units.get(unit_id);
+        let fog = self.fog(unit.owner());
+        let mut targets: Vec<Pos> = positions.filter(|&pos| {
+                                                 self.defender_at(pos).map_or(false, |u| {
+                                                     self.diplomacy.at_war(u.owner(), unit.owner())
+                                                 }) &&
+                                                 fog.map_or(true, |f| f.is_visible(pos))
+                                             })
+                                             .collect();
+        targets.sort_by_key(|pos| (pos.distance(unit.pos()), pos.y, pos.x));
+        targets
+    }
+
+    pub fn bombardable_pos(&self, unit_id: UnitID) -> HashMap<Pos, PosPath> {
+        let unit = self.units.get(unit_id);
+        let unit_height = self.terrain().get_terrain(unit.pos()).height();
+        let mut last: HashMap<Pos, usize> = HashMap::new();
+        let range = unit.type_().range() as usize;
+        let tree = walk_paths(unit.pos(), range, (), |tree, node, _| {
+            let pos = tree.pos(node);
+            last.insert(pos, node);
+            if self.terrain().get_terrain(pos).height() > unit_height {
+                // We've lost line of sight. We can bombard this tile, but no further.
+                None
+            } else {
+                Some(())
+            }
+        });
+        last.into_iter().map(|(pos, node)| (pos, tree.path(node))).collect()
+    }
+}
+
+/// Tiles a unit saw from where it stood, so that we can hide them once it moves away.
+#[derive(Clone)]
+struct UnitSight {
+    owner: Player,
+    pos: Pos,
+    unit_type: UnitType,
+    tiles: Vec<Pos>,
+}
+
+impl UnitSight {
+    /// Returns whether `unit` still sees what we saw.
+    fn is_of(&self, unit: &Unit) -> bool {
+        unit.owner() == self.owner && unit.pos() == self.pos && unit.type_() == self.unit_type
+    }
+}
+
+/// How a unit gets to where it's ordered to, as planned by `LiveMap::plan_move()`.
+#[derive(Clone, Debug)]
+pub enum MovePlan {
+    /// The unit follows the path.
+    Move(PosPath),
+    /// The unit follows the path, which can have no step, next to a unit it then attacks.
+    Attack(PosPath, UnitID),
+}
+
+/// What `reachable_pos()` keeps about a path to extend it.
+struct PathStep {
+    /// Cost in movements of the path.
+    cost: u8,
+    /// Whether the path ends in the zone of control of an enemy.
+    zoc: bool,
+    /// Whether the path moves from a cell in a ZOC to another cell in a ZOC.
+    through_zoc: bool,
+    /// Whether the unit can end its move where the path ends, which it can't on tiles full of
+    /// units.
+    can_stop: bool,
+}
+
+bitflags! {
+    #[doc="Movement hindrances on a particular position on a live map,
+        from the perspective of a player."]
+    flags Hindrances: u8 {
+        #[doc="A unit is on the cell"]
+        const HINDRANCE_UNIT = 0b01,
+        #[doc="The cell is affected by Zone of Control of an enemy unit"]
+        const HINDRANCE_ZOC = 0b10,
+    }
+}
+
+/// Returns the hindrances `mover` meets at `pos`.
+///
+/// We only look for the units of tiles that are occupied. Tiles with units of `mover` only hinder
+/// it once they hold as many units as the stacking limit allows.
+fn hindrances_at(map: &LiveMap, pos: Pos, mover: Option<Player>) -> Hindrances {
+    let mut result = Hindrances::empty();
+    if let Some(mover_owner) = mover {
+        let enemy_at = |p: Pos| {
+            map.is_occupied(p) &&
+            map.units()
+               .top_at_pos(p)
+               .map_or(false, |u| map.diplomacy().at_war(u.owner(), mover_owner))
+        };
+        if map.is_occupied(pos) {
+            let full = map.units().top_at_pos(pos).map_or(true, |u| u.owner() != mover_owner) ||
+                       map.occupants(pos) >= map.rules().stacking_limit;
+            if full {
+                result.insert(HINDRANCE_UNIT);
+            }
+            if enemy_at(pos) && map.rules().zoc {
+                result.insert(HINDRANCE_ZOC);
+            }
+        }
+        if map.rules().zoc && pos.around().iter().any(|&p| enemy_at(p)) {
+            result.insert(HINDRANCE_ZOC);
+        }
+    }
+    result
+}
+
+/// Terrain and unit information along a `PosPath`.
+pub struct LivePath {
+    /// Number of steps in the path.
+    steps: usize,
+    terrain: Vec<Terrain>,
+    /// Whether each tile can be entered, given its terrain and feature.
+    passable: Vec<bool>,
+    hindrances: Vec<Hindrances>,
+    mover: Option<Player>,
+    /// Whether the unit following the path moves through zones of control freely.
+    ignores_zoc: bool,
+    /// Whether the path ends on a unit of a player at war with `mover`.
+    at_war: bool,
+}
+
+impl LivePath {
+    pub fn new(path: &PosPath, map: &LiveMap) -> LivePath {
+        let stack = path.stack();
+        assert!(!stack.is_empty());
+        let mover_unit = map.units().top_at_pos(*stack.first().unwrap());
+        let mover = mover_unit.map(|u| u.owner());
+        let ignores_zoc = mover_unit.map_or(false, |u| map.ignores_zoc(u));
+        let target = {
+            match map.units().top_at_pos(*stack.last().unwrap()) {
+                Some(u) => Some(u.owner()),
+                None => None,
+            }
+        };
+        let at_war = match (mover, target) {
+            (Some(m), Some(t)) => map.diplomacy().at_war(m, t),
+            _ => false,
+        };
+        let terrain = stack.iter().map(|pos| map.terrain().get_terrain(*pos)).collect();
+        // Tiles of players we can't enter the territory of are as good as impassable. Units that
+        // are already there, because their owner just made peace, can still move around to get out.
+        let start_territory = map.territory_owner(stack[0]);
+        let passable = stack.iter()
+                            .map(|&pos| {
+                                let enterable = match mover {
+                                    Some(m) => {
+                                        map.can_enter(m, pos) ||
+                                        map.territory_owner(pos) == start_territory
+                                    }
+                                    None => true,
+                                };
+                                map.terrain().is_passable(pos) && enterable
+                            })
+                            .collect();
+        let hindrances = stack.iter().map(|pos| hindrances_at(map, *pos, mover)).collect();
+        LivePath {
+            steps: path.steps(),
+            terrain: terrain,
+            passable: passable,
+            hindrances: hindrances,
+            mover: mover,
+            ignores_zoc: ignores_zoc,
+            at_war: at_war,
+        }
+    }
+
+    fn moves_through_zoc(&self, including_last_index: bool) -> bool {
+        // Check for ZOC effect. A unit moving from a cell being in a ZOC to another cell being in
+        // a ZOC cannot go any further.
+        if self.ignores_zoc {
+            return false;
+        }
+        let mut last_index = self.hindrances.len();
+        if !including_last_index {
+            last_index -= 1;
+        }
+        let mut was_zoc = false;
+        for hindrance in self.hindrances[0..last_index].iter() {
+            if hindrance.contains(HINDRANCE_ZOC) {
+                if was_zoc {
+                    return true;
+                } else {
+                    was_zoc = true;
+                }
+            } else {
+                was_zoc = false;
+            }
+        }
+        false
+    }
+
+    /// Whether the path ends on a unit of a player at war with the mover.
+    pub fn is_attack(&self) -> bool {
+        self.at_war
+    }
+
+    /// Whether this path could ever become reachable by adding steps.
+    pub fn could_be_reachable(&self) -> bool {
+        if self.mover.is_none() {
+            false
+        } else if self.passable.iter().any(|&p| !p) {
+            false
+        } else {
+            !self.moves_through_zoc(false)
+        }
+    }
+
+    /// Whether this path is reachable by `self.mover()`.
+    pub fn is_reachable(&self) -> bool {
+        if !self.could_be_reachable() {
+            false
+        } else if self.steps == 0 {
+            false
+        } else {
+            let last_pos_hindrance = self.hindrances.last().unwrap();
+            !last_pos_hindrance.contains(HINDRANCE_UNIT) || self.is_attack()
+        }
+    }
+
+    /// Cost in movements required to move through that path.
+    pub fn cost(&self) -> u8 {
+        self.terrain[1..].iter().fold(0, |acc, &t| acc + t.movement_cost())
+    }
+
+    /// Whether a unit with `movements` left can follow that path.
+    ///
+    /// With `last_hex_rule`, the unit only needs movements left when it gets to the last tile of
+    /// the path. Otherwise, it needs to afford the whole path.
+    pub fn is_affordable(&self, movements: u8, last_hex_rule: bool) -> bool {
+        if last_hex_rule && self.terrain.len() > 1 {
+            let before_last = &self.terrain[1..self.terrain.len() - 1];
+            before_last.iter().fold(0, |acc, &t| acc + t.movement_cost()) < movements
+        } else {
+            self.cost() <= movements
+        }
+    }
+
+    /// Whether the movement exhaust all movements of the mover, regardless of terrain costs.
+    ///
+    /// This happens when we move through an enemy ZOC.
+    pub fn is_exhausting(&self) -> bool {
+        self.moves_through_zoc(true)
+    }
+}
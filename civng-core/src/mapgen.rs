@@ -0,0 +1,661 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Random map generation.
+//!
+//! Maps are generated in two steps. First, a preset-specific algorithm decides which tiles are land
+//! and which are water. Then, each land tile gets a random terrain type and a few of them get a
+//! strategic resource.
+//!
+//! Maps can also be made symmetric, for duels where neither player should get better land by luck.
+//! Half of the map is then stamped over the other half, terrain and resources alike.
+//!
+//! The module also analyzes maps, generated or not: `score_start()` tells how good a tile is to
+//! start on, `find_landmasses()` describes the landmasses of a map and `is_choke_point()` and
+//! `defense_score()` tell which tiles are worth holding.
+
+use rand::Rng;
+
+use hexpos::{Pos, OffsetPos};
+use terrain::{Terrain, TerrainMap, Resource};
+use city::CITY_RADIUS;
+
+/// Width of generated maps when none is specified.
+pub const DEFAULT_WIDTH: i32 = 60;
+/// Height of generated maps when none is specified.
+pub const DEFAULT_HEIGHT: i32 = 30;
+
+/// Land terrain types along with their relative frequency.
+const LAND_WEIGHTS: [(Terrain, u32); 5] = [(Terrain::Grassland, 35),
+                                           (Terrain::Plain, 30),
+                                           (Terrain::Desert, 10),
+                                           (Terrain::Hill, 15),
+                                           (Terrain::Mountain, 10)];
+/// One in this many tiles a resource can lie on gets it.
+const RESOURCE_RARITY: u32 = 10;
+/// Distance start positions try to keep between each other.
+pub const MIN_START_DISTANCE: i32 = 8;
+/// What each resource within a city's reach adds to the score of a start position.
+pub const RESOURCE_SCORE: u32 = 2;
+/// What each impassable tile around a tile adds to its defense score: it's a tile enemies can't
+/// flank its defender from, and flanking is worth 10% per enemy.
+const SHELTER_SCORE: i32 = 10;
+/// Defense score from which a tile is defensive, that of a hill.
+pub const DEFENSIVE_SCORE: i32 = 25;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MapPreset {
+    /// 2 or 3 big landmasses separated by ocean.
+    Continents,
+    /// Many small islands.
+    Archipelago,
+}
+
+impl MapPreset {
+    pub fn all() -> [MapPreset; 2] {
+        [MapPreset::Continents, MapPreset::Archipelago]
+    }
+
+    /// Name of the preset, as used on the command line.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            MapPreset::Continents => "continents",
+            MapPreset::Archipelago => "archipelago",
+        }
+    }
+
+    /// Returns the preset named `name`, if any.
+    pub fn from_name(name: &str) -> Option<MapPreset> {
+        MapPreset::all().iter().find(|p| p.name() == name).cloned()
+    }
+}
+
+/// How a generated map repeats itself.
+///
+/// Tiles are stamped on their image in a way that keeps them next to the images of their
+/// neighbors. Mirror maps need an odd width for that and rotational ones an even width: when the
+/// map doesn't have such a width, its last column is left out of the symmetry and is all ocean.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Symmetry {
+    /// The east half of the map is the west half seen in a mirror.
+    Mirror,
+    /// The map looks the same once turned upside down.
+    Rotational,
+}
+
+impl Symmetry {
+    pub fn all() -> [Symmetry; 2] {
+        [Symmetry::Mirror, Symmetry::Rotational]
+    }
+
+    /// Name of the symmetry, as used on the command line.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Symmetry::Mirror => "mirror",
+            Symmetry::Rotational => "rotational",
+        }
+    }
+
+    /// Returns the symmetry named `name`, if any.
+    pub fn from_name(name: &str) -> Option<Symmetry> {
+        Symmetry::all().iter().find(|s| s.name() == name).cloned()
+    }
+
+    /// Returns where the tile at `pos` is repeated on a map of `map`'s size, if anywhere.
+    ///
+    /// Tiles on the axis of a mirror map, or at the center of a rotational one, are their own
+    /// image.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::mapgen::Symmetry;
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::hexpos::{OffsetPos, Direction};
+    ///
+    /// let map = TerrainMap::empty_map(9, 6);
+    /// let pos = OffsetPos::new(1, 2).to_pos();
+    /// let image = Symmetry::Mirror.image(&map, pos).unwrap();
+    /// assert_eq!(image.to_offset_pos(), OffsetPos::new(7, 2));
+    /// // Neighbors stay neighbors.
+    /// let east = pos.neighbor(Direction::NorthEast);
+    /// let east_image = Symmetry::Mirror.image(&map, east).unwrap();
+    /// assert_eq!(east_image, image.neighbor(Direction::NorthWest));
+    /// // On an odd width, the last column of a rotational map has no image.
+    /// assert_eq!(Symmetry::Rotational.image(&map, OffsetPos::new(8, 0).to_pos()), None);
+    /// let image = Symmetry::Rotational.image(&map, pos).unwrap();
+    /// assert_eq!(image.to_offset_pos(), OffsetPos::new(6, 3));
+    /// ```
+    pub fn image(&self, map: &TerrainMap, pos: Pos) -> Option<Pos> {
+        let (width, height) = map.size();
+        let opos = pos.to_offset_pos();
+        if opos.x < 0 || opos.x >= width || opos.y < 0 || opos.y >= height {
+            return None;
+        }
+        // The columns that keep their neighbors when mirrored or turned around.
+        let columns = match *self {
+            Symmetry::Mirror => if width % 2 == 1 { width } else { width - 1 },
+            Symmetry::Rotational => if width % 2 == 0 { width } else { width - 1 },
+        };
+        if opos.x >= columns {
+            return None;
+        }
+        let image = match *self {
+            Symmetry::Mirror => OffsetPos::new(columns - 1 - opos.x, opos.y),
+            Symmetry::Rotational => OffsetPos::new(columns - 1 - opos.x, height - 1 - opos.y),
+        };
+        Some(image.to_pos())
+    }
+
+    /// Stamps the tiles of one half of `map` over their image in the other half.
+    ///
+    /// Tiles without an image become ocean. Water is classified again afterwards, since the
+    /// stamped half may have turned coast into ocean and the other way around.
+    fn stamp(&self, map: &mut TerrainMap) {
+        // Tiles are stamped from the one of the pair that comes first, row after row.
+        let order = |pos: Pos| {
+            let opos = pos.to_offset_pos();
+            (opos.y, opos.x)
+        };
+        let tiles: Vec<_> = map.tiles().map(|(pos, _)| pos).collect();
+        for pos in tiles {
+            match self.image(map, pos) {
+                Some(image) => {
+                    if order(image) <= order(pos) {
+                        continue;
+                    }
+                    let terrain = map.get_terrain(pos);
+                    let feature = map.get_feature(pos);
+                    let river = map.river_flags(pos);
+                    let resource = map.get_resource(pos);
+                    map.set_terrain(image, terrain);
+                    map.set_feature(image, feature);
+                    map.set_river_flags(image, river);
+                    map.set_resource(image, resource);
+                }
+                None => {
+                    map.set_terrain(pos, Terrain::Ocean);
+                    map.set_feature(pos, None);
+                    map.set_river_flags(pos, 0);
+                    map.set_resource(pos, None);
+                }
+            }
+        }
+        map.classify_water();
+    }
+}
+
+/// Land/water mask of a map being generated.
+struct LandMask {
+    width: i32,
+    height: i32,
+    land: Vec<bool>,
+}
+
+impl LandMask {
+    fn new(width: i32, height: i32) -> LandMask {
+        LandMask {
+            width: width,
+            height: height,
+            land: vec![false; (width * height) as usize],
+        }
+    }
+
+    fn index(&self, opos: OffsetPos) -> usize {
+        (opos.y * self.width + opos.x) as usize
+    }
+
+    /// Grows a landmass of about `size` tiles from `start`.
+    ///
+    /// The landmass stays within columns `xmin..xmax` and rows `ymin..ymax`. Tiles are added by
+    /// picking random tiles next to the landmass, which gives it a blobby shape.
+    fn grow<R: Rng>(&mut self,
+                    rng: &mut R,
+                    start: OffsetPos,
+                    size: usize,
+                    (xmin, xmax): (i32, i32),
+                    (ymin, ymax): (i32, i32)) {
+        let mut frontier = vec![start];
+        let mut count = 0;
+        while count < size && !frontier.is_empty() {
+            let index = rng.gen_range(0, frontier.len());
+            let opos = frontier.swap_remove(index);
+            let i = self.index(opos);
+            if self.land[i] {
+                continue;
+            }
+            self.land[i] = true;
+            count += 1;
+            for npos in opos.to_pos().around().iter() {
+                let nopos = npos.to_offset_pos();
+                if nopos.x >= xmin && nopos.x < xmax && nopos.y >= ymin && nopos.y < ymax {
+                    frontier.push(nopos);
+                }
+            }
+        }
+    }
+
+    /// Turns the mask into a terrain map, picking a random terrain type for land tiles.
+    fn into_terrain_map<R: Rng>(self, rng: &mut R) -> TerrainMap {
+        let total_weight = LAND_WEIGHTS.iter().fold(0, |acc, &(_, w)| acc + w);
+        let data = self.land
+                       .iter()
+                       .map(|&is_land| {
+                           if !is_land {
+                               return Terrain::Ocean;
+                           }
+                           let mut roll = rng.gen_range(0, total_weight);
+                           for &(terrain, weight) in LAND_WEIGHTS.iter() {
+                               if roll < weight {
+                                   return terrain;
+                               }
+                               roll -= weight;
+                           }
+                           unreachable!();
+                       })
+                       .collect();
+        let mut result = TerrainMap::new(self.width, self.height, data);
+        result.classify_water();
+        let tiles: Vec<_> = result.tiles().collect();
+        for (pos, terrain) in tiles {
+            for &resource in Resource::all().iter().filter(|r| r.can_lie_on(terrain)) {
+                if rng.gen_range(0, RESOURCE_RARITY) == 0 {
+                    result.set_resource(pos, Some(resource));
+                }
+            }
+        }
+        result
+    }
+}
+
+fn generate_continents<R: Rng>(rng: &mut R, mask: &mut LandMask) {
+    let (width, height) = (mask.width, mask.height);
+    let count = rng.gen_range(2, 4);
+    let band_width = width / count;
+    for i in 0..count {
+        // We keep a 2 tiles margin on each side of the band so that continents are always
+        // separated by ocean.
+        let xmin = i * band_width + 2;
+        let xmax = (i + 1) * band_width - 2;
+        if xmax - xmin < 2 || height < 6 {
+            continue;
+        }
+        let (ymin, ymax) = (2, height - 2);
+        let size = ((xmax - xmin) * (ymax - ymin) * 55 / 100) as usize;
+        let start = OffsetPos::new(rng.gen_range(xmin, xmax), rng.gen_range(ymin, ymax));
+        mask.grow(rng, start, size, (xmin, xmax), (ymin, ymax));
+    }
+}
+
+fn generate_archipelago<R: Rng>(rng: &mut R, mask: &mut LandMask) {
+    let (width, height) = (mask.width, mask.height);
+    if width < 3 || height < 3 {
+        return;
+    }
+    let count = (width * height / 50) + 1;
+    for _ in 0..count {
+        let start = OffsetPos::new(rng.gen_range(1, width - 1), rng.gen_range(1, height - 1));
+        let size = rng.gen_range(3, 13);
+        mask.grow(rng, start, size, (1, width - 1), (1, height - 1));
+    }
+}
+
+/// Generates a `width` x `height` map following `preset`.
+///
+/// Generation only depends on `rng`, so a seeded generator always gives the same map.
+///
+/// # Examples
+///
+/// ```
+/// extern crate rand;
+/// extern crate civng_core;
+///
+/// use rand::{SeedableRng, XorShiftRng};
+/// use civng_core::mapgen::{generate_map, MapPreset};
+/// use civng_core::terrain::Terrain;
+///
+/// # fn main() {
+/// let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+/// let map = generate_map(MapPreset::Continents, 40, 20, &mut rng);
+/// assert_eq!(map.size(), (40, 20));
+/// assert!(map.tiles().any(|(_, t)| t == Terrain::Coast));
+/// assert!(map.tiles().any(|(_, t)| !t.is_water()));
+/// let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+/// let same_map = generate_map(MapPreset::Continents, 40, 20, &mut rng);
+/// assert_eq!(same_map.to_text(), map.to_text());
+/// # }
+/// ```
+pub fn generate_map<R: Rng>(preset: MapPreset,
+                            width: i32,
+                            height: i32,
+                            rng: &mut R)
+                            -> TerrainMap {
+    let mut mask = LandMask::new(width, height);
+    match preset {
+        MapPreset::Continents => generate_continents(rng, &mut mask),
+        MapPreset::Archipelago => generate_archipelago(rng, &mut mask),
+    }
+    mask.into_terrain_map(rng)
+}
+
+/// Generates a `width` x `height` map following `preset`, made symmetric by `symmetry`.
+///
+/// # Examples
+///
+/// ```
+/// extern crate rand;
+/// extern crate civng_core;
+///
+/// use rand::{SeedableRng, XorShiftRng};
+/// use civng_core::mapgen::{generate_symmetric_map, MapPreset, Symmetry};
+///
+/// # fn main() {
+/// let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+/// let map = generate_symmetric_map(MapPreset::Continents, 40, 20, Symmetry::Rotational, &mut rng);
+/// for (pos, terrain) in map.tiles() {
+///     let image = Symmetry::Rotational.image(&map, pos).unwrap();
+///     assert!(map.get_terrain(image) == terrain);
+///     assert!(map.get_resource(image) == map.get_resource(pos));
+/// }
+/// # }
+/// ```
+pub fn generate_symmetric_map<R: Rng>(preset: MapPreset,
+                                      width: i32,
+                                      height: i32,
+                                      symmetry: Symmetry,
+                                      rng: &mut R)
+                                      -> TerrainMap {
+    let mut result = generate_map(preset, width, height, rng);
+    symmetry.stamp(&mut result);
+    result
+}
+
+/// Returns how good a place the tile at `pos` is to found a city on.
+///
+/// This is what the tiles the city would work yield, food counting twice since it makes the city
+/// grow, plus `RESOURCE_SCORE` for each resource among them. Tiles our units can't stand on are
+/// worth nothing.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::mapgen::{score_start, RESOURCE_SCORE};
+/// use civng_core::terrain::{Terrain, TerrainMap, Resource};
+/// use civng_core::hexpos::{Pos, Direction};
+///
+/// let mut map = TerrainMap::empty_map(10, 10);
+/// let pos = Pos::origin().neighbor(Direction::SouthEast).neighbor(Direction::South);
+/// let score = score_start(pos, &map);
+/// map.set_terrain(pos.neighbor(Direction::North), Terrain::Hill);
+/// map.set_resource(pos.neighbor(Direction::North), Some(Resource::Iron));
+/// // The hill trades 2 food for 2 production.
+/// assert_eq!(score_start(pos, &map), score - 2 + RESOURCE_SCORE);
+/// map.set_terrain(pos, Terrain::Mountain);
+/// assert_eq!(score_start(pos, &map), 0);
+/// ```
+pub fn score_start(pos: Pos, map: &TerrainMap) -> u32 {
+    if !map.is_passable(pos) {
+        return 0;
+    }
+    pos.within(CITY_RADIUS)
+       .into_iter()
+       .map(|p| {
+           let y = map.yield_at(p);
+           let resource = if map.get_resource(p).is_some() { RESOURCE_SCORE } else { 0 };
+           y.food as u32 * 2 + y.production as u32 + y.gold as u32 + resource
+       })
+       .sum()
+}
+
+/// A landmass of a map, as found by `find_landmasses()`.
+pub struct Landmass {
+    /// Continent of the landmass, as returned by `TerrainMap::continent_at()`.
+    pub continent: u16,
+    /// Tiles of the landmass, in map order.
+    pub tiles: Vec<Pos>,
+    /// Number of tile sides the landmass shares with water.
+    pub coastline: usize,
+    /// Number of resources lying on the landmass.
+    pub resources: usize,
+}
+
+impl Landmass {
+    pub fn size(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// Returns the share of the tiles of the landmass that have a resource.
+    pub fn resource_density(&self) -> f32 {
+        self.resources as f32 / self.tiles.len() as f32
+    }
+}
+
+/// Returns the landmasses of `map`, the biggest first.
+///
+/// Landmasses are the continents of the map. Landmasses of the same size are in the order of their
+/// continent.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::mapgen::find_landmasses;
+/// use civng_core::terrain::{Terrain, TerrainMap, Resource};
+/// use civng_core::hexpos::OffsetPos;
+///
+/// let mut map = TerrainMap::new(7, 1, vec![Terrain::Coast; 7]);
+/// for &x in [0, 1, 2, 5].iter() {
+///     map.set_terrain(OffsetPos::new(x, 0).to_pos(), Terrain::Plain);
+/// }
+/// map.set_resource(OffsetPos::new(5, 0).to_pos(), Some(Resource::Horses));
+/// let landmasses = find_landmasses(&map);
+/// assert_eq!(landmasses.len(), 2);
+/// assert_eq!(landmasses[0].size(), 3);
+/// // Only the east side of the west landmass touches water.
+/// assert_eq!(landmasses[0].coastline, 1);
+/// assert_eq!(landmasses[1].coastline, 2);
+/// assert_eq!(landmasses[1].resource_density(), 1.0);
+/// ```
+pub fn find_landmasses(map: &TerrainMap) -> Vec<Landmass> {
+    let mut result: Vec<Landmass> = Vec::new();
+    for (pos, _) in map.tiles() {
+        let continent = match map.continent_at(pos) {
+            Some(c) => c,
+            None => continue,
+        };
+        // Continents are numbered in the order of their first tile, which is the order we meet
+        // them in.
+        if continent as usize == result.len() {
+            result.push(Landmass {
+                continent: continent,
+                tiles: Vec::new(),
+                coastline: 0,
+                resources: 0,
+            });
+        }
+        let landmass = &mut result[continent as usize];
+        landmass.tiles.push(pos);
+        landmass.coastline += pos.around()
+                                 .iter()
+                                 .filter(|p| map.get_terrain(**p).is_water())
+                                 .count();
+        if map.get_resource(pos).is_some() {
+            landmass.resources += 1;
+        }
+    }
+    // The sort is stable, which keeps landmasses of the same size in continent order.
+    result.sort_by(|a, b| b.size().cmp(&a.size()));
+    result
+}
+
+/// Returns whether the tile at `pos` is a choke point, such as a narrow land bridge or a mountain
+/// pass.
+///
+/// Choke points are passable tiles with at most 3 passable tiles around them, which don't all
+/// touch each other: crossing from one side to the other means going through the choke point.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::mapgen::is_choke_point;
+/// use civng_core::terrain::{Terrain, TerrainMap};
+/// use civng_core::hexpos::{Pos, Direction};
+///
+/// let mut map = TerrainMap::empty_map(5, 5);
+/// let pos = Pos::origin().neighbor(Direction::SouthEast).neighbor(Direction::South);
+/// assert!(!is_choke_point(&map, pos));
+/// for &d in [Direction::NorthEast, Direction::SouthEast, Direction::SouthWest,
+///            Direction::NorthWest].iter() {
+///     map.set_terrain(pos.neighbor(d), Terrain::Mountain);
+/// }
+/// // Going north to south means going through `pos`.
+/// assert!(is_choke_point(&map, pos));
+/// // With the east side open, units can go around it.
+/// map.set_terrain(pos.neighbor(Direction::NorthEast), Terrain::Plain);
+/// map.set_terrain(pos.neighbor(Direction::SouthEast), Terrain::Plain);
+/// assert!(!is_choke_point(&map, pos));
+/// ```
+pub fn is_choke_point(map: &TerrainMap, pos: Pos) -> bool {
+    if !map.is_passable(pos) {
+        return false;
+    }
+    // Tiles around `pos` come in `Direction::all()` order, which goes around it.
+    let passable: Vec<bool> = pos.around().iter().map(|p| map.is_passable(*p)).collect();
+    let count = passable.iter().filter(|&&b| b).count();
+    let len = passable.len();
+    let sides = (0..len).filter(|&i| passable[i] && !passable[(i + len - 1) % len]).count();
+    count <= 3 && sides >= 2
+}
+
+/// Returns how well a unit can defend the tile at `pos`.
+///
+/// This is the defense bonus of its terrain, in percents, plus `SHELTER_SCORE` for each tile
+/// around it that enemies can't attack from, map edges included. Tiles scoring at least
+/// `DEFENSIVE_SCORE` are defensive.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::mapgen::{defense_score, DEFENSIVE_SCORE};
+/// use civng_core::terrain::{Terrain, TerrainMap};
+/// use civng_core::hexpos::{Pos, Direction};
+///
+/// let mut map = TerrainMap::empty_map(5, 5);
+/// let pos = Pos::origin().neighbor(Direction::SouthEast).neighbor(Direction::South);
+/// assert_eq!(defense_score(&map, pos), 0);
+/// map.set_terrain(pos, Terrain::Hill);
+/// assert_eq!(defense_score(&map, pos), DEFENSIVE_SCORE);
+/// map.set_terrain(pos.neighbor(Direction::North), Terrain::Coast);
+/// assert_eq!(defense_score(&map, pos), 35);
+/// ```
+pub fn defense_score(map: &TerrainMap, pos: Pos) -> i32 {
+    let sheltered = pos.around().iter().filter(|p| !map.is_passable(**p)).count() as i32;
+    map.get_terrain(pos).defense_modifier() as i32 + sheltered * SHELTER_SCORE
+}
+
+/// Returns up to `count` start positions for players on `map`, the best ones first.
+///
+/// Positions are picked by `score_start()`, at least `min_distance` apart. When the map doesn't
+/// have room for that many positions so far apart, the distance is lowered, one tile at a time,
+/// for the remaining ones. Fewer than `count` positions are returned only when there aren't enough
+/// passable tiles.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::mapgen::pick_start_positions;
+/// use civng_core::terrain::{Terrain, TerrainMap};
+/// use civng_core::hexpos::OffsetPos;
+///
+/// let mut map = TerrainMap::empty_map(20, 5);
+/// for (pos, _) in map.clone().tiles() {
+///     map.set_terrain(pos, Terrain::Desert);
+/// }
+/// let oasis = OffsetPos::new(3, 2).to_pos();
+/// map.set_terrain(oasis, Terrain::Grassland);
+/// let positions = pick_start_positions(&map, 2, 8);
+/// // Our best start is the first tile that has the oasis in its reach.
+/// assert_eq!(positions[0].distance(oasis), 2);
+/// assert!(positions[1].distance(positions[0]) >= 8);
+/// // There's only room for 2 positions 15 tiles apart, so the third one is closer.
+/// let positions = pick_start_positions(&map, 3, 15);
+/// assert_eq!(positions.len(), 3);
+/// assert!(positions[1].distance(positions[0]) >= 15);
+/// ```
+pub fn pick_start_positions(map: &TerrainMap, count: usize, min_distance: i32) -> Vec<Pos> {
+    let candidates = ranked_start_candidates(map);
+    let mut result: Vec<Pos> = Vec::new();
+    let mut distance = min_distance;
+    while result.len() < count && distance > 0 {
+        let found = candidates.iter()
+                              .cloned()
+                              .find(|pos| result.iter().all(|p| p.distance(*pos) >= distance));
+        match found {
+            Some(pos) => result.push(pos),
+            None => distance -= 1,
+        }
+    }
+    result
+}
+
+/// Returns up to `count` start positions on a map made symmetric by `symmetry`, in pairs of
+/// positions that are each other's image.
+///
+/// This works like `pick_start_positions()`, but with both positions of a pair being as good, two
+/// players starting on them start on equal terms. With an odd `count`, the last position is
+/// alone.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::mapgen::{pick_symmetric_start_positions, Symmetry};
+/// use civng_core::terrain::TerrainMap;
+///
+/// let map = TerrainMap::empty_map(21, 5);
+/// let positions = pick_symmetric_start_positions(&map, Symmetry::Mirror, 2, 8);
+/// assert_eq!(Symmetry::Mirror.image(&map, positions[0]), Some(positions[1]));
+/// assert!(positions[0].distance(positions[1]) >= 8);
+/// ```
+pub fn pick_symmetric_start_positions(map: &TerrainMap,
+                                      symmetry: Symmetry,
+                                      count: usize,
+                                      min_distance: i32)
+                                      -> Vec<Pos> {
+    let candidates = ranked_start_candidates(map);
+    let mut result: Vec<Pos> = Vec::new();
+    let mut distance = min_distance;
+    while result.len() < count && distance > 0 {
+        let found = candidates.iter()
+                              .filter_map(|&pos| symmetry.image(map, pos).map(|i| (pos, i)))
+                              .find(|&(pos, image)| {
+                                  pos.distance(image) >= distance &&
+                                  result.iter().all(|p| {
+                                      p.distance(pos) >= distance && p.distance(image) >= distance
+                                  })
+                              });
+        match found {
+            Some((pos, image)) => {
+                result.push(pos);
+                if result.len() < count {
+                    result.push(image);
+                }
+            }
+            None => distance -= 1,
+        }
+    }
+    result
+}
+
+/// Returns the tiles of `map` players can start on, the best ones first.
+fn ranked_start_candidates(map: &TerrainMap) -> Vec<Pos> {
+    let mut candidates = map.tiles()
+                            .filter(|&(pos, _)| map.is_passable(pos))
+                            .map(|(pos, _)| (score_start(pos, map), pos))
+                            .collect::<Vec<_>>();
+    // Ties go to the first tile in map order, so that picks don't depend on the sort.
+    candidates.sort_by(|a, b| b.0.cmp(&a.0));
+    candidates.into_iter().map(|(_, pos)| pos).collect()
+}
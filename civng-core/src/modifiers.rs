@@ -0,0 +1,363 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Combat modifiers and the providers computing them.
+//!
+//! Each combat rule granting a bonus or a penalty is a `ModifierProvider`. A `LiveMap` asks each
+//! of its providers for the modifiers of both sides of a combat, so adding a rule is a matter of
+//! registering a new provider with `LiveMap::add_modifier_provider()`.
+
+use std::cmp::min;
+
+use combat::{Modifier, ModifierType};
+use map::LiveMap;
+use unit::{UnitID, Order, Player, Promotion};
+use hexpos::PathWalker;
+use counters::Counters;
+
+/// Defense bonus, in percents, of fortified units.
+const FORTIFICATION_BONUS: i8 = 25;
+/// Attack penalty, in percents, of melee units attacking across a river.
+const RIVER_CROSSING_PENALTY: i8 = -20;
+/// Defense bonus, in percents, of military units garrisoned in one of their cities.
+const GARRISON_BONUS: u16 = 25;
+/// Combat penalty, in percents, of units needing a resource their owner lacks.
+const RESOURCE_DEFICIT_PENALTY: i8 = -25;
+/// Defense bonus, in percents, of units with `Promotion::Cover` against ranged attacks.
+const COVER_BONUS: i8 = 33;
+/// Combat bonus, in percents, of `Promotion::Shock` in open terrain and `Promotion::Drill` in
+/// rough terrain.
+const TERRAIN_PROMOTION_BONUS: i8 = 15;
+
+/// One side of a combat, as seen by modifier providers.
+#[derive(Clone, Copy)]
+pub struct CombatSide {
+    /// Unit we compute modifiers for.
+    pub unit_id: UnitID,
+    /// Unit `unit_id` fights against, or `None` if it's struck by a city.
+    pub against_id: Option<UnitID>,
+    /// Whether `unit_id` is the defender.
+    pub defends: bool,
+    /// Whether the combat is a ranged attack.
+    pub ranged: bool,
+}
+
+/// A combat rule granting modifiers to units.
+///
+/// Providers are shared by clones of a map, which may live in other threads.
+pub trait ModifierProvider: Send + Sync {
+    /// Returns the modifier `side` gets on `map`, if any.
+    fn modifier(&self, map: &LiveMap, side: CombatSide) -> Option<Modifier>;
+}
+
+/// Defenders get the defense bonus of the terrain they stand on, such as hills.
+pub struct TerrainProvider;
+
+impl ModifierProvider for TerrainProvider {
+    fn modifier(&self, map: &LiveMap, side: CombatSide) -> Option<Modifier> {
+        if !side.defends {
+            return None;
+        }
+        let pos = map.units().get(side.unit_id).pos();
+        let amount = map.terrain().get_terrain(pos).defense_modifier();
+        if amount != 0 {
+            Some(Modifier::new(amount, ModifierType::Terrain))
+        } else {
+            None
+        }
+    }
+}
+
+/// Units get a bonus for each enemy of their opponent, other than themselves, around it.
+pub struct FlankingProvider;
+
+impl ModifierProvider for FlankingProvider {
+    fn modifier(&self, map: &LiveMap, side: CombatSide) -> Option<Modifier> {
+        let against = match side.against_id {
+            Some(against_id) => map.units().get(against_id),
+            None => return None,
+        };
+        let mut flank_count = 0;
+        let mut walker = PathWalker::new(against.pos(), 1);
+        while let Some(p) = walker.next() {
+            if let Some(u) = map.units().top_at_pos(p.to()) {
+                if u.owner() != against.owner() {
+                    flank_count += 1;
+                }
+            }
+        }
+        if flank_count > 1 {
+            Some(Modifier::new((flank_count - 1) * 10, ModifierType::Flanking))
+        } else {
+            None
+        }
+    }
+}
+
+/// Fortified defenders, healing ones included, get a defense bonus.
+pub struct FortificationProvider;
+
+impl ModifierProvider for FortificationProvider {
+    fn modifier(&self, map: &LiveMap, side: CombatSide) -> Option<Modifier> {
+        match map.units().get(side.unit_id).order() {
+            Some(Order::Fortify) | Some(Order::Heal) if side.defends => {
+                Some(Modifier::new(FORTIFICATION_BONUS, ModifierType::Fortification))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Melee attackers get a penalty when attacking across a river, that is, when a river runs along
+/// the edge between their tile and the defender's.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::terrain::TerrainMap;
+/// use civng_core::map::LiveMap;
+/// use civng_core::unit::{Unit, UnitType, Player};
+/// use civng_core::hexpos::{OffsetPos, Direction};
+///
+/// let mut terrain = TerrainMap::empty_map(5, 5);
+/// let pos = OffsetPos::new(2, 2).to_pos();
+/// let (north, south) = (pos.neighbor(Direction::North), pos.neighbor(Direction::South));
+/// terrain.set_river(pos, Direction::North, true);
+/// // Both tiles of that fight are along a river, but not the same one.
+/// terrain.set_river(pos, Direction::SouthWest, true);
+/// terrain.set_river(south, Direction::SouthEast, true);
+/// let mut map = LiveMap::new(terrain);
+/// map.add_unit(Unit::new(UnitType::Melee, Player::Me, pos));
+/// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), north));
+/// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), south));
+/// assert_eq!(map.get_combat_stats(1, 2).attacker_modifiers_total(), -20);
+/// assert_eq!(map.get_combat_stats(1, 3).attacker_modifiers_total(), 0);
+/// ```
+pub struct RiverCrossingProvider;
+
+impl ModifierProvider for RiverCrossingProvider {
+    fn modifier(&self, map: &LiveMap, side: CombatSide) -> Option<Modifier> {
+        let against_id = match side.against_id {
+            Some(against_id) if !side.defends && !side.ranged => against_id,
+            _ => return None,
+        };
+        let from = map.units().get(side.unit_id).pos();
+        let to = map.units().get(against_id).pos();
+        if map.terrain().has_river_between(from, to) {
+            Some(Modifier::new(RIVER_CROSSING_PENALTY, ModifierType::RiverCrossing))
+        } else {
+            None
+        }
+    }
+}
+
+/// AI units get the combat modifier of the difficulty level of the map.
+pub struct DifficultyProvider;
+
+impl ModifierProvider for DifficultyProvider {
+    fn modifier(&self, map: &LiveMap, side: CombatSide) -> Option<Modifier> {
+        let amount = map.difficulty().ai_combat_bonus();
+        if amount != 0 && map.units().get(side.unit_id).owner() != Player::Me {
+            Some(Modifier::new(amount, ModifierType::Difficulty))
+        } else {
+            None
+        }
+    }
+}
+
+/// Units defending one of their cities get the defense bonus of its buildings, such as walls.
+/// Military units garrisoned there also get `GARRISON_BONUS`.
+pub struct CityDefenseProvider;
+
+impl ModifierProvider for CityDefenseProvider {
+    fn modifier(&self, map: &LiveMap, side: CombatSide) -> Option<Modifier> {
+        if !side.defends {
+            return None;
+        }
+        let unit = map.units().get(side.unit_id);
+        let city = match map.cities().get_at_pos(unit.pos()) {
+            Some(city) if city.owner() == unit.owner() => city,
+            _ => return None,
+        };
+        let mut amount = map.cities().defense_bonus(city.id());
+        if !unit.type_().is_civilian() {
+            amount += GARRISON_BONUS;
+        }
+        let amount = min(amount, i8::max_value() as u16);
+        if amount != 0 {
+            Some(Modifier::new(amount as i8, ModifierType::CityDefense))
+        } else {
+            None
+        }
+    }
+}
+
+/// Units needing a strategic resource fight with a penalty when their owner has more of them than
+/// it can maintain.
+pub struct ResourceDeficitProvider;
+
+impl ModifierProvider for ResourceDeficitProvider {
+    fn modifier(&self, map: &LiveMap, side: CombatSide) -> Option<Modifier> {
+        let unit = map.units().get(side.unit_id);
+        match unit.type_().required_resource() {
+            Some(resource) if map.has_resource_deficit(unit.owner(), resource) => {
+                Some(Modifier::new(RESOURCE_DEFICIT_PENALTY, ModifierType::ResourceDeficit))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Units get the combat bonus of their owner's pantheon, if any, when they fight in its territory.
+pub struct PantheonProvider;
+
+impl ModifierProvider for PantheonProvider {
+    fn modifier(&self, map: &LiveMap, side: CombatSide) -> Option<Modifier> {
+        let unit = map.units().get(side.unit_id);
+        let amount = map.cities().pantheon(unit.owner()).map_or(0, |b| b.combat_bonus());
+        if amount != 0 && map.territory_owner(unit.pos()) == Some(unit.owner()) {
+            Some(Modifier::new(amount, ModifierType::Pantheon))
+        } else {
+            None
+        }
+    }
+}
+
+/// Units get the combat bonuses of their promotions.
+///
+/// Fights take place on the tile of the defender, so that's the tile whose roughness counts for
+/// `Promotion::Shock` and `Promotion::Drill`.
+pub struct PromotionProvider;
+
+impl ModifierProvider for PromotionProvider {
+    fn modifier(&self, map: &LiveMap, side: CombatSide) -> Option<Modifier> {
+        let unit = map.units().get(side.unit_id);
+        let pos = match side.against_id {
+            Some(against_id) if !side.defends => map.units().get(against_id).pos(),
+            _ => unit.pos(),
+        };
+        let rough = map.terrain().is_rough(pos);
+        let mut amount = 0;
+        if side.defends && side.ranged && unit.has_promotion(Promotion::Cover) {
+            amount += COVER_BONUS;
+        }
+        if unit.has_promotion(Promotion::Shock) && !rough {
+            amount += TERRAIN_PROMOTION_BONUS;
+        }
+        if unit.has_promotion(Promotion::Drill) && rough {
+            amount += TERRAIN_PROMOTION_BONUS;
+        }
+        if amount != 0 {
+            Some(Modifier::new(amount, ModifierType::Promotion))
+        } else {
+            None
+        }
+    }
+}
+
+/// Units get the home continent bonus of the ruleset when they fight on a continent where their
+/// owner has a city.
+///
+/// Like for promotions, fights take place on the tile of the defender.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::terrain::{Terrain, TerrainMap};
+/// use civng_core::map::LiveMap;
+/// use civng_core::unit::{Unit, UnitType, Player};
+/// use civng_core::city::City;
+/// use civng_core::hexpos::OffsetPos;
+///
+/// let mut terrain = TerrainMap::empty_map(8, 1);
+/// terrain.set_terrain(OffsetPos::new(4, 0).to_pos(), Terrain::Coast);
+/// let mut map = LiveMap::new(terrain);
+/// let mut rules = *map.rules();
+/// rules.home_continent_bonus = 10;
+/// map.set_rules(rules);
+/// let pos = |x| OffsetPos::new(x, 0).to_pos();
+/// map.add_city(City::new("Washington", Player::Me, pos(0)));
+/// map.add_unit(Unit::new(UnitType::Melee, Player::Me, pos(2)));
+/// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), pos(3)));
+/// let stats = map.get_combat_stats(1, 2);
+/// assert_eq!(stats.attacker_modifiers_total(), 10);
+/// // Our opponent's owner doesn't have a city on that continent.
+/// assert_eq!(stats.defender_modifiers_total(), 0);
+/// ```
+pub struct HomeContinentProvider;
+
+impl ModifierProvider for HomeContinentProvider {
+    fn modifier(&self, map: &LiveMap, side: CombatSide) -> Option<Modifier> {
+        let amount = map.rules().home_continent_bonus;
+        if amount == 0 {
+            return None;
+        }
+        let unit = map.units().get(side.unit_id);
+        let pos = match side.against_id {
+            Some(against_id) if !side.defends => map.units().get(against_id).pos(),
+            _ => unit.pos(),
+        };
+        let terrain = map.terrain();
+        let continent = match terrain.continent_at(pos) {
+            Some(c) => c,
+            None => return None,
+        };
+        let at_home = map.cities()
+                         .all_cities()
+                         .iter()
+                         .any(|c| c.owner() == unit.owner() &&
+                                  terrain.continent_at(c.pos()) == Some(continent));
+        if at_home {
+            Some(Modifier::new(amount as i8, ModifierType::HomeContinent))
+        } else {
+            None
+        }
+    }
+}
+
+/// Units get the bonuses of `counters` against the units they fight.
+pub struct CounterProvider {
+    counters: Counters,
+}
+
+impl CounterProvider {
+    pub fn new(counters: Counters) -> CounterProvider {
+        CounterProvider { counters: counters }
+    }
+}
+
+impl ModifierProvider for CounterProvider {
+    fn modifier(&self, map: &LiveMap, side: CombatSide) -> Option<Modifier> {
+        let against = match side.against_id {
+            Some(against_id) => map.units().get(against_id),
+            None => return None,
+        };
+        let unit = map.units().get(side.unit_id);
+        let in_city = !side.defends && map.cities().get_at_pos(against.pos()).is_some();
+        let amount = self.counters.bonus(unit, against, in_city);
+        if amount != 0 {
+            Some(Modifier::new(amount, ModifierType::Counter))
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the providers every map starts with.
+pub fn default_providers() -> Vec<Box<ModifierProvider>> {
+    vec![Box::new(TerrainProvider),
+         Box::new(FortificationProvider),
+         Box::new(FlankingProvider),
+         Box::new(RiverCrossingProvider),
+         Box::new(CityDefenseProvider),
+         Box::new(DifficultyProvider),
+         Box::new(ResourceDeficitProvider),
+         Box::new(PantheonProvider),
+         Box::new(PromotionProvider),
+         Box::new(HomeContinentProvider),
+         Box::new(CounterProvider::new(Counters::builtin()))]
+}
@@ -0,0 +1,148 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Play-by-email turn files.
+//!
+//! In a play-by-email game, two players take turns on their own computer and mail each other a
+//! turn file when they're done. Each player is `Player::Me` on their own computer, and their
+//! opponent is `Player::AI(0)`. We swap them with `LiveMap::swap_players()` when importing a turn.
+//!
+//! A turn file is a JSON object with the format version, the number of the turn it ends and the
+//! game, as a save file (see `savegame`) which is obscured so that the player receiving it doesn't
+//! see what the sender explored by opening it. It isn't encrypted: it's only meant to keep honest
+//! players honest.
+//!
+//! ```text
+//! {"version":2,"turn":7,"game":[63,10,156,...]}
+//! ```
+//!
+//! Turns are numbered across both players: after sending turn 7, we expect to receive turn 8.
+
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use serde_json;
+
+use map::LiveMap;
+use unit::Player;
+use savegame::{serialize_game, deserialize_game};
+
+const TURN_VERSION: u32 = 2;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Contents of a turn file.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TurnFile {
+    version: u32,
+    turn: u16,
+    /// Save file of the game, obscured with `obscure()`.
+    game: Vec<u8>,
+}
+
+/// XORs `data` with a keystream derived from `turn`, which both obscures and unobscures it.
+fn obscure(data: &mut [u8], turn: u16) {
+    let mut state = 0x2545f491u32 ^ turn as u32;
+    for b in data.iter_mut() {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        *b ^= (state >> 16) as u8;
+    }
+}
+
+/// Returns the turn file sending `map` at the end of our turn `turn`.
+pub fn export_turn(map: &LiveMap, turn: u16) -> String {
+    let mut game = serialize_game(map, turn).into_bytes();
+    obscure(&mut game, turn);
+    let file = TurnFile {
+        version: TURN_VERSION,
+        turn: turn,
+        game: game,
+    };
+    serde_json::to_string(&file).expect("Can't serialize the turn")
+}
+
+/// Parses turn file `contents` and returns the map and turn it contains, from our point of view.
+///
+/// If we've sent turn `last_sent`, the file must be the one of the following turn. Older files
+/// are refused as stale and newer ones because we've missed a turn.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::pbem::{export_turn, import_turn};
+/// use civng_core::map::LiveMap;
+/// use civng_core::terrain::TerrainMap;
+/// use civng_core::city::City;
+/// use civng_core::unit::{Unit, UnitType, Player};
+/// use civng_core::hexpos::{Pos, Direction};
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(3, 2));
+/// map.add_unit(Unit::new(UnitType::Ranged, Player::Me, Pos::origin()));
+/// map.add_city(City::new("Rome", Player::AI(0), Pos::vector(Direction::South)));
+/// map.cities_mut().set_gold(Player::Me, 40);
+/// map.diplomacy_mut().make_peace(Player::Me, Player::AI(1));
+/// let contents = export_turn(&map, 7);
+/// assert!(!contents.contains("Ranged"));
+/// let (received, turn) = import_turn(&contents, Some(6)).unwrap();
+/// assert_eq!(turn, 7);
+/// // The sender is our opponent.
+/// assert!(received.units().get(1).owner() == Player::AI(0));
+/// assert!(received.cities().get(1).owner() == Player::Me);
+/// assert_eq!(received.cities().gold(Player::AI(0)), 40);
+/// assert!(!received.diplomacy().at_war(Player::AI(0), Player::AI(1)));
+/// assert!(import_turn(&contents, Some(7)).is_err());
+/// assert!(import_turn(&contents, Some(5)).is_err());
+/// assert!(import_turn(&contents.replace("\"turn\":7", "\"turn\":8"), Some(7)).is_err());
+/// ```
+pub fn import_turn(contents: &str, last_sent: Option<u16>) -> io::Result<(LiveMap, u16)> {
+    let file: TurnFile = try!(serde_json::from_str(contents).map_err(|e| {
+        invalid_data(&format!("Not a civng turn file or unsupported version: {}", e))
+    }));
+    if file.version != TURN_VERSION {
+        return Err(invalid_data("Unsupported turn file version"));
+    }
+    let turn = file.turn;
+    if let Some(last_sent) = last_sent {
+        if turn <= last_sent {
+            return Err(invalid_data("Stale turn file: we've already played this turn"));
+        }
+        if turn > last_sent + 1 {
+            return Err(invalid_data("Turn file is too recent: we've missed a turn"));
+        }
+    }
+    let mut game = file.game;
+    obscure(&mut game, turn);
+    let saved = match String::from_utf8(game) {
+        Ok(s) => s,
+        Err(_) => return Err(invalid_data("Corrupted turn file")),
+    };
+    let (mut map, saved_turn) = try!(deserialize_game(&saved));
+    if saved_turn != turn {
+        return Err(invalid_data("Corrupted turn file"));
+    }
+    map.swap_players(Player::Me, Player::AI(0));
+    Ok((map, turn))
+}
+
+/// Writes the turn file sending `map` at the end of our turn `turn` at `path`.
+pub fn send_turn(path: &Path, map: &LiveMap, turn: u16) -> io::Result<()> {
+    let mut fp = try!(File::create(path));
+    fp.write_all(export_turn(map, turn).as_bytes())
+}
+
+/// Reads the turn file at `path`, which must follow our turn `last_sent` if we've sent one.
+pub fn receive_turn(path: &Path, last_sent: Option<u16>) -> io::Result<(LiveMap, u16)> {
+    let mut fp = try!(File::open(path));
+    let mut contents = String::new();
+    try!(fp.read_to_string(&mut contents));
+    import_turn(&contents, last_sent)
+}
@@ -0,0 +1,102 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Quick-start games, played on a generated map.
+//!
+//! Players start on the best tiles of the map that are far enough from each other, with the
+//! default units and a capital. On symmetric maps, players start in pairs of equally good tiles.
+
+use map::LiveMap;
+use mapgen::{MapPreset, Symmetry, generate_map, generate_symmetric_map, pick_start_positions,
+             pick_symmetric_start_positions, DEFAULT_WIDTH, DEFAULT_HEIGHT, MIN_START_DISTANCE};
+use scenario::{add_default_units, add_default_cities};
+use rng;
+use difficulty::Difficulty;
+
+/// How a quick-start game is set up.
+#[derive(Clone, Copy, Debug)]
+pub struct QuickStart {
+    pub preset: MapPreset,
+    pub width: i32,
+    pub height: i32,
+    pub ai_players: u8,
+    pub difficulty: Difficulty,
+    /// When set, the map is symmetric and players start in pairs on each other's image, so that
+    /// duels aren't decided by the land.
+    pub symmetry: Option<Symmetry>,
+}
+
+impl QuickStart {
+    /// Returns the setup of a game against one AI player, at prince level, on a map of the
+    /// default size generated with `preset`.
+    pub fn new(preset: MapPreset) -> QuickStart {
+        QuickStart {
+            preset: preset,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            ai_players: 1,
+            difficulty: Difficulty::Prince,
+            symmetry: None,
+        }
+    }
+
+    /// Generates the map of the game, with the units and cities of each player.
+    ///
+    /// The map comes from our random number generator, so the same seed gives the same game.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::quickstart::QuickStart;
+    /// use civng_core::mapgen::{MapPreset, Symmetry, MIN_START_DISTANCE};
+    /// use civng_core::unit::Player;
+    /// use civng_core::rng;
+    ///
+    /// rng::seed(42);
+    /// let mut options = QuickStart::new(MapPreset::Continents);
+    /// options.ai_players = 2;
+    /// let map = options.build_map();
+    /// assert_eq!(map.terrain().size(), (options.width, options.height));
+    /// assert_eq!(map.units().my_units().count(), 2);
+    /// assert!(map.ai_players() == vec![Player::AI(0), Player::AI(1)]);
+    /// let capitals = map.cities().all_cities();
+    /// assert_eq!(capitals.len(), 3);
+    /// assert!(capitals[0].pos().distance(capitals[1].pos()) >= MIN_START_DISTANCE);
+    ///
+    /// let mut options = QuickStart::new(MapPreset::Continents);
+    /// options.symmetry = Some(Symmetry::Mirror);
+    /// let map = options.build_map();
+    /// let capitals = map.cities().all_cities();
+    /// assert_eq!(Symmetry::Mirror.image(map.terrain(), capitals[0].pos()),
+    ///            Some(capitals[1].pos()));
+    /// ```
+    pub fn build_map(&self) -> LiveMap {
+        let (preset, width, height) = (self.preset, self.width, self.height);
+        let players = self.ai_players as usize + 1;
+        let (terrainmap, start_positions) = match self.symmetry {
+            Some(symmetry) => {
+                let map = rng::with_rng(|r| {
+                    generate_symmetric_map(preset, width, height, symmetry, r)
+                });
+                let positions = pick_symmetric_start_positions(&map, symmetry, players,
+                                                               MIN_START_DISTANCE);
+                (map, positions)
+            }
+            None => {
+                let map = rng::with_rng(|r| generate_map(preset, width, height, r));
+                let positions = pick_start_positions(&map, players, MIN_START_DISTANCE);
+                (map, positions)
+            }
+        };
+        let mut map = LiveMap::new(terrainmap);
+        map.set_difficulty(self.difficulty);
+        let start_positions: Vec<_> = start_positions.into_iter().map(Some).collect();
+        add_default_units(&mut map, self.ai_players, &start_positions);
+        add_default_cities(&mut map, None);
+        map
+    }
+}
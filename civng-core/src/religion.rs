@@ -0,0 +1,101 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Faith and pantheons.
+//!
+//! Every city yields one faith to its owner each turn, plus what the natural wonders it works give
+//! (see `Feature::faith()`). Once a player accumulated `PANTHEON_FAITH`, it can found a pantheon
+//! by adopting a `Belief`. A player only ever has one belief and each belief can only be adopted
+//! by one player, so the first to found a pantheon has the most choice.
+
+use hexpos::Pos;
+use terrain::{Terrain, TerrainMap, Yield};
+
+/// Faith a player has to accumulate to found a pantheon.
+pub const PANTHEON_FAITH: u32 = 20;
+
+/// Belief of a pantheon, granting a bonus to the player who adopted it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Belief {
+    /// Grassland tiles yield one more food.
+    GoddessOfTheFields,
+    /// Hill tiles yield one more production.
+    GodOfCraftsmen,
+    /// Tiles along rivers yield one more food.
+    SacredWaters,
+    /// Units get a combat bonus in their owner's territory.
+    GodOfWar,
+}
+
+impl Belief {
+    pub fn all() -> [Belief; 4] {
+        [Belief::GoddessOfTheFields, Belief::GodOfCraftsmen, Belief::SacredWaters, Belief::GodOfWar]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Belief::GoddessOfTheFields => "Goddess of the Fields",
+            Belief::GodOfCraftsmen => "God of Craftsmen",
+            Belief::SacredWaters => "Sacred Waters",
+            Belief::GodOfWar => "God of War",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match *self {
+            Belief::GoddessOfTheFields => "+1 food from grassland",
+            Belief::GodOfCraftsmen => "+1 production from hills",
+            Belief::SacredWaters => "+1 food from tiles along rivers",
+            Belief::GodOfWar => "+15% combat strength in our territory",
+        }
+    }
+
+    /// Returns the belief named `name`, as returned by `name()`.
+    pub fn from_name(name: &str) -> Option<Belief> {
+        Belief::all().iter().find(|b| b.name() == name).cloned()
+    }
+
+    /// What the belief adds to the yield of the tile at `pos`, worked by a city of its player.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::religion::Belief;
+    /// use civng_core::terrain::{Feature, TerrainMap, Yield};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut map = TerrainMap::empty_map(2, 2);
+    /// let fields = Belief::GoddessOfTheFields;
+    /// assert_eq!(fields.tile_bonus(&map, Pos::origin()), Yield::new(1, 0, 0));
+    /// assert_eq!(Belief::GodOfCraftsmen.tile_bonus(&map, Pos::origin()), Yield::default());
+    /// map.set_feature(Pos::origin(), Some(Feature::Forest));
+    /// assert_eq!(fields.tile_bonus(&map, Pos::origin()), Yield::default());
+    /// ```
+    pub fn tile_bonus(&self, terrain: &TerrainMap, pos: Pos) -> Yield {
+        // Features hide the terrain under them.
+        let bare_terrain = match terrain.get_feature(pos) {
+            Some(_) => None,
+            None => Some(terrain.get_terrain(pos)),
+        };
+        match *self {
+            Belief::GoddessOfTheFields if bare_terrain == Some(Terrain::Grassland) => {
+                Yield::new(1, 0, 0)
+            }
+            Belief::GodOfCraftsmen if bare_terrain == Some(Terrain::Hill) => Yield::new(0, 1, 0),
+            Belief::SacredWaters if terrain.has_river(pos) => Yield::new(1, 0, 0),
+            _ => Yield::default(),
+        }
+    }
+
+    /// Combat bonus, in percents, units of the belief's player get in their owner's territory.
+    pub fn combat_bonus(&self) -> i8 {
+        match *self {
+            Belief::GodOfWar => 15,
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,248 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Random number generation.
+//!
+//! All randomness in the game goes through this module so that a whole game can be made
+//! reproducible by calling `seed()` before it starts. Unseeded, our generator is initialized from
+//! `rand::thread_rng()`.
+//!
+//! Dice rolls can be audited: with `start_audit()`, every roll is logged, one `DiceRoll` per line
+//! as a JSON object. Such a log can then be fed back to `replay()` to reproduce the same rolls.
+//!
+//! Our generator is per thread. To play part of a game in another thread without changing its
+//! outcome, hand that thread the state of ours with `take_state()` and `restore_state()`, and then
+//! take it back the same way.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Write, BufRead, BufReader};
+use std::mem::replace;
+use std::path::Path;
+
+use rand::{Rng, SeedableRng, XorShiftRng, thread_rng};
+use rand::distributions::{IndependentSample, Range};
+use serde_json;
+
+thread_local!(static RNG: RefCell<XorShiftRng> = RefCell::new(thread_rng().gen()));
+thread_local!(static AUDIT_LOG: RefCell<Option<Box<Write + Send>>> = RefCell::new(None));
+thread_local!(static REPLAYED: RefCell<VecDeque<DiceRoll>> = RefCell::new(VecDeque::new()));
+
+/// Reseeds our random number generator with `seed`.
+///
+/// # Examples
+///
+/// ```
+/// extern crate rand;
+/// extern crate civng_core;
+///
+/// use rand::Rng;
+/// use civng_core::rng;
+///
+/// # fn main() {
+/// rng::seed(42);
+/// let first = rng::with_rng(|r| r.gen::<u32>());
+/// rng::seed(42);
+/// assert_eq!(rng::with_rng(|r| r.gen::<u32>()), first);
+/// # }
+/// ```
+pub fn seed(seed: u32) {
+    // XorShiftRng panics on an all-zero seed, so we make sure that some parts aren't.
+    let seed = [seed, seed ^ 0x9e3779b9, 0x243f6a88, 0x85a308d3];
+    RNG.with(|r| *r.borrow_mut() = XorShiftRng::from_seed(seed));
+}
+
+/// State of the random number generation of a thread: its generator, audit log and replayed rolls.
+pub struct RngState {
+    rng: XorShiftRng,
+    audit_log: Option<Box<Write + Send>>,
+    replayed: VecDeque<DiceRoll>,
+}
+
+/// Takes the state of the random number generation of the current thread, leaving it with a
+/// fresh generator, no audit log and no rolls to replay.
+///
+/// # Examples
+///
+/// ```
+/// use std::thread;
+/// use civng_core::rng;
+///
+/// rng::seed(42);
+/// let expected = rng::roll_dice(1, 100);
+/// rng::seed(42);
+/// let state = rng::take_state();
+/// let rolled = thread::spawn(move || {
+///     rng::restore_state(state);
+///     rng::roll_dice(1, 100)
+/// }).join().unwrap();
+/// assert_eq!(rolled, expected);
+/// ```
+pub fn take_state() -> RngState {
+    RngState {
+        rng: RNG.with(|r| replace(&mut *r.borrow_mut(), thread_rng().gen())),
+        audit_log: AUDIT_LOG.with(|l| l.borrow_mut().take()),
+        replayed: REPLAYED.with(|r| replace(&mut *r.borrow_mut(), VecDeque::new())),
+    }
+}
+
+/// Returns a copy of the state of the current thread, minus its audit log, which can't be copied.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::rng;
+///
+/// rng::seed(42);
+/// let backup = rng::copy_state();
+/// let expected = rng::roll_dice(1, 100);
+/// let mut state = rng::take_state();
+/// state.rewind(backup);
+/// rng::restore_state(state);
+/// assert_eq!(rng::roll_dice(1, 100), expected);
+/// ```
+pub fn copy_state() -> RngState {
+    RngState {
+        rng: RNG.with(|r| r.borrow().clone()),
+        audit_log: None,
+        replayed: REPLAYED.with(|r| r.borrow().clone()),
+    }
+}
+
+impl RngState {
+    /// Rewinds the generator and the rolls to replay to `backup`, as returned by `copy_state()`.
+    ///
+    /// Our audit log is kept, along with the rolls it logged since `backup` was taken.
+    pub fn rewind(&mut self, backup: RngState) {
+        self.rng = backup.rng;
+        self.replayed = backup.replayed;
+    }
+}
+
+/// Makes `state`, as returned by `take_state()`, the state of the current thread.
+pub fn restore_state(state: RngState) {
+    let RngState { rng, audit_log, replayed } = state;
+    RNG.with(|r| *r.borrow_mut() = rng);
+    AUDIT_LOG.with(|l| *l.borrow_mut() = audit_log);
+    REPLAYED.with(|r| *r.borrow_mut() = replayed);
+}
+
+/// Calls `f` with our random number generator.
+pub fn with_rng<T, F: FnOnce(&mut XorShiftRng) -> T>(f: F) -> T {
+    RNG.with(|r| f(&mut *r.borrow_mut()))
+}
+
+/// A dice roll, as logged in audit mode.
+///
+/// # Examples
+///
+/// ```
+/// extern crate serde_json;
+/// extern crate civng_core;
+///
+/// use civng_core::rng::DiceRoll;
+///
+/// # fn main() {
+/// let roll = DiceRoll { min: 40, max: 70, state: 0xdeadbeef, result: 52 };
+/// let json = serde_json::to_string(&roll).unwrap();
+/// assert_eq!(json, "{\"min\":40,\"max\":70,\"state\":3735928559,\"result\":52}");
+/// assert_eq!(serde_json::from_str::<DiceRoll>(&json).unwrap(), roll);
+/// assert!(serde_json::from_str::<DiceRoll>("{\"min\":40}").is_err());
+/// # }
+/// ```
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DiceRoll {
+    pub min: u8,
+    pub max: u8,
+    /// Fingerprint of our generator's state before the roll: the next number it would generate.
+    pub state: u32,
+    pub result: u8,
+}
+
+/// Logs every subsequent dice roll to `log`.
+pub fn start_audit(log: Box<Write + Send>) {
+    AUDIT_LOG.with(|l| *l.borrow_mut() = Some(log));
+}
+
+/// Stops logging dice rolls.
+pub fn stop_audit() {
+    AUDIT_LOG.with(|l| *l.borrow_mut() = None);
+}
+
+/// Makes subsequent dice rolls return the results of `rolls`, in order.
+///
+/// Our generator is still used as usual, so that everything else random stays the same. Once
+/// `rolls` are exhausted, dice rolls are random again.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::rng::{self, DiceRoll};
+///
+/// rng::replay(vec![DiceRoll { min: 1, max: 6, state: 0, result: 6 }]);
+/// assert_eq!(rng::roll_dice(1, 6), 6);
+/// let next = rng::roll_dice(1, 6);
+/// assert!(next >= 1 && next <= 6);
+/// ```
+pub fn replay(rolls: Vec<DiceRoll>) {
+    REPLAYED.with(|r| *r.borrow_mut() = rolls.into_iter().collect());
+}
+
+/// Loads the dice rolls of the audit log at `path`, ignoring lines that aren't rolls.
+///
+/// # Examples
+///
+/// ```
+/// use std::env;
+/// use std::fs::File;
+/// use civng_core::rng;
+///
+/// let path = env::temp_dir().join("civng-doctest-rolls.log");
+/// rng::start_audit(Box::new(File::create(&path).unwrap()));
+/// let rolled: Vec<u8> = (0..3).map(|_| rng::roll_dice(1, 6)).collect();
+/// rng::stop_audit();
+/// let rolls = rng::load_rolls(&path).unwrap();
+/// assert_eq!(rolls.iter().map(|r| r.result).collect::<Vec<u8>>(), rolled);
+/// rng::replay(rolls);
+/// assert_eq!((0..3).map(|_| rng::roll_dice(1, 6)).collect::<Vec<u8>>(), rolled);
+/// ```
+pub fn load_rolls(path: &Path) -> io::Result<Vec<DiceRoll>> {
+    let fp = try!(File::open(path));
+    let mut result = Vec::new();
+    for line in BufReader::new(fp).lines() {
+        if let Ok(roll) = serde_json::from_str(&try!(line)) {
+            result.push(roll);
+        }
+    }
+    Ok(result)
+}
+
+/// Rolls a number between `min` and `max`, both included.
+///
+/// This is where audit and replay happen.
+pub fn roll_dice(min: u8, max: u8) -> u8 {
+    let (state, rolled) = with_rng(|r| {
+        let state = r.clone().next_u32();
+        // max+1 because Range excludes high bound.
+        (state, Range::new(min, max + 1).ind_sample(r))
+    });
+    let result = REPLAYED.with(|r| r.borrow_mut().pop_front()).map_or(rolled, |roll| roll.result);
+    let roll = DiceRoll {
+        min: min,
+        max: max,
+        state: state,
+        result: result,
+    };
+    AUDIT_LOG.with(|l| {
+        if let Some(ref mut log) = *l.borrow_mut() {
+            let line = serde_json::to_string(&roll).expect("Can't log dice roll");
+            let _ = writeln!(log, "{}", line);
+        }
+    });
+    result
+}
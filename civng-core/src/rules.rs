@@ -0,0 +1,130 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! House rules.
+//!
+//! A `Ruleset` turns optional rules on and off, so that variants can be played, and tested,
+//! without touching the code. The default ruleset follows Civ5.
+
+use std::str::FromStr;
+
+use unit::{HEAL_RATE, FRIENDLY_HEAL_RATE, CITY_HEAL_RATE, ENEMY_HEAL_RATE, MEDIC_HEAL};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Ruleset {
+    /// Whether units moving from a tile next to an enemy to another such tile have to stop.
+    pub zoc: bool,
+    /// Whether scouts and units with `Promotion::Mobility` ignore zones of control.
+    pub zoc_exemptions: bool,
+    /// Whether units with movements left can always enter an adjacent tile, whatever its cost.
+    pub last_hex_rule: bool,
+    /// Whether defenders strike back at their ranged attackers as they do in melee.
+    pub ranged_retaliation: bool,
+    /// Whether the last attack a unit can make in a turn spends all the movements it has left.
+    pub attack_ends_movement: bool,
+    /// Number of units of a player that can stand on the same tile.
+    pub stacking_limit: u8,
+    /// HP units heal at the start of a turn when they rested during the previous one in neutral
+    /// territory.
+    pub heal_rate: u8,
+    /// HP resting units heal in their owner's territory.
+    pub friendly_heal_rate: u8,
+    /// HP resting units heal in their owner's cities.
+    pub city_heal_rate: u8,
+    /// HP resting units heal in the territory of an enemy.
+    pub enemy_heal_rate: u8,
+    /// HP units next to one of their medics heal on top of the rate of where they are.
+    pub medic_heal: u8,
+    /// Combat bonus, in percents, of units fighting on a continent where their owner has a city.
+    pub home_continent_bonus: u8,
+}
+
+impl Ruleset {
+    /// Returns Civ5's rules.
+    pub fn new() -> Ruleset {
+        Ruleset {
+            zoc: true,
+            zoc_exemptions: true,
+            last_hex_rule: true,
+            ranged_retaliation: false,
+            attack_ends_movement: true,
+            stacking_limit: 1,
+            heal_rate: HEAL_RATE,
+            friendly_heal_rate: FRIENDLY_HEAL_RATE,
+            city_heal_rate: CITY_HEAL_RATE,
+            enemy_heal_rate: ENEMY_HEAL_RATE,
+            medic_heal: MEDIC_HEAL,
+            home_continent_bonus: 0,
+        }
+    }
+
+    /// Sets the rule named `key` to `value`, as written in config files.
+    ///
+    /// Returns false, leaving rules as they were, if `key` isn't the name of a rule or `value`
+    /// isn't valid for it. Units can't stack by less than one and combat bonuses can't go over
+    /// 127%.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::rules::Ruleset;
+    ///
+    /// let mut rules = Ruleset::new();
+    /// assert!(rules.set("zoc", "false"));
+    /// assert!(!rules.zoc);
+    /// assert!(rules.set("stacking_limit", "2"));
+    /// assert_eq!(rules.stacking_limit, 2);
+    /// assert!(!rules.set("stacking_limit", "0"));
+    /// assert!(!rules.set("heal_rate", "lots"));
+    /// assert!(!rules.set("theme", "monochrome"));
+    /// assert_eq!(rules.heal_rate, Ruleset::new().heal_rate);
+    /// assert!(!rules.set("home_continent_bonus", "200"));
+    /// ```
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            "zoc" => set_parsed(&mut self.zoc, value),
+            "zoc_exemptions" => set_parsed(&mut self.zoc_exemptions, value),
+            "last_hex_rule" => set_parsed(&mut self.last_hex_rule, value),
+            "ranged_retaliation" => set_parsed(&mut self.ranged_retaliation, value),
+            "attack_ends_movement" => set_parsed(&mut self.attack_ends_movement, value),
+            "stacking_limit" => {
+                match value.parse() {
+                    Ok(limit) if limit > 0 => {
+                        self.stacking_limit = limit;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            "heal_rate" => set_parsed(&mut self.heal_rate, value),
+            "friendly_heal_rate" => set_parsed(&mut self.friendly_heal_rate, value),
+            "city_heal_rate" => set_parsed(&mut self.city_heal_rate, value),
+            "enemy_heal_rate" => set_parsed(&mut self.enemy_heal_rate, value),
+            "medic_heal" => set_parsed(&mut self.medic_heal, value),
+            "home_continent_bonus" => {
+                match value.parse() {
+                    Ok(bonus) if bonus <= i8::max_value() as u8 => {
+                        self.home_continent_bonus = bonus;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+fn set_parsed<T: FromStr>(field: &mut T, value: &str) -> bool {
+    match value.parse() {
+        Ok(v) => {
+            *field = v;
+            true
+        }
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Saving and loading games.
+//!
+//! Save files are JSON objects with the format version, the current turn, the difficulty level,
+//! the terrain, units, cities and diplomacy of the map, serialized the way their own `Serialize`
+//! implementations write them, and the tiles each player explored:
+//!
+//! ```text
+//! {"version":2,"turn":3,"difficulty":"prince",
+//!  "terrain":{"width":3,"height":2,"terrain":["Grassland",...],...},
+//!  "units":{"maxid":2,"units":[{"id":1,"type_":"Melee",...},...],"on_top":[]},
+//!  "cities":{"maxid":1,"cities":[{"id":1,"name":"New York",...}],...},
+//!  "diplomacy":{"peace":[["Me","AI1"]],"open_borders":[],"wars":[],"peace_offers":[]},
+//!  "explored":[["Me",[true,true,false,...]]]}
+//! ```
+//!
+//! Explored tiles are listed in the same order as terrain. What the map computes from the rest,
+//! such as what units see or which tiles citizens work, is computed again when the game is loaded.
+
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use serde_json;
+
+use hexpos::Pos;
+use terrain::TerrainMap;
+use unit::{Units, Player};
+use city::Cities;
+use map::LiveMap;
+use difficulty::Difficulty;
+use diplomacy::Diplomacy;
+
+const SAVE_VERSION: u32 = 2;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Contents of a save file.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SaveFile {
+    version: u32,
+    turn: u16,
+    difficulty: Difficulty,
+    terrain: TerrainMap,
+    units: Units,
+    cities: Cities,
+    diplomacy: Diplomacy,
+    /// Whether each player explored each tile, in `TerrainMap` order.
+    explored: Vec<(Player, Vec<bool>)>,
+}
+
+/// Returns the save file contents for `map` at turn `turn`.
+pub fn serialize_game(map: &LiveMap, turn: u16) -> String {
+    let mut explored = Vec::new();
+    for player in Some(Player::Me).into_iter().chain(map.ai_players()) {
+        if let Some(fog) = map.fog(player) {
+            let tiles = map.terrain().tiles().map(|(p, _)| fog.is_explored(p)).collect();
+            explored.push((player, tiles));
+        }
+    }
+    let save = SaveFile {
+        version: SAVE_VERSION,
+        turn: turn,
+        difficulty: map.difficulty(),
+        terrain: map.terrain().clone(),
+        units: map.units().clone(),
+        cities: map.cities().clone(),
+        diplomacy: map.diplomacy().clone(),
+        explored: explored,
+    };
+    serde_json::to_string(&save).expect("Can't serialize the game")
+}
+
+/// Parses save file `contents` and returns the map and turn it contains.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::savegame::{serialize_game, deserialize_game};
+/// use civng_core::map::LiveMap;
+/// use civng_core::terrain::{TerrainMap, Resource};
+/// use civng_core::unit::{Unit, UnitType, Player, Order, Promotion};
+/// use civng_core::city::{City, CityStatus, Production};
+/// use civng_core::religion::Belief;
+/// use civng_core::hexpos::{Pos, Direction};
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(3, 2));
+/// let mut unit = Unit::new(UnitType::Ranged, Player::AI(0), Pos::origin());
+/// unit.set_order(Some(Order::Sentry));
+/// unit.add_xp(12);
+/// unit.promote(Promotion::Cover);
+/// unit.set_name("The Old Guard");
+/// map.add_unit(unit);
+/// let mut escort = Unit::new(UnitType::Melee, Player::AI(0), Pos::vector(Direction::South));
+/// escort.set_order(Some(Order::Follow(1)));
+/// map.add_unit(escort);
+/// let contents = serialize_game(&map, 42);
+/// let (loaded, turn) = deserialize_game(&contents).unwrap();
+/// assert_eq!(turn, 42);
+/// assert_eq!(loaded.terrain().size(), (3, 2));
+/// let unit = loaded.units().top_at_pos(Pos::origin()).unwrap();
+/// assert_eq!(unit.name(), "The Old Guard");
+/// assert!(unit.type_() == UnitType::Ranged);
+/// assert_eq!(unit.order(), Some(Order::Sentry));
+/// assert_eq!((unit.xp(), unit.promotions()), (12, &[Promotion::Cover][..]));
+/// assert_eq!(loaded.units().get(2).order(), Some(Order::Follow(1)));
+/// assert!(loaded.fog(Player::AI(0)).unwrap().is_explored(Pos::origin()));
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(3, 2));
+/// map.add_city(City::with_state("New York", Player::Me, Pos::origin(), 2, 5, 8));
+/// map.toggle_locked_tile(1, Pos::vector(Direction::South));
+/// let (loaded, _) = deserialize_game(&serialize_game(&map, 1)).unwrap();
+/// let city = loaded.cities().get(1);
+/// assert_eq!((city.name(), city.population(), city.food()), ("New York", 2, 5));
+/// assert_eq!(city.locked_tiles(), &[Pos::vector(Direction::South)]);
+///
+/// let granary = map.cities().buildings().find("Granary").unwrap();
+/// map.cities_mut().get_mut(1).add_building(granary);
+/// map.set_production(1, Some(Production::Unit(UnitType::Worker)));
+/// let (loaded, _) = deserialize_game(&serialize_game(&map, 1)).unwrap();
+/// assert!(loaded.cities().get(1).has_building(granary));
+/// assert!(loaded.cities().get(1).producing() == Some(Production::Unit(UnitType::Worker)));
+///
+/// map.cities_mut().capture(1, Player::AI(0));
+/// map.set_city_status(1, CityStatus::Puppet);
+/// let (loaded, _) = deserialize_game(&serialize_game(&map, 1)).unwrap();
+/// assert_eq!(loaded.cities().get(1).status(), CityStatus::Puppet);
+/// assert_eq!(loaded.cities().gold(Player::AI(0)), 20);
+///
+/// map.diplomacy_mut().make_peace(Player::Me, Player::AI(0));
+/// map.diplomacy_mut().set_open_borders(Player::Me, Player::AI(0), true);
+/// let (loaded, _) = deserialize_game(&serialize_game(&map, 1)).unwrap();
+/// assert!(!loaded.diplomacy().at_war(Player::AI(0), Player::Me));
+/// assert!(loaded.diplomacy().has_open_borders(Player::Me, Player::AI(0)));
+/// assert!(loaded.diplomacy().at_war(Player::AI(1), Player::Me));
+///
+/// map.diplomacy_mut().add_loss(Player::AI(1), Player::Me);
+/// map.diplomacy_mut().pass_turn();
+/// map.offer_peace(Player::AI(1), Player::Me);
+/// let (loaded, _) = deserialize_game(&serialize_game(&map, 1)).unwrap();
+/// let war = loaded.diplomacy().war(Player::Me, Player::AI(1)).unwrap();
+/// assert_eq!((war.turns, war.losses_of(Player::AI(1)), war.losses_of(Player::Me)), (1, 1, 0));
+/// assert!(loaded.diplomacy().has_peace_offer(Player::AI(1), Player::Me));
+///
+/// map.terrain_mut().set_resource(Pos::origin(), Some(Resource::Horses));
+/// let (loaded, _) = deserialize_game(&serialize_game(&map, 1)).unwrap();
+/// assert_eq!(loaded.terrain().get_resource(Pos::origin()), Some(Resource::Horses));
+///
+/// map.cities_mut().set_faith(Player::AI(0), 30);
+/// map.cities_mut().found_pantheon(Player::AI(0), Belief::SacredWaters);
+/// let (loaded, _) = deserialize_game(&serialize_game(&map, 1)).unwrap();
+/// assert_eq!(loaded.cities().faith(Player::AI(0)), 10);
+/// assert_eq!(loaded.cities().pantheon(Player::AI(0)), Some(Belief::SacredWaters));
+///
+/// let contents = serialize_game(&map, 1);
+/// assert!(deserialize_game(&contents.replace("\"version\":2", "\"version\":1")).is_err());
+/// assert!(deserialize_game("civng-save 1\nturn 3\n").is_err());
+/// ```
+pub fn deserialize_game(contents: &str) -> io::Result<(LiveMap, u16)> {
+    let save: SaveFile = try!(serde_json::from_str(contents).map_err(|e| {
+        invalid_data(&format!("Not a civng save file or unsupported version: {}", e))
+    }));
+    if save.version != SAVE_VERSION {
+        return Err(invalid_data("Unsupported save file version"));
+    }
+    let mut map = LiveMap::new(save.terrain);
+    map.set_difficulty(save.difficulty);
+    *map.cities_mut() = save.cities;
+    *map.diplomacy_mut() = save.diplomacy;
+    map.set_units(save.units);
+    let tiles: Vec<Pos> = map.terrain().tiles().map(|(p, _)| p).collect();
+    for (player, explored) in save.explored {
+        if explored.len() != tiles.len() {
+            return Err(invalid_data("Invalid explored tiles in save file"));
+        }
+        for (&pos, is_explored) in tiles.iter().zip(explored) {
+            if is_explored {
+                map.fog_mut(player).explore(pos);
+            }
+        }
+    }
+    map.assign_citizens();
+    Ok((map, save.turn))
+}
+
+/// Saves `map` at turn `turn` in the file at `path`.
+pub fn save_game(path: &Path, map: &LiveMap, turn: u16) -> io::Result<()> {
+    let mut fp = try!(File::create(path));
+    fp.write_all(serialize_game(map, turn).as_bytes())
+}
+
+/// Loads the game saved in the file at `path`.
+pub fn load_game(path: &Path) -> io::Result<(LiveMap, u16)> {
+    let mut fp = try!(File::open(path));
+    let mut contents = String::new();
+    try!(fp.read_to_string(&mut contents));
+    deserialize_game(&contents)
+}
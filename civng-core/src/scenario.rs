@@ -0,0 +1,435 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Scenario files, describing a whole game setup.
+//!
+//! Scenario files are [TOML](https://toml.io) files following the schema of `ScenarioFile`. Unknown
+//! keys and invalid values are errors.
+//!
+//! ```text
+//! # Either a map file, relative to the scenario file, or a generator preset.
+//! map = "../resources/pangea-duel.Civ5Map"
+//! seed = 42
+//! ai_players = 2
+//! # Difficulty level, "prince" by default. See the `difficulty` module.
+//! difficulty = "king"
+//! # Type, owner and offset position. Without any unit, players get the default units.
+//! units = [
+//!     { type = "Melee", owner = "Me", pos = [0, 0] },
+//!     { type = "Melee", owner = "AI0", pos = [4, 3] },
+//!     { type = "Ranged", owner = "AI1", pos = [8, 3] },
+//! ]
+//! # Scripted events, see the `script` module.
+//! triggers = ["turn_start 10 => spawn Melee AI1 8 3"]
+//! # Mod script, relative to the scenario file. See the `script` module as well.
+//! script = "raiders.rhai"
+//!
+//! # We win when any of these conditions is met.
+//! [victory]
+//! conquest = true
+//! survive = 50
+//! ```
+
+use std::cmp::{min, max};
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use hexpos::{Pos, OffsetPos};
+use terrain::Terrain;
+use unit::{Unit, UnitType, Player};
+use city::City;
+use map::LiveMap;
+use civ5map;
+use civ5map::load_civ5map_scenario;
+use mapgen::{MapPreset, generate_map, DEFAULT_WIDTH, DEFAULT_HEIGHT};
+use rng;
+use difficulty::Difficulty;
+use toml;
+use script::{Trigger, ModScript, parse_trigger};
+
+/// Where the map of a scenario comes from.
+pub enum ScenarioMap {
+    File(PathBuf),
+    Generated(MapPreset),
+}
+
+/// A way to win a scenario.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum VictoryCondition {
+    /// We win when no enemy unit is left.
+    Conquest,
+    /// We win if we still have units after that many turns.
+    Survive(u16),
+}
+
+impl VictoryCondition {
+    /// Returns whether we win on `map` at turn `turn`.
+    pub fn is_met(&self, map: &LiveMap, turn: u16) -> bool {
+        match *self {
+            VictoryCondition::Conquest => map.units().enemy_units().next().is_none(),
+            VictoryCondition::Survive(turns) => {
+                turn > turns && map.units().my_units().next().is_some()
+            }
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match *self {
+            VictoryCondition::Conquest => "Destroy all enemy units".to_owned(),
+            VictoryCondition::Survive(turns) => format!("Survive {} turns", turns),
+        }
+    }
+}
+
+/// A unit placed by the scenario.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioUnit {
+    #[serde(rename = "type")]
+    pub type_: UnitType,
+    pub owner: Player,
+    pub pos: OffsetPos,
+}
+
+/// The ways to win a scenario, as written in its file.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct VictoryFile {
+    #[serde(default)]
+    conquest: bool,
+    survive: Option<u16>,
+}
+
+/// A scenario file as written, before its map and script paths are resolved and its triggers
+/// parsed. See the module documentation for an example.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    /// Map file, relative to the scenario file, or name of a generator preset.
+    map: String,
+    seed: Option<u32>,
+    #[serde(default = "default_ai_players")]
+    ai_players: u8,
+    difficulty: Option<Difficulty>,
+    #[serde(default)]
+    units: Vec<ScenarioUnit>,
+    #[serde(default)]
+    victory: VictoryFile,
+    /// Triggers in the syntax of `parse_trigger()`.
+    #[serde(default)]
+    triggers: Vec<String>,
+    /// Mod script file, relative to the scenario file.
+    script: Option<String>,
+}
+
+fn default_ai_players() -> u8 {
+    1
+}
+
+pub struct Scenario {
+    pub map: ScenarioMap,
+    /// Seed of the random number generator, applied before the map is generated.
+    pub seed: Option<u32>,
+    pub ai_players: u8,
+    /// When `None`, the difficulty chosen by the player is used.
+    pub difficulty: Option<Difficulty>,
+    /// When empty, players get default units.
+    pub units: Vec<ScenarioUnit>,
+    pub victory_conditions: Vec<VictoryCondition>,
+    pub triggers: Vec<Trigger>,
+    pub script: Option<ModScript>,
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl Scenario {
+    /// Parses scenario `contents`. Relative map and script paths are relative to `base_dir`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use civng_core::scenario::{Scenario, VictoryCondition};
+    ///
+    /// let contents = "map = \"continents\"\n\
+    ///                 units = [{ type = \"Melee\", owner = \"AI0\", pos = [4, 3] }]\n\
+    ///                 victory = { survive = 10 }\n";
+    /// let scenario = Scenario::parse(contents, Path::new(".")).unwrap();
+    /// assert_eq!(scenario.units.len(), 1);
+    /// assert_eq!(scenario.victory_conditions, vec![VictoryCondition::Survive(10)]);
+    /// let parse = |contents| Scenario::parse(contents, Path::new("."));
+    /// // The owner is missing.
+    /// assert!(parse("map = \"continents\"\nunits = [{ type = \"Melee\", pos = [4, 3] }]\n")
+    ///             .is_err());
+    /// assert!(parse("map = \"continents\"\nsid = 42\n").is_err());
+    /// assert!(parse("seed = 42\n").is_err());
+    /// ```
+    pub fn parse(contents: &str, base_dir: &Path) -> io::Result<Scenario> {
+        let file: ScenarioFile = try!(toml::from_str(contents).map_err(|e| {
+            invalid_data(&format!("Invalid scenario: {}", e))
+        }));
+        let map = match MapPreset::from_name(&file.map) {
+            Some(preset) => ScenarioMap::Generated(preset),
+            None => ScenarioMap::File(base_dir.join(&file.map)),
+        };
+        let mut victory_conditions = Vec::new();
+        if file.victory.conquest {
+            victory_conditions.push(VictoryCondition::Conquest);
+        }
+        if let Some(turns) = file.victory.survive {
+            victory_conditions.push(VictoryCondition::Survive(turns));
+        }
+        let mut triggers = Vec::new();
+        for trigger in file.triggers.iter() {
+            match parse_trigger(trigger) {
+                Some(trigger) => triggers.push(trigger),
+                None => return Err(invalid_data(&format!("Invalid trigger: {}", trigger))),
+            }
+        }
+        let script = match file.script {
+            Some(path) => Some(try!(ModScript::load(&base_dir.join(path)))),
+            None => None,
+        };
+        Ok(Scenario {
+            map: map,
+            seed: file.seed,
+            ai_players: file.ai_players,
+            difficulty: file.difficulty,
+            units: file.units,
+            victory_conditions: victory_conditions,
+            triggers: triggers,
+            script: script,
+        })
+    }
+
+    /// Loads the scenario file at `path`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use civng_core::scenario::Scenario;
+    ///
+    /// let scenario = Scenario::load(Path::new("../resources/skirmish.toml")).unwrap();
+    /// assert_eq!(scenario.triggers.len(), 2);
+    /// assert!(scenario.script.is_some());
+    /// ```
+    pub fn load(path: &Path) -> io::Result<Scenario> {
+        let mut fp = try!(File::open(path));
+        let mut contents = String::new();
+        try!(fp.read_to_string(&mut contents));
+        Scenario::parse(&contents, path.parent().unwrap_or(Path::new(".")))
+    }
+
+    /// Builds the map of the scenario along with its units.
+    ///
+    /// If the scenario has a seed, the random number generator is seeded first. If it doesn't have
+    /// a difficulty, the map is played at `difficulty`.
+    pub fn build_map(&self, difficulty: Difficulty) -> io::Result<LiveMap> {
+        if let Some(seed) = self.seed {
+            rng::seed(seed);
+        }
+        let (terrainmap, civ5scenario) = match self.map {
+            ScenarioMap::File(ref path) => {
+                match load_civ5map_scenario(path) {
+                    Ok(loaded) => loaded,
+                    Err(err) => return Err(invalid_data(&format!("Can't load map: {}", err))),
+                }
+            }
+            ScenarioMap::Generated(preset) => {
+                let map = rng::with_rng(|r| {
+                    generate_map(preset, DEFAULT_WIDTH, DEFAULT_HEIGHT, r)
+                });
+                (map, None)
+            }
+        };
+        let mut map = LiveMap::new(terrainmap);
+        map.set_difficulty(self.difficulty.unwrap_or(difficulty));
+        if self.units.is_empty() {
+            if !add_scenario_units(&mut map, civ5scenario.as_ref()) {
+                let start_positions = start_positions(civ5scenario.as_ref());
+                add_default_units(&mut map, self.ai_players, &start_positions);
+            }
+        } else {
+            for unit in self.units.iter() {
+                let pos = unit.pos.to_pos();
+                // Like default units, scenario units can share their tile with friendly units.
+                let enemy_there = map.units()
+                                     .top_at_pos(pos)
+                                     .map_or(false, |u| u.owner() != unit.owner);
+                if !map.terrain().is_passable(pos) || enemy_there {
+                    return Err(invalid_data(&format!("Unit can't be placed at {}",
+                                                     unit.pos.fmt())));
+                }
+                map.add_unit(Unit::new(unit.type_, unit.owner, pos));
+            }
+        }
+        add_default_cities(&mut map, civ5scenario.as_ref());
+        Ok(map)
+    }
+}
+
+/// Returns the start positions of `scenario`, if any.
+pub fn start_positions(scenario: Option<&civ5map::Scenario>) -> Vec<Option<Pos>> {
+    scenario.map_or(Vec::new(), |s| s.start_positions.clone())
+}
+
+/// Gives two units to each player, plus the extra units the difficulty level of `map` gives to AI
+/// players.
+///
+/// The first of `start_positions` is ours and the following ones go to AI players. Players without
+/// a start position, or with one outside the map, start at the top left corner for us and along
+/// the middle row of the map for AI players. Each unit is placed on the first free passable tile
+/// from there, so that units don't start stacked, and units without such a tile left aren't added.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::terrain::TerrainMap;
+/// use civng_core::map::LiveMap;
+/// use civng_core::scenario::add_default_units;
+/// use civng_core::difficulty::Difficulty;
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(10, 10));
+/// map.set_difficulty(Difficulty::Deity);
+/// add_default_units(&mut map, 2, &[]);
+/// let mut positions: Vec<String> = map.units()
+///                                     .all_units()
+///                                     .map(|u| u.pos().to_offset_pos().fmt())
+///                                     .collect();
+/// assert_eq!(positions.len(), 2 + 2 * 5);
+/// positions.sort();
+/// positions.dedup();
+/// assert_eq!(positions.len(), 2 + 2 * 5);
+/// ```
+pub fn add_default_units(map: &mut LiveMap, ai_players: u8, start_positions: &[Option<Pos>]) {
+    let add_unit = |map: &mut LiveMap, type_: UnitType, owner: Player, index: usize, default| {
+        let pos = match start_positions.get(index) {
+            Some(&Some(pos)) if map.terrain().get_terrain(pos) != Terrain::OutOfBounds => pos,
+            _ => default,
+        };
+        let pos = map.find_passable(pos);
+        if let Some(pos) = pos {
+            map.add_unit(Unit::new(type_, owner, pos));
+        }
+        pos
+    };
+    add_unit(map, UnitType::Melee, Player::Me, 0, Pos::origin());
+    add_unit(map, UnitType::Ranged, Player::Me, 0, Pos::origin());
+    let (width, height) = map.terrain().size();
+    for i in 0..ai_players {
+        let x = width * (i as i32 + 1) / (ai_players as i32 + 1);
+        let default = OffsetPos::new(x, height / 2).to_pos();
+        add_unit(map, UnitType::Melee, Player::AI(i), i as usize + 1, default);
+        add_unit(map, UnitType::Melee, Player::AI(i), i as usize + 1, default);
+        for _ in 0..map.difficulty().ai_extra_units() {
+            add_unit(map, UnitType::Ranged, Player::AI(i), i as usize + 1, default);
+        }
+    }
+}
+
+/// Adds the units `scenario` places, if any, and returns whether it placed some.
+///
+/// In scenarios, player 0 is us and the following players are AI players. Like default units,
+/// each unit is placed on the first free passable tile from its position.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use civng_core::civ5map::load_civ5map_scenario;
+/// use civng_core::map::LiveMap;
+/// use civng_core::scenario::add_scenario_units;
+/// use civng_core::unit::Player;
+///
+/// let path = Path::new("../resources/duel-scenario.Civ5Map");
+/// let (terrain, scenario) = load_civ5map_scenario(path).unwrap();
+/// let mut map = LiveMap::new(terrain);
+/// assert!(!add_scenario_units(&mut map, None));
+/// assert!(add_scenario_units(&mut map, scenario.as_ref()));
+/// assert_eq!(map.units().all_units().count(), 4);
+/// assert!(map.units().ai_players() == vec![Player::AI(0)]);
+/// ```
+pub fn add_scenario_units(map: &mut LiveMap, scenario: Option<&civ5map::Scenario>) -> bool {
+    let units = match scenario {
+        Some(scenario) if !scenario.units.is_empty() => &scenario.units,
+        _ => return false,
+    };
+    for unit in units.iter() {
+        let owner = match unit.owner {
+            0 => Player::Me,
+            n => Player::AI(n - 1),
+        };
+        if let Some(pos) = map.find_passable(unit.pos) {
+            map.add_unit(Unit::new(unit.type_, owner, pos));
+        }
+    }
+    true
+}
+
+/// Names of the capitals founded by `add_default_cities()`, ours first.
+const CAPITAL_NAMES: [&'static str; 8] = ["Washington", "Moscow", "Beijing", "Paris", "London",
+                                          "Berlin", "Rome", "Cairo"];
+
+/// Founds the cities `scenario` places, if any, and a capital for each player still without a
+/// city, where its first unit stands.
+///
+/// In scenarios, player 0 is us and the following players are AI players.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::terrain::TerrainMap;
+/// use civng_core::map::LiveMap;
+/// use civng_core::scenario::{add_default_units, add_default_cities};
+/// use civng_core::unit::Player;
+/// use civng_core::hexpos::Pos;
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(10, 10));
+/// add_default_units(&mut map, 1, &[]);
+/// add_default_cities(&mut map, None);
+/// let capital = map.cities().get(1);
+/// assert_eq!((capital.name(), capital.pos()), ("Washington", Pos::origin()));
+/// assert!(map.cities().get(2).owner() == Player::AI(0));
+/// ```
+pub fn add_default_cities(map: &mut LiveMap, scenario: Option<&civ5map::Scenario>) {
+    if let Some(scenario) = scenario {
+        for city in scenario.cities.iter() {
+            let owner = match city.owner {
+                0 => Player::Me,
+                n => Player::AI(n - 1),
+            };
+            let pos = city.pos;
+            if map.terrain().get_terrain(pos) == Terrain::OutOfBounds ||
+               map.cities().get_at_pos(pos).is_some() {
+                continue;
+            }
+            let population = max(min(city.population, u8::max_value() as u16), 1) as u8;
+            map.add_city(City::with_state(&city.name, owner, pos, population, 0, 0));
+        }
+    }
+    let players = Some(Player::Me).into_iter().chain(map.units().ai_players());
+    for (index, player) in players.enumerate() {
+        if map.cities().all_cities().iter().any(|c| c.owner() == player) {
+            continue;
+        }
+        let first_unit = map.units()
+                            .all_units()
+                            .filter(|u| u.owner() == player)
+                            .min_by_key(|u| u.id());
+        let pos = match first_unit {
+            Some(unit) if map.cities().get_at_pos(unit.pos()).is_none() => unit.pos(),
+            _ => continue,
+        };
+        let name = CAPITAL_NAMES.get(index).cloned().unwrap_or("Capital");
+        map.add_city(City::new(name, player, pos));
+    }
+}
@@ -0,0 +1,524 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Scripted scenario events.
+//!
+//! Scenarios can declare triggers, each running an action when a game event happens. They're
+//! listed under `triggers` in scenario files:
+//!
+//! ```text
+//! triggers = [
+//!     "turn_start 5 => spawn Melee AI0 4 3",
+//!     "unit_moved 10 4 => set_terrain 11 4 Mountain",
+//!     "combat => message Blood has been spilled!",
+//! ]
+//! ```
+//!
+//! Events are `turn_start [turn]`, `unit_moved [x y]` (a unit entering a tile), `combat` and
+//! `city_founded [x y]`. Without arguments, they match any turn or tile. Actions are
+//! `spawn <type> <owner> <x> <y>`, `set_terrain <x> <y> <terrain>`, `message <text>`,
+//! `teleport <x> <y> <to_x> <to_y>`, which moves the unit at `x y` without following a path, and
+//! `toggle_owner <x> <y>`, which hands the unit at `x y` over to us or, if it's ours, to the first
+//! AI player.
+//!
+//! The debug console of the game runs actions too, typed one at a time.
+//!
+//! # Mod scripts
+//!
+//! Scenarios needing more than triggers can have a script in the [Rhai][rhai] language, with a
+//! `script` line in their file. The script defines functions, called hooks, which the game calls
+//! when events happen:
+//!
+//! ```text
+//! fn on_turn_start(turn) {
+//!     if turn % 10 == 0 {
+//!         spawn_unit("Horseman", "AI0", 4, 3);
+//!         message("Raiders come from the hills!");
+//!     }
+//! }
+//!
+//! fn on_unit_moved(x, y) {
+//!     if x == 10 && y == 4 {
+//!         set_terrain(11, 4, "Mountain");
+//!     }
+//! }
+//! ```
+//!
+//! Hooks are `on_turn_start(turn)`, `on_unit_moved(x, y)`, `on_combat(x, y, defender_x,
+//! defender_y)`, with the tiles of the attacker and the defender, and `on_city_founded(x, y)`. A
+//! script doesn't have to define them all. Hooks act on the game through functions matching the
+//! actions of triggers: `spawn_unit(type, owner, x, y)`, `set_terrain(x, y, terrain)`,
+//! `message(text)`, `teleport(x, y, to_x, to_y)` and `toggle_owner(x, y)`. Actions are run once
+//! the hook returns, in the order they were called, and skipped when they fail, like the actions
+//! of triggers. `print()` shows a message as well. Statements outside of hooks aren't run.
+//!
+//! [rhai]: https://rhai.rs/
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+use std::rc::Rc;
+
+use rhai::{Engine, AST, Scope, Dynamic, EvalAltResult, CallFnOptions, INT};
+
+use hexpos::{Pos, OffsetPos};
+use terrain::Terrain;
+use unit::{Unit, UnitType, Player};
+use map::LiveMap;
+use events::GameEvent;
+
+/// Operations a hook of a mod script can run before it's stopped, so that an endless loop in a
+/// script doesn't freeze the game.
+const MAX_HOOK_OPERATIONS: u64 = 1000000;
+
+/// Something that happened in the game, which triggers can react to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScriptEvent {
+    TurnStart(u16),
+    UnitMoved(Pos),
+    /// A combat between the attacker on the first tile and the defender on the second.
+    Combat(Pos, Pos),
+    CityFounded(Pos),
+}
+
+impl ScriptEvent {
+    /// Returns the script event corresponding to `event`, if any.
+    ///
+    /// Turn starts aren't converted because map events don't know the turn number.
+    pub fn from_game_event(event: &GameEvent) -> Option<ScriptEvent> {
+        match *event {
+            GameEvent::UnitMoved(_, ref path) => Some(ScriptEvent::UnitMoved(path.to())),
+            GameEvent::CombatResolved(ref stats) => {
+                Some(ScriptEvent::Combat(stats.attacker_pos, stats.defender_pos))
+            }
+            GameEvent::CityFounded(_, _, pos) => Some(ScriptEvent::CityFounded(pos)),
+            _ => None,
+        }
+    }
+}
+
+/// Events a trigger reacts to. `None` arguments match anything.
+#[derive(Clone, Copy, PartialEq)]
+pub enum EventPattern {
+    TurnStart(Option<u16>),
+    UnitMoved(Option<OffsetPos>),
+    Combat,
+    CityFounded(Option<OffsetPos>),
+}
+
+impl EventPattern {
+    pub fn matches(&self, event: ScriptEvent) -> bool {
+        match (*self, event) {
+            (EventPattern::TurnStart(turn), ScriptEvent::TurnStart(t)) => {
+                turn.map_or(true, |turn| turn == t)
+            }
+            (EventPattern::UnitMoved(opos), ScriptEvent::UnitMoved(pos)) => {
+                opos.map_or(true, |opos| opos.to_pos() == pos)
+            }
+            (EventPattern::Combat, ScriptEvent::Combat(..)) => true,
+            (EventPattern::CityFounded(opos), ScriptEvent::CityFounded(pos)) => {
+                opos.map_or(true, |opos| opos.to_pos() == pos)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum ScriptAction {
+    Spawn(UnitType, Player, OffsetPos),
+    SetTerrain(OffsetPos, Terrain),
+    /// Shows a message to the player.
+    Message(String),
+    /// Moves the unit at the first position to the second one, bypassing pathing.
+    Teleport(OffsetPos, OffsetPos),
+    ToggleOwner(OffsetPos),
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Trigger {
+    pub event: EventPattern,
+    pub action: ScriptAction,
+}
+
+fn parse_offset_pos(x: &str, y: &str) -> Option<OffsetPos> {
+    match (x.parse(), y.parse()) {
+        (Ok(x), Ok(y)) => Some(OffsetPos::new(x, y)),
+        _ => None,
+    }
+}
+
+fn parse_event(s: &str) -> Option<EventPattern> {
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    match parts.get(0) {
+        Some(&"turn_start") if parts.len() == 1 => Some(EventPattern::TurnStart(None)),
+        Some(&"turn_start") if parts.len() == 2 => {
+            parts[1].parse().ok().map(|turn| EventPattern::TurnStart(Some(turn)))
+        }
+        Some(&"unit_moved") if parts.len() == 1 => Some(EventPattern::UnitMoved(None)),
+        Some(&"unit_moved") if parts.len() == 3 => {
+            parse_offset_pos(parts[1], parts[2]).map(|opos| EventPattern::UnitMoved(Some(opos)))
+        }
+        Some(&"combat") if parts.len() == 1 => Some(EventPattern::Combat),
+        Some(&"city_founded") if parts.len() == 1 => Some(EventPattern::CityFounded(None)),
+        Some(&"city_founded") if parts.len() == 3 => {
+            parse_offset_pos(parts[1], parts[2]).map(|opos| EventPattern::CityFounded(Some(opos)))
+        }
+        _ => None,
+    }
+}
+
+/// Parses an action, such as `spawn Melee AI0 4 3`.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::script::{parse_action, ScriptAction};
+/// use civng_core::hexpos::OffsetPos;
+///
+/// let action = parse_action("teleport 1 2 8 4").unwrap();
+/// assert!(action == ScriptAction::Teleport(OffsetPos::new(1, 2), OffsetPos::new(8, 4)));
+/// assert!(parse_action("toggle_owner 1").is_none());
+/// ```
+pub fn parse_action(s: &str) -> Option<ScriptAction> {
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    match parts.get(0) {
+        Some(&"spawn") if parts.len() == 5 => {
+            let type_ = UnitType::all().iter().find(|t| t.name() == parts[1]).cloned();
+            let owner = Player::from_name(parts[2]);
+            let opos = parse_offset_pos(parts[3], parts[4]);
+            match (type_, owner, opos) {
+                (Some(type_), Some(owner), Some(opos)) => {
+                    Some(ScriptAction::Spawn(type_, owner, opos))
+                }
+                _ => None,
+            }
+        }
+        Some(&"set_terrain") if parts.len() == 4 => {
+            let terrain = Terrain::all().iter().find(|t| t.name() == parts[3]).cloned();
+            match (parse_offset_pos(parts[1], parts[2]), terrain) {
+                (Some(opos), Some(terrain)) => Some(ScriptAction::SetTerrain(opos, terrain)),
+                _ => None,
+            }
+        }
+        Some(&"message") if parts.len() > 1 => {
+            Some(ScriptAction::Message(s.trim()["message".len()..].trim().to_owned()))
+        }
+        Some(&"teleport") if parts.len() == 5 => {
+            match (parse_offset_pos(parts[1], parts[2]), parse_offset_pos(parts[3], parts[4])) {
+                (Some(from), Some(to)) => Some(ScriptAction::Teleport(from, to)),
+                _ => None,
+            }
+        }
+        Some(&"toggle_owner") if parts.len() == 3 => {
+            parse_offset_pos(parts[1], parts[2]).map(ScriptAction::ToggleOwner)
+        }
+        _ => None,
+    }
+}
+
+/// Parses a trigger of the form `<event> => <action>`.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::script::{parse_trigger, EventPattern, ScriptAction};
+///
+/// let trigger = parse_trigger("turn_start 3 => message Reinforcements!").unwrap();
+/// assert!(trigger.event == EventPattern::TurnStart(Some(3)));
+/// assert!(trigger.action == ScriptAction::Message("Reinforcements!".to_owned()));
+/// assert!(parse_trigger("turn_start => spawn Cavalry Me 0 0").is_none());
+/// ```
+pub fn parse_trigger(s: &str) -> Option<Trigger> {
+    let mut parts = s.splitn(2, "=>");
+    let event = parts.next().and_then(parse_event);
+    let action = parts.next().and_then(parse_action);
+    match (event, action) {
+        (Some(event), Some(action)) => {
+            Some(Trigger {
+                event: event,
+                action: action,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Runs `action` on `map` and returns the message to show, if any.
+///
+/// Units spawned on an impassable or occupied tile are placed on the next passable tile. Returns
+/// an error, leaving the map as it was, when no tile is left for a spawned unit, when the action
+/// targets a tile outside the map or without a unit, when a tile occupied by a unit would become
+/// impassable and when the teleported unit can't stop where it's sent (see
+/// `LiveMap::teleport_unit()`).
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::terrain::TerrainMap;
+/// use civng_core::map::LiveMap;
+/// use civng_core::unit::{Unit, UnitType, Player};
+/// use civng_core::hexpos::{Pos, OffsetPos};
+/// use civng_core::script::{parse_action, run_action};
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(6, 6));
+/// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+/// assert!(run_action(&parse_action("teleport 0 0 4 5").unwrap(), &mut map).is_ok());
+/// assert!(run_action(&parse_action("toggle_owner 4 5").unwrap(), &mut map).is_ok());
+/// let unit = map.units().get(1);
+/// assert!(unit.pos() == OffsetPos::new(4, 5).to_pos());
+/// assert!(unit.owner() == Player::AI(0));
+/// assert_eq!(run_action(&parse_action("toggle_owner 0 0").unwrap(), &mut map),
+///            Err("There's no unit at 0,0.".to_owned()));
+/// assert!(run_action(&parse_action("spawn Melee Me 9 9").unwrap(), &mut map).is_err());
+/// ```
+pub fn run_action(action: &ScriptAction, map: &mut LiveMap) -> Result<Option<String>, String> {
+    let no_tile = |opos: OffsetPos| format!("There's no tile at {}.", opos.fmt());
+    let no_unit = |opos: OffsetPos| format!("There's no unit at {}.", opos.fmt());
+    match *action {
+        ScriptAction::Spawn(type_, owner, opos) => {
+            let pos = opos.to_pos();
+            if map.terrain().get_terrain(pos) == Terrain::OutOfBounds {
+                return Err(no_tile(opos));
+            }
+            match map.find_passable(pos) {
+                Some(pos) => map.add_unit(Unit::new(type_, owner, pos)),
+                None => return Err("No passable tile is left for the unit.".to_owned()),
+            }
+        }
+        ScriptAction::SetTerrain(opos, terrain) => {
+            let pos = opos.to_pos();
+            if map.terrain().get_terrain(pos) == Terrain::OutOfBounds {
+                return Err(no_tile(opos));
+            }
+            if !terrain.is_passable() && map.units().top_at_pos(pos).is_some() {
+                return Err(format!("Units stand on {}.", opos.fmt()));
+            }
+            map.terrain_mut().set_terrain(pos, terrain);
+        }
+        ScriptAction::Message(ref msg) => return Ok(Some(msg.clone())),
+        ScriptAction::Teleport(from, to) => {
+            let unit_id = match map.units().top_at_pos(from.to_pos()) {
+                Some(unit) => unit.id(),
+                None => return Err(no_unit(from)),
+            };
+            if !map.teleport_unit(unit_id, to.to_pos()) {
+                return Err(format!("The unit can't stop at {}.", to.fmt()));
+            }
+        }
+        ScriptAction::ToggleOwner(opos) => {
+            let unit_id = match map.units().top_at_pos(opos.to_pos()) {
+                Some(unit) => unit.id(),
+                None => return Err(no_unit(opos)),
+            };
+            map.toggle_unit_owner(unit_id);
+        }
+    }
+    Ok(None)
+}
+
+/// Runs the actions of `triggers` matching `event` on `map` and returns messages to show.
+///
+/// Actions that fail, as `run_action()` tells, are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use civng_core::terrain::{TerrainMap, Terrain};
+/// use civng_core::map::LiveMap;
+/// use civng_core::hexpos::OffsetPos;
+/// use civng_core::script::{parse_trigger, run_triggers, ScriptEvent};
+///
+/// let mut map = LiveMap::new(TerrainMap::empty_map(4, 4));
+/// map.terrain_mut().set_terrain(OffsetPos::new(3, 3).to_pos(), Terrain::Ocean);
+/// let triggers = vec![parse_trigger("turn_start => spawn Melee AI0 3 3").unwrap(),
+///                     parse_trigger("turn_start => spawn Melee AI0 2 3").unwrap()];
+/// run_triggers(&triggers, ScriptEvent::TurnStart(2), &mut map);
+/// // No tile is left for the first unit, which isn't spawned, and the second one is.
+/// assert_eq!(map.units().all_units().count(), 1);
+/// ```
+pub fn run_triggers(triggers: &[Trigger], event: ScriptEvent, map: &mut LiveMap) -> Vec<String> {
+    triggers.iter()
+            .filter(|t| t.event.matches(event))
+            .filter_map(|t| run_action(&t.action, map).ok().and_then(|msg| msg))
+            .collect()
+}
+
+/// Actions queued by the API functions of a mod script.
+type ActionQueue = Rc<RefCell<Vec<ScriptAction>>>;
+
+fn script_pos(x: INT, y: INT) -> OffsetPos {
+    OffsetPos::new(x as i32, y as i32)
+}
+
+/// Registers the functions through which `engine` queues actions in `queue`.
+fn register_actions(engine: &mut Engine, queue: &ActionQueue) {
+    let q = queue.clone();
+    engine.register_fn("spawn_unit",
+                       move |type_name: &str,
+                             owner_name: &str,
+                             x: INT,
+                             y: INT|
+                             -> Result<(), Box<EvalAltResult>> {
+        let type_ = match UnitType::all().iter().find(|t| t.name() == type_name) {
+            Some(&t) => t,
+            None => return Err(format!("Unknown unit type: {}", type_name).into()),
+        };
+        let owner = match Player::from_name(owner_name) {
+            Some(p) => p,
+            None => return Err(format!("Unknown player: {}", owner_name).into()),
+        };
+        q.borrow_mut().push(ScriptAction::Spawn(type_, owner, script_pos(x, y)));
+        Ok(())
+    });
+    let q = queue.clone();
+    engine.register_fn("set_terrain",
+                       move |x: INT, y: INT, name: &str| -> Result<(), Box<EvalAltResult>> {
+        match Terrain::all().iter().find(|t| t.name() == name) {
+            Some(&terrain) => {
+                q.borrow_mut().push(ScriptAction::SetTerrain(script_pos(x, y), terrain));
+                Ok(())
+            }
+            None => Err(format!("Unknown terrain: {}", name).into()),
+        }
+    });
+    let q = queue.clone();
+    engine.register_fn("message", move |text: &str| {
+        q.borrow_mut().push(ScriptAction::Message(text.to_owned()));
+    });
+    let q = queue.clone();
+    engine.on_print(move |text| {
+        q.borrow_mut().push(ScriptAction::Message(text.to_owned()));
+    });
+    let q = queue.clone();
+    engine.register_fn("teleport", move |x: INT, y: INT, to_x: INT, to_y: INT| {
+        q.borrow_mut().push(ScriptAction::Teleport(script_pos(x, y), script_pos(to_x, to_y)));
+    });
+    let q = queue.clone();
+    engine.register_fn("toggle_owner", move |x: INT, y: INT| {
+        q.borrow_mut().push(ScriptAction::ToggleOwner(script_pos(x, y)));
+    });
+}
+
+/// A mod script, in the Rhai language, whose hooks run when script events happen.
+///
+/// See the module documentation for hooks and the functions scripts can call.
+pub struct ModScript {
+    engine: Engine,
+    ast: AST,
+    actions: ActionQueue,
+}
+
+impl ModScript {
+    /// Compiles the mod script in `source`, or returns what's wrong with it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::script::ModScript;
+    ///
+    /// assert!(ModScript::compile("fn on_turn_start(turn) { message(\"Hi\"); }").is_ok());
+    /// assert!(ModScript::compile("fn on_turn_start(turn) {").is_err());
+    /// ```
+    pub fn compile(source: &str) -> Result<ModScript, String> {
+        let actions = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_HOOK_OPERATIONS);
+        register_actions(&mut engine, &actions);
+        let ast = try!(engine.compile(source).map_err(|err| err.to_string()));
+        Ok(ModScript {
+            engine: engine,
+            ast: ast,
+            actions: actions,
+        })
+    }
+
+    /// Loads the mod script at `path`.
+    pub fn load(path: &Path) -> io::Result<ModScript> {
+        let mut fp = try!(File::open(path));
+        let mut source = String::new();
+        try!(fp.read_to_string(&mut source));
+        ModScript::compile(&source).map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData,
+                           format!("Invalid script {}: {}", path.display(), err))
+        })
+    }
+
+    /// Calls the hook of the script for `event`, if it has one, runs the actions it queued on
+    /// `map` and returns messages to show.
+    ///
+    /// Actions that fail, as `run_action()` tells, are skipped. When the hook itself fails, its
+    /// error is one of the messages.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{TerrainMap, Terrain};
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::Player;
+    /// use civng_core::hexpos::{Pos, OffsetPos};
+    /// use civng_core::script::{ModScript, ScriptEvent};
+    ///
+    /// let script = ModScript::compile("
+    ///     fn on_turn_start(turn) {
+    ///         if turn == 2 {
+    ///             spawn_unit(\"Scout\", \"AI0\", 1, 1);
+    ///             set_terrain(2, 2, \"Hill\");
+    ///             message(`Turn ${turn}`);
+    ///         }
+    ///     }
+    ///     fn on_combat(x, y, defender_x, defender_y) { spawn_unit(\"Dragon\", \"Me\", x, y); }
+    /// ").unwrap();
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(4, 4));
+    /// assert!(script.run_hooks(ScriptEvent::TurnStart(1), &mut map).is_empty());
+    /// assert_eq!(script.run_hooks(ScriptEvent::TurnStart(2), &mut map), vec!["Turn 2"]);
+    /// assert!(map.units().get(1).owner() == Player::AI(0));
+    /// assert!(map.terrain().get_terrain(OffsetPos::new(2, 2).to_pos()) == Terrain::Hill);
+    /// // Hooks the script doesn't have do nothing, and errors are reported.
+    /// assert!(script.run_hooks(ScriptEvent::CityFounded(Pos::origin()), &mut map).is_empty());
+    /// let combat = ScriptEvent::Combat(Pos::origin(), Pos::origin());
+    /// assert!(script.run_hooks(combat, &mut map)[0].contains("Unknown unit type: Dragon"));
+    /// ```
+    pub fn run_hooks(&self, event: ScriptEvent, map: &mut LiveMap) -> Vec<String> {
+        let coords = |pos: Pos| {
+            let opos = pos.to_offset_pos();
+            vec![opos.x as INT, opos.y as INT]
+        };
+        let (name, args) = match event {
+            ScriptEvent::TurnStart(turn) => ("on_turn_start", vec![turn as INT]),
+            ScriptEvent::UnitMoved(pos) => ("on_unit_moved", coords(pos)),
+            ScriptEvent::Combat(attacker_pos, defender_pos) => {
+                let mut args = coords(attacker_pos);
+                args.extend(coords(defender_pos));
+                ("on_combat", args)
+            }
+            ScriptEvent::CityFounded(pos) => ("on_city_founded", coords(pos)),
+        };
+        if !self.ast.iter_functions().any(|f| f.name == name && f.params.len() == args.len()) {
+            return Vec::new();
+        }
+        let mut messages = Vec::new();
+        let options = CallFnOptions::new().eval_ast(false);
+        let result = self.engine.call_fn_with_options::<Dynamic>(options,
+                                                                  &mut Scope::new(),
+                                                                  &self.ast,
+                                                                  name,
+                                                                  args);
+        if let Err(err) = result {
+            messages.push(format!("Script error in {}: {}", name, err));
+        }
+        let actions: Vec<ScriptAction> = self.actions.borrow_mut().drain(..).collect();
+        for action in actions {
+            if let Ok(Some(msg)) = run_action(&action, map) {
+                messages.push(msg);
+            }
+        }
+        messages
+    }
+}
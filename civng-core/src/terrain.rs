@@ -0,0 +1,966 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Manages map information (terrain and stuff).
+//!
+//! Map coordinates are in `OffsetPos` because that's the easiest layout for serialization.
+
+use std::fs::File;
+use std::path::Path;
+use std::collections::HashMap;
+use std::iter::FromIterator;
+use std::io;
+use std::io::{Read, Write};
+use std::ops::Add;
+use std::slice::Iter;
+
+use num::integer::Integer;
+
+use hexpos::{Pos, OffsetPos, PosPath, HexGrid, Direction};
+use bitset::BitSet;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de;
+use serde::ser::SerializeStruct;
+
+/// Terrain type
+///
+/// Each tile in civng has a terrain type, which is represented by this structure.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Terrain {
+    Plain,
+    Grassland,
+    Desert,
+    Hill,
+    Mountain,
+    /// Shallow water along the shores of seas and oceans.
+    Coast,
+    /// Deep water, away from land.
+    Ocean,
+    /// Water body too small to be a sea.
+    Lake,
+    OutOfBounds,
+}
+
+/// Water bodies with fewer tiles than this are lakes.
+pub const LAKE_MAX_SIZE: usize = 10;
+/// Gold a tile gets from having a river.
+const RIVER_GOLD: u8 = 1;
+/// Edges whose river each tile keeps track of, by bit of its river flags.
+///
+/// The other edges of a tile are kept track of by its neighbors on the other side.
+pub const RIVER_EDGES: [Direction; 3] = [Direction::NorthEast,
+                                         Direction::SouthEast,
+                                         Direction::South];
+/// Number of units each tile of a strategic resource supplies with it.
+pub const RESOURCE_SUPPLY: u16 = 2;
+
+/// What a tile produces each turn when it's worked.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Yield {
+    pub food: u8,
+    pub production: u8,
+    pub gold: u8,
+}
+
+impl Yield {
+    pub fn new(food: u8, production: u8, gold: u8) -> Yield {
+        Yield {
+            food: food,
+            production: production,
+            gold: gold,
+        }
+    }
+
+    pub fn fmt(&self) -> String {
+        format!("{}F {}P {}G", self.food, self.production, self.gold)
+    }
+}
+
+impl Add for Yield {
+    type Output = Yield;
+
+    fn add(self, other: Yield) -> Yield {
+        Yield::new(self.food + other.food,
+                   self.production + other.production,
+                   self.gold + other.gold)
+    }
+}
+
+impl Terrain {
+    pub fn all() -> [Terrain; 8] {
+        [Terrain::Plain,
+         Terrain::Grassland,
+         Terrain::Desert,
+         Terrain::Hill,
+         Terrain::Mountain,
+         Terrain::Coast,
+         Terrain::Ocean,
+         Terrain::Lake]
+    }
+
+    /// Returns the character representing a particular terrain on screen.
+    pub fn map_char(&self) -> char {
+        match *self {
+            Terrain::Plain => '\'',
+            Terrain::Grassland => '"',
+            Terrain::Desert => ' ',
+            Terrain::Hill => '^',
+            Terrain::Mountain => 'A',
+            Terrain::Coast => '~',
+            Terrain::Ocean => '=',
+            Terrain::Lake => '_',
+            Terrain::OutOfBounds => '?',
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match *self {
+            Terrain::Plain => "Plain",
+            Terrain::Grassland => "Grassland",
+            Terrain::Desert => "Desert",
+            Terrain::Hill => "Hill",
+            Terrain::Mountain => "Mountain",
+            Terrain::Coast => "Coast",
+            Terrain::Ocean => "Ocean",
+            Terrain::Lake => "Lake",
+            Terrain::OutOfBounds => "Out of bounds",
+        }
+    }
+
+    /// Conceptual relative height of a terrain type.
+    ///
+    /// This is used for determining line of sight.
+    pub fn height(&self) -> u8 {
+        match *self {
+            Terrain::Plain => 0,
+            Terrain::Grassland => 0,
+            Terrain::Desert => 0,
+            Terrain::Hill => 1,
+            Terrain::Mountain => 2,
+            Terrain::Coast | Terrain::Ocean | Terrain::Lake => 0,
+            Terrain::OutOfBounds => 0,
+        }
+    }
+
+    pub fn defense_modifier(&self) -> i8 {
+        match *self {
+            Terrain::Plain => 0,
+            Terrain::Grassland => 0,
+            Terrain::Desert => 0,
+            Terrain::Hill => 25,
+            Terrain::Mountain => 0,
+            Terrain::Coast | Terrain::Ocean | Terrain::Lake => 0,
+            Terrain::OutOfBounds => 0,
+        }
+    }
+
+    /// Returns what a tile of that terrain yields, before features and rivers.
+    pub fn yields(&self) -> Yield {
+        match *self {
+            Terrain::Plain => Yield::new(1, 1, 0),
+            Terrain::Grassland => Yield::new(2, 0, 0),
+            Terrain::Desert => Yield::new(0, 0, 0),
+            Terrain::Hill => Yield::new(0, 2, 0),
+            Terrain::Mountain => Yield::new(0, 0, 0),
+            Terrain::Coast => Yield::new(1, 0, 1),
+            Terrain::Ocean => Yield::new(1, 0, 0),
+            Terrain::Lake => Yield::new(2, 0, 1),
+            Terrain::OutOfBounds => Yield::new(0, 0, 0),
+        }
+    }
+
+    /// Returns whether the terrain is coast, ocean or lake.
+    pub fn is_water(&self) -> bool {
+        match *self {
+            Terrain::Coast | Terrain::Ocean | Terrain::Lake => true,
+            _ => false,
+        }
+    }
+
+    /// Returns whether the terrain is passable by our moving unit.
+    ///
+    /// We only have land units, which can't go on water of any kind.
+    pub fn is_passable(&self) -> bool {
+        match *self {
+            Terrain::Mountain | Terrain::OutOfBounds => false,
+            t => !t.is_water(),
+        }
+    }
+
+    /// Returns how much farther than usual units standing on that terrain see.
+    pub fn sight_bonus(&self) -> u8 {
+        match *self {
+            Terrain::Hill => 1,
+            _ => 0,
+        }
+    }
+
+    /// Returns how much movement points it costs to move on that terrain.
+    pub fn movement_cost(&self) -> u8 {
+        match *self {
+            Terrain::Hill => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Natural feature covering a tile, on top of its terrain.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Feature {
+    /// Polar ice, on water.
+    Ice,
+    /// Natural wonder rising like a range of mountains.
+    MountainRange,
+    Forest,
+    Jungle,
+    Marsh,
+}
+
+impl Feature {
+    pub fn all() -> [Feature; 5] {
+        [Feature::Ice, Feature::MountainRange, Feature::Forest, Feature::Jungle, Feature::Marsh]
+    }
+
+    /// Returns the character representing the feature on screen, in place of its terrain.
+    pub fn map_char(&self) -> char {
+        match *self {
+            Feature::Ice => '#',
+            Feature::MountainRange => 'M',
+            Feature::Forest => '&',
+            Feature::Jungle => '%',
+            Feature::Marsh => ';',
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match *self {
+            Feature::Ice => "Ice",
+            Feature::MountainRange => "Mountain range",
+            Feature::Forest => "Forest",
+            Feature::Jungle => "Jungle",
+            Feature::Marsh => "Marsh",
+        }
+    }
+
+    /// Returns what a tile having the feature yields. It replaces the yield of the terrain.
+    ///
+    /// Natural wonders also give culture, which we don't have, and faith (see `faith()`).
+    pub fn yields(&self) -> Yield {
+        match *self {
+            Feature::Ice => Yield::new(0, 0, 0),
+            Feature::MountainRange => Yield::new(0, 0, 2),
+            Feature::Forest => Yield::new(1, 1, 0),
+            Feature::Jungle => Yield::new(1, 0, 0),
+            Feature::Marsh => Yield::new(1, 0, 0),
+        }
+    }
+
+    /// Faith a city working a tile having the feature gets every turn.
+    pub fn faith(&self) -> u8 {
+        match *self {
+            Feature::MountainRange => 2,
+            _ => 0,
+        }
+    }
+
+    /// How much the feature adds to the height of its tile when it comes to blocking sight.
+    pub fn height(&self) -> u8 {
+        match *self {
+            Feature::Ice | Feature::Marsh => 0,
+            Feature::Forest | Feature::Jungle => 1,
+            Feature::MountainRange => 2,
+        }
+    }
+
+    /// Returns whether units can enter tiles having the feature, whatever their terrain.
+    ///
+    /// Ice blocks ships like mountain ranges block land units, so neither can be crossed.
+    pub fn is_passable(&self) -> bool {
+        match *self {
+            Feature::Ice | Feature::MountainRange => false,
+            Feature::Forest | Feature::Jungle | Feature::Marsh => true,
+        }
+    }
+
+    /// Returns how many turns of work it takes a worker to remove the feature, if it can be.
+    pub fn removal_turns(&self) -> Option<u8> {
+        match *self {
+            Feature::Ice | Feature::MountainRange => None,
+            Feature::Forest => Some(3),
+            Feature::Marsh => Some(5),
+            Feature::Jungle => Some(6),
+        }
+    }
+
+    /// Production a city gets, once, when the feature is removed near it.
+    ///
+    /// Only forests are worth chopping: jungles and marshes are cleared for what the bare terrain
+    /// yields.
+    pub fn removal_production(&self) -> u8 {
+        match *self {
+            Feature::Forest => 20,
+            _ => 0,
+        }
+    }
+}
+
+/// Strategic resource lying on a tile.
+///
+/// Some units can only be trained by players having the resource they need in their territory.
+/// Players don't see resources until they accumulated enough science to know what to make of them.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Resource {
+    Horses,
+    Iron,
+}
+
+impl Resource {
+    pub fn all() -> [Resource; 2] {
+        [Resource::Horses, Resource::Iron]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Resource::Horses => "Horses",
+            Resource::Iron => "Iron",
+        }
+    }
+
+    /// What the resource adds to the yield of its tile, for players who know about it.
+    pub fn yields(&self) -> Yield {
+        match *self {
+            Resource::Horses => Yield::new(0, 1, 0),
+            Resource::Iron => Yield::new(0, 1, 0),
+        }
+    }
+
+    /// Science a player must have accumulated to see the resource and use it.
+    pub fn revealed_at(&self) -> u32 {
+        match *self {
+            Resource::Horses => 20,
+            Resource::Iron => 60,
+        }
+    }
+
+    /// Returns whether the resource can lie on tiles of `terrain`.
+    pub fn can_lie_on(&self, terrain: Terrain) -> bool {
+        match *self {
+            Resource::Horses => terrain == Terrain::Grassland || terrain == Terrain::Plain,
+            Resource::Iron => terrain == Terrain::Hill || terrain == Terrain::Desert,
+        }
+    }
+}
+
+// You would think that it would be simpler for fn tiles() to simply return an enumerated and
+// mapped iterator rather than having this whole struct, right? Think again! There's all kinds
+// of complications when you try to do that (I spent *hours* on this), the fatal one being
+// closure lifetime. Hence, this iterator struct.
+
+pub struct TilesIterator<'a> {
+    map_width: i32,
+    counter: usize,
+    terrain_iter: Iter<'a, Terrain>,
+}
+
+impl<'a> TilesIterator<'a> {
+    pub fn new(terrain_iter: Iter<Terrain>, map_width: i32) -> TilesIterator {
+        TilesIterator {
+            map_width: map_width,
+            counter: 0,
+            terrain_iter: terrain_iter,
+        }
+    }
+}
+
+impl<'a> Iterator for TilesIterator<'a> {
+    type Item = (Pos, Terrain);
+
+    fn next(&mut self) -> Option<(Pos, Terrain)> {
+        match self.terrain_iter.next() {
+            Some(t) => {
+                let index = self.counter as i32;
+                let (y, x) = index.div_rem(&self.map_width);
+                self.counter += 1;
+                Some((OffsetPos::new(x, y).to_pos(), *t))
+            }
+            None => None,
+        }
+    }
+}
+
+/// Map of terrain tiles
+///
+/// top left corner is (0, 0) in offset pos.
+#[derive(Clone)]
+pub struct TerrainMap {
+    width: i32,
+    height: i32,
+    data: Vec<Terrain>, // sequence of rows, then cols. len == width * height.
+    /// River flags of each tile, in the same order as `data`. See `RIVER_EDGES`.
+    rivers: Vec<u8>,
+    /// Feature of each tile, in the same order as `data`.
+    features: Vec<Option<Feature>>,
+    /// Resource of each tile, in the same order as `data`.
+    resources: Vec<Option<Resource>>,
+    grid: HexGrid,
+    /// Tiles that can't be entered, given their terrain and feature.
+    impassable: BitSet,
+    /// Continent of each land tile, in the same order as `data`.
+    continents: Vec<Option<u16>>,
+}
+
+impl TerrainMap {
+    pub fn new(width: i32, height: i32, data: Vec<Terrain>) -> TerrainMap {
+        if data.len() != (width * height) as usize {
+            panic!("Inconsistent TerrainMap data");
+        }
+        let len = data.len();
+        let mut impassable = BitSet::new(len);
+        for (i, t) in data.iter().enumerate() {
+            impassable.set(i, !t.is_passable());
+        }
+        let mut result = TerrainMap {
+            width: width,
+            height: height,
+            data: data,
+            rivers: vec![0; len],
+            features: vec![None; len],
+            resources: vec![None; len],
+            grid: HexGrid::new(width, height),
+            impassable: impassable,
+            continents: vec![None; len],
+        };
+        result.label_continents();
+        result
+    }
+
+    /// Creates a map filled with grassland.
+    ///
+    /// Useful for testing.
+    pub fn empty_map(width: i32, height: i32) -> TerrainMap {
+        TerrainMap::new(width,
+                        height,
+                        vec![Terrain::Grassland; (width * height) as usize])
+    }
+
+    /// Loads terrain map from text file.
+    ///
+    /// The file is a series of lines of the same length, each character representing a terrain
+    /// tile. That character is defined by `Terrain.map_char()`.
+    ///
+    /// If the character can't be recognized, it defaults as Ocean.
+    ///
+    /// Panics if anything goes wrong.
+    pub fn fromfile(path: &Path) -> TerrainMap {
+        let fp = File::open(path).unwrap();
+        let mut width: Option<i32> = None;
+        let mut chcount: i32 = 0;
+        let allterrain = Terrain::all();
+        let char2terrain = HashMap::<char, &Terrain>::from_iter(allterrain.iter().map(|t| {
+            (t.map_char(), t)
+        }));
+        let mut data: Vec<Terrain> = Vec::new();
+        for byte in fp.bytes() {
+            let ch = match byte {
+                Ok(ch) => ch as char,
+                Err(_) => break,
+            };
+            if ch == '\n' {
+                match width {
+                    Some(w) => assert!(chcount == w),
+                    None => width = Some(data.len() as i32),
+                }
+                chcount = 0;
+            } else {
+                chcount += 1;
+                match char2terrain.get(&ch) {
+                    Some(t) => data.push(**t),
+                    None => data.push(Terrain::Ocean),
+                };
+            }
+        }
+        let height = (data.len() as i32) / width.unwrap();
+        TerrainMap::new(width.unwrap(), height, data)
+    }
+
+    /// Returns the map as text, in the format `fromfile()` reads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{Terrain, TerrainMap};
+    ///
+    /// let map = TerrainMap::new(2, 2, vec![Terrain::Hill, Terrain::Coast,
+    ///                                      Terrain::Plain, Terrain::Mountain]);
+    /// assert_eq!(map.to_text(), "^~\n'A\n");
+    /// ```
+    pub fn to_text(&self) -> String {
+        let mut result = String::new();
+        for row in self.data.chunks(self.width as usize) {
+            result.extend(row.iter().map(|t| t.map_char()));
+            result.push('\n');
+        }
+        result
+    }
+
+    /// Writes the map in a text file that `fromfile()` can load back.
+    pub fn to_file(&self, path: &Path) -> io::Result<()> {
+        let mut fp = try!(File::create(path));
+        fp.write_all(self.to_text().as_bytes())
+    }
+
+    pub fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    /// Returns the tiles of the map as flat indices, which are also the indices of our data.
+    pub fn grid(&self) -> &HexGrid {
+        &self.grid
+    }
+
+    /// Returns the index of `pos` in our data, or `None` if it's out of bounds.
+    fn index(&self, pos: Pos) -> Option<usize> {
+        self.grid.index(pos)
+    }
+
+    fn pos_at(&self, index: usize) -> Pos {
+        self.grid.pos(index)
+    }
+
+    fn update_passability(&mut self, index: usize) {
+        let passable = self.data[index].is_passable() &&
+                       self.features[index].map_or(true, |f| f.is_passable());
+        self.impassable.set(index, !passable);
+    }
+
+    /// Returns tiles that can't be entered, indexed like our tiles.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{TerrainMap, Terrain, Feature};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut map = TerrainMap::empty_map(2, 2);
+    /// assert_eq!(map.impassable().count(), 0);
+    /// map.set_terrain(Pos::origin(), Terrain::Mountain);
+    /// assert!(map.impassable().contains(0));
+    /// map.set_terrain(Pos::origin(), Terrain::Plain);
+    /// map.set_feature(Pos::origin(), Some(Feature::Ice));
+    /// assert!(!map.is_passable(Pos::origin()));
+    /// map.set_feature(Pos::origin(), None);
+    /// assert!(map.is_passable(Pos::origin()));
+    /// ```
+    pub fn impassable(&self) -> &BitSet {
+        &self.impassable
+    }
+
+    /// Numbers the continents of the map, in the order of their first tile.
+    fn label_continents(&mut self) {
+        let mut continents = vec![None; self.data.len()];
+        let mut count = 0;
+        for start in 0..self.data.len() {
+            if continents[start].is_some() || self.data[start].is_water() {
+                continue;
+            }
+            continents[start] = Some(count);
+            let mut frontier = vec![start];
+            while let Some(i) = frontier.pop() {
+                for neighbor in self.grid.neighbors(i).iter() {
+                    if let Some(ni) = *neighbor {
+                        if continents[ni].is_none() && !self.data[ni].is_water() {
+                            continents[ni] = Some(count);
+                            frontier.push(ni);
+                        }
+                    }
+                }
+            }
+            count += 1;
+        }
+        self.continents = continents;
+    }
+
+    /// Returns the continent of the tile at `pos`, or `None` if it's water or out of the map.
+    ///
+    /// Continents are land tiles connected to each other, mountains included. They're numbered
+    /// from 0, in the order in which their first tile comes on the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{Terrain, TerrainMap};
+    /// use civng_core::hexpos::OffsetPos;
+    ///
+    /// let mut map = TerrainMap::new(5, 1, vec![Terrain::Plain; 5]);
+    /// let pos = |x| OffsetPos::new(x, 0).to_pos();
+    /// assert_eq!(map.continent_at(pos(4)), Some(0));
+    /// map.set_terrain(pos(2), Terrain::Coast);
+    /// assert_eq!(map.continent_at(pos(2)), None);
+    /// assert_eq!(map.continent_at(pos(4)), Some(1));
+    /// map.set_terrain(pos(2), Terrain::Mountain);
+    /// assert_eq!(map.continent_at(pos(4)), Some(0));
+    /// assert_eq!(map.continent_at(pos(5)), None);
+    /// ```
+    pub fn continent_at(&self, pos: Pos) -> Option<u16> {
+        self.index(pos).and_then(|i| self.continents[i])
+    }
+
+    /// Returns the connected bodies of water of the map, as lists of tile indexes.
+    fn water_bodies(&self) -> Vec<Vec<usize>> {
+        let mut seen = vec![false; self.data.len()];
+        let mut result = Vec::new();
+        for start in 0..self.data.len() {
+            if seen[start] || !self.data[start].is_water() {
+                continue;
+            }
+            seen[start] = true;
+            let mut body = Vec::new();
+            let mut frontier = vec![start];
+            while let Some(i) = frontier.pop() {
+                body.push(i);
+                for neighbor in self.grid.neighbors(i).iter() {
+                    if let Some(ni) = *neighbor {
+                        if !seen[ni] && self.data[ni].is_water() {
+                            seen[ni] = true;
+                            frontier.push(ni);
+                        }
+                    }
+                }
+            }
+            result.push(body);
+        }
+        result
+    }
+
+    /// Turns water bodies smaller than `LAKE_MAX_SIZE` into lakes, leaving other water as it is.
+    pub fn mark_lakes(&mut self) {
+        for body in self.water_bodies() {
+            if body.len() < LAKE_MAX_SIZE {
+                for i in body {
+                    self.data[i] = Terrain::Lake;
+                }
+            }
+        }
+    }
+
+    /// Decides what kind of water each water tile is.
+    ///
+    /// Small water bodies are lakes. In other bodies, tiles next to land are coast and the rest
+    /// is ocean. This is for maps which only know where water is, such as generated ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{Terrain, TerrainMap};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut map = TerrainMap::new(12, 1, vec![Terrain::Ocean; 12]);
+    /// map.set_terrain(Pos::origin(), Terrain::Plain);
+    /// map.classify_water();
+    /// assert_eq!(map.to_text(), "'~==========\n");
+    /// let mut map = TerrainMap::new(3, 1, vec![Terrain::Ocean, Terrain::Hill, Terrain::Ocean]);
+    /// map.classify_water();
+    /// assert_eq!(map.to_text(), "_^_\n");
+    /// ```
+    pub fn classify_water(&mut self) {
+        for body in self.water_bodies() {
+            let is_lake = body.len() < LAKE_MAX_SIZE;
+            for i in body {
+                let near_land = self.pos_at(i).around().iter().any(|p| {
+                    let t = self.get_terrain(*p);
+                    t != Terrain::OutOfBounds && !t.is_water()
+                });
+                self.data[i] = if is_lake {
+                    Terrain::Lake
+                } else if near_land {
+                    Terrain::Coast
+                } else {
+                    Terrain::Ocean
+                };
+            }
+        }
+    }
+
+    /// Returns terrain at a particular pos.
+    ///
+    /// We take care of converting `Pos` into `OffsetPos`. If out of bounds, returns OutOfBounds.
+    pub fn get_terrain(&self, pos: Pos) -> Terrain {
+        match self.index(pos) {
+            Some(i) => self.data[i],
+            None => Terrain::OutOfBounds,
+        }
+    }
+
+    /// Changes terrain at `pos`. Does nothing if `pos` is out of bounds.
+    pub fn set_terrain(&mut self, pos: Pos, terrain: Terrain) {
+        if let Some(i) = self.index(pos) {
+            let was_water = self.data[i].is_water();
+            self.data[i] = terrain;
+            self.update_passability(i);
+            if terrain.is_water() != was_water {
+                self.label_continents();
+            }
+        }
+    }
+
+    /// Returns whether a river runs along any edge of the tile at `pos`.
+    pub fn has_river(&self, pos: Pos) -> bool {
+        pos.around().iter().any(|&p| self.has_river_between(pos, p))
+    }
+
+    /// Returns whether a river runs along the edge between the adjacent tiles at `a` and `b`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::hexpos::{OffsetPos, Direction};
+    ///
+    /// let mut map = TerrainMap::empty_map(3, 3);
+    /// let pos = OffsetPos::new(1, 1).to_pos();
+    /// map.set_river(pos, Direction::North, true);
+    /// assert!(map.has_river_between(pos.neighbor(Direction::North), pos));
+    /// assert!(!map.has_river_between(pos, pos.neighbor(Direction::South)));
+    /// assert!(map.has_river(pos) && map.has_river(pos.neighbor(Direction::North)));
+    /// assert!(!map.has_river(pos.neighbor(Direction::South)));
+    /// ```
+    pub fn has_river_between(&self, a: Pos, b: Pos) -> bool {
+        for (bit, &direction) in RIVER_EDGES.iter().enumerate() {
+            if a.neighbor(direction) == b {
+                return self.river_flags(a) & 1 << bit != 0;
+            } else if b.neighbor(direction) == a {
+                return self.river_flags(b) & 1 << bit != 0;
+            }
+        }
+        false
+    }
+
+    /// Adds or removes a river along the `direction` edge of the tile at `pos`.
+    ///
+    /// Does nothing if the tile keeping track of that edge is out of bounds.
+    pub fn set_river(&mut self, pos: Pos, direction: Direction, river: bool) {
+        for (bit, &edge) in RIVER_EDGES.iter().enumerate() {
+            let owner = if edge == direction {
+                pos
+            } else if edge.opposite() == direction {
+                pos.neighbor(direction)
+            } else {
+                continue;
+            };
+            if let Some(i) = self.index(owner) {
+                if river {
+                    self.rivers[i] |= 1 << bit;
+                } else {
+                    self.rivers[i] &= !(1 << bit);
+                }
+            }
+        }
+    }
+
+    /// Returns the river flags of the tile at `pos`, whose bit `i` tells whether a river runs
+    /// along its `RIVER_EDGES[i]` edge.
+    pub fn river_flags(&self, pos: Pos) -> u8 {
+        self.index(pos).map_or(0, |i| self.rivers[i])
+    }
+
+    /// Sets the river flags of the tile at `pos`. Does nothing if `pos` is out of bounds.
+    pub fn set_river_flags(&mut self, pos: Pos, flags: u8) {
+        if let Some(i) = self.index(pos) {
+            self.rivers[i] = flags;
+        }
+    }
+
+    /// Returns the natural feature of the tile at `pos`, if any.
+    pub fn get_feature(&self, pos: Pos) -> Option<Feature> {
+        self.index(pos).and_then(|i| self.features[i])
+    }
+
+    /// Changes the feature of the tile at `pos`. Does nothing if `pos` is out of bounds.
+    pub fn set_feature(&mut self, pos: Pos, feature: Option<Feature>) {
+        if let Some(i) = self.index(pos) {
+            self.features[i] = feature;
+            self.update_passability(i);
+        }
+    }
+
+    /// Returns whether the tile at `pos` is rough: hills, mountains or a forest, jungle or marsh.
+    /// Other tiles are open terrain.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{Feature, Terrain, TerrainMap};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut map = TerrainMap::empty_map(2, 2);
+    /// assert!(!map.is_rough(Pos::origin()));
+    /// map.set_feature(Pos::origin(), Some(Feature::Forest));
+    /// assert!(map.is_rough(Pos::origin()));
+    /// map.set_feature(Pos::origin(), None);
+    /// map.set_terrain(Pos::origin(), Terrain::Hill);
+    /// assert!(map.is_rough(Pos::origin()));
+    /// ```
+    pub fn is_rough(&self, pos: Pos) -> bool {
+        match self.get_feature(pos) {
+            Some(Feature::Forest) | Some(Feature::Jungle) | Some(Feature::Marsh) => true,
+            _ => {
+                match self.get_terrain(pos) {
+                    Terrain::Hill | Terrain::Mountain => true,
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    /// Returns the resource of the tile at `pos`, if any.
+    pub fn get_resource(&self, pos: Pos) -> Option<Resource> {
+        self.index(pos).and_then(|i| self.resources[i])
+    }
+
+    /// Changes the resource of the tile at `pos`. Does nothing if `pos` is out of bounds.
+    pub fn set_resource(&mut self, pos: Pos, resource: Option<Resource>) {
+        if let Some(i) = self.index(pos) {
+            self.resources[i] = resource;
+        }
+    }
+
+    /// Returns whether our units can enter the tile at `pos`, given its terrain and feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{Feature, TerrainMap};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut map = TerrainMap::empty_map(2, 2);
+    /// assert!(map.is_passable(Pos::origin()));
+    /// map.set_feature(Pos::origin(), Some(Feature::MountainRange));
+    /// assert!(!map.is_passable(Pos::origin()));
+    /// ```
+    pub fn is_passable(&self, pos: Pos) -> bool {
+        self.index(pos).map_or(false, |i| !self.impassable.contains(i))
+    }
+
+    /// Returns how high the tile at `pos` rises when it comes to blocking sight.
+    ///
+    /// This is the height of its terrain, raised by forests, jungles and mountain ranges.
+    pub fn sight_height(&self, pos: Pos) -> u8 {
+        self.get_terrain(pos).height() + self.get_feature(pos).map_or(0, |f| f.height())
+    }
+
+    /// Returns what the tile at `pos` yields given its terrain, feature and river.
+    ///
+    /// Features replace the yield of the terrain, while rivers add gold on top of either.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::{Feature, TerrainMap, Yield};
+    /// use civng_core::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = TerrainMap::empty_map(2, 2);
+    /// assert_eq!(map.yield_at(Pos::origin()), Yield::new(2, 0, 0));
+    /// map.set_river(Pos::origin(), Direction::SouthEast, true);
+    /// assert_eq!(map.yield_at(Pos::origin()), Yield::new(2, 0, 1));
+    /// map.set_feature(Pos::origin(), Some(Feature::Forest));
+    /// assert_eq!(map.yield_at(Pos::origin()), Yield::new(1, 1, 1));
+    /// map.set_river(Pos::origin(), Direction::SouthEast, false);
+    /// assert_eq!(map.yield_at(Pos::origin()), Yield::new(1, 1, 0));
+    /// map.set_feature(Pos::origin(), Some(Feature::Ice));
+    /// assert_eq!(map.yield_at(Pos::origin()), Yield::new(0, 0, 0));
+    /// ```
+    pub fn yield_at(&self, pos: Pos) -> Yield {
+        let base = match self.get_feature(pos) {
+            Some(feature) => feature.yields(),
+            None => self.get_terrain(pos).yields(),
+        };
+        if self.has_river(pos) {
+            base + Yield::new(0, 0, RIVER_GOLD)
+        } else {
+            base
+        }
+    }
+
+    pub fn tiles(&self) -> TilesIterator {
+        TilesIterator::new(self.data.iter(), self.width)
+    }
+
+    pub fn movement_cost(&self, path: &PosPath) -> u8 {
+        path.stack()[1..].iter().fold(0, |acc, &p| acc + self.get_terrain(p).movement_cost())
+    }
+}
+
+/// A map is serialized as its size and its tiles, in rows. Passability and continents are
+/// computed again when it's deserialized.
+///
+/// # Examples
+///
+/// ```
+/// extern crate serde_json;
+/// extern crate civng_core;
+///
+/// use civng_core::terrain::{Terrain, Feature, TerrainMap};
+/// use civng_core::hexpos::{Direction, OffsetPos};
+///
+/// # fn main() {
+/// let mut map = TerrainMap::empty_map(2, 1);
+/// let pos = OffsetPos::new(1, 0).to_pos();
+/// map.set_terrain(pos, Terrain::Hill);
+/// map.set_feature(pos, Some(Feature::MountainRange));
+/// map.set_river(pos, Direction::South, true);
+/// let json = serde_json::to_string(&map).unwrap();
+/// assert!(json.starts_with("{\"width\":2,\"height\":1,\"terrain\":[\"Grassland\",\"Hill\"]"));
+/// let copy: TerrainMap = serde_json::from_str(&json).unwrap();
+/// assert!(copy.get_terrain(pos) == Terrain::Hill);
+/// assert!(copy.has_river(pos));
+/// assert!(!copy.is_passable(pos));
+/// let bad = json.replace("\"width\":2", "\"width\":3");
+/// assert!(serde_json::from_str::<TerrainMap>(&bad).is_err());
+/// # }
+/// ```
+impl Serialize for TerrainMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = try!(serializer.serialize_struct("TerrainMap", 6));
+        try!(state.serialize_field("width", &self.width));
+        try!(state.serialize_field("height", &self.height));
+        try!(state.serialize_field("terrain", &self.data));
+        try!(state.serialize_field("rivers", &self.rivers));
+        try!(state.serialize_field("features", &self.features));
+        try!(state.serialize_field("resources", &self.resources));
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for TerrainMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<TerrainMap, D::Error> {
+        #[derive(Deserialize)]
+        struct Tiles {
+            width: i32,
+            height: i32,
+            terrain: Vec<Terrain>,
+            rivers: Vec<u8>,
+            features: Vec<Option<Feature>>,
+            resources: Vec<Option<Resource>>,
+        }
+
+        let tiles = try!(Tiles::deserialize(deserializer));
+        let len = tiles.terrain.len();
+        if tiles.width < 0 || tiles.height < 0 || len != (tiles.width * tiles.height) as usize ||
+           tiles.rivers.len() != len || tiles.features.len() != len ||
+           tiles.resources.len() != len {
+            return Err(de::Error::custom("Inconsistent TerrainMap size"));
+        }
+        let mut result = TerrainMap::new(tiles.width, tiles.height, tiles.terrain);
+        result.rivers = tiles.rivers;
+        result.features = tiles.features;
+        result.resources = tiles.resources;
+        for i in 0..len {
+            result.update_passability(i);
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,1319 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Unit management logic.
+
+use std::cmp::min;
+use std::collections::{HashMap, HashSet};
+
+use combat::CombatStats;
+use hexpos::Pos;
+use terrain::Resource;
+use rules::Ruleset;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de;
+use serde::ser::SerializeStruct;
+
+pub type UnitID = usize;
+
+/// HP of a unit at full health.
+pub const MAX_HP: u8 = 100;
+/// HP a unit heals at the start of a turn after spending the previous one without moving in
+/// neutral territory, unless house rules say otherwise.
+pub const HEAL_RATE: u8 = 10;
+/// HP a resting unit heals in the territory of its owner.
+pub const FRIENDLY_HEAL_RATE: u8 = 15;
+/// HP a resting unit heals in one of its owner's cities.
+pub const CITY_HEAL_RATE: u8 = 20;
+/// HP a resting unit heals in the territory of a player it's at war with. Only medics help there.
+pub const ENEMY_HEAL_RATE: u8 = 0;
+/// HP units next to a unit with `Promotion::Medic`, and the medic itself, heal on top of the rest.
+pub const MEDIC_HEAL: u8 = 5;
+/// XP units surviving a combat earn.
+pub const COMBAT_XP: u16 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Player {
+    Me,
+    /// Computer-controlled player, numbered from 0.
+    AI(u8),
+}
+
+impl Player {
+    /// Name of the player in save and scenario files: `Me` or `AI<n>`.
+    pub fn name(&self) -> String {
+        match *self {
+            Player::Me => "Me".to_owned(),
+            Player::AI(n) => format!("AI{}", n),
+        }
+    }
+
+    /// Returns the player named `name`, as returned by `name()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::Player;
+    ///
+    /// assert!(Player::from_name("AI2") == Some(Player::AI(2)));
+    /// assert!(Player::from_name("Me") == Some(Player::Me));
+    /// assert!(Player::from_name("NotMe").is_none());
+    /// ```
+    pub fn from_name(name: &str) -> Option<Player> {
+        match name {
+            "Me" => Some(Player::Me),
+            s if s.starts_with("AI") => s[2..].parse().ok().map(Player::AI),
+            _ => None,
+        }
+    }
+
+    /// Returns `b` if we're `a`, `a` if we're `b` and ourselves otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::Player;
+    ///
+    /// assert!(Player::Me.swapped(Player::Me, Player::AI(0)) == Player::AI(0));
+    /// assert!(Player::AI(0).swapped(Player::Me, Player::AI(0)) == Player::Me);
+    /// assert!(Player::AI(1).swapped(Player::Me, Player::AI(0)) == Player::AI(1));
+    /// ```
+    pub fn swapped(self, a: Player, b: Player) -> Player {
+        if self == a {
+            b
+        } else if self == b {
+            a
+        } else {
+            self
+        }
+    }
+}
+
+/// A player is serialized as its name.
+///
+/// # Examples
+///
+/// ```
+/// extern crate serde_json;
+/// extern crate civng_core;
+///
+/// use civng_core::unit::Player;
+///
+/// # fn main() {
+/// assert_eq!(serde_json::to_string(&Player::AI(2)).unwrap(), "\"AI2\"");
+/// assert!(serde_json::from_str::<Player>("\"Me\"").unwrap() == Player::Me);
+/// assert!(serde_json::from_str::<Player>("\"NotMe\"").is_err());
+/// # }
+/// ```
+impl Serialize for Player {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Player {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Player, D::Error> {
+        let name = try!(String::deserialize(deserializer));
+        Player::from_name(&name)
+            .ok_or_else(|| de::Error::custom(format!("Invalid player: {}", name)))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UnitType {
+    Melee,
+    Ranged,
+    /// Civilian unit clearing features off tiles.
+    Worker,
+    /// Fast melee unit needing `Resource::Horses`.
+    Horseman,
+    /// Strong melee unit needing `Resource::Iron`.
+    Swordsman,
+    /// Weak recon unit slipping through zones of control.
+    Scout,
+    /// Civilian unit founding a new city.
+    Settler,
+}
+
+impl UnitType {
+    pub fn all() -> [UnitType; 7] {
+        [UnitType::Melee,
+         UnitType::Ranged,
+         UnitType::Worker,
+         UnitType::Horseman,
+         UnitType::Swordsman,
+         UnitType::Scout,
+         UnitType::Settler]
+    }
+
+    pub fn map_symbol(&self) -> char {
+        match *self {
+            UnitType::Melee => 'M',
+            UnitType::Ranged => 'R',
+            UnitType::Worker => 'W',
+            UnitType::Horseman => 'H',
+            UnitType::Swordsman => 'S',
+            UnitType::Scout => 'C',
+            UnitType::Settler => 'T',
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match *self {
+            UnitType::Melee => "Melee",
+            UnitType::Ranged => "Ranged",
+            UnitType::Worker => "Worker",
+            UnitType::Horseman => "Horseman",
+            UnitType::Swordsman => "Swordsman",
+            UnitType::Scout => "Scout",
+            UnitType::Settler => "Settler",
+        }
+    }
+
+    pub fn strength(&self) -> u8 {
+        match *self {
+            UnitType::Melee => 8,
+            UnitType::Ranged => 5,
+            UnitType::Worker | UnitType::Settler => 0,
+            UnitType::Horseman => 10,
+            UnitType::Swordsman => 12,
+            UnitType::Scout => 4,
+        }
+    }
+
+    pub fn ranged_strength(&self) -> u8 {
+        match *self {
+            UnitType::Ranged => 7,
+            _ => 0,
+        }
+    }
+
+    pub fn movements_per_turn(&self) -> u8 {
+        match *self {
+            UnitType::Horseman => 3,
+            _ => 2,
+        }
+    }
+
+    /// Production it takes a city to train the unit.
+    pub fn cost(&self) -> u16 {
+        match *self {
+            UnitType::Melee | UnitType::Ranged => 40,
+            UnitType::Worker => 50,
+            UnitType::Scout => 25,
+            UnitType::Settler => 80,
+            UnitType::Horseman | UnitType::Swordsman => 60,
+        }
+    }
+
+    pub fn range(&self) -> u8 {
+        match *self {
+            UnitType::Ranged => 2,
+            _ => 0,
+        }
+    }
+
+    /// Strategic resource the unit needs, to be trained and to fight at full strength.
+    pub fn required_resource(&self) -> Option<Resource> {
+        match *self {
+            UnitType::Horseman => Some(Resource::Horses),
+            UnitType::Swordsman => Some(Resource::Iron),
+            _ => None,
+        }
+    }
+
+    pub fn is_ranged(&self) -> bool {
+        self.ranged_strength() > 0
+    }
+
+    /// Whether units of that type are skirmishers, which can withdraw before melee attacks.
+    pub fn withdraws(&self) -> bool {
+        *self == UnitType::Horseman
+    }
+
+    /// Whether the unit can't fight. Civilians never attack and they don't stand a chance when
+    /// they're attacked.
+    pub fn is_civilian(&self) -> bool {
+        self.strength() == 0
+    }
+
+    /// Whether units of that type move through zones of control as if they weren't there.
+    pub fn ignores_zoc(&self) -> bool {
+        *self == UnitType::Scout
+    }
+
+    /// Distance, in tiles, at which the unit sees other units.
+    pub fn sight(&self) -> u8 {
+        2
+    }
+
+    /// Class of the unit, which combat bonuses against other units can target.
+    pub fn class(&self) -> UnitClass {
+        match *self {
+            UnitType::Melee | UnitType::Swordsman => UnitClass::Infantry,
+            UnitType::Horseman => UnitClass::Mounted,
+            UnitType::Ranged => UnitClass::Archery,
+            UnitType::Worker | UnitType::Settler => UnitClass::Civilian,
+            UnitType::Scout => UnitClass::Recon,
+        }
+    }
+}
+
+/// Group of unit types fighting the same way.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UnitClass {
+    Infantry,
+    Mounted,
+    Archery,
+    Civilian,
+    Recon,
+}
+
+impl UnitClass {
+    pub fn all() -> [UnitClass; 5] {
+        [UnitClass::Infantry,
+         UnitClass::Mounted,
+         UnitClass::Archery,
+         UnitClass::Civilian,
+         UnitClass::Recon]
+    }
+
+    /// Name of the class in content files.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            UnitClass::Infantry => "Infantry",
+            UnitClass::Mounted => "Mounted",
+            UnitClass::Archery => "Archery",
+            UnitClass::Civilian => "Civilian",
+            UnitClass::Recon => "Recon",
+        }
+    }
+
+    /// Returns the class named `name`, as returned by `name()`.
+    pub fn from_name(name: &str) -> Option<UnitClass> {
+        UnitClass::all().iter().find(|c| c.name() == name).cloned()
+    }
+}
+
+/// Standing order keeping a unit busy over several turns.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Order {
+    /// The unit stays put until an enemy unit comes in sight.
+    Sentry,
+    /// The unit stays put and gets a defense bonus.
+    Fortify,
+    /// The unit stays fortified until it's back to full health.
+    Heal,
+    /// The unit heads for unexplored tiles every turn until there's nothing left to explore or an
+    /// enemy unit comes in sight.
+    Explore,
+    /// The unit heads for a position every turn until it gets there or an enemy unit comes in
+    /// sight.
+    GoTo(Pos),
+    /// The unit escorts another unit, moving along to stay next to it.
+    Follow(UnitID),
+    /// The unit walks back and forth between two positions, heading for the second one, until an
+    /// enemy unit comes in sight.
+    Patrol(Pos, Pos),
+    /// The unit removes the feature of its tile, which takes the given number of turns of work.
+    Clear(u8),
+}
+
+impl Order {
+    /// Orders that don't carry any data, which are the ones `from_name()` can return.
+    pub fn all() -> [Order; 4] {
+        [Order::Sentry, Order::Fortify, Order::Heal, Order::Explore]
+    }
+
+    /// Name of the order in save files.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Order::Sentry => "sentry",
+            Order::Fortify => "fortify",
+            Order::Heal => "heal",
+            Order::Explore => "explore",
+            Order::GoTo(_) => "goto",
+            Order::Follow(_) => "follow",
+            Order::Patrol(_, _) => "patrol",
+            Order::Clear(_) => "clear",
+        }
+    }
+
+    /// Describes the state of a unit under that order.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            Order::Sentry => "Sentry",
+            Order::Fortify => "Fortified",
+            Order::Heal => "Healing",
+            Order::Explore => "Exploring",
+            Order::GoTo(_) => "Moving",
+            Order::Follow(_) => "Escorting",
+            Order::Patrol(_, _) => "Patrolling",
+            Order::Clear(_) => "Clearing",
+        }
+    }
+
+    /// Returns the order named `name`, as returned by `name()`.
+    pub fn from_name(name: &str) -> Option<Order> {
+        Order::all().iter().find(|o| o.name() == name).cloned()
+    }
+}
+
+/// Permanent improvement a unit can pick when it earned enough experience.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Promotion {
+    /// The unit heals even on turns it moved or fought.
+    March,
+    /// Friendly units next to the unit, and the unit itself, heal faster.
+    Medic,
+    /// The unit defends better against ranged attacks.
+    Cover,
+    /// The unit fights better in open terrain.
+    Shock,
+    /// The unit fights better in rough terrain (see `TerrainMap::is_rough()`).
+    Drill,
+    /// The unit can withdraw before melee attacks, like skirmishers.
+    Withdraw,
+    /// The ranged unit can attack twice per turn.
+    Logistics,
+    /// The melee unit can attack twice per turn.
+    Blitz,
+    /// The unit moves through zones of control as if they weren't there.
+    Mobility,
+}
+
+impl Promotion {
+    pub fn all() -> [Promotion; 9] {
+        [Promotion::March,
+         Promotion::Medic,
+         Promotion::Cover,
+         Promotion::Shock,
+         Promotion::Drill,
+         Promotion::Withdraw,
+         Promotion::Logistics,
+         Promotion::Blitz,
+         Promotion::Mobility]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Promotion::March => "March",
+            Promotion::Medic => "Medic",
+            Promotion::Cover => "Cover",
+            Promotion::Shock => "Shock",
+            Promotion::Drill => "Drill",
+            Promotion::Withdraw => "Withdraw",
+            Promotion::Logistics => "Logistics",
+            Promotion::Blitz => "Blitz",
+            Promotion::Mobility => "Mobility",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match *self {
+            Promotion::March => "Heals every turn, even after moving",
+            Promotion::Medic => "Adjacent units heal 5 more HP per turn",
+            Promotion::Cover => "+33% defense against ranged attacks",
+            Promotion::Shock => "+15% combat strength in open terrain",
+            Promotion::Drill => "+15% combat strength in rough terrain",
+            Promotion::Withdraw => "May withdraw before melee attacks",
+            Promotion::Logistics => "1 additional ranged attack per turn",
+            Promotion::Blitz => "1 additional melee attack per turn",
+            Promotion::Mobility => "Ignores zones of control",
+        }
+    }
+
+    /// Returns the promotion named `name`, as returned by `name()`.
+    pub fn from_name(name: &str) -> Option<Promotion> {
+        Promotion::all().iter().find(|p| p.name() == name).cloned()
+    }
+
+    /// XP a unit needs to earn its promotion number `count + 1`: 10, then 30, 60, 100 and so on.
+    pub fn xp_needed(count: usize) -> u16 {
+        let n = count as u16 + 1;
+        10 * n * (n + 1) / 2
+    }
+}
+
+/// A unit on a map.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Unit {
+    id: UnitID,
+    /// Type of the unit
+    type_: UnitType,
+    /// Position on the map
+    pos: Pos,
+    /// Movement points left this turn
+    movements: u8,
+    hp: u8,
+    /// Experience of the unit
+    xp: u16,
+    /// Promotions the unit picked, in order.
+    promotions: Vec<Promotion>,
+    /// Player the unit belongs to
+    owner: Player,
+    /// Standing orders of the unit, carried out one after the other.
+    orders: Vec<Order>,
+    /// Whether the unit was told to do nothing for the rest of the turn.
+    skipped: bool,
+    /// Whether the unit was pushed back to the end of the activation queue this turn.
+    waiting: bool,
+    /// Number of attacks the unit made this turn.
+    attacks: u8,
+    /// Name we gave the unit, if any.
+    custom_name: Option<String>,
+}
+
+impl Unit {
+    pub fn new(type_: UnitType, owner: Player, pos: Pos) -> Unit {
+        Unit {
+            id: 0, // set in Units::add_unit()
+            type_: type_,
+            pos: pos,
+            movements: 0,
+            hp: MAX_HP,
+            xp: 0,
+            promotions: Vec::new(),
+            owner: owner,
+            orders: Vec::new(),
+            skipped: false,
+            waiting: false,
+            attacks: 0,
+            custom_name: None,
+        }
+    }
+
+    /// Creates a unit with specific movements and HP, for example when loading a saved game.
+    pub fn with_state(type_: UnitType, owner: Player, pos: Pos, movements: u8, hp: u8) -> Unit {
+        let mut result = Unit::new(type_, owner, pos);
+        result.movements = movements;
+        result.hp = hp;
+        result
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn type_(&self) -> UnitType {
+        self.type_
+    }
+
+    pub fn pos(&self) -> Pos {
+        self.pos
+    }
+
+    pub fn movements(&self) -> u8 {
+        self.movements
+    }
+
+    pub fn strength(&self) -> u8 {
+        self.type_.strength()
+    }
+
+    pub fn hp(&self) -> u8 {
+        self.hp
+    }
+
+    pub fn xp(&self) -> u16 {
+        self.xp
+    }
+
+    pub fn add_xp(&mut self, amount: u16) {
+        self.xp += amount;
+    }
+
+    pub fn promotions(&self) -> &[Promotion] {
+        &self.promotions[..]
+    }
+
+    pub fn has_promotion(&self, promotion: Promotion) -> bool {
+        self.promotions.contains(&promotion)
+    }
+
+    /// Whether the unit can withdraw before melee attacks, as a skirmisher or by promotion.
+    pub fn can_withdraw(&self) -> bool {
+        self.type_.withdraws() || self.has_promotion(Promotion::Withdraw)
+    }
+
+    /// Whether the unit moves through zones of control as if they weren't there, because of its
+    /// type or of `Promotion::Mobility`.
+    ///
+    /// `Ruleset::zoc_exemptions` decides whether this is honored on a map.
+    pub fn ignores_zoc(&self) -> bool {
+        self.type_.ignores_zoc() || self.has_promotion(Promotion::Mobility)
+    }
+
+    /// Whether the unit earned enough XP for a new promotion it didn't pick yet.
+    ///
+    /// Civilians are never promoted.
+    pub fn can_promote(&self) -> bool {
+        !self.type_.is_civilian() && self.promotions.len() < Promotion::all().len() &&
+        self.xp >= Promotion::xp_needed(self.promotions.len())
+    }
+
+    /// Promotions the unit can pick right now. Empty if it can't be promoted.
+    ///
+    /// Skirmishers already withdraw, so they can't pick `Promotion::Withdraw`, nor can recon units
+    /// pick `Promotion::Mobility`. Only ranged units can pick `Promotion::Logistics` and only melee
+    /// ones `Promotion::Blitz`.
+    pub fn available_promotions(&self) -> Vec<Promotion> {
+        if !self.can_promote() {
+            return Vec::new();
+        }
+        let ranged = self.type_.is_ranged();
+        Promotion::all()
+            .iter()
+            .filter(|p| !self.has_promotion(**p))
+            .filter(|p| **p != Promotion::Withdraw || !self.type_.withdraws())
+            .filter(|p| **p != Promotion::Logistics || ranged)
+            .filter(|p| **p != Promotion::Blitz || !ranged)
+            .filter(|p| **p != Promotion::Mobility || !self.type_.ignores_zoc())
+            .cloned()
+            .collect()
+    }
+
+    /// Gives `promotion` to the unit if it's among `available_promotions()`.
+    ///
+    /// Returns whether the unit was promoted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::{Unit, UnitType, Player, Promotion};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut unit = Unit::new(UnitType::Melee, Player::Me, Pos::origin());
+    /// assert!(!unit.promote(Promotion::Shock));
+    /// unit.add_xp(10);
+    /// assert!(unit.promote(Promotion::Shock));
+    /// assert!(unit.has_promotion(Promotion::Shock));
+    /// // The next promotion needs 30 XP.
+    /// unit.add_xp(10);
+    /// assert!(!unit.can_promote());
+    /// unit.add_xp(10);
+    /// assert!(!unit.promote(Promotion::Shock));
+    /// assert!(unit.promote(Promotion::Drill));
+    /// ```
+    pub fn promote(&mut self, promotion: Promotion) -> bool {
+        if self.available_promotions().contains(&promotion) {
+            self.promotions.push(promotion);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the name we gave the unit or, if we didn't, the name of its type.
+    pub fn name(&self) -> &str {
+        match self.custom_name {
+            Some(ref name) => name,
+            None => self.type_.name(),
+        }
+    }
+
+    /// Returns the name we gave the unit, if any.
+    pub fn custom_name(&self) -> Option<&str> {
+        self.custom_name.as_ref().map(|s| &s[..])
+    }
+
+    /// Gives the unit a name of its own. A blank name gives it back the name of its type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut unit = Unit::new(UnitType::Melee, Player::Me, Pos::origin());
+    /// unit.set_name(" Old Guard ");
+    /// assert_eq!(unit.name(), "Old Guard");
+    /// unit.set_name("");
+    /// assert_eq!((unit.name(), unit.custom_name()), ("Melee", None));
+    /// ```
+    pub fn set_name(&mut self, name: &str) {
+        let name = name.trim();
+        self.custom_name = if name.is_empty() {
+            None
+        } else {
+            Some(name.to_owned())
+        };
+    }
+
+    pub fn owner(&self) -> Player {
+        self.owner
+    }
+
+    /// Hands the unit over to `owner`, which cancels its orders.
+    pub fn set_owner(&mut self, owner: Player) {
+        self.owner = owner;
+        self.orders.clear();
+    }
+
+    /// Returns the order the unit is currently carrying out, if any.
+    pub fn order(&self) -> Option<Order> {
+        self.orders.first().cloned()
+    }
+
+    /// Replaces all orders of the unit with `order`, or cancels them if `None`.
+    pub fn set_order(&mut self, order: Option<Order>) {
+        self.orders = order.into_iter().collect();
+    }
+
+    /// All orders of the unit, starting with the current one.
+    pub fn orders(&self) -> &[Order] {
+        &self.orders[..]
+    }
+
+    pub fn set_orders(&mut self, orders: Vec<Order>) {
+        self.orders = orders;
+    }
+
+    /// Adds `order` at the end of the order queue of the unit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::{Unit, UnitType, Player, Order};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut unit = Unit::new(UnitType::Melee, Player::Me, Pos::origin());
+    /// unit.queue_order(Order::GoTo(Pos::origin()));
+    /// unit.queue_order(Order::Fortify);
+    /// assert_eq!(unit.order(), Some(Order::GoTo(Pos::origin())));
+    /// unit.complete_order();
+    /// assert_eq!(unit.order(), Some(Order::Fortify));
+    /// unit.complete_order();
+    /// assert_eq!(unit.order(), None);
+    /// ```
+    pub fn queue_order(&mut self, order: Order) {
+        self.orders.push(order);
+    }
+
+    /// Moves on to the next queued order, if any.
+    pub fn complete_order(&mut self) {
+        if !self.orders.is_empty() {
+            self.orders.remove(0);
+        }
+    }
+
+    /// One letter symbol to represent the unit with on the map.
+    ///
+    /// Usually the first letter od the base unit type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// assert_eq!(Unit::new(UnitType::Melee, Player::Me, Pos::origin()).map_symbol(), 'M');
+    /// ```
+    pub fn map_symbol(&self) -> char {
+        self.type_.map_symbol()
+    }
+
+    /// Whether the unit as exhausted all movement points this turn.
+    pub fn is_exhausted(&self) -> bool {
+        self.movements == 0
+    }
+
+    /// Whether the unit can move this turn, doesn't have a standing order and wasn't skipped.
+    pub fn needs_orders(&self) -> bool {
+        !self.is_exhausted() && self.orders.is_empty() && !self.skipped
+    }
+
+    /// Spends all movement points the unit has left this turn.
+    pub fn exhaust(&mut self) {
+        self.movements = 0;
+    }
+
+    /// Takes the unit off the map for good, like a settler founding a city. It's purged along
+    /// with dead units.
+    pub fn use_up(&mut self) {
+        self.hp = 0;
+        self.movements = 0;
+    }
+
+    /// Makes the unit do nothing for the rest of the turn, keeping its movements.
+    pub fn skip_turn(&mut self) {
+        self.skipped = true;
+    }
+
+    /// Makes the unit wait for other units to be activated before being activated again.
+    pub fn wait(&mut self) {
+        self.waiting = true;
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    /// Number of attacks the unit made this turn.
+    pub fn attacks(&self) -> u8 {
+        self.attacks
+    }
+
+    /// Sets the number of attacks the unit made this turn, for example when loading a saved game.
+    pub fn set_attacks(&mut self, attacks: u8) {
+        self.attacks = attacks;
+    }
+
+    pub fn has_attacked(&self) -> bool {
+        self.attacks > 0
+    }
+
+    /// Number of times the unit can attack in a turn, twice with `Promotion::Logistics` or
+    /// `Promotion::Blitz`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::{Unit, UnitType, Player, Promotion};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut unit = Unit::new(UnitType::Ranged, Player::Me, Pos::origin());
+    /// assert_eq!(unit.attacks_per_turn(), 1);
+    /// unit.add_xp(10);
+    /// // Blitz is for melee units.
+    /// assert!(!unit.promote(Promotion::Blitz));
+    /// assert!(unit.promote(Promotion::Logistics));
+    /// assert_eq!(unit.attacks_per_turn(), 2);
+    /// ```
+    pub fn attacks_per_turn(&self) -> u8 {
+        if self.has_promotion(Promotion::Logistics) || self.has_promotion(Promotion::Blitz) {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Whether the unit has attacks left this turn. It still needs movements to attack.
+    pub fn can_attack(&self) -> bool {
+        self.attacks < self.attacks_per_turn()
+    }
+
+    /// Whether the unit has less than `MAX_HP`.
+    pub fn is_damaged(&self) -> bool {
+        self.hp < MAX_HP
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.hp == 0
+    }
+
+    /// Move `self` in the position `target`.
+    ///
+    /// `cost` is the movement cost of the move, which will be subtracted of the unit's movements.
+    /// Moving cancels the standing orders of the unit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    /// use civng_core::terrain::Terrain;
+    ///
+    /// let mut unit = Unit::new(UnitType::Melee, Player::Me, Pos::origin());
+    /// unit.refresh();
+    /// let newpos = Pos::origin().neighbor(Direction::South);
+    /// unit.move_to(newpos, 1);
+    /// assert_eq!(unit.movements(), 1);
+    /// assert_eq!(unit.pos(), newpos);
+    /// ```
+    pub fn move_to(&mut self, target: Pos, cost: u8) {
+        self.pos = target;
+        self.orders.clear();
+        self.movements -= min(self.movements, cost);
+    }
+
+    /// Counts an attack of the unit. Its last attack of the turn ends its turn if `rules` say so.
+    fn spend_attack(&mut self, rules: &Ruleset) {
+        self.attacks += 1;
+        if rules.attack_ends_movement && !self.can_attack() {
+            self.movements = 0;
+        }
+    }
+
+    /// Makes the unit fresh for a new turn.
+    ///
+    /// That is, regenerates its movement points, lets it attack again and forgets about skip and
+    /// wait orders.
+    pub fn refresh(&mut self) {
+        self.movements = self.type_.movements_per_turn();
+        self.skipped = false;
+        self.waiting = false;
+        self.attacks = 0;
+    }
+}
+
+/// Where a unit heals, as far as borders go.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HealingGround {
+    /// One of the unit owner's cities.
+    City,
+    /// The territory of the unit's owner.
+    Friendly,
+    /// Tiles nobody owns, or owned by a player the unit's owner is at peace with.
+    Neutral,
+    /// The territory of a player the unit's owner is at war with.
+    Enemy,
+}
+
+impl HealingGround {
+    /// HP units resting on that ground heal per turn under `rules`, medics aside.
+    pub fn heal_rate(&self, rules: &Ruleset) -> u8 {
+        match *self {
+            HealingGround::City => rules.city_heal_rate,
+            HealingGround::Friendly => rules.friendly_heal_rate,
+            HealingGround::Neutral => rules.heal_rate,
+            HealingGround::Enemy => rules.enemy_heal_rate,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Units {
+    maxid: UnitID,
+    units: HashMap<UnitID, Unit>,
+    /// Unit brought on top of the others on its tile, by position. It might have moved since.
+    on_top: HashMap<Pos, UnitID>,
+}
+
+impl Units {
+    pub fn new() -> Units {
+        Units {
+            maxid: 0,
+            units: HashMap::new(),
+            on_top: HashMap::new(),
+        }
+    }
+
+    pub fn all_units<'a>(&'a self) -> Box<Iterator<Item = &'a Unit> + 'a> {
+        Box::new(self.units.values().filter(|u| !u.is_dead()))
+    }
+
+    pub fn my_units<'a>(&'a self) -> Box<Iterator<Item = &'a Unit> + 'a> {
+        Box::new(self.all_units().filter(|u| u.owner() == Player::Me))
+    }
+
+    pub fn enemy_units<'a>(&'a self) -> Box<Iterator<Item = &'a Unit> + 'a> {
+        Box::new(self.all_units().filter(|u| u.owner() != Player::Me))
+    }
+
+    /// Returns AI players that still have units, in order.
+    pub fn ai_players(&self) -> Vec<Player> {
+        let mut numbers: Vec<u8> = self.enemy_units()
+                                       .filter_map(|u| {
+                                           match u.owner() {
+                                               Player::AI(n) => Some(n),
+                                               Player::Me => None,
+                                           }
+                                       })
+                                       .collect();
+        numbers.sort();
+        numbers.dedup();
+        numbers.into_iter().map(Player::AI).collect()
+    }
+
+    pub fn add_unit(&mut self, mut unit: Unit) {
+        self.maxid += 1;
+        unit.id = self.maxid;
+        self.units.insert(unit.id, unit);
+    }
+
+    /// Rolls `combat_stats` and applies its outcome.
+    ///
+    /// The attacker spends one of its attacks and, if it was its last one this turn and `rules`
+    /// say so, all its movements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::terrain::TerrainMap;
+    /// use civng_core::map::LiveMap;
+    /// use civng_core::unit::{Unit, UnitType, Player};
+    /// use civng_core::hexpos::{Pos, Direction};
+    /// use civng_core::rules::Ruleset;
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(6, 6));
+    /// let mut rules = Ruleset::new();
+    /// rules.attack_ends_movement = false;
+    /// map.set_rules(rules);
+    /// let target = Pos::vector(Direction::South).amplify(2);
+    /// map.add_unit(Unit::new(UnitType::Ranged, Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), target));
+    /// map.refresh();
+    /// let mut stats = map.bombard_at(1, target).unwrap();
+    /// map.attack(&mut stats);
+    /// assert_eq!(map.units().get(1).movements(), 2);
+    /// assert!(!map.units().get(1).can_attack());
+    /// ```
+    pub fn attack(&mut self, rules: &Ruleset, combat_stats: &mut CombatStats) {
+        let attacker_id = combat_stats.attacker_id;
+        let defender_id = combat_stats.defender_id;
+        combat_stats.roll();
+        if combat_stats.withdrew {
+            // The defender slips away unharmed and the attack is spent.
+            {
+                let defender = self.get_mut(defender_id);
+                defender.pos = combat_stats.withdraw_to.unwrap();
+                defender.orders.clear();
+            }
+            self.get_mut(attacker_id).spend_attack(rules);
+            return;
+        }
+        let defender_pos = {
+            let defender = self.get_mut(defender_id);
+            defender.hp = combat_stats.defender_remaining_hp();
+            if !defender.is_dead() {
+                defender.xp += COMBAT_XP;
+            }
+            defender.pos
+        };
+        {
+            let attacker = self.get_mut(attacker_id);
+            attacker.hp = combat_stats.attacker_remaining_hp();
+            attacker.spend_attack(rules);
+            if !attacker.is_dead() {
+                attacker.xp += COMBAT_XP;
+            }
+            if !combat_stats.ranged && combat_stats.defender_remaining_hp() == 0 {
+                attacker.pos = defender_pos;
+            }
+        }
+    }
+
+    /// Applies the damage of a city striking a unit, after rolling `combat_stats`.
+    pub fn city_strike(&mut self, combat_stats: &mut CombatStats) {
+        combat_stats.roll();
+        self.get_mut(combat_stats.defender_id).hp = combat_stats.defender_remaining_hp();
+    }
+
+    pub fn max_id(&self) -> UnitID {
+        self.maxid
+    }
+
+    /// Returns the smallest id of our units matching `pred` after `after_id`.
+    ///
+    /// If it doesn't exist, we return the smallest id of our units matching `pred`.
+    fn next_unit_matching<F: Fn(&Unit) -> bool>(&self,
+                                                after_id: UnitID,
+                                                pred: F)
+                                                -> Option<UnitID> {
+        let mut result_before = None;
+        let mut result_after = None;
+        for unit in self.my_units() {
+            if pred(unit) {
+                if unit.id() > after_id {
+                    if result_after.is_none() || result_after.unwrap() > unit.id() {
+                        result_after = Some(unit.id());
+                    }
+                } else {
+                    if result_before.is_none() || result_before.unwrap() > unit.id() {
+                        result_before = Some(unit.id());
+                    }
+                }
+            }
+        }
+        result_after.or(result_before)
+    }
+
+    /// Returns the next unit that should be activated after `after_id`.
+    ///
+    /// That unit is the smallest unit needing orders after the `after_id` unit. If it doesn't
+    /// exist, it's the smallest id needing orders. Othewise, we return `None`. Waiting units come
+    /// after all other units needing orders.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use civng_core::unit::{Units, Unit, UnitType, Player};
+    /// use civng_core::hexpos::Pos;
+    /// use civng_core::rules::Ruleset;
+    ///
+    /// let mut units = Units::new();
+    /// for _ in 0..3 {
+    ///     units.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// }
+    /// units.refresh(&Ruleset::new(), &HashMap::new());
+    /// units.get_mut(2).wait();
+    /// units.get_mut(3).skip_turn();
+    /// assert_eq!(units.next_active_unit(1), Some(1));
+    /// assert_eq!(units.next_active_unit(3), Some(1));
+    /// units.get_mut(1).wait();
+    /// assert_eq!(units.next_active_unit(1), Some(2));
+    /// ```
+    pub fn next_active_unit(&self, after_id: UnitID) -> Option<UnitID> {
+        self.next_unit_matching(after_id, |u| u.needs_orders() && !u.is_waiting())
+            .or_else(|| self.next_unit_matching(after_id, |u| u.needs_orders()))
+    }
+
+    /// Cancels the orders of sentries having an enemy unit in sight.
+    ///
+    /// Returns the IDs of units that were woken up.
+    pub fn wake_sentries(&mut self) -> Vec<UnitID> {
+        let woken: Vec<UnitID> = self.all_units()
+                                     .filter(|u| u.order() == Some(Order::Sentry))
+                                     .filter(|u| {
+                                         let sight = u.type_().sight() as i32;
+                                         self.all_units().any(|e| {
+                                             e.owner() != u.owner() &&
+                                             e.pos().distance(u.pos()) <= sight
+                                         })
+                                     })
+                                     .map(|u| u.id())
+                                     .collect();
+        for &unit_id in woken.iter() {
+            self.get_mut(unit_id).orders.clear();
+        }
+        woken
+    }
+
+    /// Returns all live units at `pos`, the one on top first and the others by ID.
+    ///
+    /// Units only share tiles with units of the same player, when house rules let them stack.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_core::unit::{Units, Unit, UnitType, Player};
+    /// use civng_core::hexpos::Pos;
+    ///
+    /// let mut units = Units::new();
+    /// units.add_unit(Unit::new(UnitType::Worker, Player::Me, Pos::origin()));
+    /// units.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// units.add_unit(Unit::new(UnitType::Ranged, Player::Me, Pos::origin()));
+    /// assert_eq!(units.top_at_pos(Pos::origin()).unwrap().id(), 1);
+    /// assert_eq!(units.military_at_pos(Pos::origin()).unwrap().id(), 2);
+    /// units.bring_to_top(3);
+    /// let ids: Vec<_> = units.all_at_pos(Pos::origin()).iter().map(|u| u.id()).collect();
+    /// assert_eq!(ids, vec![3, 1, 2]);
+    /// assert_eq!(units.military_at_pos(Pos::origin()).unwrap().id(), 3);
+    /// ```
+    pub fn all_at_pos(&self, pos: Pos) -> Vec<&Unit> {
+        let mut result: Vec<&Unit> = self.all_units().filter(|u| u.pos() == pos).collect();
+        let top_id = self.on_top.get(&pos).cloned();
+        result.sort_by_key(|u| (Some(u.id()) != top_id, u.id()));
+        result
+    }
+
+    /// Returns the unit on top of the others at `pos`: the one we draw there.
+    pub fn top_at_pos(&self, pos: Pos) -> Option<&Unit> {
+        let top = self.on_top.get(&pos).and_then(|&unit_id| self.find(unit_id));
+        match top {
+            Some(u) if !u.is_dead() && u.pos() == pos => Some(u),
+            _ => self.all_units().filter(|u| u.pos() == pos).min_by_key(|u| u.id()),
+        }
+    }
+
+    /// Returns the military unit at `pos` that comes first in `all_at_pos()`, if any.
+    pub fn military_at_pos(&self, pos: Pos) -> Option<&Unit> {
+        self.all_at_pos(pos).into_iter().find(|u| !u.type_().is_civilian())
+    }
+
+    /// Puts `unit_id` on top of the other units sharing its tile.
+    pub fn bring_to_top(&mut self, unit_id: UnitID) {
+        let pos = self.get(unit_id).pos();
+        self.on_top.insert(pos, unit_id);
+    }
+
+    /// Returns whether `unit` is next to one of its owner's medics, or is one.
+    fn is_near_medic(&self, unit: &Unit) -> bool {
+        self.all_units().any(|u| {
+            u.has_promotion(Promotion::Medic) && u.owner == unit.owner &&
+            u.pos.distance(unit.pos) <= 1
+        })
+    }
+
+    /// Returns the HP `unit_id` heals at the start of next turn on `ground` if it rests until
+    /// then, medics included.
+    pub fn heal_rate(&self, unit_id: UnitID, ground: HealingGround, rules: &Ruleset) -> u8 {
+        let mut heal = ground.heal_rate(rules);
+        if self.is_near_medic(self.get(unit_id)) {
+            heal = heal.saturating_add(rules.medic_heal);
+        }
+        heal
+    }
+
+    /// Returns the HP `unit` heals at the start of a turn, given how it spent the previous one.
+    fn turn_heal(&self,
+                 unit: &Unit,
+                 rules: &Ruleset,
+                 grounds: &HashMap<UnitID, HealingGround>)
+                 -> u8 {
+        let rested = unit.movements == unit.type_.movements_per_turn() && !unit.has_attacked();
+        if rested || unit.has_promotion(Promotion::March) {
+            let ground = grounds.get(&unit.id).cloned().unwrap_or(HealingGround::Neutral);
+            self.heal_rate(unit.id, ground, rules)
+        } else if self.is_near_medic(unit) {
+            rules.medic_heal
+        } else {
+            0
+        }
+    }
+
+    /// Refreshes all units for a new turn and purges dead units from memory.
+    ///
+    /// Units that didn't move or fight last turn heal at the rate of their ground in `grounds`,
+    /// neutral if they're not there, as do units with `Promotion::March`. Units next to one of
+    /// their medics heal `rules.medic_heal` more, even after moving.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use civng_core::unit::{Unit, Units, UnitType, Player, Promotion, HealingGround};
+    /// use civng_core::hexpos::{Pos, Direction};
+    /// use civng_core::rules::Ruleset;
+    ///
+    /// let mut units = Units::new();
+    /// let mut medic = Unit::with_state(UnitType::Melee, Player::Me, Pos::origin(), 2, 50);
+    /// medic.add_xp(10);
+    /// medic.promote(Promotion::Medic);
+    /// units.add_unit(medic);
+    /// let pos = Pos::origin().neighbor(Direction::South);
+    /// units.add_unit(Unit::with_state(UnitType::Melee, Player::Me, pos, 0, 50));
+    /// let mut grounds = HashMap::new();
+    /// grounds.insert(1, HealingGround::Enemy);
+    /// units.refresh(&Ruleset::new(), &grounds);
+    /// assert_eq!(units.get(1).hp(), 55);
+    /// assert_eq!(units.get(2).hp(), 55);
+    /// ```
+    pub fn refresh(&mut self, rules: &Ruleset, grounds: &HashMap<UnitID, HealingGround>) {
+        let heals: HashMap<UnitID, u8> = self.all_units()
+                                             .map(|u| (u.id, self.turn_heal(u, rules, grounds)))
+                                             .collect();
+        let mut dead_unitids = HashSet::<UnitID>::new();
+        for (_, unit) in self.units.iter_mut() {
+            if !unit.is_dead() {
+                let heal = heals.get(&unit.id).cloned().unwrap_or(0);
+                unit.hp = min(unit.hp.saturating_add(heal), MAX_HP);
+                unit.refresh();
+            } else {
+                dead_unitids.insert(unit.id());
+            }
+        }
+        for unit_id in dead_unitids {
+            self.units.remove(&unit_id);
+        }
+        let units = &self.units;
+        self.on_top.retain(|pos, unit_id| units.get(unit_id).map_or(false, |u| u.pos == *pos));
+    }
+
+    /// Returns the unit with `unit_id`.
+    ///
+    /// Panics if there's no such unit. When `unit_id` might be stale, for example because it was
+    /// kept across a turn start, which purges dead units, use `find()` instead.
+    pub fn get(&self, unit_id: UnitID) -> &Unit {
+        self.units.get(&unit_id).unwrap()
+    }
+
+    pub fn get_mut(&mut self, unit_id: UnitID) -> &mut Unit {
+        self.units.get_mut(&unit_id).unwrap()
+    }
+
+    /// Returns the unit with `unit_id`, or `None` if there's no such unit.
+    ///
+    /// Dead units are still found until they're purged by `refresh()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use civng_core::unit::{Units, Unit, UnitType, Player};
+    /// use civng_core::hexpos::Pos;
+    /// use civng_core::rules::Ruleset;
+    ///
+    /// let mut units = Units::new();
+    /// units.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// let dead = Unit::with_state(UnitType::Melee, Player::AI(0), Pos::origin(), 0, 0);
+    /// units.add_unit(dead);
+    /// assert!(units.find(1).is_some());
+    /// assert!(units.find(2).is_some());
+    /// assert!(units.find(3).is_none());
+    /// units.refresh(&Ruleset::new(), &HashMap::new());
+    /// assert!(units.find(2).is_none());
+    /// ```
+    pub fn find(&self, unit_id: UnitID) -> Option<&Unit> {
+        self.units.get(&unit_id)
+    }
+
+    pub fn find_mut(&mut self, unit_id: UnitID) -> Option<&mut Unit> {
+        self.units.get_mut(&unit_id)
+    }
+
+    /// Gives the units of `a` to `b` and those of `b` to `a`.
+    pub fn swap_players(&mut self, a: Player, b: Player) {
+        for unit in self.units.values_mut() {
+            unit.owner = unit.owner.swapped(a, b);
+        }
+    }
+}
+
+/// Units are serialized as a list sorted by id, and units brought on top as `(pos, id)` pairs,
+/// positions not being valid keys in every format.
+///
+/// # Examples
+///
+/// ```
+/// extern crate serde_json;
+/// extern crate civng_core;
+///
+/// use civng_core::unit::{Unit, Units, UnitType, Player};
+/// use civng_core::hexpos::{Pos, Direction};
+///
+/// # fn main() {
+/// let mut units = Units::new();
+/// units.add_unit(Unit::with_state(UnitType::Melee, Player::Me, Pos::origin(), 2, 60));
+/// units.add_unit(Unit::new(UnitType::Scout, Player::AI(1), Pos::vector(Direction::South)));
+/// units.get_mut(1).set_name("The Old Guard");
+/// let json = serde_json::to_string(&units).unwrap();
+/// assert!(json.starts_with("{\"maxid\":2,\"units\":[{\"id\":1,\"type_\":\"Melee\""));
+/// let copy: Units = serde_json::from_str(&json).unwrap();
+/// let unit = copy.get(1);
+/// assert_eq!((unit.movements(), unit.hp()), (2, 60));
+/// assert_eq!(unit.name(), "The Old Guard");
+/// assert!(copy.get(2).owner() == Player::AI(1));
+/// assert_eq!(serde_json::to_string(&copy).unwrap(), json);
+/// # }
+/// ```
+impl Serialize for Units {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut units: Vec<&Unit> = self.units.values().collect();
+        units.sort_by_key(|u| u.id);
+        let mut on_top: Vec<(Pos, UnitID)> = self.on_top.iter().map(|(&p, &id)| (p, id)).collect();
+        on_top.sort_by_key(|&(_, id)| id);
+        let mut state = try!(serializer.serialize_struct("Units", 3));
+        try!(state.serialize_field("maxid", &self.maxid));
+        try!(state.serialize_field("units", &units));
+        try!(state.serialize_field("on_top", &on_top));
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Units {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Units, D::Error> {
+        #[derive(Deserialize)]
+        struct UnitList {
+            maxid: UnitID,
+            units: Vec<Unit>,
+            on_top: Vec<(Pos, UnitID)>,
+        }
+
+        let list = try!(UnitList::deserialize(deserializer));
+        Ok(Units {
+            maxid: list.maxid,
+            units: list.units.into_iter().map(|u| (u.id, u)).collect(),
+            on_top: list.on_top.into_iter().collect(),
+        })
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Alerts drawing the player's attention to important events, such as our units being attacked
+//! during AI turns.
+//!
+//! An alert rings the terminal bell, briefly flashes the tile where the event happened, or both.
+
+use std::io::{self, Write};
+
+/// How the player is alerted of a type of event.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AlertKind {
+    Off,
+    Bell,
+    Flash,
+    Both,
+}
+
+impl AlertKind {
+    /// Name of the alert kind, as written in config files.
+    pub fn name(&self) -> &str {
+        match *self {
+            AlertKind::Off => "off",
+            AlertKind::Bell => "bell",
+            AlertKind::Flash => "flash",
+            AlertKind::Both => "both",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AlertKind> {
+        match name {
+            "off" => Some(AlertKind::Off),
+            "bell" => Some(AlertKind::Bell),
+            "flash" => Some(AlertKind::Flash),
+            "both" => Some(AlertKind::Both),
+            _ => None,
+        }
+    }
+
+    pub fn rings_bell(&self) -> bool {
+        *self == AlertKind::Bell || *self == AlertKind::Both
+    }
+
+    pub fn flashes(&self) -> bool {
+        *self == AlertKind::Flash || *self == AlertKind::Both
+    }
+}
+
+/// Rings the terminal bell.
+///
+/// Most terminals also mark their window or tab as needing attention when the bell rings in the
+/// background.
+pub fn ring_bell() {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(b"\x07");
+    let _ = stdout.flush();
+}
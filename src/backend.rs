@@ -0,0 +1,255 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Terminal backends.
+//!
+//! Everything the game needs from a terminal goes through `Backend`. Backends are grids of cells,
+//! as described by rustty's `CellAccessor`, into which we draw a whole frame before showing it
+//! with `present()`. rustty's `Terminal`, wrapped in `TermBackend`, is our only backend so far,
+//! but any library able to put those cells on screen and to read keys can be made into one
+//! without touching game logic.
+//!
+//! `FakeBackend` keeps everything in memory, which lets us test our drawing code.
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use libc;
+use rustty::{Terminal, Event, Cell, CellAccessor, HasSize, Size};
+
+pub trait Backend: CellAccessor + HasSize {
+    /// Starts a new frame: all cells are blank and the grid has the size of the terminal, which
+    /// might have been resized since the last frame.
+    fn new_frame(&mut self);
+
+    /// Shows the cells we've drawn on screen.
+    fn present(&mut self);
+
+    /// Waits up to `timeout` for a keypress and returns it, if any.
+    fn poll_key(&mut self, timeout: Duration) -> Option<char>;
+}
+
+/// What the terminal sends for Ctrl-Z, which rustty's raw mode keeps from becoming a SIGTSTP.
+const KEY_SUSPEND: char = '\x1a';
+
+/// Set when we receive a SIGTSTP, which we handle the same way as Ctrl-Z.
+static SIGTSTP_RECEIVED: AtomicBool = AtomicBool::new(false);
+/// Set when we receive a SIGCONT without having stopped ourselves, for example after a SIGSTOP.
+static SIGCONT_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigtstp(_: libc::c_int) {
+    SIGTSTP_RECEIVED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn on_sigcont(_: libc::c_int) {
+    SIGCONT_RECEIVED.store(true, Ordering::SeqCst);
+}
+
+fn set_signal_handler(signal: libc::c_int, handler: extern "C" fn(libc::c_int)) {
+    unsafe {
+        libc::signal(signal, handler as libc::sighandler_t);
+    }
+}
+
+/// rustty's `Terminal`, which we let go of while the game is suspended.
+///
+/// Ctrl-Z and SIGTSTP restore the terminal to the state we found it in and stop the process. When
+/// the shell resumes us, the terminal is set up anew and the next frame is drawn in full, so
+/// whatever the shell printed in the meantime doesn't linger on screen. We do the same when we're
+/// resumed after having been stopped by someone else.
+pub struct TermBackend {
+    // Only `None` while we're suspended.
+    term: Option<Terminal>,
+}
+
+impl TermBackend {
+    pub fn new() -> io::Result<TermBackend> {
+        let term = try!(Terminal::new());
+        set_signal_handler(libc::SIGTSTP, on_sigtstp);
+        set_signal_handler(libc::SIGCONT, on_sigcont);
+        Ok(TermBackend { term: Some(term) })
+    }
+
+    fn term(&self) -> &Terminal {
+        self.term.as_ref().unwrap()
+    }
+
+    fn term_mut(&mut self) -> &mut Terminal {
+        self.term.as_mut().unwrap()
+    }
+
+    /// Sets up the terminal again, with empty buffers which make the next frame a full redraw.
+    fn reopen(&mut self) {
+        // rustty only allows one terminal at a time, so the old one has to go first.
+        self.term = None;
+        self.term = Some(Terminal::new().expect("Can't set up the terminal again"));
+    }
+
+    /// Gives the terminal back to the shell and stops until we're resumed.
+    fn suspend(&mut self) {
+        // Dropping the terminal restores it.
+        self.term = None;
+        unsafe {
+            libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+            libc::raise(libc::SIGTSTP);
+        }
+        // We only get here once we're resumed.
+        set_signal_handler(libc::SIGTSTP, on_sigtstp);
+        // That SIGCONT is ours, we're about to reopen the terminal anyway.
+        SIGCONT_RECEIVED.store(false, Ordering::SeqCst);
+        self.reopen();
+    }
+}
+
+impl HasSize for TermBackend {
+    fn size(&self) -> Size {
+        self.term().size()
+    }
+}
+
+impl CellAccessor for TermBackend {
+    fn cellvec(&self) -> &Vec<Cell> {
+        self.term().cellvec()
+    }
+
+    fn cellvec_mut(&mut self) -> &mut Vec<Cell> {
+        self.term_mut().cellvec_mut()
+    }
+}
+
+impl Backend for TermBackend {
+    fn new_frame(&mut self) {
+        let _ = self.term_mut().clear();
+    }
+
+    fn present(&mut self) {
+        let _ = self.term_mut().swap_buffers();
+    }
+
+    /// Suspends the game on Ctrl-Z or SIGTSTP, in which case no key is returned and the caller
+    /// draws its next frame on a fresh terminal.
+    fn poll_key(&mut self, timeout: Duration) -> Option<char> {
+        if SIGTSTP_RECEIVED.swap(false, Ordering::SeqCst) {
+            self.suspend();
+            return None;
+        }
+        if SIGCONT_RECEIVED.swap(false, Ordering::SeqCst) {
+            self.reopen();
+            return None;
+        }
+        match self.term_mut().get_event(timeout) {
+            Ok(Some(Event::Key(KEY_SUSPEND))) => {
+                self.suspend();
+                None
+            }
+            Ok(Some(Event::Key(k))) => Some(k),
+            _ => None,
+        }
+    }
+}
+
+/// In-memory backend, for testing what we draw without a terminal.
+///
+/// Keys to be polled are queued with `push_key()` and the last presented frame can be read back
+/// as text with `lines()`.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use civng_tui::backend::{Backend, FakeBackend};
+/// use civng_tui::menu_dialog::create_message_dialog;
+/// use civng_tui::theme::{Theme, ThemeKind, ColorDepth};
+///
+/// let mut term = FakeBackend::new(30, 8);
+/// let theme = Theme::new(ThemeKind::Monochrome, ColorDepth::Basic);
+/// let mut d = create_message_dialog("Hello", &theme);
+/// term.new_frame();
+/// d.window_mut().draw_into(&mut term);
+/// term.present();
+/// assert_eq!(term.lines()[1], "│  Hello           │");
+/// term.push_key('o');
+/// assert_eq!(term.poll_key(Duration::from_millis(0)), Some('o'));
+/// assert_eq!(term.poll_key(Duration::from_millis(0)), None);
+/// ```
+pub struct FakeBackend {
+    cols: usize,
+    rows: usize,
+    cells: Vec<Cell>,
+    presented: Vec<Cell>,
+    keys: VecDeque<char>,
+}
+
+impl FakeBackend {
+    pub fn new(cols: usize, rows: usize) -> FakeBackend {
+        FakeBackend {
+            cols: cols,
+            rows: rows,
+            cells: vec![Cell::default(); cols * rows],
+            presented: vec![Cell::default(); cols * rows],
+            keys: VecDeque::new(),
+        }
+    }
+
+    /// Queues `key` to be returned by `poll_key()`.
+    pub fn push_key(&mut self, key: char) {
+        self.keys.push_back(key);
+    }
+
+    /// Returns the characters of the last presented frame, one string per row, without trailing
+    /// spaces.
+    pub fn lines(&self) -> Vec<String> {
+        self.presented
+            .chunks(self.cols)
+            .map(|row| {
+                let line: String = row.iter().map(|c| c.ch()).collect();
+                line.trim_end().to_owned()
+            })
+            .collect()
+    }
+
+    /// Returns the cell at `(x, y)` in the last presented frame.
+    pub fn presented_cell(&self, x: usize, y: usize) -> Option<&Cell> {
+        if x < self.cols && y < self.rows {
+            self.presented.get(y * self.cols + x)
+        } else {
+            None
+        }
+    }
+}
+
+impl HasSize for FakeBackend {
+    fn size(&self) -> Size {
+        (self.cols, self.rows)
+    }
+}
+
+impl CellAccessor for FakeBackend {
+    fn cellvec(&self) -> &Vec<Cell> {
+        &self.cells
+    }
+
+    fn cellvec_mut(&mut self) -> &mut Vec<Cell> {
+        &mut self.cells
+    }
+}
+
+impl Backend for FakeBackend {
+    fn new_frame(&mut self) {
+        self.clear(Cell::default());
+    }
+
+    fn present(&mut self) {
+        self.presented = self.cells.clone();
+    }
+
+    fn poll_key(&mut self, _: Duration) -> Option<char> {
+        self.keys.pop_front()
+    }
+}
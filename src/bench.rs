@@ -0,0 +1,186 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Measures how long the hot paths of the engine take on a large generated map.
+//!
+//! Half of the units are ours and the other half belong to an AI player we're at war with, so
+//! that zones of control, attacks and the AI all have something to chew on.
+
+use std::env;
+use std::process;
+use std::time::{Duration, Instant};
+
+use civng_core::map::LiveMap;
+use civng_core::mapgen::{generate_map, MapPreset};
+use civng_core::unit::{Unit, UnitID, UnitType, Player};
+use civng_core::hexpos::Pos;
+use civng_core::ai::play_turn;
+use civng_core::rng;
+
+extern crate civng_core;
+
+struct BenchOptions {
+    width: i32,
+    height: i32,
+    units: usize,
+    runs: usize,
+    seed: u32,
+}
+
+fn usage() -> &'static str {
+    "Usage: civng-bench [OPTIONS]
+
+Options:
+    --width N   Width of the generated map (default: 120)
+    --height N  Height of the generated map (default: 80)
+    --units N   Number of units to spawn, half of them for the AI (default: 60)
+    --runs N    Number of times each measure is taken (default: 5)
+    --seed N    Seed of the random number generator (default: 1)
+    --help      Print this message"
+}
+
+fn parse_args(args: &[String]) -> Result<BenchOptions, String> {
+    let mut result = BenchOptions {
+        width: 120,
+        height: 80,
+        units: 60,
+        runs: 5,
+        seed: 1,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        // An empty error means we only print usage.
+        if arg == "--help" {
+            return Err(String::new());
+        }
+        let value = match args.next() {
+            Some(value) => value,
+            None => return Err(format!("Missing value for {}", arg)),
+        };
+        let invalid = |_| format!("Invalid number for {}: {}", arg, value);
+        match &arg[..] {
+            "--width" => result.width = try!(value.parse().map_err(invalid)),
+            "--height" => result.height = try!(value.parse().map_err(invalid)),
+            "--units" => result.units = try!(value.parse().map_err(invalid)),
+            "--runs" => result.runs = try!(value.parse().map_err(invalid)),
+            "--seed" => result.seed = try!(value.parse().map_err(invalid)),
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+    Ok(result)
+}
+
+/// Returns a map of `width` by `height` with `count` units spread over its land, alternating
+/// between us and the AI, and the land tiles of that map.
+fn populated_map(width: i32, height: i32, count: usize) -> (LiveMap, Vec<Pos>) {
+    let terrain = rng::with_rng(|r| generate_map(MapPreset::Continents, width, height, r));
+    let mut map = LiveMap::new(terrain);
+    let land: Vec<Pos> = map.terrain()
+                            .tiles()
+                            .filter(|&(pos, _)| map.terrain().is_passable(pos))
+                            .map(|(pos, _)| pos)
+                            .collect();
+    if land.len() < count {
+        println!("The map only has {} land tiles for {} units", land.len(), count);
+        process::exit(1);
+    }
+    for i in 0..count {
+        let pos = land[i * land.len() / count];
+        let owner = if i % 2 == 0 { Player::Me } else { Player::AI(0) };
+        map.add_unit(Unit::new(UnitType::Melee, owner, pos));
+    }
+    map.refresh();
+    (map, land)
+}
+
+fn as_micros(duration: Duration) -> u64 {
+    duration.as_secs() * 1000000 + duration.subsec_nanos() as u64 / 1000
+}
+
+/// Runs `f` `runs` times and prints the average and worst time it took, per call of what it
+/// measures. `f` returns how many such calls it made.
+fn measure<F: FnMut() -> usize>(name: &str, runs: usize, mut f: F) {
+    let mut total = 0;
+    let mut worst = 0;
+    let mut calls = 0;
+    for _ in 0..runs {
+        let start = Instant::now();
+        let count = f();
+        let elapsed = as_micros(start.elapsed());
+        total += elapsed;
+        calls += count;
+        worst = std::cmp::max(worst, elapsed / std::cmp::max(count, 1) as u64);
+    }
+    let average = total / std::cmp::max(calls, 1) as u64;
+    println!("{:<15} {:>8} calls {:>10} us avg {:>10} us worst",
+             name,
+             calls,
+             average,
+             worst);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(ref msg) if msg.is_empty() => {
+            println!("{}", usage());
+            return;
+        }
+        Err(msg) => {
+            println!("{}\n\n{}", msg, usage());
+            process::exit(1);
+        }
+    };
+    rng::seed(options.seed);
+    let (map, land) = populated_map(options.width, options.height, options.units);
+    let unit_ids: Vec<UnitID> = map.units().all_units().map(|u| u.id()).collect();
+    println!("{}x{} map, {} units, {} runs",
+             options.width,
+             options.height,
+             unit_ids.len(),
+             options.runs);
+    measure("reachable_pos", options.runs, || {
+        for &unit_id in unit_ids.iter() {
+            map.reachable_pos(unit_id);
+        }
+        unit_ids.len()
+    });
+    // Each unit heads for a free land tile halfway through the land, which is as far as it gets
+    // on the map. Some of them are on other continents, so failed searches are measured too.
+    let targets: Vec<Pos> = (0..unit_ids.len())
+                                .map(|i| {
+                                    let from = (i * land.len() / unit_ids.len() + land.len() / 2) %
+                                               land.len();
+                                    land.iter()
+                                        .cycle()
+                                        .skip(from)
+                                        .take(land.len())
+                                        .cloned()
+                                        .find(|&p| map.units().top_at_pos(p).is_none())
+                                        .unwrap_or(land[from])
+                                })
+                                .collect();
+    measure("find_path", options.runs, || {
+        for (&unit_id, &to) in unit_ids.iter().zip(targets.iter()) {
+            map.find_path(unit_id, to);
+        }
+        unit_ids.len()
+    });
+    measure("visible_tiles", options.runs, || {
+        for &unit_id in unit_ids.iter() {
+            map.visible_tiles(unit_id);
+        }
+        unit_ids.len()
+    });
+    // Every run plays the same turn, on a fresh copy of the map.
+    measure("ai turn", options.runs, || {
+        let mut map = map.clone();
+        play_turn(Player::AI(0), &mut map);
+        1
+    });
+}
@@ -0,0 +1,214 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+use rustty::CellAccessor;
+use rustty::ui::{Painter, HorizontalAlign, Dialog, DialogResult};
+
+use dialog::item_key;
+use city::{CityID, CityStatus, Production, FOOD_PER_CITIZEN, growth_threshold};
+use map::LiveMap;
+use terrain::Resource;
+use theme::Theme;
+
+/// Returned by the city screen when we want to change what the city builds.
+pub const CITY_PRODUCTION: i32 = 1;
+/// Returned by the capture dialog when we keep the city.
+pub const CAPTURE_ANNEX: i32 = 2;
+/// Returned by the capture dialog when we make a puppet of the city.
+pub const CAPTURE_PUPPET: i32 = 3;
+/// Returned by the capture dialog when we raze the city.
+pub const CAPTURE_RAZE: i32 = 4;
+
+/// Describes what `city_id` builds, with its progress.
+fn production_line(city_id: CityID, map: &LiveMap) -> String {
+    let cities = map.cities();
+    let city = cities.get(city_id);
+    match city.producing() {
+        Some(p) => {
+            format!("{} ({}/{})",
+                    cities.production_name(p),
+                    city.production(),
+                    cities.production_cost(p))
+        }
+        None => format!("Nothing ({} stocked)", city.production()),
+    }
+}
+
+/// Creates the screen of `city_id`, telling how it grows, what it builds and which tiles its
+/// citizens work.
+///
+/// Tiles are listed with the key locking or unlocking them. "Ok" returns `DialogResult::Ok` and
+/// "Production" returns `DialogResult::Custom(CITY_PRODUCTION)`.
+pub fn create_city_dialog(city_id: CityID, map: &LiveMap, theme: &Theme) -> Dialog {
+    let cities = map.cities();
+    let terrain = map.terrain();
+    let city = cities.get(city_id);
+    let tiles = cities.city_tiles(city_id, terrain);
+    let rows = (tiles.len() + 1) / 2;
+    let mut d = Dialog::new(70, 19 + rows);
+    {
+        let w = d.window_mut();
+        w.clear(theme.dialog_cell());
+        let title = match city.status() {
+            CityStatus::Founded => city.name().to_owned(),
+            status => format!("{} ({})", city.name(), status.name()),
+        };
+        let x = w.halign_line(&title, HorizontalAlign::Middle, 1);
+        w.printline(x, 1, &title);
+        let city_yield = cities.city_yield(city_id, terrain);
+        let eaten = city.population() as i32 * FOOD_PER_CITIZEN as i32;
+        let buildings: Vec<&str> = city.buildings()
+                                       .iter()
+                                       .map(|&b| &cities.buildings().get(b).name[..])
+                                       .collect();
+        let garrison = match map.garrison(city_id) {
+            Some(unit_id) => map.units().get(unit_id).name().to_owned(),
+            None => "none".to_owned(),
+        };
+        // Units using each resource we know about, out of the units it can supply.
+        let resources: Vec<String> = Resource::all()
+                                         .iter()
+                                         .filter(|&&r| map.knows_resource(city.owner(), r))
+                                         .map(|&r| {
+                                             format!("{} {}/{}",
+                                                     r.name(),
+                                                     map.resource_usage(city.owner(), r),
+                                                     map.resource_supply(city.owner(), r))
+                                         })
+                                         .collect();
+        let pantheon = cities.pantheon(city.owner()).map_or("none", |b| b.name());
+        let lines = [format!("Population | {}", city.population()),
+                     format!("Food       | {}/{} ({:+})",
+                             city.food(),
+                             growth_threshold(city.population()),
+                             city_yield.food as i32 - eaten),
+                     format!("Production | +{}, building {}",
+                             city_yield.production,
+                             production_line(city_id, map)),
+                     format!("Science    | +{}", cities.city_science(city_id)),
+                     format!("Buildings  | {}", buildings.join(", ")),
+                     format!("Strength   | {} (garrison: {})",
+                             map.city_strength(city_id),
+                             garrison),
+                     format!("Faith      | +{} ({} stocked), pantheon: {}",
+                             cities.city_faith(city_id, terrain),
+                             cities.faith(city.owner()),
+                             pantheon),
+                     format!("Empire     | {} gold, happiness {:+}",
+                             cities.gold(city.owner()),
+                             cities.happiness(city.owner())),
+                     format!("Resources  | {}", resources.join(", "))];
+        for (i, s) in lines.iter().enumerate() {
+            w.printline(2, 3 + i, s);
+        }
+        for (i, &pos) in tiles.iter().enumerate() {
+            let marker = if city.locked_tiles().contains(&pos) {
+                '#'
+            } else if city.worked_tiles().contains(&pos) {
+                '+'
+            } else {
+                ' '
+            };
+            let name = match terrain.get_feature(pos) {
+                Some(f) => f.name().to_owned(),
+                None => terrain.get_terrain(pos).name().to_owned(),
+            };
+            let s = format!("{} {} {:<14} {}",
+                            item_key(i),
+                            marker,
+                            name,
+                            cities.tile_yield(city.owner(), pos, terrain).fmt());
+            w.printline(2 + (i % 2) * 34, 13 + i / 2, &s);
+        }
+        w.printline(2,
+                    14 + rows,
+                    "+ worked, # locked. Press a tile's letter to lock or unlock it.");
+    }
+    d.add_button("Ok", 'o', DialogResult::Ok);
+    d.add_button("Production", 'p', DialogResult::Custom(CITY_PRODUCTION));
+    d.draw_buttons();
+    d.window_mut().draw_box();
+    d
+}
+
+/// Creates the dialog asking what to do with `city_id`, which we've just captured.
+///
+/// "Annex", "Puppet" and "Raze" return `DialogResult::Custom()` with `CAPTURE_ANNEX`,
+/// `CAPTURE_PUPPET` and `CAPTURE_RAZE`.
+pub fn create_capture_dialog(city_id: CityID, map: &LiveMap, theme: &Theme) -> Dialog {
+    let city = map.cities().get(city_id);
+    let mut d = Dialog::new(70, 11);
+    {
+        let w = d.window_mut();
+        w.clear(theme.dialog_cell());
+        let msg = format!("We captured {}! What should we do with it?", city.name());
+        let x = w.halign_line(&msg, HorizontalAlign::Middle, 1);
+        w.printline(x, 1, &msg);
+        let statuses = [CityStatus::Annexed, CityStatus::Puppet, CityStatus::Razing];
+        let descriptions = ["Annex: we run it like our other cities.",
+                            "Puppet: it builds nothing and makes gold instead.",
+                            "Raze: it loses a citizen each turn until it's gone."];
+        for (i, (status, desc)) in statuses.iter().zip(descriptions.iter()).enumerate() {
+            let s = format!("{:<52} {:+} happiness", desc, -status.unhappiness());
+            w.printline(2, 3 + i, &s);
+        }
+    }
+    d.add_button("Annex", 'a', DialogResult::Custom(CAPTURE_ANNEX));
+    d.add_button("Puppet", 'p', DialogResult::Custom(CAPTURE_PUPPET));
+    d.add_button("Raze", 'r', DialogResult::Custom(CAPTURE_RAZE));
+    d.draw_buttons();
+    d.window_mut().draw_box();
+    d
+}
+
+/// Creates the dialog choosing what `city_id` builds among `choices`.
+///
+/// Choices are listed with the key picking them. "Back" returns `DialogResult::Cancel`.
+pub fn create_production_dialog(city_id: CityID,
+                                choices: &[Production],
+                                map: &LiveMap,
+                                theme: &Theme)
+                                -> Dialog {
+    let cities = map.cities();
+    let production = cities.city_yield(city_id, map.terrain()).production as u16;
+    let mut d = Dialog::new(70, 8 + choices.len());
+    {
+        let w = d.window_mut();
+        w.clear(theme.dialog_cell());
+        let msg = format!("What should {} build?", cities.get(city_id).name());
+        let x = w.halign_line(&msg, HorizontalAlign::Middle, 1);
+        w.printline(x, 1, &msg);
+        w.printline(2, 3, &format!("Building {}", production_line(city_id, map)));
+        for (i, &choice) in choices.iter().enumerate() {
+            let cost = cities.production_cost(choice);
+            let turns = (cost + production - 1) / production;
+            let details = match choice {
+                Production::Unit(_) => "Unit".to_owned(),
+                Production::Building(b) => {
+                    let building = cities.buildings().get(b);
+                    let kind = if building.wonder {
+                        "Wonder"
+                    } else {
+                        "Building"
+                    };
+                    format!("{}: {}", kind, building.effects())
+                }
+            };
+            let s = format!("{} {:<16} {:>3} ({:>2} turns) {}",
+                            item_key(i),
+                            cities.production_name(choice),
+                            cost,
+                            turns,
+                            details);
+            w.printline(2, 5 + i, &s);
+        }
+    }
+    d.add_button("Back", 'b', DialogResult::Cancel);
+    d.draw_buttons();
+    d.window_mut().draw_box();
+    d
+}
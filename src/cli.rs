@@ -0,0 +1,222 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Command line arguments.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use mapgen::{MapPreset, Symmetry};
+use difficulty::Difficulty;
+
+pub struct LaunchOptions {
+    /// Map to play on. When `None`, the player picks one in the map chooser.
+    pub map_path: Option<PathBuf>,
+    /// When set, we play on a map generated with this preset instead of a map file.
+    pub generate: Option<MapPreset>,
+    /// When set, the generated map is made symmetric this way.
+    pub symmetry: Option<Symmetry>,
+    /// When set, we play on a map made from this heightmap image instead of a map file.
+    pub heightmap_path: Option<PathBuf>,
+    /// Moisture image going with the heightmap, if any.
+    pub moisture_path: Option<PathBuf>,
+    /// When set, the whole game setup comes from this scenario file.
+    pub scenario_path: Option<PathBuf>,
+    /// Seed of the random number generator. When `None`, games aren't reproducible.
+    pub seed: Option<u32>,
+    /// Number of computer-controlled players.
+    pub ai_players: u8,
+    /// Difficulty level. When `None`, the player picks one after picking a map.
+    pub difficulty: Option<Difficulty>,
+    /// When set, the game is played by the AI alone for that many turns, without a terminal UI.
+    pub headless_turns: Option<u16>,
+    /// Location of the config file.
+    pub config_path: PathBuf,
+    /// When set, every dice roll is logged to this file.
+    pub audit_path: Option<PathBuf>,
+    /// When set, dice rolls replay those logged in this file.
+    pub replay_path: Option<PathBuf>,
+    /// When set, the game state is written to this file as JSON at the start of each turn.
+    pub dump_path: Option<PathBuf>,
+    /// When set, `Player::Me` is played in headless mode by this program, through the bot
+    /// protocol.
+    pub bot_command: Option<String>,
+    /// When set, spectators can watch the game through a WebSocket at this address.
+    pub spectate_addr: Option<String>,
+    /// Whether the debug console can be opened in the game.
+    pub debug: bool,
+    /// Whether we should print usage and exit.
+    pub show_help: bool,
+}
+
+impl LaunchOptions {
+    /// Returns launch options with all default values.
+    pub fn new() -> LaunchOptions {
+        LaunchOptions {
+            map_path: None,
+            generate: None,
+            symmetry: None,
+            heightmap_path: None,
+            moisture_path: None,
+            scenario_path: None,
+            seed: None,
+            ai_players: 1,
+            difficulty: None,
+            headless_turns: None,
+            config_path: PathBuf::from("civng.conf"),
+            audit_path: None,
+            replay_path: None,
+            dump_path: None,
+            bot_command: None,
+            spectate_addr: None,
+            debug: false,
+            show_help: false,
+        }
+    }
+}
+
+/// Returns the usage message of the `civng` binary.
+pub fn usage() -> &'static str {
+    "Usage: civng [OPTIONS]
+
+Options:
+    --map PATH          Play on the Civ5Map at PATH instead of picking one
+    --generate PRESET   Play on a generated map (continents or archipelago)
+    --symmetry KIND     Make the generated map symmetric (mirror or rotational), with players
+                        starting on equally good land
+    --heightmap PATH    Play on a map made from the PNG or PGM heightmap image at PATH
+    --moisture PATH     Use the PNG or PGM image at PATH as the heightmap's moisture map
+    --scenario PATH     Set the game up from the scenario file at PATH
+    --seed N            Seed the random number generator, and thus map generation, to make the
+                        game reproducible
+    --ai-players N      Number of AI players (default: 1)
+    --difficulty LEVEL  Difficulty level, from settler to deity (default in headless mode: prince)
+    --headless TURNS    Let the AI play every player for TURNS turns and print a summary
+    --config PATH       Load config from PATH (default: civng.conf)
+    --audit-rolls PATH  Log every dice roll to PATH
+    --replay-rolls PATH Replay the dice rolls logged in PATH by --audit-rolls
+    --dump-state PATH   Write the game state we know of to PATH as JSON, one line per turn
+    --bot PROGRAM       In headless mode, let PROGRAM play against the AI through the bot protocol
+    --spectate ADDR     Let browsers spectate the game through a WebSocket at ADDR, such as
+                        localhost:8765
+    --debug             Enable the debug console, opened with the backquote key
+    --help              Print this message"
+}
+
+/// Parses `args`, which don't include the program name.
+///
+/// # Examples
+///
+/// ```
+/// use civng_tui::cli::parse_args;
+///
+/// let args: Vec<String> = vec!["--seed".to_owned(), "42".to_owned()];
+/// let options = parse_args(&args).unwrap();
+/// assert_eq!(options.seed, Some(42));
+/// assert_eq!(options.ai_players, 1);
+/// assert!(parse_args(&["--seed".to_owned()]).is_err());
+/// assert!(parse_args(&["--foo".to_owned()]).is_err());
+/// assert!(!options.debug);
+/// let args: Vec<String> = vec!["--debug".to_owned(), "--headless".to_owned(), "5".to_owned()];
+/// assert!(parse_args(&args).is_err());
+/// ```
+pub fn parse_args(args: &[String]) -> Result<LaunchOptions, String> {
+    fn value_of<'a, I: Iterator<Item = &'a String>>(flag: &str,
+                                                    args: &mut I)
+                                                    -> Result<&'a str, String> {
+        match args.next() {
+            Some(s) => Ok(s),
+            None => Err(format!("Missing value for {}", flag)),
+        }
+    }
+
+    fn number_of<'a, T: FromStr, I: Iterator<Item = &'a String>>(flag: &str,
+                                                                  args: &mut I)
+                                                                  -> Result<T, String> {
+        let value = try!(value_of(flag, args));
+        value.parse().map_err(|_| format!("Invalid number for {}: {}", flag, value))
+    }
+
+    let mut result = LaunchOptions::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--map" => result.map_path = Some(PathBuf::from(try!(value_of(arg, &mut args)))),
+            "--generate" => {
+                let name = try!(value_of(arg, &mut args));
+                match MapPreset::from_name(name) {
+                    Some(preset) => result.generate = Some(preset),
+                    None => return Err(format!("Unknown map preset: {}", name)),
+                }
+            }
+            "--symmetry" => {
+                let name = try!(value_of(arg, &mut args));
+                match Symmetry::from_name(name) {
+                    Some(symmetry) => result.symmetry = Some(symmetry),
+                    None => return Err(format!("Unknown symmetry: {}", name)),
+                }
+            }
+            "--heightmap" => {
+                result.heightmap_path = Some(PathBuf::from(try!(value_of(arg, &mut args))));
+            }
+            "--moisture" => {
+                result.moisture_path = Some(PathBuf::from(try!(value_of(arg, &mut args))));
+            }
+            "--scenario" => {
+                result.scenario_path = Some(PathBuf::from(try!(value_of(arg, &mut args))));
+            }
+            "--difficulty" => {
+                let name = try!(value_of(arg, &mut args));
+                match Difficulty::from_name(name) {
+                    Some(d) => result.difficulty = Some(d),
+                    None => return Err(format!("Unknown difficulty level: {}", name)),
+                }
+            }
+            "--seed" => result.seed = Some(try!(number_of(arg, &mut args))),
+            "--ai-players" => result.ai_players = try!(number_of(arg, &mut args)),
+            "--headless" => result.headless_turns = Some(try!(number_of(arg, &mut args))),
+            "--config" => result.config_path = PathBuf::from(try!(value_of(arg, &mut args))),
+            "--audit-rolls" => {
+                result.audit_path = Some(PathBuf::from(try!(value_of(arg, &mut args))));
+            }
+            "--replay-rolls" => {
+                result.replay_path = Some(PathBuf::from(try!(value_of(arg, &mut args))));
+            }
+            "--dump-state" => {
+                result.dump_path = Some(PathBuf::from(try!(value_of(arg, &mut args))));
+            }
+            "--bot" => result.bot_command = Some(try!(value_of(arg, &mut args)).to_owned()),
+            "--spectate" => {
+                result.spectate_addr = Some(try!(value_of(arg, &mut args)).to_owned());
+            }
+            "--debug" => result.debug = true,
+            "--help" | "-h" => result.show_help = true,
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    let map_sources = [result.map_path.is_some(),
+                       result.generate.is_some(),
+                       result.heightmap_path.is_some(),
+                       result.scenario_path.is_some()];
+    if map_sources.iter().filter(|&&b| b).count() > 1 {
+        return Err("Only one of --map, --generate, --heightmap and --scenario can be used"
+                       .to_owned());
+    }
+    if result.symmetry.is_some() && result.generate.is_none() {
+        return Err("--symmetry requires --generate".to_owned());
+    }
+    if result.moisture_path.is_some() && result.heightmap_path.is_none() {
+        return Err("--moisture requires --heightmap".to_owned());
+    }
+    if result.bot_command.is_some() && result.headless_turns.is_none() {
+        return Err("--bot requires --headless".to_owned());
+    }
+    if result.debug && result.headless_turns.is_some() {
+        return Err("--debug can't be used with --headless".to_owned());
+    }
+    Ok(result)
+}
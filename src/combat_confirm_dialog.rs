@@ -7,38 +7,53 @@
 
 use std::cmp::max;
 
-use rustty::{CellAccessor, Cell};
+use rustty::CellAccessor;
 use rustty::ui::{Painter, HorizontalAlign, Dialog, DialogResult};
 
 use combat::CombatStats;
+use theme::Theme;
 
-pub fn create_combat_confirm_dialog(result: &CombatStats) -> Dialog {
+pub fn create_combat_confirm_dialog(result: &CombatStats, theme: &Theme) -> Dialog {
     let modscount = max(result.attacker_modifiers.len(),
                         result.defender_modifiers.len());
-    let mut d = Dialog::new(55, 11 + modscount);
+    let withdraw_odds = result.withdraw_odds();
+    let extra = if withdraw_odds > 0.0 { 1 } else { 0 };
+    let mut d = Dialog::new(55, 12 + extra + modscount);
     {
         let w = d.window_mut();
-        w.clear(Cell::default());
-        let msg = "Expected results";
+        w.clear(theme.dialog_cell());
+        let msg = if result.attacker_city.is_some() {
+            "City strike: expected results"
+        } else {
+            "Expected results"
+        };
         let x = w.halign_line(msg, HorizontalAlign::Middle, 1);
         w.printline(x, 1, msg);
         let (amin, amax) = result.dmgrange_to_attacker();
         let admgfmt = format!("{}-{}", amin, amax);
         let (dmin, dmax) = result.dmgrange_to_defender();
         let ddmgfmt = format!("{}-{}", dmin, dmax);
-        let lines = [format!("Name          | {:<15} | {:<15}",
-                             result.attacker_name,
-                             result.defender_name),
-                     format!("Base Strength | {:<15} | {:<15}",
-                             result.attacker_base_strength,
-                             result.defender_base_strength),
-                     format!("Real Strength | {:<15.1} | {:<15.1}",
-                             result.attacker_strength(),
-                             result.defender_strength()),
-                     format!("HP            | {:<15} | {:<15}",
-                             result.attacker_starting_hp,
-                             result.defender_starting_hp),
-                     format!("Dmg incoming  | {:<15} | {:<15}", admgfmt, ddmgfmt)];
+        let odds = result.outcome_probabilities();
+        let adeathfmt = format!("{:.0}%", odds.attacker_dies * 100.0);
+        let ddeathfmt = format!("{:.0}%", odds.defender_dies * 100.0);
+        let mut lines = vec![format!("Name          | {:<15} | {:<15}",
+                                 result.attacker_name,
+                                 result.defender_name),
+                         format!("Base Strength | {:<15} | {:<15}",
+                                 result.attacker_base_strength,
+                                 result.defender_base_strength),
+                         format!("Real Strength | {:<15.1} | {:<15.1}",
+                                 result.attacker_strength(),
+                                 result.defender_strength()),
+                         format!("HP            | {:<15} | {:<15}",
+                                 result.attacker_starting_hp,
+                                 result.defender_starting_hp),
+                         format!("Dmg incoming  | {:<15} | {:<15}", admgfmt, ddmgfmt),
+                         format!("Death chance  | {:<15} | {:<15}", adeathfmt, ddeathfmt)];
+        if withdraw_odds > 0.0 {
+            let withdrawfmt = format!("{:.0}%", withdraw_odds * 100.0);
+            lines.push(format!("Withdraw      | {:<15} | {:<15}", "", withdrawfmt));
+        }
         for (i, s) in lines.iter().enumerate() {
             w.printline(2, 3 + i, &s[..]);
         }
@@ -57,7 +72,7 @@ pub fn create_combat_confirm_dialog(result: &CombatStats) -> Dialog {
                 None => "".to_owned(),
             };
             w.printline(2,
-                        8 + i,
+                        9 + extra + i,
                         &format!("{:<13} | {:<15} | {:15}", title, amod, dmod)[..]);
         }
     }
@@ -5,17 +5,20 @@
 // http://www.gnu.org/licenses/gpl-3.0.html
 //
 
-use rustty::{CellAccessor, Cell};
+use rustty::CellAccessor;
 use rustty::ui::{Painter, HorizontalAlign, Dialog, DialogResult};
 
 use combat::CombatStats;
+use theme::Theme;
 
-pub fn create_combat_result_dialog(result: &CombatStats) -> Dialog {
+pub fn create_combat_result_dialog(result: &CombatStats, theme: &Theme) -> Dialog {
     let mut d = Dialog::new(35, 12);
     {
         let w = d.window_mut();
-        w.clear(Cell::default());
-        let result_desc = if result.attacker_remaining_hp() == 0 {
+        w.clear(theme.dialog_cell());
+        let result_desc = if result.withdrew {
+            "Withdrawal"
+        } else if result.attacker_remaining_hp() == 0 {
             "Crushing Defeat"
         } else if result.defender_remaining_hp() == 0 {
             "Decisive Victory"
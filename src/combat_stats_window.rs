@@ -0,0 +1,65 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+use rustty::CellAccessor;
+use rustty::ui::{Painter, HorizontalAlign, Dialog, DialogResult};
+
+use combat_log::CombatLog;
+use unit::Player;
+use theme::Theme;
+
+/// Number of past combats listed in the dialog.
+const RECENT_COMBATS: usize = 5;
+
+/// Creates a dialog summarizing our combats, followed by the most recent ones.
+pub fn create_combat_stats_dialog(log: &CombatLog, theme: &Theme) -> Dialog {
+    let mut d = Dialog::new(50, 11 + RECENT_COMBATS);
+    {
+        let w = d.window_mut();
+        w.clear(theme.dialog_cell());
+        let title = "Combat Statistics";
+        let x = w.halign_line(title, HorizontalAlign::Middle, 1);
+        w.printline(x, 1, title);
+        let summary = log.summary(Player::Me);
+        let lines = [format!("Combats: {}", summary.combats),
+                     format!("Won: {} ({:.0}%)", summary.won, summary.win_rate()),
+                     format!("Luck: {:+.1} dmg per combat", summary.average_luck),
+                     format!("Dice deviation: {:+.1} dmg per roll", summary.average_deviation)];
+        for (i, s) in lines.iter().enumerate() {
+            w.printline(2, 3 + i, &s[..]);
+        }
+        w.printline(2, 8, "Recent combats (expected dmg):");
+        let ours = log.records().iter().rev().filter(|r| r.luck_of(Player::Me).is_some());
+        for (i, record) in ours.take(RECENT_COMBATS).enumerate() {
+            let (action, dealt, expected_dealt, received, expected_received) =
+                if record.attacker == Player::Me {
+                    ("Attacked",
+                     record.dmg_to_defender,
+                     record.expected_dmg_to_defender,
+                     record.dmg_to_attacker,
+                     record.expected_dmg_to_attacker)
+                } else {
+                    ("Defended",
+                     record.dmg_to_attacker,
+                     record.expected_dmg_to_attacker,
+                     record.dmg_to_defender,
+                     record.expected_dmg_to_defender)
+                };
+            let line = format!("{}: dealt {} ({:.0}), got {} ({:.0})",
+                               action,
+                               dealt,
+                               expected_dealt,
+                               received,
+                               expected_received);
+            w.printline(2, 9 + i, &line);
+        }
+    }
+    d.add_button("Ok", 'o', DialogResult::Ok);
+    d.draw_buttons();
+    d.window_mut().draw_box();
+    d
+}
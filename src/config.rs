@@ -0,0 +1,180 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! User configuration.
+//!
+//! The config file is a series of `key = value` lines. Empty lines and lines starting with `#` are
+//! ignored, as well as unknown keys and invalid values, which leave the default value in place.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use theme::ThemeKind;
+use glyphs::GlyphMode;
+use alert::AlertKind;
+use rules::Ruleset;
+
+pub struct Config {
+    /// Color scheme used to draw the game.
+    pub theme: ThemeKind,
+    /// Glyph set used to draw terrain and units.
+    pub glyphs: GlyphMode,
+    /// Glyph file overriding glyphs of the chosen glyph set.
+    pub glyphs_file: Option<PathBuf>,
+    /// Whether turns end by themselves once all our units are done.
+    pub auto_end_turn: bool,
+    /// Seconds we have to play each turn before it ends by itself. Zero leaves turns untimed.
+    pub turn_timer: u64,
+    /// House rules of the games we start or load.
+    pub rules: Ruleset,
+    /// How we're alerted when enemies attack our units.
+    pub alert_attacked: AlertKind,
+    /// How we're alerted when one of our units dies.
+    pub alert_killed: AlertKind,
+    /// How we're alerted when enemies come next to one of our cities or attack it.
+    pub alert_siege: AlertKind,
+    /// Time, in milliseconds, each step of the animation of an enemy action stays on screen.
+    pub ai_animation_delay: u64,
+    /// Whether enemy actions are left unanimated.
+    pub quick_moves: bool,
+}
+
+impl Config {
+    /// Returns a config with all default values.
+    pub fn new() -> Config {
+        Config {
+            theme: ThemeKind::Default,
+            glyphs: GlyphMode::Ascii,
+            glyphs_file: None,
+            auto_end_turn: false,
+            turn_timer: 0,
+            rules: Ruleset::new(),
+            alert_attacked: AlertKind::Both,
+            alert_killed: AlertKind::Both,
+            alert_siege: AlertKind::Both,
+            ai_animation_delay: 150,
+            quick_moves: false,
+        }
+    }
+
+    /// Parses config `contents`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::config::Config;
+    /// use civng_tui::theme::ThemeKind;
+    /// use civng_tui::alert::AlertKind;
+    ///
+    /// let config = Config::parse("# My config\ntheme = monochrome\n");
+    /// assert_eq!(config.theme, ThemeKind::Monochrome);
+    /// let config = Config::parse("theme = foobar\n");
+    /// assert_eq!(config.theme, ThemeKind::Default);
+    /// let config = Config::parse("auto_end_turn = true\n");
+    /// assert!(config.auto_end_turn);
+    /// let config = Config::parse("turn_timer = 90\n");
+    /// assert_eq!(config.turn_timer, 90);
+    /// let config = Config::parse("alert_attacked = flash\n");
+    /// assert_eq!(config.alert_attacked, AlertKind::Flash);
+    /// assert_eq!(config.alert_killed, AlertKind::Both);
+    /// let config = Config::parse("alert_siege = off\n");
+    /// assert_eq!(config.alert_siege, AlertKind::Off);
+    /// let config = Config::parse("ai_animation_delay = 80\nquick_moves = true\n");
+    /// assert_eq!((config.ai_animation_delay, config.quick_moves), (80, true));
+    /// let config = Config::parse("last_hex_rule = false\nstacking_limit = 2\n");
+    /// assert_eq!((config.rules.last_hex_rule, config.rules.stacking_limit), (false, 2));
+    /// ```
+    pub fn parse(contents: &str) -> Config {
+        let mut result = Config::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap().trim();
+            let value = match parts.next() {
+                Some(v) => v.trim(),
+                None => continue,
+            };
+            match key {
+                "theme" => {
+                    if let Some(kind) = ThemeKind::from_name(value) {
+                        result.theme = kind;
+                    }
+                }
+                "glyphs" => {
+                    if let Some(mode) = GlyphMode::from_name(value) {
+                        result.glyphs = mode;
+                    }
+                }
+                "glyphs_file" => {
+                    result.glyphs_file = Some(PathBuf::from(value));
+                }
+                "auto_end_turn" => {
+                    if let Ok(b) = value.parse() {
+                        result.auto_end_turn = b;
+                    }
+                }
+                "turn_timer" => {
+                    if let Ok(secs) = value.parse() {
+                        result.turn_timer = secs;
+                    }
+                }
+                "alert_attacked" => {
+                    if let Some(kind) = AlertKind::from_name(value) {
+                        result.alert_attacked = kind;
+                    }
+                }
+                "alert_killed" => {
+                    if let Some(kind) = AlertKind::from_name(value) {
+                        result.alert_killed = kind;
+                    }
+                }
+                "alert_siege" => {
+                    if let Some(kind) = AlertKind::from_name(value) {
+                        result.alert_siege = kind;
+                    }
+                }
+                "ai_animation_delay" => {
+                    if let Ok(ms) = value.parse() {
+                        result.ai_animation_delay = ms;
+                    }
+                }
+                "quick_moves" => {
+                    if let Ok(b) = value.parse() {
+                        result.quick_moves = b;
+                    }
+                }
+                // Other keys may name house rules, see `Ruleset::set()`.
+                _ => {
+                    result.rules.set(key, value);
+                }
+            }
+        }
+        result
+    }
+
+    /// Loads config from the file at `path`.
+    ///
+    /// If the file can't be read, we return a default config.
+    pub fn load(path: &Path) -> Config {
+        let mut contents = String::new();
+        match File::open(path) {
+            Ok(mut fp) => {
+                if fp.read_to_string(&mut contents).is_err() {
+                    return Config::new();
+                }
+            }
+            Err(_) => {
+                return Config::new();
+            }
+        }
+        Config::parse(&contents)
+    }
+}
@@ -5,11 +5,29 @@
 // http://www.gnu.org/licenses/gpl-3.0.html
 //
 
-use rustty::{CellAccessor, Cell, HasSize};
+use std::time::Duration;
+
+use rustty::{CellAccessor, HasSize};
 use rustty::ui::{Painter, Widget, Alignable, HorizontalAlign, VerticalAlign};
 
+use combat::CombatStats;
 use hexpos::Pos;
 use map::LiveMap;
+use theme::Theme;
+use unit::Player;
+
+/// What the details window shows, apart from what it reads off the map.
+pub struct Details<'a> {
+    /// Tile described in the window, if any.
+    pub selected_pos: Option<Pos>,
+    pub turn: u16,
+    /// Time left before the turn timer runs out, if there's one.
+    pub time_left: Option<Duration>,
+    /// Description of the current movement mode, shown on the last line.
+    pub movemode: &'a str,
+    /// Combat the active unit would get into at `selected_pos`, if any.
+    pub preview: Option<&'a CombatStats>,
+}
 
 pub struct DetailsWindow {
     window: Widget,
@@ -17,7 +35,7 @@ pub struct DetailsWindow {
 
 impl DetailsWindow {
     pub fn new(parent: &HasSize) -> DetailsWindow {
-        let mut window = Widget::new(16, 7);
+        let mut window = Widget::new(21, 9);
         window.align(parent, HorizontalAlign::Right, VerticalAlign::Bottom, 0);
         DetailsWindow { window: window }
     }
@@ -26,24 +44,120 @@ impl DetailsWindow {
         self.window.draw_into(cells);
     }
 
-    pub fn update(&mut self, selected_pos: Option<Pos>, map: &LiveMap, turn: u16, movemode: &str) {
-        let turn_line = format!("Turn {}", turn);
-        let (terrain_name, maybe_unit_id) = match selected_pos {
+    /// Redraws the window with `details`.
+    ///
+    /// When the active unit would attack the selected tile, the odds of that combat replace the
+    /// tile description.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::game::Game;
+    /// use civng_tui::backend::{Backend, FakeBackend};
+    /// use civng_tui::config::Config;
+    /// use civng_tui::map::LiveMap;
+    /// use civng_tui::terrain::TerrainMap;
+    /// use civng_tui::unit::{Unit, UnitType, Player};
+    /// use civng_tui::hexpos::{Pos, Direction};
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(10, 10));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Ranged, Player::AI(0), Pos::vector(Direction::South)));
+    /// let mut term = FakeBackend::new(80, 30);
+    /// // Enter move mode and select the enemy south of us.
+    /// term.push_key('m');
+    /// term.push_key('s');
+    /// let mut game = Game::with_map(term, map, &Config::new());
+    /// game.new_turn();
+    /// game.handle_events();
+    /// game.handle_events();
+    /// game.draw();
+    /// let lines = game.term().lines();
+    /// assert!(lines[24].contains("│ Deal 54-96 (+0%)  │"));
+    /// assert!(lines[25].contains("│ Take 29-50 (+0%)  │"));
+    /// ```
+    pub fn update(&mut self, details: &Details, map: &LiveMap, theme: &Theme) {
+        let turn = details.turn;
+        let turn_line = match details.time_left {
+            Some(t) => format!("Turn {} ({}:{:02})", turn, t.as_secs() / 60, t.as_secs() % 60),
+            None => format!("Turn {}", turn),
+        };
+        let supply_line = match map.supply_penalty(Player::Me) {
+            0 => {
+                format!("Supply {}/{}",
+                        map.supply_used(Player::Me),
+                        map.cities().supply_cap(Player::Me))
+            }
+            penalty => {
+                format!("Supply {}/{} -{}%",
+                        map.supply_used(Player::Me),
+                        map.cities().supply_cap(Player::Me),
+                        penalty)
+            }
+        };
+        let fog = map.fog(Player::Me);
+        let (terrain_name, tile_yield, maybe_unit_id) = match details.selected_pos {
+            Some(pos) if fog.map_or(false, |f| !f.is_explored(pos)) => {
+                ("Unexplored".to_owned(), "".to_owned(), None)
+            }
             Some(pos) => {
-                (map.terrain().get_terrain(pos).name().to_owned(),
-                 map.units().unit_at_pos(pos))
+                let is_visible = fog.map_or(true, |f| f.is_visible(pos));
+                let unit_id = map.units().top_at_pos(pos).and_then(|u| {
+                    if is_visible || u.owner() == Player::Me {
+                        Some(u.id())
+                    } else {
+                        None
+                    }
+                });
+                let name = match map.terrain().get_feature(pos) {
+                    Some(feature) => feature.name().to_owned(),
+                    None => map.terrain().get_terrain(pos).name().to_owned(),
+                };
+                (name, map.yield_at(Player::Me, pos).fmt(), unit_id)
             }
-            None => ("".to_owned(), None),
+            None => ("".to_owned(), "".to_owned(), None),
         };
         let (unit_name, unit_stats) = if let Some(uid) = maybe_unit_id {
             let unit = map.units().get(uid);
-            (unit.name(),
-             format!("MV {} / HP {}", unit.movements(), unit.hp()))
+            let name = match unit.order() {
+                Some(order) => format!("{} ({})", unit.name(), order.description()),
+                None => unit.name().to_owned(),
+            };
+            let promote = if unit.can_promote() { " +" } else { "" };
+            // Damaged units show what they'd heal by resting where they are.
+            let heal = if unit.is_damaged() && unit.owner() == Player::Me {
+                format!("+{}", map.heal_rate(uid))
+            } else {
+                "".to_owned()
+            };
+            (name, format!("MV {} / HP {}{}{}", unit.movements(), unit.hp(), heal, promote))
         } else {
-            ("", "".to_owned())
+            ("".to_owned(), "".to_owned())
+        };
+        // A combat preview takes the place of the tile description: we know what's there.
+        let (terrain_name, tile_yield) = match details.preview {
+            Some(stats) => {
+                let (dealt_min, dealt_max) = stats.dmgrange_to_defender();
+                let (taken_min, taken_max) = stats.dmgrange_to_attacker();
+                (format!("Deal {}-{} ({:+}%)",
+                         dealt_min,
+                         dealt_max,
+                         stats.attacker_modifiers_total()),
+                 format!("Take {}-{} ({:+}%)",
+                         taken_min,
+                         taken_max,
+                         stats.defender_modifiers_total()))
+            }
+            None => (terrain_name, tile_yield),
         };
-        let lines = [unit_name, &unit_stats[..], &terrain_name[..], &turn_line[..], movemode];
-        self.window.clear(Cell::default());
+        let lines = [&unit_name[..],
+                     &unit_stats[..],
+                     &terrain_name[..],
+                     &tile_yield[..],
+                     &turn_line[..],
+                     &supply_line[..],
+                     details.movemode];
+        self.window.clear(theme.dialog_cell());
         for (index, line) in lines.iter().enumerate() {
             self.window.printline(2, index + 1, line);
         }
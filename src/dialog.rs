@@ -0,0 +1,241 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Dialogs shown over the map.
+//!
+//! The game keeps its open dialogs in a stack. Only the top one gets our keypresses, and closing
+//! it brings back the one below it, such as the menu below the options.
+
+use std::cmp::max;
+
+use rustty::{Cell, CellAccessor};
+use rustty::ui::{self, Widget, Painter, DialogResult, HorizontalAlign, VerticalAlign, Alignable};
+
+use theme::Theme;
+
+pub const KEY_ESCAPE: char = '\x1b';
+
+/// Returns the upper case letter picking the `index`th item of a list.
+pub fn item_key(index: usize) -> char {
+    (b'A' + index as u8) as char
+}
+
+/// Returns the index of the item `key` picks in a list, if it's an upper case letter.
+///
+/// # Examples
+///
+/// ```
+/// use civng_tui::dialog::{item_key, item_index};
+///
+/// assert_eq!(item_index(item_key(2)), Some(2));
+/// assert_eq!(item_index('a'), None);
+/// ```
+pub fn item_index(key: char) -> Option<usize> {
+    if key >= 'A' && key <= 'Z' {
+        Some(key as usize - 'A' as usize)
+    } else {
+        None
+    }
+}
+
+/// Returns the digit picking the `index`th item of a numbered list, which starts at 1.
+pub fn numbered_item_key(index: usize) -> char {
+    (b'1' + index as u8) as char
+}
+
+/// Returns the index of the item `key` picks in a numbered list, if it's a digit other than 0.
+///
+/// # Examples
+///
+/// ```
+/// use civng_tui::dialog::{numbered_item_key, numbered_item_index};
+///
+/// assert_eq!(numbered_item_key(0), '1');
+/// assert_eq!(numbered_item_index('3'), Some(2));
+/// assert_eq!(numbered_item_index('0'), None);
+/// ```
+pub fn numbered_item_index(key: char) -> Option<usize> {
+    if key >= '1' && key <= '9' {
+        Some(key as usize - '1' as usize)
+    } else {
+        None
+    }
+}
+
+/// What was chosen in a dialog.
+#[derive(Clone)]
+pub enum Choice {
+    /// One of the dialog's buttons, by its result.
+    Button(DialogResult),
+    /// One of the items listed with a letter, by index.
+    Item(usize),
+    /// One of the items listed with a number, by index.
+    NumberedItem(usize),
+    /// Text typed in the dialog, confirmed with the enter key.
+    Text(String),
+    /// The dialog was dismissed with the escape key.
+    Escape,
+}
+
+pub trait Dialog {
+    /// Draws the dialog in the middle of `target`.
+    fn draw(&mut self, target: &mut CellAccessor);
+
+    /// Returns what `key` chooses in the dialog, if anything.
+    fn handle_key(&mut self, key: char) -> Option<Choice>;
+}
+
+/// A rustty dialog, whose choices are its buttons and, optionally, its listed items.
+///
+/// # Examples
+///
+/// ```
+/// use civng_tui::dialog::{Dialog, ChoiceDialog, Choice, KEY_ESCAPE};
+/// use civng_tui::menu_dialog::create_message_dialog;
+/// use civng_tui::theme::{Theme, ThemeKind, ColorDepth};
+///
+/// let theme = Theme::new(ThemeKind::Monochrome, ColorDepth::Basic);
+/// let mut d = ChoiceDialog::new(create_message_dialog("Hello", &theme)).with_items();
+/// match d.handle_key('B') {
+///     Some(Choice::Item(1)) => (),
+///     _ => panic!("B chooses the second item"),
+/// }
+/// // Our dialog can't be escaped from.
+/// assert!(d.handle_key(KEY_ESCAPE).is_none());
+/// ```
+pub struct ChoiceDialog {
+    dialog: ui::Dialog,
+    items: bool,
+    numbered_items: bool,
+    escapable: bool,
+}
+
+impl ChoiceDialog {
+    pub fn new(dialog: ui::Dialog) -> ChoiceDialog {
+        ChoiceDialog {
+            dialog: dialog,
+            items: false,
+            numbered_items: false,
+            escapable: false,
+        }
+    }
+
+    /// Lets upper case letters choose the items listed in the dialog.
+    pub fn with_items(mut self) -> ChoiceDialog {
+        self.items = true;
+        self
+    }
+
+    /// Lets digits choose the items listed in the dialog.
+    pub fn with_numbered_items(mut self) -> ChoiceDialog {
+        self.numbered_items = true;
+        self
+    }
+
+    /// Lets the escape key dismiss the dialog.
+    pub fn escapable(mut self) -> ChoiceDialog {
+        self.escapable = true;
+        self
+    }
+}
+
+impl Dialog for ChoiceDialog {
+    fn draw(&mut self, target: &mut CellAccessor) {
+        let w = self.dialog.window_mut();
+        w.align(target, HorizontalAlign::Middle, VerticalAlign::Middle, 0);
+        w.draw_into(target);
+    }
+
+    fn handle_key(&mut self, key: char) -> Option<Choice> {
+        if key == KEY_ESCAPE {
+            return if self.escapable { Some(Choice::Escape) } else { None };
+        }
+        if self.items {
+            if let Some(index) = item_index(key) {
+                return Some(Choice::Item(index));
+            }
+        }
+        if self.numbered_items {
+            if let Some(index) = numbered_item_index(key) {
+                return Some(Choice::NumberedItem(index));
+            }
+        }
+        self.dialog.result_for_key(key).map(Choice::Button)
+    }
+}
+
+/// A dialog in which we type a line of text.
+///
+/// Enter confirms the text, escape cancels it and backspace erases its last character.
+///
+/// # Examples
+///
+/// ```
+/// use civng_tui::dialog::{Dialog, TextInputDialog, Choice};
+/// use civng_tui::theme::{Theme, ThemeKind, ColorDepth};
+///
+/// let theme = Theme::new(ThemeKind::Monochrome, ColorDepth::Basic);
+/// let mut d = TextInputDialog::new("Name:", "Bob", 5, &theme);
+/// for key in "\x7fnnie!".chars() {
+///     assert!(d.handle_key(key).is_none());
+/// }
+/// match d.handle_key('\r') {
+///     // The text can't be longer than 5 characters.
+///     Some(Choice::Text(ref text)) if text == "Bonni" => (),
+///     _ => panic!("Enter confirms the text"),
+/// }
+/// ```
+pub struct TextInputDialog {
+    prompt: String,
+    text: String,
+    max_len: usize,
+    cell: Cell,
+    window: Widget,
+}
+
+impl TextInputDialog {
+    /// Creates a dialog asking for a text of up to `max_len` characters, starting with `text`.
+    pub fn new(prompt: &str, text: &str, max_len: usize, theme: &Theme) -> TextInputDialog {
+        let width = max(prompt.chars().count(), max_len + 1) + 6;
+        TextInputDialog {
+            prompt: prompt.to_owned(),
+            text: text.chars().take(max_len).collect(),
+            max_len: max_len,
+            cell: theme.dialog_cell(),
+            window: Widget::new(width, 6),
+        }
+    }
+}
+
+impl Dialog for TextInputDialog {
+    fn draw(&mut self, target: &mut CellAccessor) {
+        let w = &mut self.window;
+        w.clear(self.cell);
+        w.printline(3, 1, &self.prompt);
+        // The underscore stands for the cursor.
+        w.printline(3, 3, &format!("{}_", self.text));
+        w.draw_box();
+        w.align(target, HorizontalAlign::Middle, VerticalAlign::Middle, 0);
+        w.draw_into(target);
+    }
+
+    fn handle_key(&mut self, key: char) -> Option<Choice> {
+        match key {
+            KEY_ESCAPE => Some(Choice::Escape),
+            '\r' => Some(Choice::Text(self.text.clone())),
+            '\x7f' | '\x08' => {
+                self.text.pop();
+                None
+            }
+            k if !k.is_control() && self.text.chars().count() < self.max_len => {
+                self.text.push(k);
+                None
+            }
+            _ => None,
+        }
+    }
+}
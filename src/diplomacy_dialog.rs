@@ -0,0 +1,90 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+use rustty::CellAccessor;
+use rustty::ui::{Painter, HorizontalAlign, Dialog, DialogResult};
+
+use dialog::{item_key, numbered_item_key};
+use map::LiveMap;
+use unit::Player;
+use theme::Theme;
+
+/// Describes what our war with `player` cost each of us so far, and whether it offers peace.
+fn war_line(player: Player, map: &LiveMap) -> String {
+    let diplomacy = map.diplomacy();
+    let mut result = match diplomacy.war(Player::Me, player) {
+        Some(war) => {
+            format!("{} turns of war, {} units lost, {} killed, -{} happiness",
+                    war.turns,
+                    war.losses_of(Player::Me),
+                    war.losses_of(player),
+                    war.weariness(Player::Me))
+        }
+        None if diplomacy.at_war(Player::Me, player) => "No fighting yet".to_owned(),
+        None => String::new(),
+    };
+    if diplomacy.has_peace_offer(player, Player::Me) {
+        result.push_str(". Offers peace!");
+    }
+    result
+}
+
+/// Creates the diplomacy screen, telling our relations with each of `players`.
+///
+/// Players are listed with the key proposing peace to them or declaring war on them, and the key
+/// proposing to open or closing our borders. Below each of them, we see what our war costs us.
+/// "Ok" returns `DialogResult::Ok`.
+pub fn create_diplomacy_dialog(players: &[Player],
+                               message: &str,
+                               map: &LiveMap,
+                               theme: &Theme)
+                               -> Dialog {
+    let diplomacy = map.diplomacy();
+    let mut d = Dialog::new(70, 12 + players.len() * 2);
+    {
+        let w = d.window_mut();
+        w.clear(theme.dialog_cell());
+        let title = "Diplomacy";
+        let x = w.halign_line(title, HorizontalAlign::Middle, 1);
+        w.printline(x, 1, title);
+        for (i, &player) in players.iter().enumerate() {
+            let relation = if diplomacy.at_war(Player::Me, player) {
+                "At war"
+            } else {
+                "At peace"
+            };
+            let borders = if diplomacy.has_open_borders(Player::Me, player) {
+                "open"
+            } else {
+                "closed"
+            };
+            let s = format!("{} {} {:<6} | {:<8} | borders {}",
+                            item_key(i),
+                            numbered_item_key(i),
+                            player.name(),
+                            relation,
+                            borders);
+            w.printline(2, 3 + i * 2, &s);
+            w.printline(6, 4 + i * 2, &war_line(player, map));
+        }
+        let bottom = 3 + players.len() * 2;
+        let weariness = format!("War weariness: -{} happiness",
+                                diplomacy.war_weariness(Player::Me));
+        w.printline(2, bottom, &weariness);
+        w.printline(2,
+                    bottom + 2,
+                    "Press a player's letter to propose peace or declare war.");
+        w.printline(2,
+                    bottom + 3,
+                    "Press its number to propose to open borders or to close them.");
+        w.printline(2, bottom + 5, message);
+    }
+    d.add_button("Ok", 'o', DialogResult::Ok);
+    d.draw_buttons();
+    d.window_mut().draw_box();
+    d
+}
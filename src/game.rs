@@ -5,32 +5,103 @@
 // http://www.gnu.org/licenses/gpl-3.0.html
 //
 
-use std::path::Path;
-use std::collections::HashSet;
-use std::time::Duration;
+use std::cell::RefCell;
+use std::cmp::max;
+use std::io::{self, Write};
+use std::mem::replace;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::collections::{HashSet, VecDeque};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use rustty::{Event, Terminal};
-use rustty::ui::{Dialog, DialogResult, HorizontalAlign, VerticalAlign, Alignable};
+use rustty::ui::DialogResult;
 
-use hexpos::{Pos, Direction};
-use unit::{Unit, UnitID};
+use hexpos::{Pos, OffsetPos, PosPath, Direction};
+use unit::{Unit, UnitID, UnitType, Order, Player};
+use city::{CityID, CityStatus, Production};
 use screen::{Screen, DrawOptions};
-use civ5map::load_civ5map;
+use civ5map::{load_civ5map, MapLoadError};
 use map::LiveMap;
+use terrain::Terrain;
 use combat::CombatStats;
 use combat_result_window::create_combat_result_dialog;
+use combat_stats_window::create_combat_stats_dialog;
+use tile_info_dialog::create_tile_info_dialog;
 use combat_confirm_dialog::create_combat_confirm_dialog;
+use dialog::{Dialog, ChoiceDialog, TextInputDialog, Choice, KEY_ESCAPE};
+use city_dialog::{create_city_dialog, create_production_dialog, create_capture_dialog,
+                  CITY_PRODUCTION, CAPTURE_ANNEX, CAPTURE_PUPPET, CAPTURE_RAZE};
+use diplomacy_dialog::create_diplomacy_dialog;
+use pantheon_dialog::create_pantheon_dialog;
+use promotion_dialog::create_promotion_dialog;
 use selection::Selection;
-use ai::wander;
-use overhead::draw_overhead_map;
-use details_window::DetailsWindow;
+use ai::{play_turn, accepts_peace, accepts_open_borders};
+use overhead::{OverheadView, draw_overhead_map};
+use details_window::{DetailsWindow, Details};
+use menu_dialog::{create_menu_dialog, create_options_dialog, create_email_dialog,
+                  create_message_dialog, MENU_SAVE, MENU_LOAD, MENU_EMAIL, MENU_OPTIONS, MENU_QUIT,
+                  OPTIONS_THEME, OPTIONS_GLYPHS, EMAIL_SEND, EMAIL_RECEIVE};
+use savegame::{save_game, load_game};
+use pbem::{send_turn, receive_turn};
+use theme::Theme;
+use config::Config;
+use glyphs::{Glyphs, GlyphMode};
+use scenario::{Scenario, VictoryCondition};
+use quickstart::QuickStart;
+use difficulty::Difficulty;
+use script::{Trigger, ModScript, ScriptEvent, run_triggers, parse_action, run_action};
+use events::GameEvent;
+use blockers::{TurnBlocker, turn_blockers};
+use backend::Backend;
+use alert::{AlertKind, ring_bell};
+use export::{game_state_json, full_state_json, event_json};
+use spectator::SpectatorServer;
+use rng::{self, RngState};
 
 #[derive(Clone)]
 enum MainloopState {
     Normal,
-    CombatConfirm(CombatStats),
-    MessageDialog,
     OverheadMap,
+    /// AI players are playing their turn in the background. We can only scroll the map.
+    AiTurn,
+    /// We're picking, with a digit, the bookmark to jump to.
+    BookmarkJump,
+}
+
+/// What an open dialog is about, which tells what to do with what's chosen in it.
+#[derive(Clone)]
+enum DialogKind {
+    /// A message, after which we go back to the dialog below it, if any.
+    Message,
+    CombatConfirm(CombatStats),
+    Menu,
+    Options,
+    /// Play-by-email dialog, opened from the menu.
+    Email,
+    /// The game is won or lost. We quit when the message is dismissed.
+    GameOver,
+    /// Screen of one of our cities.
+    CityScreen(CityID),
+    /// Choice of what one of our cities builds, opened from its screen.
+    ProductionChoice(CityID),
+    /// Choice of what to do with a city we've just captured.
+    CaptureChoice(CityID),
+    /// Our relations with other players.
+    Diplomacy,
+    /// Choice of the belief of the pantheon we're founding.
+    PantheonChoice,
+    /// Choice of the promotion of one of our units.
+    PromotionChoice(UnitID),
+    /// Name we give to one of our units.
+    UnitName(UnitID),
+    /// Offset position of the tile we want to see.
+    FindTile,
+    /// Everything there is to know about a tile.
+    TileInfo,
+    /// Script action we type to set up the map while debugging.
+    DebugConsole,
 }
 
 /// Mode under which the game interprets movement keypresses.
@@ -46,6 +117,71 @@ enum MovementMode {
     Bombard,
 }
 
+/// A unit movement being animated on screen, one tile at a time.
+struct MoveAnimation {
+    unit_id: UnitID,
+    /// Positions that are left to be shown. The first one is the currently shown one.
+    steps: VecDeque<Pos>,
+    /// Whether the unit belongs to someone else than us.
+    enemy: bool,
+}
+
+/// A combat being animated on screen: the tiles of both sides flash and the damage they took
+/// floats above them.
+struct CombatAnimation {
+    positions: Vec<Pos>,
+    damage: Vec<(Pos, u8)>,
+    /// Animation steps left before the combat stops being shown.
+    steps_left: u64,
+    /// Whether the attack was made by someone else than us.
+    enemy: bool,
+}
+
+/// Something happening on the map, animated in the order it happened.
+enum Animation {
+    Move(MoveAnimation),
+    Combat(CombatAnimation),
+}
+
+impl Animation {
+    fn is_enemy(&self) -> bool {
+        match *self {
+            Animation::Move(ref a) => a.enemy,
+            Animation::Combat(ref a) => a.enemy,
+        }
+    }
+
+    /// Tile where the action is currently shown.
+    fn pos(&self) -> Pos {
+        match *self {
+            Animation::Move(ref a) => *a.steps.front().unwrap(),
+            Animation::Combat(ref a) => a.positions[a.positions.len() - 1],
+        }
+    }
+}
+
+/// Path of the file games are saved to and loaded from.
+const SAVE_PATH: &'static str = "civng.sav";
+/// Path of the file the game is saved to when it crashes. It can be loaded like any saved game.
+pub const CRASH_SAVE_PATH: &'static str = "civng-crash.sav";
+/// File through which we send and receive turns when playing by email.
+const PBEM_PATH: &'static str = "civng.pbem";
+
+/// Keys setting bookmarks, which are the shifted digits of US keyboards, from 0 to 9.
+const BOOKMARK_KEYS: &'static str = ")!@#$%^&*(";
+
+/// Longest name we can give a unit, which fits in the details window.
+const UNIT_NAME_MAX_LEN: usize = 16;
+
+/// Time during which alerted tiles are flashed.
+const FLASH_DURATION_MS: u64 = 400;
+/// Time during which a combat is shown, roughly, before we move on.
+const COMBAT_ANIMATION_MS: u64 = 1000;
+/// Frames of the spinner shown while AI players play their turn.
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+/// Time between two frames of the spinner.
+const SPINNER_DELAY_MS: u64 = 100;
+
 fn direction_for_key(key: char) -> Option<Direction> {
     match key {
         '8' | 'w' => Some(Direction::North),
@@ -58,43 +194,416 @@ fn direction_for_key(key: char) -> Option<Direction> {
     }
 }
 
-pub struct Game {
+/// What the AI worker sends back: the map and random state once AI players played their turn,
+/// or the message of the panic that stopped them and the random state as they left it.
+type AiTurnResult = Result<(LiveMap, RngState), (String, RngState)>;
+
+thread_local!(static PANIC_MESSAGE: RefCell<Option<String>> = RefCell::new(None));
+
+/// Makes panics keep their message for `take_panic_message()` instead of printing it.
+///
+/// While the terminal is in raw mode, a printed message would be lost. Each thread keeps the
+/// message of its own last panic, so that a panicking AI worker neither hides nor overwrites the
+/// message of a panic in our thread.
+///
+/// # Examples
+///
+/// ```
+/// use std::panic;
+/// use std::thread;
+/// use civng_tui::game::{keep_panic_messages, take_panic_message};
+///
+/// keep_panic_messages();
+/// let worker = thread::spawn(|| {
+///     let _ = panic::catch_unwind(|| panic!("worker trouble"));
+///     take_panic_message()
+/// });
+/// assert!(worker.join().unwrap().unwrap().contains("worker trouble"));
+/// assert_eq!(take_panic_message(), None);
+/// let _ = panic::catch_unwind(|| panic!("main trouble"));
+/// assert!(take_panic_message().unwrap().contains("main trouble"));
+/// let _ = panic::take_hook();
+/// ```
+pub fn keep_panic_messages() {
+    panic::set_hook(Box::new(|info| {
+        let message = info.to_string();
+        let _ = PANIC_MESSAGE.try_with(|m| *m.borrow_mut() = Some(message));
+    }));
+}
+
+/// Returns the message kept by `keep_panic_messages()` for the last panic of the current thread.
+pub fn take_panic_message() -> Option<String> {
+    PANIC_MESSAGE.with(|m| m.borrow_mut().take())
+}
+
+pub struct Game<B: Backend> {
     state: MainloopState,
     movemode: MovementMode,
-    term: Terminal,
+    term: B,
     screen: Screen,
     map: LiveMap,
     turn: u16,
     selection: Selection,
     show_pos_markers: bool,
+    show_terrain_analysis: bool,
     details_window: DetailsWindow,
-    current_dialog: Option<Dialog>,
+    /// Open dialogs, the one on top last.
+    dialogs: Vec<(DialogKind, Box<Dialog>)>,
+    animations: VecDeque<Animation>,
+    /// Time each step of a movement animation stays on screen. Zero disables animations.
+    animation_delay: Duration,
+    /// Time each step of the animation of an enemy action stays on screen.
+    ai_animation_delay: Duration,
+    /// Whether enemy actions are left unanimated.
+    quick_moves: bool,
+    /// Whether the screen was centered on the current enemy action.
+    animation_centered: bool,
+    /// Glyph file overriding built-in glyphs, if any.
+    glyphs_file: Option<PathBuf>,
+    /// Ways to win the game. When empty, the game never ends.
+    victory_conditions: Vec<VictoryCondition>,
+    /// Scripted events of the scenario.
+    triggers: Vec<Trigger>,
+    /// Mod script of the scenario, if any.
+    script: Option<ModScript>,
+    /// Messages from triggers and cities waiting to be shown.
+    messages: Vec<String>,
+    /// Whether we start a new turn as soon as all our units are done.
+    auto_end_turn: bool,
+    /// Time we have to play each turn, if turns are timed.
+    turn_timer: Option<Duration>,
+    /// When our current turn ends by itself, if turns are timed.
+    turn_deadline: Option<Instant>,
+    /// Unit to which we're giving a series of orders, if any.
+    queueing_unit: Option<UnitID>,
+    /// How we're alerted when enemies attack our units.
+    alert_attacked: AlertKind,
+    /// How we're alerted when one of our units dies.
+    alert_killed: AlertKind,
+    /// How we're alerted when enemies come next to one of our cities or attack it.
+    alert_siege: AlertKind,
+    /// Tiles being flashed because of an alert.
+    flashing: Vec<Pos>,
+    /// Where the game state is dumped at the start of each turn, if anywhere.
+    state_dump: Option<Box<Write>>,
+    /// Whether we play by email, in which case our opponent isn't played by the AI.
+    play_by_email: bool,
+    /// Turn we've sent by email, if we're waiting for our opponent's.
+    pbem_sent: Option<u16>,
+    /// Server to which we broadcast the game, if anyone can spectate it.
+    spectators: Option<SpectatorServer>,
+    /// Our cities we've asked this turn whether to strike an enemy.
+    strike_prompted: HashSet<CityID>,
+    /// Whether we've been asked this turn which belief to adopt for our pantheon.
+    pantheon_prompted: bool,
+    /// Result of the attack we've just made, shown once its animation is over.
+    combat_result: Option<CombatStats>,
+    /// Where the map and random state come back from once AI players played their turn in the
+    /// background, along with a copy of the random state to go back to should they fail.
+    /// Meanwhile, `map` is a copy of the map as it was when our turn ended.
+    ai_turn: Option<(Receiver<AiTurnResult>, RngState)>,
+    /// Current frame of the spinner shown during the AI turn.
+    spinner: usize,
+    /// Part of the map the overhead map shows.
+    overhead_view: OverheadView,
+    /// Positions we can jump back to, by digit.
+    bookmarks: [Option<Pos>; 10],
+    /// Number of times we were taken to a turn blocker this turn.
+    blockers_visited: usize,
+    /// Whether we can open the debug console, which lets us change the map at will.
+    debug_console: bool,
 }
 
-impl Game {
-    pub fn new(map_path: &Path) -> Game {
-        let term = Terminal::new().unwrap();
-        let screen = Screen::new(&term);
+/// Returns the glyph set for `mode`, overridden by `glyphs_file` if there's one.
+fn load_glyphs(mode: GlyphMode, glyphs_file: Option<&Path>) -> Glyphs {
+    let mut result = Glyphs::new(mode);
+    if let Some(path) = glyphs_file {
+        result.load_overrides(path);
+    }
+    result
+}
+
+impl<B: Backend> Game<B> {
+    pub fn new(term: B, map_path: &Path, config: &Config) -> Result<Game<B>, MapLoadError> {
+        let terrainmap = try!(load_civ5map(map_path));
+        Ok(Game::with_map(term, LiveMap::new(terrainmap), config))
+    }
+
+    /// Creates a game played on `map`, which may already contain units.
+    ///
+    /// With a `turn_timer` in `config`, the details window counts down the time left and the turn
+    /// ends by itself when it runs out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use civng_tui::game::Game;
+    /// use civng_tui::backend::FakeBackend;
+    /// use civng_tui::config::Config;
+    /// use civng_tui::map::LiveMap;
+    /// use civng_tui::terrain::TerrainMap;
+    /// use civng_tui::unit::{Unit, UnitType, Player};
+    /// use civng_tui::hexpos::Pos;
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(10, 10));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// let mut config = Config::new();
+    /// config.turn_timer = 1;
+    /// let mut game = Game::with_map(FakeBackend::new(80, 30), map, &config);
+    /// game.new_turn();
+    /// game.draw();
+    /// assert!(game.term().lines()[26].contains("│ Turn 1 (0:00)     │"));
+    /// thread::sleep(Duration::from_millis(1100));
+    /// let mut tries = 0;
+    /// while !game.term().lines()[26].contains("Turn 2") && tries < 500 {
+    ///     game.handle_events();
+    ///     game.draw();
+    ///     tries += 1;
+    ///     thread::sleep(Duration::from_millis(10));
+    /// }
+    /// assert!(game.term().lines()[26].contains("Turn 2"));
+    /// assert!(game.term().lines().iter().any(|l| l.contains("Time's up!")));
+    /// ```
+    pub fn with_map(term: B, mut map: LiveMap, config: &Config) -> Game<B> {
+        map.set_rules(config.rules);
+        let mut screen = Screen::new(&term);
+        screen.set_theme(Theme::detect(config.theme));
+        let glyphs_file = config.glyphs_file.clone();
+        screen.set_glyphs(load_glyphs(config.glyphs, glyphs_file.as_ref().map(|p| p.as_path())));
         let details_window = DetailsWindow::new(&term);
         Game {
             state: MainloopState::Normal,
             movemode: MovementMode::Normal,
             term: term,
             screen: screen,
-            map: {
-                let terrainmap = load_civ5map(map_path);
-                LiveMap::new(terrainmap)
-            },
+            map: map,
             turn: 0,
             selection: Selection::new(),
             show_pos_markers: false,
+            show_terrain_analysis: false,
             details_window: details_window,
-            current_dialog: None,
+            dialogs: Vec::new(),
+            animations: VecDeque::new(),
+            animation_delay: Duration::from_millis(150),
+            ai_animation_delay: Duration::from_millis(config.ai_animation_delay),
+            quick_moves: config.quick_moves,
+            animation_centered: false,
+            glyphs_file: glyphs_file,
+            victory_conditions: Vec::new(),
+            triggers: Vec::new(),
+            script: None,
+            messages: Vec::new(),
+            auto_end_turn: config.auto_end_turn,
+            turn_timer: if config.turn_timer > 0 {
+                Some(Duration::from_secs(config.turn_timer))
+            } else {
+                None
+            },
+            turn_deadline: None,
+            queueing_unit: None,
+            alert_attacked: config.alert_attacked,
+            alert_killed: config.alert_killed,
+            alert_siege: config.alert_siege,
+            flashing: Vec::new(),
+            state_dump: None,
+            play_by_email: false,
+            pbem_sent: None,
+            spectators: None,
+            strike_prompted: HashSet::new(),
+            pantheon_prompted: false,
+            combat_result: None,
+            ai_turn: None,
+            spinner: 0,
+            overhead_view: OverheadView::new(),
+            bookmarks: [None; 10],
+            blockers_visited: 0,
+            debug_console: false,
+        }
+    }
+
+    /// Creates a game set up by the scenario file at `path`.
+    ///
+    /// The game is played at `difficulty` unless the scenario has its own difficulty.
+    pub fn from_scenario(term: B,
+                         path: &Path,
+                         difficulty: Difficulty,
+                         config: &Config)
+                         -> io::Result<Game<B>> {
+        let scenario = try!(Scenario::load(path));
+        let map = try!(scenario.build_map(difficulty));
+        let mut result = Game::with_map(term, map, config);
+        result.victory_conditions = scenario.victory_conditions;
+        result.triggers = scenario.triggers;
+        result.script = scenario.script;
+        Ok(result)
+    }
+
+    /// Creates a game on a freshly generated map, set up as `options` says.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::game::Game;
+    /// use civng_tui::backend::FakeBackend;
+    /// use civng_tui::config::Config;
+    /// use civng_tui::quickstart::QuickStart;
+    /// use civng_tui::mapgen::MapPreset;
+    ///
+    /// let options = QuickStart::new(MapPreset::Archipelago);
+    /// let game = Game::quick_start(FakeBackend::new(80, 30), &options, &Config::new());
+    /// assert!(game.dump_state_json().contains("\"Washington\""));
+    /// ```
+    pub fn quick_start(term: B, options: &QuickStart, config: &Config) -> Game<B> {
+        Game::with_map(term, options.build_map(), config)
+    }
+
+    /// Sets the time each step of a movement animation stays on screen.
+    ///
+    /// A zero delay disables movement animations altogether.
+    pub fn set_animation_delay(&mut self, delay: Duration) {
+        self.animation_delay = delay;
+    }
+
+    /// Writes the game state, as returned by `dump_state_json()`, to `dest` at the start of each
+    /// turn, one line per turn.
+    pub fn set_state_dump(&mut self, dest: Box<Write>) {
+        self.state_dump = Some(dest);
+    }
+
+    /// Lets us open the debug console with the backquote key.
+    pub fn enable_debug_console(&mut self) {
+        self.debug_console = true;
+    }
+
+    /// Broadcasts the game to spectators of `server`.
+    pub fn set_spectator_server(&mut self, server: SpectatorServer) {
+        self.spectators = Some(server);
+        self.broadcast_state();
+    }
+
+    fn broadcast_state(&self) {
+        if let Some(ref server) = self.spectators {
+            server.broadcast_state(&full_state_json(&self.map, self.turn));
+        }
+    }
+
+    /// Returns the game state as we know it, in the JSON format described in `export`.
+    pub fn dump_state_json(&self) -> String {
+        game_state_json(&self.map, self.turn, Player::Me)
+    }
+
+    /// Returns whether the actions of `unit_id` are played by someone else than us.
+    ///
+    /// Units that aren't there anymore died during the AI turn: our own actions are animated as
+    /// soon as we make them, before dead units are purged.
+    fn is_enemy_unit(&self, unit_id: UnitID) -> bool {
+        self.map.units().find(unit_id).map_or(true, |u| u.owner() != Player::Me)
+    }
+
+    /// Whether we skip the animation of an action, by an enemy if `enemy`, happening at
+    /// `positions`.
+    ///
+    /// Enemy actions aren't animated with quick moves or when we can't see any of their tiles.
+    fn skips_animation(&self, enemy: bool, positions: &[Pos]) -> bool {
+        if self.animation_delay == Duration::from_millis(0) {
+            return true;
+        }
+        if !enemy {
+            return false;
+        }
+        let in_sight = self.map.fog(Player::Me).map_or(true, |f| {
+            positions.iter().any(|&p| f.is_visible(p))
+        });
+        self.quick_moves || !in_sight
+    }
+
+    /// Queues the animation of `unit_id` moving along `path`.
+    ///
+    /// Single-step movements aren't animated: there's no intermediate position to show.
+    fn queue_move_animation(&mut self, unit_id: UnitID, path: PosPath) {
+        let enemy = self.is_enemy_unit(unit_id);
+        if path.steps() <= 1 || self.skips_animation(enemy, path.stack()) {
+            return;
+        }
+        if self.animations.is_empty() {
+            self.animation_centered = false;
+        }
+        let stack = path.stack();
+        self.animations.push_back(Animation::Move(MoveAnimation {
+            unit_id: unit_id,
+            steps: stack[1..stack.len() - 1].iter().cloned().collect(),
+            enemy: enemy,
+        }));
+    }
+
+    /// Queues the animation of the combat resolved with `stats`, if we can see it.
+    ///
+    /// Attackers only float their damage when they can take any, that is in melee. A withdrawal
+    /// only flashes the tiles.
+    fn queue_combat_animation(&mut self, stats: &CombatStats) {
+        let enemy = match stats.attacker_city {
+            Some(city_id) => self.map.cities().get(city_id).owner() != Player::Me,
+            None => self.is_enemy_unit(stats.attacker_id),
+        };
+        let positions = vec![stats.attacker_pos, stats.defender_pos];
+        if self.skips_animation(enemy, &positions) {
+            return;
+        }
+        let mut damage = Vec::new();
+        if !stats.withdrew {
+            damage.push((stats.defender_pos, stats.dmg_to_defender));
+            if !stats.ranged {
+                damage.push((stats.attacker_pos, stats.dmg_to_attacker));
+            }
+        }
+        if self.animations.is_empty() {
+            self.animation_centered = false;
+        }
+        let delay = if enemy {
+            self.ai_animation_delay
+        } else {
+            self.animation_delay
+        };
+        let delay_ms = delay.as_secs() * 1000 + delay.subsec_nanos() as u64 / 1000000;
+        self.animations.push_back(Animation::Combat(CombatAnimation {
+            positions: positions,
+            damage: damage,
+            steps_left: max(COMBAT_ANIMATION_MS / max(delay_ms, 1), 1),
+            enemy: enemy,
+        }));
+    }
+
+    /// Time the current animation step stays on screen. Enemy actions have their own speed.
+    fn current_animation_delay(&self) -> Duration {
+        match self.animations.front() {
+            Some(anim) if anim.is_enemy() => self.ai_animation_delay,
+            _ => self.animation_delay,
+        }
+    }
+
+    /// Shows the next step of the current animation.
+    fn advance_animation(&mut self) {
+        let finished = match self.animations.front_mut() {
+            Some(&mut Animation::Move(ref mut anim)) => {
+                let _ = anim.steps.pop_front();
+                anim.steps.is_empty()
+            }
+            Some(&mut Animation::Combat(ref mut anim)) => {
+                anim.steps_left -= 1;
+                anim.steps_left == 0
+            }
+            None => false,
+        };
+        if finished {
+            let _ = self.animations.pop_front();
+            self.animation_centered = false;
         }
     }
 
     fn active_unit(&self) -> Option<&Unit> {
-        self.selection.unit_id.map(|uid| self.map.units().get(uid))
+        self.selection.unit_id.and_then(|uid| self.map.units().find(uid))
     }
 
     fn cycle_active_unit(&mut self) {
@@ -103,26 +612,252 @@ impl Game {
             None => self.map.units().max_id() + 1,
         };
         self.selection.unit_id = self.map.units().next_active_unit(active_index);
-        if let Some(unitpos) = self.active_unit().map(|u| u.pos()) {
+        if let Some((unit_id, unitpos)) = self.active_unit().map(|u| (u.id(), u.pos())) {
+            self.map.bring_to_top(unit_id);
             let terrainmap = self.map.terrain();
             self.screen.center_on_pos(unitpos, terrainmap);
         }
     }
 
+    /// Makes the next of our units sharing the active unit's tile the active one, on top of the
+    /// others.
+    fn cycle_units_on_tile(&mut self) {
+        let (active_id, pos) = match self.active_unit() {
+            Some(u) => (u.id(), u.pos()),
+            None => return,
+        };
+        let mut ids: Vec<UnitID> = self.map
+                                       .units()
+                                       .all_at_pos(pos)
+                                       .iter()
+                                       .filter(|u| u.owner() == Player::Me)
+                                       .map(|u| u.id())
+                                       .collect();
+        ids.sort();
+        let next_id = ids.iter().cloned().find(|&id| id > active_id).unwrap_or(ids[0]);
+        self.selection.unit_id = Some(next_id);
+        self.map.bring_to_top(next_id);
+        self.update_details();
+    }
+
+    /// Returns whether all our units are done for this turn and we have no choice left to make.
+    ///
+    /// If we don't have any unit left, our turn is never done: there's nothing to wait for.
+    fn turn_done(&self) -> bool {
+        self.map.units().my_units().next().is_some() &&
+        turn_blockers(&self.map, Player::Me).is_empty()
+    }
+
+    /// Returns whether our units are done for this turn but some cities or promotions still wait
+    /// on us.
+    fn has_pending_choices(&self) -> bool {
+        let blockers = turn_blockers(&self.map, Player::Me);
+        !blockers.is_empty() &&
+        blockers.iter().all(|b| {
+            match *b {
+                TurnBlocker::IdleUnit(_) => false,
+                _ => true,
+            }
+        })
+    }
+
+    /// Makes `unit_id` the active unit and centers the screen on it.
+    fn select_unit(&mut self, unit_id: UnitID) {
+        self.selection.unit_id = Some(unit_id);
+        self.map.bring_to_top(unit_id);
+        let pos = self.map.units().get(unit_id).pos();
+        self.screen.center_on_pos(pos, self.map.terrain());
+        self.update_details();
+    }
+
+    /// Takes us to the next choice we have to make before our turn ends or, if there's none left,
+    /// ends our turn.
+    ///
+    /// We go through blockers in turn, so that those we leave aside don't keep us from the others.
+    fn visit_next_blocker(&mut self) {
+        let blockers = turn_blockers(&self.map, Player::Me);
+        if blockers.is_empty() {
+            self.new_turn();
+            return;
+        }
+        let blocker = blockers[self.blockers_visited % blockers.len()];
+        self.blockers_visited += 1;
+        match blocker {
+            TurnBlocker::IdleUnit(unit_id) => {
+                self.select_unit(unit_id);
+                self.flashing = vec![self.map.units().get(unit_id).pos()];
+            }
+            TurnBlocker::NoProduction(city_id) => {
+                self.open_city(city_id);
+                self.open_production(city_id);
+            }
+            TurnBlocker::Promotion(unit_id) => {
+                self.select_unit(unit_id);
+                self.open_promotions(unit_id);
+            }
+        }
+    }
+
+    /// Applies `order` to the active unit, if any, and moves on to the next unit.
+    fn give_order<F: FnOnce(&mut LiveMap, UnitID)>(&mut self, order: F) {
+        if let Some(unit_id) = self.active_unit().map(|u| u.id()) {
+            order(&mut self.map, unit_id);
+            self.cycle_active_unit();
+            self.update_details();
+        }
+    }
+
+    /// Gives `order` to the active unit and carries it out.
+    ///
+    /// If we're queueing orders for the active unit, `order` is added to its queue instead, and
+    /// the queue is carried out once we're done queueing.
+    fn assign_order(&mut self, order: Order) {
+        if self.queueing_unit.is_some() && self.queueing_unit == self.selection.unit_id {
+            self.map.queue_order(self.queueing_unit.unwrap(), order);
+            self.update_details();
+        } else {
+            self.give_order(|map, unit_id| {
+                map.set_order(unit_id, Some(order));
+                map.carry_out_unit_orders(unit_id);
+            });
+        }
+    }
+
     fn update_details(&mut self) {
+        let queueing = self.queueing_unit.is_some() && self.queueing_unit == self.selection.unit_id;
+        let enemy_turn = format!("Enemy turn {}", SPINNER[self.spinner % SPINNER.len()]);
         let movemode = match self.movemode {
+            _ if self.ai_turn.is_some() => &enemy_turn[..],
             MovementMode::Scroll => "Scroll Mode",
+            MovementMode::Move if !self.selection_is_reachable() => "Move Mode (out of reach)",
             MovementMode::Move => "Move Mode",
+            _ if self.is_jumping() => "Bookmark (0-9)",
+            _ if queueing => "Queueing (o: done)",
+            _ if self.turn_done() => "Next turn (Enter)",
+            _ if self.has_pending_choices() => "Choices pending (Enter)",
             _ => "",
         };
         let selected_pos = self.selection.pos.or(self.active_unit().map(|u| u.pos()));
-        self.details_window.update(selected_pos, &self.map, self.turn, movemode);
+        let preview = self.combat_preview();
+        let time_left = self.time_left();
+        let details = Details {
+            selected_pos: selected_pos,
+            turn: self.turn,
+            time_left: time_left,
+            movemode: movemode,
+            preview: preview.as_ref(),
+        };
+        self.details_window.update(&details, &self.map, self.screen.theme());
     }
 
-    fn play_ai_turn(&mut self) {
-        let enemy_ids: Vec<UnitID> = self.map.units().enemy_units().map(|u| u.id()).collect();
-        for enemy_id in enemy_ids.iter() {
-            wander(*enemy_id, &mut self.map);
+    /// Returns the combat the active unit would get into if we confirmed our selection in move or
+    /// bombard mode, if the selection rests on an enemy we can see.
+    ///
+    /// We play the move on a copy of the map, so that the preview takes the steps a melee unit
+    /// takes before attacking into account.
+    fn combat_preview(&self) -> Option<CombatStats> {
+        let (unit_id, pos) = match (self.active_unit().map(|u| u.id()), self.selection.pos) {
+            (Some(unit_id), Some(pos)) => (unit_id, pos),
+            _ => return None,
+        };
+        let is_visible = self.map.fog(Player::Me).map_or(true, |f| f.is_visible(pos));
+        match self.map.units().top_at_pos(pos) {
+            Some(u) if u.owner() != Player::Me && is_visible => (),
+            _ => return None,
+        }
+        let mut map = self.map.clone();
+        match self.movemode {
+            MovementMode::Move => map.moveunit_to(unit_id, pos),
+            MovementMode::Bombard => map.bombard_at(unit_id, pos),
+            _ => None,
+        }
+    }
+
+    /// Makes AI players play their turn in a worker thread.
+    ///
+    /// The worker gets the map and the random state, so that the game plays out exactly as if
+    /// the AI played in our thread. We keep showing a copy of the map until the worker sends them
+    /// back, which `handle_events()` waits for.
+    fn start_ai_turn(&mut self) {
+        let snapshot = self.map.clone();
+        let mut map = replace(&mut self.map, snapshot);
+        let backup = rng::copy_state();
+        let rng_state = rng::take_state();
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            rng::restore_state(rng_state);
+            let played = panic::catch_unwind(AssertUnwindSafe(|| {
+                for player in map.units().ai_players() {
+                    play_turn(player, &mut map);
+                }
+            }));
+            let result = match played {
+                Ok(()) => Ok((map, rng::take_state())),
+                Err(_) => {
+                    let message = take_panic_message();
+                    Err((message.unwrap_or_else(|| "unknown error".to_owned()), rng::take_state()))
+                }
+            };
+            let _ = sender.send(result);
+        });
+        self.ai_turn = Some((receiver, backup));
+        self.turn_deadline = None;
+        self.spinner = 0;
+        self.state = MainloopState::AiTurn;
+        self.update_details();
+    }
+
+    /// Starts our turn if AI players are done with theirs.
+    ///
+    /// Should they fail, we tell the player and go on with the map as we left it and the random
+    /// state as it was before the AI turn.
+    fn check_ai_turn(&mut self) {
+        let result = match self.ai_turn {
+            Some((ref receiver, _)) => receiver.try_recv(),
+            None => return,
+        };
+        if let Err(TryRecvError::Empty) = result {
+            self.spinner += 1;
+            self.update_details();
+            return;
+        }
+        let (_, backup) = self.ai_turn.take().unwrap();
+        let failure = match result {
+            Ok(Ok((map, rng_state))) => {
+                self.map = map;
+                rng::restore_state(rng_state);
+                None
+            }
+            Ok(Err((message, mut rng_state))) => {
+                rng_state.rewind(backup);
+                rng::restore_state(rng_state);
+                Some(format!("The AI turn failed: {}", message))
+            }
+            // The worker died without a word, taking our audit log with it.
+            Err(_) => {
+                rng::restore_state(backup);
+                Some("The AI turn failed.".to_owned())
+            }
+        };
+        self.state = MainloopState::Normal;
+        self.start_turn();
+        if let Some(msg) = failure {
+            self.show_message(&msg);
+        }
+    }
+
+    fn handle_aiturn_keypress(&mut self, key: char) {
+        match key {
+            'K' => self.screen.scroll_pages(0, -1, self.map.terrain()),
+            'J' => self.screen.scroll_pages(0, 1, self.map.terrain()),
+            'H' => self.screen.scroll_pages(-1, 0, self.map.terrain()),
+            'L' => self.screen.scroll_pages(1, 0, self.map.terrain()),
+            '5' => self.screen.scroll_to_center(self.map.terrain()),
+            k => {
+                if let Some(d) = direction_for_key(k) {
+                    self.screen.scroll(Pos::origin().neighbor(d));
+                }
+            }
         }
     }
 
@@ -130,16 +865,22 @@ impl Game {
         &self.map
     }
 
+    /// Returns the backend we draw on, which lets tests look at what we drew.
+    pub fn term(&self) -> &B {
+        &self.term
+    }
+
     pub fn add_unit(&mut self, unit: Unit) {
         self.map.add_unit(unit)
     }
 
     pub fn moveunit_to(&mut self, target: Pos) -> Option<CombatStats> {
-        if self.selection.unit_id.is_none() {
-            return None;
-        }
-        let result = self.map.moveunit_to(self.selection.unit_id.unwrap(), target);
-        if self.active_unit().unwrap().is_exhausted() {
+        let unit_id = match self.active_unit() {
+            Some(unit) => unit.id(),
+            None => return None,
+        };
+        let result = self.map.moveunit_to(unit_id, target);
+        if self.active_unit().map_or(true, |u| u.is_exhausted()) {
             self.cycle_active_unit();
         }
         self.update_details();
@@ -153,11 +894,70 @@ impl Game {
         }
     }
 
+    /// Whether the selector can move to `pos` in the current mode.
+    ///
+    /// In Bombard mode, it stays within range of the active unit.
+    fn can_select(&self, pos: Pos) -> bool {
+        if self.map.terrain().get_terrain(pos) == Terrain::OutOfBounds {
+            return false;
+        }
+        match self.active_unit() {
+            Some(unit) if self.movemode == MovementMode::Bombard => {
+                self.map.bombardable_pos(unit.id()).contains_key(&pos)
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether the selector rests on an enemy the active unit can bombard.
+    fn selection_is_target(&self) -> bool {
+        match (self.selection.pos, self.active_unit()) {
+            (Some(pos), Some(unit)) => self.map.bombard_targets(unit.id()).contains(&pos),
+            _ => false,
+        }
+    }
+
+    /// Whether the active unit can get to the selector this turn, or is already there.
+    fn selection_is_reachable(&self) -> bool {
+        match (self.selection.pos, self.active_unit()) {
+            (Some(pos), Some(unit)) => {
+                pos == unit.pos() || self.map.reachable_pos(unit.id()).contains_key(&pos)
+            }
+            _ => false,
+        }
+    }
+
+    /// Moves the selector to the next enemy the active unit can attack, nearest first.
+    ///
+    /// After the farthest one, it's back to the nearest one.
+    fn select_next_target(&mut self) {
+        let unit_id = match self.active_unit() {
+            Some(unit) => unit.id(),
+            None => return,
+        };
+        let targets = match self.movemode {
+            MovementMode::Move => self.map.attack_targets(unit_id),
+            MovementMode::Bombard => self.map.bombard_targets(unit_id),
+            _ => return,
+        };
+        let next = match self.selection.pos.and_then(|p| targets.iter().position(|&t| t == p)) {
+            Some(index) => targets.get((index + 1) % targets.len()),
+            None => targets.first(),
+        };
+        if let Some(&target) = next {
+            self.selection.pos = Some(target);
+            self.screen.ensure_visible(target, self.map.terrain());
+            self.update_details();
+        }
+    }
+
     pub fn bombard(&mut self) -> Option<CombatStats> {
-        if let Some(target_pos) = self.selection.pos {
-            let source_unit = self.selection.unit_id.unwrap();
+        let source_unit = self.active_unit().map(|u| u.id());
+        if let (Some(source_unit), Some(target_pos)) = (source_unit, self.selection.pos) {
             let result = self.map.bombard_at(source_unit, target_pos);
-            self.cycle_active_unit();
+            if self.active_unit().map_or(true, |u| u.is_exhausted()) {
+                self.cycle_active_unit();
+            }
             self.update_details();
             result
         } else {
@@ -165,29 +965,255 @@ impl Game {
         }
     }
 
+    /// Ends our turn and lets the AI players play theirs, or starts the first turn.
+    ///
+    /// AI actions we can see are animated once the AI turn is over, with the screen centered on
+    /// them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use civng_tui::game::Game;
+    /// use civng_tui::backend::{Backend, FakeBackend};
+    /// use civng_tui::config::Config;
+    /// use civng_tui::map::LiveMap;
+    /// use civng_tui::terrain::TerrainMap;
+    /// use civng_tui::unit::{Unit, UnitType, Player};
+    /// use civng_tui::hexpos::{OffsetPos, Direction};
+    ///
+    /// fn shows_damage(lines: &[String]) -> bool {
+    ///     let is_digit = |c: char| c.is_digit(10);
+    ///     lines.iter().any(|l| l.split('-').skip(1).any(|s| s.starts_with(is_digit)))
+    /// }
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(30, 30));
+    /// let pos = OffsetPos::new(15, 20).to_pos();
+    /// map.add_unit(Unit::with_state(UnitType::Melee, Player::Me, pos, 2, 30));
+    /// map.add_unit(Unit::new(UnitType::Ranged, Player::Me, pos.neighbor(Direction::North)));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::AI(0), pos.neighbor(Direction::SouthEast)));
+    /// let mut term = FakeBackend::new(80, 30);
+    /// // Scroll the view far away from our units.
+    /// term.push_key('S');
+    /// for _ in 0..30 {
+    ///     term.push_key('w');
+    /// }
+    /// term.push_key('S');
+    /// let mut game = Game::with_map(term, map, &Config::new());
+    /// game.new_turn();
+    /// for _ in 0..32 {
+    ///     game.handle_events();
+    /// }
+    /// game.draw();
+    /// assert!(!game.term().lines().iter().any(|l| l.contains(" R ")));
+    /// // The AI attacks our wounded melee unit.
+    /// game.new_turn();
+    /// let mut tries = 0;
+    /// while game.term().lines()[28].contains("Enemy turn") || tries == 0 {
+    ///     assert!(tries < 500);
+    ///     game.handle_events();
+    ///     game.draw();
+    ///     tries += 1;
+    ///     thread::sleep(Duration::from_millis(10));
+    /// }
+    /// let lines = game.term().lines();
+    /// assert!(lines.iter().any(|l| l.contains(" R ")));
+    /// assert!(shows_damage(&lines));
+    /// ```
     pub fn new_turn(&mut self) {
-        if self.turn > 0 {
-            self.play_ai_turn();
+        if self.pbem_sent.is_some() {
+            self.show_message("We're waiting for our opponent's turn. Receive it from the menu.");
+            return;
         }
+        if self.turn > 0 && !self.play_by_email {
+            self.start_ai_turn();
+        } else {
+            self.start_turn();
+        }
+    }
+
+    /// Starts our next turn, once other players are done with theirs.
+    fn start_turn(&mut self) {
         self.turn += 1;
+        self.turn_deadline = self.turn_timer.map(|t| Instant::now() + t);
         self.map.refresh();
+        self.strike_prompted.clear();
+        self.pantheon_prompted = false;
+        self.blockers_visited = 0;
+        self.fire_event(ScriptEvent::TurnStart(self.turn));
+        self.map.carry_out_orders(Player::Me);
         self.cycle_active_unit();
-        self.update_details()
+        self.update_details();
+        self.check_game_over();
+        // Spectators get events of the AI turn before the state they led to.
+        self.process_map_events();
+        self.broadcast_state();
+        let json = self.dump_state_json();
+        if let Some(ref mut dest) = self.state_dump {
+            let _ = writeln!(dest, "{}", json);
+        }
+    }
+
+    /// Returns the time left before our turn ends by itself, if turns are timed.
+    fn time_left(&self) -> Option<Duration> {
+        self.turn_deadline.map(|deadline| {
+            let now = Instant::now();
+            if now < deadline { deadline - now } else { Duration::from_secs(0) }
+        })
+    }
+
+    /// Ends our turn because its time is up, dropping whatever we were in the middle of.
+    ///
+    /// We wait for our animations to be over and never end a game that's over.
+    fn end_timed_out_turn(&mut self) {
+        let game_over = self.dialogs.iter().any(|&(ref kind, _)| match *kind {
+            DialogKind::GameOver => true,
+            _ => false,
+        });
+        if game_over || !self.animations.is_empty() || self.ai_turn.is_some() ||
+           self.pbem_sent.is_some() {
+            return;
+        }
+        self.turn_deadline = None;
+        self.dialogs.clear();
+        self.state = MainloopState::Normal;
+        self.movemode = MovementMode::Normal;
+        self.selection.pos = None;
+        self.queueing_unit = None;
+        self.messages.push("Time's up!".to_owned());
+        self.new_turn();
+    }
+
+    /// Returns the message telling us that `city_id` built `production`, if it concerns us.
+    ///
+    /// We hear about what our cities build and about wonders built elsewhere in the world.
+    fn built_message(&self, city_id: CityID, production: Production) -> Option<String> {
+        let cities = self.map.cities();
+        let city = cities.get(city_id);
+        let name = cities.production_name(production);
+        match production {
+            _ if city.owner() == Player::Me => Some(format!("{} built {}.", city.name(), name)),
+            Production::Building(b) if cities.buildings().get(b).wonder => {
+                Some(format!("{} was built elsewhere in the world.", name))
+            }
+            _ => None,
+        }
+    }
+
+    fn fire_event(&mut self, event: ScriptEvent) {
+        let messages = run_triggers(&self.triggers, event, &mut self.map);
+        self.messages.extend(messages);
+        if let Some(ref script) = self.script {
+            self.messages.extend(script.run_hooks(event, &mut self.map));
+        }
+    }
+
+    /// Alerts the player of an event at `pos` the way `kind` says.
+    fn alert(&mut self, kind: AlertKind, pos: Pos) {
+        if kind.rings_bell() {
+            ring_bell();
+        }
+        if kind.flashes() && !self.flashing.contains(&pos) {
+            self.flashing.push(pos);
+        }
+    }
+
+    /// Handles events that happened on the map since the last call.
+    ///
+    /// Movements and combats trigger scripted events and are animated. Attacks on our units, their
+    /// deaths and sieges of our cities trigger alerts.
+    fn process_map_events(&mut self) {
+        for event in self.map.take_events() {
+            if let Some(ref server) = self.spectators {
+                server.broadcast(&event_json(&event));
+            }
+            if let Some(script_event) = ScriptEvent::from_game_event(&event) {
+                self.fire_event(script_event);
+            }
+            match event {
+                GameEvent::UnitMoved(unit_id, path) => self.queue_move_animation(unit_id, path),
+                GameEvent::CombatResolved(ref stats) => self.queue_combat_animation(stats),
+                GameEvent::UnitAttacked(_, Player::Me, pos) => {
+                    let kind = self.alert_attacked;
+                    self.alert(kind, pos);
+                }
+                GameEvent::UnitKilled(_, Player::Me, pos) => {
+                    let kind = self.alert_killed;
+                    self.alert(kind, pos);
+                }
+                GameEvent::UnitHealed(unit_id, Player::Me, _) => {
+                    let name = self.map.units().get(unit_id).name().to_owned();
+                    self.messages.push(format!("{} is back to full health.", name));
+                }
+                GameEvent::CityBuilt(city_id, production) => {
+                    if let Some(msg) = self.built_message(city_id, production) {
+                        self.messages.push(msg);
+                    }
+                }
+                GameEvent::CityBesieged(_, Player::Me, pos) => {
+                    let kind = self.alert_siege;
+                    self.alert(kind, pos);
+                }
+                GameEvent::CityCaptured(city_id, Player::Me, _) => {
+                    let name = self.map.cities().get(city_id).name().to_owned();
+                    self.messages.push(format!("{} was captured by the enemy!", name));
+                }
+                GameEvent::CityRazed(name, _, _) => {
+                    self.messages.push(format!("{} was razed to the ground.", name));
+                }
+                GameEvent::PeaceOffered(player, Player::Me) => {
+                    self.messages.push(format!("{} offers peace. Press D to accept it.",
+                                               player.name()));
+                }
+                _ => {}
+            }
+        }
+        if !self.messages.is_empty() {
+            if self.is_idle() {
+                let msg = self.messages.join(" ");
+                self.messages.clear();
+                self.show_message(&msg);
+            }
+        }
+    }
+
+    /// Ends the game if one of our victory conditions is met or if we lost all our units.
+    fn check_game_over(&mut self) {
+        if self.victory_conditions.is_empty() {
+            return;
+        }
+        let msg = match self.victory_conditions.iter().find(|c| c.is_met(&self.map, self.turn)) {
+            Some(c) => format!("Victory! ({})", c.description()),
+            None if self.map.units().my_units().next().is_none() => "Defeat!".to_owned(),
+            None => return,
+        };
+        self.dialogs.clear();
+        let dialog = ChoiceDialog::new(create_message_dialog(&msg, self.screen.theme()));
+        self.push_dialog(DialogKind::GameOver, dialog);
     }
 
     pub fn draw(&mut self) {
-        let _ = self.term.clear();
+        self.term.new_frame();
         match self.state {
             MainloopState::OverheadMap => {
                 let selected_pos = self.selection
                                        .unit_id
-                                       .map(|uid| self.map.units().get(uid).pos());
-                draw_overhead_map(&mut self.term, self.map.terrain(), selected_pos);
+                                       .and_then(|uid| self.map.units().find(uid))
+                                       .map(|u| u.pos());
+                self.overhead_view.clamp(self.map.terrain().size(), self.term.size());
+                draw_overhead_map(&mut self.term,
+                                  self.map.terrain(),
+                                  self.map.fog(Player::Me),
+                                  selected_pos,
+                                  &self.overhead_view,
+                                  self.screen.theme(),
+                                  self.screen.glyphs());
             }
             _ => {
                 let positions_to_highlight = match self.movemode {
                     MovementMode::Move => {
-                        if let Some(uid) = self.selection.unit_id {
+                        if let Some(uid) = self.active_unit().map(|u| u.id()) {
                             let posmap = self.map.reachable_pos(uid);
                             let result: HashSet<Pos> = posmap.keys().map(|x| *x).collect();
                             Some(result)
@@ -196,7 +1222,7 @@ impl Game {
                         }
                     }
                     MovementMode::Bombard => {
-                        if let Some(uid) = self.selection.unit_id {
+                        if let Some(uid) = self.active_unit().map(|u| u.id()) {
                             let posmap = self.map.bombardable_pos(uid);
                             let result: HashSet<Pos> = posmap.keys().map(|x| *x).collect();
                             Some(result)
@@ -206,80 +1232,684 @@ impl Game {
                     }
                     _ => None,
                 };
+                let mut flashing = self.flashing.clone();
+                let mut damage = Vec::new();
+                // The screen follows enemy actions, centered on each of them when they start.
+                let enemy_pos = match self.animations.front() {
+                    Some(a) if a.is_enemy() && !self.animation_centered => Some(a.pos()),
+                    _ => None,
+                };
+                if let Some(pos) = enemy_pos {
+                    self.screen.center_on_pos(pos, self.map.terrain());
+                    self.animation_centered = true;
+                }
+                let moving_unit = match self.animations.front() {
+                    Some(&Animation::Move(ref a)) => Some((a.unit_id, *a.steps.front().unwrap())),
+                    Some(&Animation::Combat(ref a)) => {
+                        flashing.extend(a.positions.iter().cloned());
+                        damage = a.damage.clone();
+                        None
+                    }
+                    None => None,
+                };
                 let options = DrawOptions {
                     pos_markers: self.show_pos_markers,
+                    terrain_analysis: self.show_terrain_analysis,
                     positions_to_highlight: positions_to_highlight,
+                    moving_unit: moving_unit,
+                    flashing: flashing,
+                    damage: damage,
                 };
                 self.screen.update_screen_size(&self.term);
                 self.screen.draw(&mut self.term, &self.map, &self.selection, options);
                 self.details_window.draw_into(&mut self.term);
-                if let Some(ref mut d) = self.current_dialog {
-                    let w = d.window_mut();
-                    w.align(&self.term,
-                            HorizontalAlign::Middle,
-                            VerticalAlign::Middle,
-                            0);
-                    w.draw_into(&mut self.term);
+                for &mut (_, ref mut dialog) in self.dialogs.iter_mut() {
+                    dialog.draw(&mut self.term);
                 }
             }
         }
-        let _ = self.term.swap_buffers();
+        self.term.present();
+    }
+
+    /// Opens `dialog` on top of the open ones.
+    fn push_dialog<D: Dialog + 'static>(&mut self, kind: DialogKind, dialog: D) {
+        self.dialogs.push((kind, Box::new(dialog)));
+    }
+
+    /// Closes the dialog on top, which brings back the one below it.
+    fn close_dialog(&mut self) {
+        self.dialogs.pop();
     }
 
-    /// Returns whether the keypress was handled by the current dialog.
+    /// Returns whether we're playing on the map, without any dialog open.
+    fn is_idle(&self) -> bool {
+        match self.state {
+            MainloopState::Normal => self.dialogs.is_empty(),
+            _ => false,
+        }
+    }
+
+    fn show_message(&mut self, msg: &str) {
+        let dialog = ChoiceDialog::new(create_message_dialog(msg, self.screen.theme()));
+        self.push_dialog(DialogKind::Message, dialog);
+    }
+
+    /// Passes `key` to the dialog on top and does what's chosen in it, if anything.
     ///
-    /// If it was, then it shouldn't be handled by the normal loop.
-    fn handle_messagedialog_keypress(&mut self, key: char) {
-        assert!(self.current_dialog.is_some());
-        let r = self.current_dialog.as_ref().unwrap().result_for_key(key);
-        match r {
-            Some(DialogResult::Ok) => {
-                self.state = MainloopState::Normal;
-                self.current_dialog = None;
+    /// Returns whether the mainloop should continue.
+    fn handle_dialog_keypress(&mut self, key: char) -> bool {
+        let (kind, choice) = match self.dialogs.last_mut() {
+            Some(&mut (ref kind, ref mut dialog)) => (kind.clone(), dialog.handle_key(key)),
+            None => return true,
+        };
+        let choice = match choice {
+            Some(choice) => choice,
+            None => return true,
+        };
+        match kind {
+            DialogKind::Message => self.handle_message_choice(choice),
+            DialogKind::CombatConfirm(c) => self.handle_combatconfirm_choice(choice, c),
+            DialogKind::Menu => return self.handle_menu_choice(choice),
+            DialogKind::Options => self.handle_options_choice(choice),
+            DialogKind::Email => self.handle_email_choice(choice),
+            DialogKind::GameOver => {
+                if let Choice::Button(DialogResult::Ok) = choice {
+                    return false;
+                }
+            }
+            DialogKind::CityScreen(city_id) => self.handle_cityscreen_choice(choice, city_id),
+            DialogKind::ProductionChoice(city_id) => {
+                self.handle_productionchoice_choice(choice, city_id)
+            }
+            DialogKind::CaptureChoice(city_id) => {
+                self.handle_capturechoice_choice(choice, city_id)
+            }
+            DialogKind::Diplomacy => self.handle_diplomacy_choice(choice),
+            DialogKind::PantheonChoice => self.handle_pantheonchoice_choice(choice),
+            DialogKind::PromotionChoice(unit_id) => {
+                self.handle_promotionchoice_choice(choice, unit_id)
+            }
+            DialogKind::FindTile => {
+                self.close_dialog();
+                if let Choice::Text(text) = choice {
+                    self.find_tile(&text);
+                }
+            }
+            DialogKind::TileInfo => self.close_dialog(),
+            DialogKind::DebugConsole => {
+                self.close_dialog();
+                if let Choice::Text(text) = choice {
+                    self.run_debug_command(&text);
+                }
+            }
+            DialogKind::UnitName(unit_id) => {
+                if let Choice::Text(name) = choice {
+                    self.map.rename_unit(unit_id, &name);
+                    self.update_details();
+                }
+                self.close_dialog();
+            }
+        }
+        true
+    }
+
+    fn handle_message_choice(&mut self, choice: Choice) {
+        if let Choice::Button(DialogResult::Ok) = choice {
+            self.close_dialog();
+            // Back on the map, we go on with the next unit waiting for orders, unless the active
+            // one, which may have just attacked, has movements left.
+            let active_done = self.active_unit().map_or(true, |u| u.is_dead() || !u.needs_orders());
+            if self.dialogs.is_empty() && active_done {
                 self.cycle_active_unit();
                 self.update_details();
             }
+        }
+    }
+
+    /// Shows the combat confirmation dialog for `combat_stats`.
+    fn confirm_combat(&mut self, combat_stats: CombatStats) {
+        let dialog = ChoiceDialog::new(create_combat_confirm_dialog(&combat_stats,
+                                                                    self.screen.theme()));
+        self.push_dialog(DialogKind::CombatConfirm(combat_stats), dialog);
+    }
+
+    /// Shows the result of the attack we've just made, if any.
+    fn show_combat_result(&mut self) {
+        if let Some(stats) = self.combat_result.take() {
+            let dialog = ChoiceDialog::new(create_combat_result_dialog(&stats,
+                                                                       self.screen.theme()));
+            self.push_dialog(DialogKind::Message, dialog);
+        }
+    }
+
+    /// Asks what to do with the first city we've captured and haven't decided about yet.
+    fn prompt_captured_city(&mut self) {
+        let captured = self.map
+                           .cities()
+                           .all_cities()
+                           .iter()
+                           .find(|c| c.owner() == Player::Me && c.status() == CityStatus::Captured)
+                           .map(|c| c.id());
+        if let Some(city_id) = captured {
+            let dialog = ChoiceDialog::new(create_capture_dialog(city_id,
+                                                                 &self.map,
+                                                                 self.screen.theme()));
+            self.push_dialog(DialogKind::CaptureChoice(city_id), dialog);
+        }
+    }
+
+    fn handle_capturechoice_choice(&mut self, choice: Choice, city_id: CityID) {
+        let status = match choice {
+            Choice::Button(DialogResult::Custom(CAPTURE_ANNEX)) => CityStatus::Annexed,
+            Choice::Button(DialogResult::Custom(CAPTURE_PUPPET)) => CityStatus::Puppet,
+            Choice::Button(DialogResult::Custom(CAPTURE_RAZE)) => CityStatus::Razing,
+            _ => return,
+        };
+        self.map.set_city_status(city_id, status);
+        self.close_dialog();
+    }
+
+    /// Asks which belief to adopt, once per turn, when we have enough faith to found a pantheon.
+    fn prompt_pantheon(&mut self) {
+        if self.pantheon_prompted || !self.map.cities().can_found_pantheon(Player::Me) {
+            return;
+        }
+        self.pantheon_prompted = true;
+        let beliefs = self.map.cities().available_beliefs();
+        let dialog = ChoiceDialog::new(create_pantheon_dialog(&beliefs, self.screen.theme()));
+        self.push_dialog(DialogKind::PantheonChoice, dialog.with_items().escapable());
+    }
+
+    fn handle_pantheonchoice_choice(&mut self, choice: Choice) {
+        match choice {
+            Choice::Item(index) => {
+                let beliefs = self.map.cities().available_beliefs();
+                if let Some(&belief) = beliefs.get(index) {
+                    self.map.cities_mut().found_pantheon(Player::Me, belief);
+                    self.close_dialog();
+                }
+            }
+            _ => self.close_dialog(),
+        }
+    }
+
+    /// Asks which promotion `unit_id` picks, if it can be promoted.
+    fn open_promotions(&mut self, unit_id: UnitID) {
+        let dialog = {
+            let unit = self.map.units().get(unit_id);
+            if !unit.can_promote() {
+                return;
+            }
+            create_promotion_dialog(unit, self.screen.theme())
+        };
+        self.push_dialog(DialogKind::PromotionChoice(unit_id),
+                         ChoiceDialog::new(dialog).with_items().escapable());
+    }
+
+    fn handle_promotionchoice_choice(&mut self, choice: Choice, unit_id: UnitID) {
+        match choice {
+            Choice::Item(index) => {
+                let promotions = self.map.units().get(unit_id).available_promotions();
+                if let Some(&promotion) = promotions.get(index) {
+                    self.map.promote(unit_id, promotion);
+                    self.close_dialog();
+                    self.update_details();
+                }
+            }
+            _ => self.close_dialog(),
+        }
+    }
+
+    fn is_jumping(&self) -> bool {
+        match self.state {
+            MainloopState::BookmarkJump => true,
+            _ => false,
+        }
+    }
+
+    /// Asks for the offset position of the tile we want to see.
+    fn prompt_find_tile(&mut self) {
+        let dialog = TextInputDialog::new("Go to tile (x y):", "", 9, self.screen.theme());
+        self.push_dialog(DialogKind::FindTile, dialog);
+    }
+
+    /// Shows everything about the selected tile, or the active unit's tile, and the units on it.
+    fn inspect_tile(&mut self) {
+        let pos = match self.selection.pos.or(self.active_unit().map(|u| u.pos())) {
+            Some(pos) => pos,
+            None => return,
+        };
+        let dialog = create_tile_info_dialog(pos, &self.map, self.screen.theme());
+        self.push_dialog(DialogKind::TileInfo, ChoiceDialog::new(dialog).escapable());
+    }
+
+    /// Centers the screen on the tile at the offset position in `text`, such as "12 7" or "12,7",
+    /// and flashes it.
+    fn find_tile(&mut self, text: &str) {
+        let coords: Vec<i32> = text.split(|c: char| c == ',' || c.is_whitespace())
+                                   .filter(|s| !s.is_empty())
+                                   .filter_map(|s| s.parse().ok())
+                                   .collect();
+        let (width, height) = self.map.terrain().size();
+        let found = coords.len() == 2 && coords[0] >= 0 && coords[0] < width && coords[1] >= 0 &&
+                    coords[1] < height;
+        if found {
+            let pos = OffsetPos::new(coords[0], coords[1]).to_pos();
+            self.screen.center_on_pos(pos, self.map.terrain());
+            self.flashing = vec![pos];
+        } else {
+            self.show_message(&format!("There's no tile at \"{}\".", text.trim()));
+        }
+    }
+
+    /// Asks for a script action to run right away, such as `teleport 3 4 10 4`.
+    fn prompt_debug_command(&mut self) {
+        let dialog = TextInputDialog::new("Debug command:", "", 40, self.screen.theme());
+        self.push_dialog(DialogKind::DebugConsole, dialog);
+    }
+
+    /// Runs the script action in `text` on our map and tells what went wrong, if anything.
+    ///
+    /// See `script::parse_action()`.
+    fn run_debug_command(&mut self, text: &str) {
+        let action = match parse_action(text) {
+            Some(action) => action,
+            None => {
+                self.show_message(&format!("Unknown debug command \"{}\".", text.trim()));
+                return;
+            }
+        };
+        match run_action(&action, &mut self.map) {
+            Ok(Some(msg)) | Err(msg) => self.show_message(&msg),
+            Ok(None) => (),
+        }
+        self.update_details();
+    }
+
+    /// Bookmarks the center of the screen as `index`.
+    fn set_bookmark(&mut self, index: usize) {
+        self.bookmarks[index] = Some(self.screen.center_pos());
+    }
+
+    fn handle_bookmarkjump_keypress(&mut self, key: char) {
+        self.state = MainloopState::Normal;
+        let bookmark = key.to_digit(10).and_then(|d| self.bookmarks[d as usize]);
+        if let Some(pos) = bookmark {
+            self.screen.center_on_pos(pos, self.map.terrain());
+        }
+        self.update_details();
+    }
+
+    /// Asks for the name of the active unit, if any.
+    fn prompt_unit_name(&mut self) {
+        let unit = self.active_unit().map(|u| (u.id(), u.custom_name().unwrap_or("").to_owned()));
+        if let Some((unit_id, name)) = unit {
+            let dialog = TextInputDialog::new("Name of the unit (blank for its type):",
+                                              &name,
+                                              UNIT_NAME_MAX_LEN,
+                                              self.screen.theme());
+            self.push_dialog(DialogKind::UnitName(unit_id), dialog);
+        }
+    }
+
+    /// Asks whether one of our cities having enemies in range should strike the weakest of them.
+    ///
+    /// Each city is only asked once per turn, whether it strikes or not.
+    fn prompt_city_strike(&mut self) {
+        let mut city_ids: Vec<CityID> = self.map
+                                            .cities()
+                                            .all_cities()
+                                            .iter()
+                                            .filter(|c| c.owner() == Player::Me)
+                                            .map(|c| c.id())
+                                            .filter(|id| !self.strike_prompted.contains(id))
+                                            .collect();
+        city_ids.sort();
+        for city_id in city_ids {
+            if let Some(&target_id) = self.map.city_strike_targets(city_id).first() {
+                self.strike_prompted.insert(city_id);
+                let stats = self.map.get_city_strike_stats(city_id, target_id);
+                self.confirm_combat(stats);
+                return;
+            }
+        }
+    }
+
+    fn handle_combatconfirm_choice(&mut self, choice: Choice, mut combat_stats: CombatStats) {
+        match choice {
+            Choice::Button(DialogResult::Ok) if combat_stats.attacker_city.is_some() => {
+                self.map.city_strike(&mut combat_stats);
+                self.combat_result = Some(combat_stats);
+                self.close_dialog();
+            }
+            Choice::Button(DialogResult::Ok) => {
+                self.map.attack(&mut combat_stats);
+                self.update_details();
+                // The result is shown once the combat is animated.
+                self.combat_result = Some(combat_stats);
+                self.close_dialog();
+            }
+            Choice::Button(DialogResult::Cancel) => self.close_dialog(),
             _ => {}
         }
     }
 
-    fn handle_combatconfirm_keypress(&mut self, key: char, combat_stats: &mut CombatStats) {
-        assert!(self.current_dialog.is_some());
-        let r = self.current_dialog.as_ref().unwrap().result_for_key(key);
-        match r {
-            Some(DialogResult::Ok) => {
-                self.map.attack(combat_stats);
+    /// Opens the screen of our city closest to the selected position or to the active unit.
+    fn open_nearest_city(&mut self) {
+        let pos = self.selection
+                      .pos
+                      .or(self.active_unit().map(|u| u.pos()))
+                      .unwrap_or(Pos::origin());
+        if let Some(city_id) = self.map.cities().nearest_city(Player::Me, pos) {
+            self.open_city(city_id);
+        }
+    }
+
+    fn open_city(&mut self, city_id: CityID) {
+        let dialog = ChoiceDialog::new(create_city_dialog(city_id,
+                                                          &self.map,
+                                                          self.screen.theme()));
+        self.push_dialog(DialogKind::CityScreen(city_id), dialog.with_items().escapable());
+    }
+
+    /// Replaces the screen of `city_id`, on top, with an up to date one.
+    fn refresh_city(&mut self, city_id: CityID) {
+        self.close_dialog();
+        self.open_city(city_id);
+    }
+
+    fn handle_cityscreen_choice(&mut self, choice: Choice, city_id: CityID) {
+        let managed = self.map.cities().get(city_id).status().is_managed();
+        match choice {
+            Choice::Item(index) => {
+                let tiles = self.map.cities().city_tiles(city_id, self.map.terrain());
+                if let Some(&pos) = tiles.get(index) {
+                    self.map.toggle_locked_tile(city_id, pos);
+                    self.refresh_city(city_id);
+                }
+            }
+            // Puppets and cities being razed build nothing.
+            Choice::Button(DialogResult::Custom(CITY_PRODUCTION)) if !managed => {}
+            Choice::Button(DialogResult::Custom(CITY_PRODUCTION)) => self.open_production(city_id),
+            _ => self.close_dialog(),
+        }
+    }
+
+    /// Asks what `city_id` builds, over its screen.
+    fn open_production(&mut self, city_id: CityID) {
+        let choices = self.map.production_choices(city_id);
+        let dialog = ChoiceDialog::new(create_production_dialog(city_id,
+                                                                &choices,
+                                                                &self.map,
+                                                                self.screen.theme()));
+        self.push_dialog(DialogKind::ProductionChoice(city_id), dialog.with_items().escapable());
+    }
+
+    fn handle_productionchoice_choice(&mut self, choice: Choice, city_id: CityID) {
+        match choice {
+            Choice::Item(index) => {
+                let choices = self.map.production_choices(city_id);
+                if let Some(production) = choices.get(index).cloned() {
+                    self.map.set_production(city_id, Some(production));
+                    self.close_dialog();
+                    self.refresh_city(city_id);
+                }
+            }
+            _ => self.close_dialog(),
+        }
+    }
+
+    /// Opens the diplomacy screen, with `message` telling how our last proposal went.
+    fn open_diplomacy(&mut self, message: &str) {
+        let players = self.map.ai_players();
+        let dialog = ChoiceDialog::new(create_diplomacy_dialog(&players,
+                                                               message,
+                                                               &self.map,
+                                                               self.screen.theme()));
+        self.push_dialog(DialogKind::Diplomacy,
+                         dialog.with_items().with_numbered_items().escapable());
+    }
+
+    fn handle_diplomacy_choice(&mut self, choice: Choice) {
+        let players = self.map.ai_players();
+        let msg = match choice {
+            Choice::Item(index) => {
+                let player = match players.get(index) {
+                    Some(&player) => player,
+                    None => return,
+                };
+                if !self.map.diplomacy().at_war(Player::Me, player) {
+                    self.map.diplomacy_mut().declare_war(Player::Me, player);
+                    format!("We declared war on {}.", player.name())
+                } else if self.map.diplomacy().has_peace_offer(player, Player::Me) ||
+                          accepts_peace(player, Player::Me, &self.map) {
+                    self.map.diplomacy_mut().make_peace(Player::Me, player);
+                    format!("{} accepted peace.", player.name())
+                } else {
+                    format!("{} refused peace.", player.name())
+                }
+            }
+            Choice::NumberedItem(index) => {
+                let player = match players.get(index) {
+                    Some(&player) => player,
+                    None => return,
+                };
+                if self.map.diplomacy().has_open_borders(Player::Me, player) {
+                    self.map.diplomacy_mut().set_open_borders(Player::Me, player, false);
+                    format!("We closed our borders to {}.", player.name())
+                } else if self.map.diplomacy().at_war(Player::Me, player) {
+                    format!("We have to be at peace with {} to open borders.", player.name())
+                } else if accepts_open_borders(player, Player::Me, &self.map) {
+                    self.map.diplomacy_mut().set_open_borders(Player::Me, player, true);
+                    format!("{} agreed to open borders.", player.name())
+                } else {
+                    format!("{} refused to open borders.", player.name())
+                }
+            }
+            _ => {
+                self.close_dialog();
+                return;
+            }
+        };
+        self.close_dialog();
+        self.open_diplomacy(&msg);
+    }
+
+    fn open_menu(&mut self) {
+        let dialog = ChoiceDialog::new(create_menu_dialog(self.screen.theme()));
+        self.push_dialog(DialogKind::Menu, dialog.escapable());
+    }
+
+    fn open_email(&mut self) {
+        let dialog = ChoiceDialog::new(create_email_dialog(self.screen.theme(), PBEM_PATH));
+        self.push_dialog(DialogKind::Email, dialog.escapable());
+    }
+
+    fn open_options(&mut self) {
+        let glyphs_name = self.screen.glyphs().mode().name().to_owned();
+        let dialog = ChoiceDialog::new(create_options_dialog(self.screen.theme(), &glyphs_name));
+        self.push_dialog(DialogKind::Options, dialog.escapable());
+    }
+
+    fn cycle_theme(&mut self) {
+        let kind = self.screen.theme().kind().next();
+        self.screen.set_theme(Theme::detect(kind));
+        self.update_details();
+    }
+
+    fn toggle_glyphs(&mut self) {
+        let mode = match self.screen.glyphs().mode() {
+            GlyphMode::Ascii => GlyphMode::Unicode,
+            GlyphMode::Unicode => GlyphMode::Ascii,
+        };
+        let glyphs = load_glyphs(mode, self.glyphs_file.as_ref().map(|p| p.as_path()));
+        self.screen.set_glyphs(glyphs);
+    }
+
+    /// Saves the game to `CRASH_SAVE_PATH`, after a panic left us in an unknown state.
+    pub fn crash_save(&self) -> io::Result<()> {
+        save_game(Path::new(CRASH_SAVE_PATH), &self.map, self.turn)
+    }
+
+    fn save(&mut self) {
+        match save_game(Path::new(SAVE_PATH), &self.map, self.turn) {
+            Ok(()) => self.show_message(&format!("Game saved to {}", SAVE_PATH)),
+            Err(e) => self.show_message(&format!("Could not save game: {}", e)),
+        }
+    }
+
+    fn load(&mut self) {
+        match load_game(Path::new(SAVE_PATH)) {
+            Ok((mut map, turn)) => {
+                map.set_rules(*self.map.rules());
+                self.map = map;
+                self.turn = turn;
+                self.selection = Selection::new();
+                self.movemode = MovementMode::Normal;
+                self.animations.clear();
+                self.cycle_active_unit();
                 self.update_details();
-                self.current_dialog = Some(create_combat_result_dialog(combat_stats));
-                self.state = MainloopState::MessageDialog;
+                self.broadcast_state();
+                self.show_message(&format!("Game loaded from {}", SAVE_PATH));
             }
-            Some(DialogResult::Cancel) => {
-                self.state = MainloopState::Normal;
-                self.current_dialog = None;
+            Err(e) => self.show_message(&format!("Could not load game: {}", e)),
+        }
+    }
+
+    fn send_turn(&mut self) {
+        match send_turn(Path::new(PBEM_PATH), &self.map, self.turn) {
+            Ok(()) => {
+                self.play_by_email = true;
+                self.pbem_sent = Some(self.turn);
+                self.turn_deadline = None;
+                let msg = format!("Turn {} sent to {}. Mail it to your opponent.",
+                                  self.turn,
+                                  PBEM_PATH);
+                self.show_message(&msg);
+            }
+            Err(e) => self.show_message(&format!("Could not send turn: {}", e)),
+        }
+    }
+
+    fn receive_turn(&mut self) {
+        if self.play_by_email && self.pbem_sent.is_none() {
+            self.show_message("We have to send our turn before receiving the next one.");
+            return;
+        }
+        match receive_turn(Path::new(PBEM_PATH), self.pbem_sent) {
+            Ok((mut map, turn)) => {
+                map.set_rules(*self.map.rules());
+                self.map = map;
+                self.turn = turn;
+                self.play_by_email = true;
+                self.pbem_sent = None;
+                self.selection = Selection::new();
+                self.movemode = MovementMode::Normal;
+                self.animations.clear();
+                self.dialogs.clear();
+                self.new_turn();
+            }
+            Err(e) => self.show_message(&format!("Could not receive turn: {}", e)),
+        }
+    }
+
+    /// Returns whether the mainloop should continue
+    fn handle_menu_choice(&mut self, choice: Choice) -> bool {
+        match choice {
+            Choice::Escape | Choice::Button(DialogResult::Cancel) => self.close_dialog(),
+            Choice::Button(DialogResult::Custom(MENU_SAVE)) => self.save(),
+            Choice::Button(DialogResult::Custom(MENU_LOAD)) => self.load(),
+            Choice::Button(DialogResult::Custom(MENU_EMAIL)) => self.open_email(),
+            Choice::Button(DialogResult::Custom(MENU_OPTIONS)) => self.open_options(),
+            Choice::Button(DialogResult::Custom(MENU_QUIT)) => {
+                return false;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_options_choice(&mut self, choice: Choice) {
+        match choice {
+            Choice::Escape | Choice::Button(DialogResult::Cancel) => self.close_dialog(),
+            Choice::Button(DialogResult::Custom(OPTIONS_THEME)) => {
+                self.cycle_theme();
+                // Dialogs keep the colors of the theme they were created with.
+                self.dialogs.clear();
+                self.open_menu();
+                self.open_options();
             }
+            Choice::Button(DialogResult::Custom(OPTIONS_GLYPHS)) => {
+                self.toggle_glyphs();
+                self.close_dialog();
+                self.open_options();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_email_choice(&mut self, choice: Choice) {
+        match choice {
+            Choice::Escape | Choice::Button(DialogResult::Cancel) => self.close_dialog(),
+            Choice::Button(DialogResult::Custom(EMAIL_SEND)) => self.send_turn(),
+            Choice::Button(DialogResult::Custom(EMAIL_RECEIVE)) => self.receive_turn(),
             _ => {}
         }
     }
 
     fn handle_overheadmap_keypress(&mut self, key: char) {
+        let (cols, rows) = self.term.size();
         match key {
             'z' => {
                 self.state = MainloopState::Normal;
                 self.draw()
             }
-            _ => {}
+            '-' => self.overhead_view.toggle_downsample((cols, rows)),
+            'c' => self.overhead_view.toggle_continents(),
+            'K' => self.overhead_view.scroll(0, -(rows as i32)),
+            'J' => self.overhead_view.scroll(0, rows as i32),
+            'H' => self.overhead_view.scroll(-(cols as i32), 0),
+            'L' => self.overhead_view.scroll(cols as i32, 0),
+            k => {
+                if let Some(d) = direction_for_key(k) {
+                    // The overhead map is a plain grid: diagonal directions scroll diagonally.
+                    let (dx, dy) = match d {
+                        Direction::North => (0, -1),
+                        Direction::NorthEast => (1, -1),
+                        Direction::SouthEast => (1, 1),
+                        Direction::South => (0, 1),
+                        Direction::SouthWest => (-1, 1),
+                        Direction::NorthWest => (-1, -1),
+                    };
+                    self.overhead_view.scroll(dx, dy);
+                }
+            }
         }
     }
 
     /// Returns whether the mainloop should continue
     fn handle_normal_keypress(&mut self, key: char) -> bool {
         match key {
-            'Q' => {
-                return false;
+            'Q' | KEY_ESCAPE => {
+                self.open_menu();
             }
             'P' => {
                 self.show_pos_markers = !self.show_pos_markers;
             }
+            'O' => {
+                self.show_terrain_analysis = !self.show_terrain_analysis;
+            }
+            'T' => {
+                self.cycle_theme();
+            }
+            'G' => {
+                self.toggle_glyphs();
+            }
+            'C' => {
+                let dialog = create_combat_stats_dialog(self.map.combat_log(),
+                                                        self.screen.theme());
+                self.push_dialog(DialogKind::Message, ChoiceDialog::new(dialog));
+            }
             'S' => {
                 self.movemode = if self.movemode == MovementMode::Scroll {
                     MovementMode::Normal
@@ -305,11 +1935,17 @@ impl Game {
                     self.movemode = MovementMode::Normal;
                     self.selection.pos = None;
                 } else {
-                    if let Some((selpos, range)) = self.active_unit()
-                                                       .map(|u| (u.pos(), u.type_().range())) {
-                        if range > 0 {
-                            self.movemode = MovementMode::Bombard;
-                            self.selection.pos = Some(selpos);
+                    let unit = self.active_unit().map(|u| (u.id(), u.type_().range()));
+                    if let Some((unit_id, range)) = unit {
+                        // The selector starts on the nearest target, if there's one.
+                        match self.map.bombard_targets(unit_id).first() {
+                            Some(&target) if range > 0 => {
+                                self.movemode = MovementMode::Bombard;
+                                self.selection.pos = Some(target);
+                                self.screen.ensure_visible(target, self.map.terrain());
+                            }
+                            _ if range > 0 => self.show_message("No enemy in range."),
+                            _ => {}
                         }
                     }
                 }
@@ -319,53 +1955,171 @@ impl Game {
                 match self.movemode {
                     MovementMode::Move => {
                         let target = self.selection.pos.unwrap();
-                        if let Some(ref combat_result) = self.moveunit_to(target) {
-                            self.current_dialog = Some(create_combat_confirm_dialog(combat_result));
-                            self.state = MainloopState::CombatConfirm(combat_result.clone());
+                        // Tiles out of reach are for go-to and patrol orders.
+                        if !self.selection_is_reachable() {
+                            return true;
+                        }
+                        if let Some(combat_result) = self.moveunit_to(target) {
+                            self.confirm_combat(combat_result);
                         }
                         self.movemode = MovementMode::Normal;
                         self.selection.pos = None;
                         self.update_details();
                     }
                     MovementMode::Bombard => {
-                        if let Some(ref combat_result) = self.bombard() {
-                            self.current_dialog = Some(create_combat_confirm_dialog(combat_result));
-                            self.state = MainloopState::CombatConfirm(combat_result.clone());
+                        // Only targets are worth leaving the mode for.
+                        if !self.selection_is_target() {
+                            return true;
+                        }
+                        if let Some(combat_result) = self.bombard() {
+                            self.confirm_combat(combat_result);
                         }
                         self.movemode = MovementMode::Normal;
                         self.selection.pos = None;
                         self.update_details();
                     }
+                    _ => self.visit_next_blocker(),
+                }
+            }
+            '\t' => self.select_next_target(),
+            'g' => {
+                if self.movemode == MovementMode::Move {
+                    let target = self.selection.pos.unwrap();
+                    self.movemode = MovementMode::Normal;
+                    self.selection.pos = None;
+                    // Going to one of our units means escorting it.
+                    let leader_id = match self.map.units().top_at_pos(target) {
+                        Some(u) if u.owner() == Player::Me => Some(u.id()),
+                        _ => None,
+                    };
+                    if leader_id.is_some() && leader_id == self.selection.unit_id {
+                        self.update_details();
+                        return true;
+                    }
+                    self.assign_order(match leader_id {
+                        Some(leader_id) => Order::Follow(leader_id),
+                        None => Order::GoTo(target),
+                    });
+                }
+            }
+            'p' => {
+                if self.movemode == MovementMode::Move {
+                    let target = self.selection.pos.unwrap();
+                    self.movemode = MovementMode::Normal;
+                    self.selection.pos = None;
+                    // The route starts where previously queued orders bring the unit.
+                    let from = self.active_unit().map(|u| {
+                        u.orders()
+                         .iter()
+                         .filter_map(|o| {
+                             match *o {
+                                 Order::GoTo(p) => Some(p),
+                                 _ => None,
+                             }
+                         })
+                         .next_back()
+                         .unwrap_or(u.pos())
+                    });
+                    if let Some(from) = from {
+                        self.assign_order(Order::Patrol(from, target));
+                    }
+                }
+            }
+            'N' => {
+                if self.movemode != MovementMode::Move && self.movemode != MovementMode::Bombard {
+                    self.new_turn();
+                }
+            }
+            'x' => self.assign_order(Order::Sentry),
+            'f' => self.assign_order(Order::Fortify),
+            'h' => self.assign_order(Order::Heal),
+            'E' => self.assign_order(Order::Explore),
+            'v' => self.open_nearest_city(),
+            'R' => self.prompt_unit_name(),
+            'F' => self.prompt_find_tile(),
+            'i' => self.inspect_tile(),
+            '`' if self.debug_console => self.prompt_debug_command(),
+            ',' => self.cycle_units_on_tile(),
+            '\'' => {
+                self.state = MainloopState::BookmarkJump;
+                self.update_details();
+            }
+            k if BOOKMARK_KEYS.contains(k) => {
+                self.set_bookmark(BOOKMARK_KEYS.chars().position(|c| c == k).unwrap());
+            }
+            'D' => self.open_diplomacy(""),
+            'u' => {
+                if let Some(unit_id) = self.active_unit().map(|u| u.id()) {
+                    self.open_promotions(unit_id);
+                }
+            }
+            'c' => {
+                if self.active_unit().map_or(false, |u| u.type_() == UnitType::Worker) {
+                    self.assign_order(Order::Clear(0));
+                }
+            }
+            'B' => {
+                if self.active_unit().map_or(false, |u| u.type_() == UnitType::Settler) {
+                    self.give_order(|map, unit_id| {
+                        map.found_city(unit_id);
+                    });
+                }
+            }
+            'o' => {
+                match self.queueing_unit.take() {
+                    Some(unit_id) if Some(unit_id) == self.selection.unit_id => {
+                        self.give_order(|map, unit_id| map.carry_out_unit_orders(unit_id));
+                    }
                     _ => {
-                        self.new_turn();
+                        if let Some(unit_id) = self.active_unit().map(|u| u.id()) {
+                            self.map.set_order(unit_id, None);
+                            self.queueing_unit = Some(unit_id);
+                        }
+                        self.update_details();
                     }
                 }
             }
+            ' ' => self.give_order(|map, unit_id| map.skip_turn(unit_id)),
+            'W' => self.give_order(|map, unit_id| map.wait(unit_id)),
             '.' => {
                 self.cycle_active_unit();
                 self.update_details();
                 self.draw()
             }
             'z' => {
+                if let Some(pos) = self.active_unit().map(|u| u.pos()) {
+                    self.overhead_view.center_on(pos, self.term.size());
+                }
                 self.state = MainloopState::OverheadMap;
                 self.draw()
             }
+            'K' => self.screen.scroll_pages(0, -1, self.map.terrain()),
+            'J' => self.screen.scroll_pages(0, 1, self.map.terrain()),
+            'H' => self.screen.scroll_pages(-1, 0, self.map.terrain()),
+            'L' => self.screen.scroll_pages(1, 0, self.map.terrain()),
+            '[' => self.screen.scroll_to_corner(false, false, self.map.terrain()),
+            ']' => self.screen.scroll_to_corner(true, false, self.map.terrain()),
+            '{' => self.screen.scroll_to_corner(false, true, self.map.terrain()),
+            '}' => self.screen.scroll_to_corner(true, true, self.map.terrain()),
+            '5' => self.screen.scroll_to_center(self.map.terrain()),
             k => {
                 if let Some(d) = direction_for_key(k) {
                     match self.movemode {
                         MovementMode::Normal => {
-                            if let Some(ref combat_result) = self.moveunit(d) {
-                                self.current_dialog =
-                                    Some(create_combat_confirm_dialog(combat_result));
-                                self.state = MainloopState::CombatConfirm(combat_result.clone());
+                            if let Some(combat_result) = self.moveunit(d) {
+                                self.confirm_combat(combat_result);
                             }
                         }
                         MovementMode::Scroll => {
                             self.screen.scroll(Pos::origin().neighbor(d));
                         }
                         MovementMode::Move | MovementMode::Bombard => {
-                            self.selection.pos = Some(self.selection.pos.unwrap().neighbor(d));
-                            self.update_details();
+                            let newpos = self.selection.pos.unwrap().neighbor(d);
+                            if self.can_select(newpos) {
+                                self.selection.pos = Some(newpos);
+                                self.screen.ensure_visible(newpos, self.map.terrain());
+                                self.update_details();
+                            }
                         }
                     }
                 }
@@ -375,28 +2129,140 @@ impl Game {
     }
 
     /// Returns whether the mainloop should continue
+    ///
+    /// While a movement or combat animation is running, we don't wait for events longer than
+    /// its step delay so that the mainloop can redraw the next animation step. A keypress skips
+    /// all pending animations. The result of our own attacks is shown once they're animated.
+    /// Alerted tiles flash until animations are over, and then for `FLASH_DURATION_MS`, unless a
+    /// key is pressed. During the AI turn, we check every `SPINNER_DELAY_MS` whether it's over.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::game::Game;
+    /// use civng_tui::backend::{Backend, FakeBackend};
+    /// use civng_tui::config::Config;
+    /// use civng_tui::map::LiveMap;
+    /// use civng_tui::terrain::TerrainMap;
+    /// use civng_tui::unit::{Unit, UnitType, Player};
+    /// use civng_tui::hexpos::{Pos, Direction};
+    ///
+    /// // Damage floats over units as "-NN".
+    /// fn shows_damage(lines: &[String]) -> bool {
+    ///     let is_digit = |c: char| c.is_digit(10);
+    ///     lines.iter().any(|l| l.split('-').skip(1).any(|s| s.starts_with(is_digit)))
+    /// }
+    ///
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(10, 10));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// map.add_unit(Unit::new(UnitType::Ranged, Player::AI(0), Pos::vector(Direction::South)));
+    /// let mut term = FakeBackend::new(80, 30);
+    /// // Attack south, then confirm.
+    /// term.push_key('s');
+    /// term.push_key('a');
+    /// let mut game = Game::with_map(term, map, &Config::new());
+    /// game.new_turn();
+    /// game.handle_events();
+    /// game.handle_events();
+    /// game.draw();
+    /// assert!(shows_damage(&game.term().lines()));
+    /// // Without keypresses, the animation runs its course and then the result shows up.
+    /// let mut tries = 0;
+    /// while shows_damage(&game.term().lines()) && tries < 100 {
+    ///     game.handle_events();
+    ///     game.draw();
+    ///     tries += 1;
+    /// }
+    /// assert!(game.term().lines().iter().any(|l| l.contains("Attacker: Melee")));
+    /// ```
     pub fn handle_events(&mut self) -> bool {
-        match self.term.get_event(Duration::from_secs(1)) {
-            Ok(Some(Event::Key(k))) => {
-                match self.state.clone() {
-                    MainloopState::Normal => {
-                        if !self.handle_normal_keypress(k) {
-                            return false;
-                        }
-                    }
-                    MainloopState::MessageDialog => {
-                        self.handle_messagedialog_keypress(k);
-                    }
-                    MainloopState::CombatConfirm(mut c) => {
-                        self.handle_combatconfirm_keypress(k, &mut c);
-                    }
-                    MainloopState::OverheadMap => {
-                        self.handle_overheadmap_keypress(k);
-                    }
+        let timeout = if self.ai_turn.is_some() {
+            Duration::from_millis(SPINNER_DELAY_MS)
+        } else if !self.animations.is_empty() {
+            self.current_animation_delay()
+        } else if !self.flashing.is_empty() {
+            Duration::from_millis(FLASH_DURATION_MS)
+        } else {
+            Duration::from_secs(1)
+        };
+        let result = match self.term.poll_key(timeout) {
+            Some(k) => {
+                self.animations.clear();
+                self.flashing.clear();
+                self.handle_keypress(k)
+            }
+            None => {
+                if self.animations.is_empty() {
+                    self.flashing.clear();
+                } else {
+                    self.advance_animation();
+                }
+                // The countdown of a timed turn goes on.
+                if self.turn_deadline.is_some() {
+                    self.update_details();
                 }
+                true
             }
-            _ => {
-                return true;
+        };
+        if self.ai_turn.is_some() {
+            self.check_ai_turn();
+            // The map we show is a copy, whose events will come with the real one.
+            if self.ai_turn.is_some() {
+                return result;
+            }
+        }
+        self.process_map_events();
+        if self.is_idle() {
+            if self.animations.is_empty() {
+                self.show_combat_result();
+            }
+        }
+        if self.is_idle() {
+            if self.animations.is_empty() {
+                self.prompt_captured_city();
+            }
+        }
+        if self.is_idle() {
+            if self.animations.is_empty() {
+                self.prompt_city_strike();
+            }
+        }
+        if self.is_idle() {
+            if self.animations.is_empty() {
+                self.prompt_pantheon();
+            }
+        }
+        if self.auto_end_turn && self.animations.is_empty() && self.turn_done() &&
+           self.pbem_sent.is_none() {
+            if self.is_idle() {
+                self.new_turn();
+            }
+        }
+        if self.time_left() == Some(Duration::from_secs(0)) {
+            self.end_timed_out_turn();
+        }
+        result
+    }
+
+    /// Returns whether the mainloop should continue
+    fn handle_keypress(&mut self, k: char) -> bool {
+        if !self.dialogs.is_empty() {
+            return self.handle_dialog_keypress(k);
+        }
+        match self.state.clone() {
+            MainloopState::Normal => {
+                if !self.handle_normal_keypress(k) {
+                    return false;
+                }
+            }
+            MainloopState::OverheadMap => {
+                self.handle_overheadmap_keypress(k);
+            }
+            MainloopState::AiTurn => {
+                self.handle_aiturn_keypress(k);
+            }
+            MainloopState::BookmarkJump => {
+                self.handle_bookmarkjump_keypress(k);
             }
         }
         true
@@ -0,0 +1,252 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Characters used to draw terrain and units on screen.
+//!
+//! The ASCII glyph set is made of `Terrain::map_char()`, `Feature::map_char()` and
+//! `UnitType::map_symbol()`. The Unicode set uses richer characters, which requires a terminal
+//! font that has them.
+//!
+//! Both sets can be overridden by a glyph file, which is a series of `kind.name = glyph` lines,
+//! `kind` being either `terrain`, `feature` or `unit` and `name` being the lowercased name of the
+//! terrain, feature or unit type, with underscores instead of spaces. For example:
+//!
+//! ```text
+//! # My mod's glyphs
+//! terrain.coast = ~
+//! feature.mountain_range = m
+//! unit.melee = ♜
+//! ```
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use terrain::{Terrain, Feature};
+use unit::UnitType;
+
+/// Glyph sets the user can choose from.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GlyphMode {
+    Ascii,
+    Unicode,
+}
+
+impl GlyphMode {
+    /// Name of the mode, as written in config files.
+    pub fn name(&self) -> &str {
+        match *self {
+            GlyphMode::Ascii => "ascii",
+            GlyphMode::Unicode => "unicode",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GlyphMode> {
+        match name {
+            "ascii" => Some(GlyphMode::Ascii),
+            "unicode" => Some(GlyphMode::Unicode),
+            _ => None,
+        }
+    }
+}
+
+pub struct Glyphs {
+    mode: GlyphMode,
+    terrain: HashMap<Terrain, char>,
+    features: HashMap<Feature, char>,
+    units: HashMap<UnitType, char>,
+}
+
+impl Glyphs {
+    /// Returns the built-in glyph set for `mode`.
+    pub fn new(mode: GlyphMode) -> Glyphs {
+        let mut terrain = HashMap::new();
+        for t in Terrain::all().iter() {
+            let ch = match mode {
+                GlyphMode::Ascii => t.map_char(),
+                GlyphMode::Unicode => {
+                    match *t {
+                        Terrain::Plain => '·',
+                        Terrain::Grassland => '∴',
+                        Terrain::Desert => '∵',
+                        Terrain::Hill => '∩',
+                        Terrain::Mountain => '▲',
+                        Terrain::Coast => '≈',
+                        Terrain::Ocean => '≋',
+                        Terrain::Lake => '∽',
+                        Terrain::OutOfBounds => '?',
+                    }
+                }
+            };
+            terrain.insert(*t, ch);
+        }
+        let mut features = HashMap::new();
+        for f in Feature::all().iter() {
+            let ch = match mode {
+                GlyphMode::Ascii => f.map_char(),
+                GlyphMode::Unicode => {
+                    match *f {
+                        Feature::Ice => '░',
+                        Feature::MountainRange => '⋀',
+                        Feature::Forest => '♣',
+                        Feature::Jungle => '♠',
+                        Feature::Marsh => 'ψ',
+                    }
+                }
+            };
+            features.insert(*f, ch);
+        }
+        let mut units = HashMap::new();
+        for u in UnitType::all().iter() {
+            let ch = match mode {
+                GlyphMode::Ascii => u.map_symbol(),
+                GlyphMode::Unicode => {
+                    match *u {
+                        UnitType::Melee => '♞',
+                        UnitType::Ranged => '↟',
+                        UnitType::Worker => '⚒',
+                        UnitType::Horseman => '♘',
+                        UnitType::Swordsman => '†',
+                        UnitType::Scout => '⚑',
+                        UnitType::Settler => '♙',
+                    }
+                }
+            };
+            units.insert(*u, ch);
+        }
+        Glyphs {
+            mode: mode,
+            terrain: terrain,
+            features: features,
+            units: units,
+        }
+    }
+
+    pub fn mode(&self) -> GlyphMode {
+        self.mode
+    }
+
+    /// Overrides glyphs with the ones defined in glyph file `contents`.
+    ///
+    /// Unknown names and malformed lines are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::glyphs::{Glyphs, GlyphMode};
+    /// use civng_tui::terrain::{Terrain, Feature};
+    /// use civng_tui::unit::UnitType;
+    ///
+    /// let mut glyphs = Glyphs::new(GlyphMode::Unicode);
+    /// glyphs.apply_overrides("terrain.coast = ~\nunit.ranged = r\nunit.foobar = x\n\
+    ///                         feature.mountain_range = m\n");
+    /// assert_eq!(glyphs.feature_char(Feature::MountainRange), 'm');
+    /// assert_eq!(glyphs.terrain_char(Terrain::Coast), '~');
+    /// assert_eq!(glyphs.unit_char(UnitType::Ranged), 'r');
+    /// assert_eq!(glyphs.terrain_char(Terrain::Mountain), '▲');
+    /// ```
+    pub fn apply_overrides(&mut self, contents: &str) {
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap().trim();
+            let glyph = match parts.next().and_then(|v| v.trim().chars().next()) {
+                Some(ch) => ch,
+                None => continue,
+            };
+            let mut keyparts = key.splitn(2, '.');
+            let kind = keyparts.next().unwrap();
+            let name = match keyparts.next() {
+                Some(n) => n,
+                None => continue,
+            };
+            match kind {
+                "terrain" => {
+                    let all = Terrain::all();
+                    if let Some(t) = all.iter().find(|t| t.name().to_lowercase() == name) {
+                        self.terrain.insert(*t, glyph);
+                    }
+                }
+                "feature" => {
+                    let all = Feature::all();
+                    let key = |f: &Feature| f.name().to_lowercase().replace(' ', "_");
+                    if let Some(f) = all.iter().find(|f| key(f) == name) {
+                        self.features.insert(*f, glyph);
+                    }
+                }
+                "unit" => {
+                    let all = UnitType::all();
+                    if let Some(u) = all.iter().find(|u| u.name().to_lowercase() == name) {
+                        self.units.insert(*u, glyph);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Overrides glyphs with the ones defined in the glyph file at `path`.
+    ///
+    /// If the file can't be read, glyphs are left untouched.
+    pub fn load_overrides(&mut self, path: &Path) {
+        let mut contents = String::new();
+        if let Ok(mut fp) = File::open(path) {
+            if fp.read_to_string(&mut contents).is_ok() {
+                self.apply_overrides(&contents);
+            }
+        }
+    }
+
+    pub fn terrain_char(&self, terrain: Terrain) -> char {
+        match self.terrain.get(&terrain) {
+            Some(ch) => *ch,
+            None => terrain.map_char(),
+        }
+    }
+
+    pub fn feature_char(&self, feature: Feature) -> char {
+        match self.features.get(&feature) {
+            Some(ch) => *ch,
+            None => feature.map_char(),
+        }
+    }
+
+    pub fn unit_char(&self, unit_type: UnitType) -> char {
+        match self.units.get(&unit_type) {
+            Some(ch) => *ch,
+            None => unit_type.map_symbol(),
+        }
+    }
+
+    pub fn city_char(&self) -> char {
+        match self.mode {
+            GlyphMode::Ascii => '@',
+            GlyphMode::Unicode => '⌂',
+        }
+    }
+
+    /// Returns the characters of the filled and empty parts of HP bars.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::glyphs::{Glyphs, GlyphMode};
+    ///
+    /// assert_eq!(Glyphs::new(GlyphMode::Ascii).hp_bar_chars(), ('=', '-'));
+    /// assert_eq!(Glyphs::new(GlyphMode::Unicode).hp_bar_chars(), ('█', '░'));
+    /// ```
+    pub fn hp_bar_chars(&self) -> (char, char) {
+        match self.mode {
+            GlyphMode::Ascii => ('=', '-'),
+            GlyphMode::Unicode => ('█', '░'),
+        }
+    }
+}
@@ -5,11 +5,12 @@
 // http://www.gnu.org/licenses/gpl-3.0.html
 //
 
-//! # civng
+//! # civng-tui
 //!
-//! A Civ 5 implementation with a Text-based UI.
+//! The text-based UI of civng, a Civ 5 implementation.
 //!
-//! See README for app-level details. This is simply an API documentation.
+//! See README for app-level details. This is simply an API documentation. The game engine itself
+//! lives in `civng-core`, whose modules are re-exported here.
 
 // This lib.rs unit is there so we can run doctests. There's a limitation on cargo where it can
 // only run tests on libraries. See https://github.com/rust-lang/cargo/issues/1274
@@ -18,27 +19,42 @@
 //
 
 // Makes doctest so much simpler... Instead of having to declare extern crates in doctest, we
-// simply import rustty stuff from the civng namespace.
+// simply import rustty stuff from the civng_tui namespace.
 pub use rustty::ui::Widget;
 
+// Engine modules, re-exported so that our modules can use them as if they were our own.
+pub use civng_core::{hexpos, bitset, fog, terrain, map, events, unit, city, building, combat,
+                     combat_log, modifiers, civ5map, ai, rng, mapgen, heightmap, savegame, pbem,
+                     export, bot, scenario, script, difficulty, diplomacy, religion, blockers,
+                     rules, quickstart, counters};
+
+extern crate civng_core;
+extern crate libc;
 extern crate num;
+extern crate base64;
+extern crate sha1_smol;
 extern crate rustty;
-extern crate byteorder;
-extern crate rand;
-#[macro_use]
-extern crate bitflags;
 
-pub mod hexpos;
-pub mod terrain;
-pub mod map;
-pub mod unit;
-pub mod combat;
+pub mod backend;
+pub mod spectator;
 pub mod screen;
-pub mod civ5map;
 pub mod selection;
 pub mod game;
-pub mod ai;
 pub mod overhead;
+pub mod theme;
+pub mod config;
+pub mod glyphs;
+pub mod dialog;
+pub mod menu_dialog;
+pub mod map_chooser;
 pub mod details_window;
+pub mod city_dialog;
+pub mod diplomacy_dialog;
+pub mod pantheon_dialog;
+pub mod promotion_dialog;
 pub mod combat_confirm_dialog;
 pub mod combat_result_window;
+pub mod combat_stats_window;
+pub mod tile_info_dialog;
+pub mod cli;
+pub mod alert;
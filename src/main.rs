@@ -5,30 +5,408 @@
 // http://www.gnu.org/licenses/gpl-3.0.html
 //
 
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
+use std::process::{self, Command, Stdio, ChildStdin, ChildStdout};
 
-use civng::game::Game;
-use civng::unit::{Unit, UnitType, Player};
-use civng::hexpos::{Pos, OffsetPos};
+use civng_tui::game::{Game, CRASH_SAVE_PATH, keep_panic_messages, take_panic_message};
+use civng_tui::backend::{Backend, TermBackend};
+use civng_tui::spectator::SpectatorServer;
+use civng_tui::config::Config;
+use civng_tui::cli::{LaunchOptions, parse_args, usage};
+use civng_core::civ5map::{list_civ5maps, load_civ5map_scenario};
+use civng_tui::map_chooser::{choose_map, choose_difficulty, show_error};
+use civng_core::map::LiveMap;
+use civng_tui::theme::Theme;
+use civng_core::unit::Player;
+use civng_core::scenario::{Scenario, add_default_units, add_scenario_units, add_default_cities,
+                           start_positions};
+use civng_core::ai::play_turn;
+use civng_core::rng;
+use civng_core::export::{game_state_json, full_state_json, event_json};
+use civng_core::bot::Bot;
+use civng_core::difficulty::Difficulty;
+use civng_core::script::{Trigger, ModScript, ScriptEvent, run_triggers};
+use civng_core::terrain::TerrainMap;
+use civng_core::heightmap::{GrayImage, import_heightmap};
+use civng_core::quickstart::QuickStart;
 
 extern crate rustty;
-extern crate civng;
+extern crate civng_core;
+extern crate civng_tui;
+
+/// Returns the map made from the heightmap image at `path` and the moisture image at
+/// `moisture_path`.
+fn load_heightmap(path: &Path, moisture_path: Option<&Path>) -> io::Result<TerrainMap> {
+    let heightmap = try!(GrayImage::load(path));
+    let moisture = match moisture_path {
+        Some(p) => Some(try!(GrayImage::load(p))),
+        None => None,
+    };
+    import_heightmap(&heightmap, moisture.as_ref())
+}
+
+/// Bot program we talk to through its standard input and output.
+type PipeBot = Bot<BufReader<ChildStdout>, ChildStdin>;
+
+/// Starts `command` as a bot, through the shell.
+fn spawn_bot(command: &str) -> PipeBot {
+    let child = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .spawn();
+    match child {
+        Ok(child) => Bot::new(BufReader::new(child.stdout.unwrap()), child.stdin.unwrap()),
+        Err(err) => {
+            println!("Can't start bot {}: {}", command, err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Runs `triggers` and the hooks of `script` for `event`, adding their messages to `messages`.
+fn fire_event(triggers: &[Trigger],
+              script: Option<&ModScript>,
+              event: ScriptEvent,
+              map: &mut LiveMap,
+              messages: &mut Vec<String>) {
+    messages.extend(run_triggers(triggers, event, map));
+    if let Some(script) = script {
+        messages.extend(script.run_hooks(event, map));
+    }
+}
+
+/// Lets the AI play every player for `turns` turns, printing how many units each has left.
+///
+/// Combat statistics of each player are printed at the end, which helps checking our dice.
+///
+/// Messages of `triggers` and of the hooks of `script` are printed as they're triggered. If we
+/// have a `dump`, the game state, as known by `Player::Me`, is written to it after each turn.
+///
+/// If we have a `bot`, it plays `Player::Me` instead of the AI. Should it go away, the AI takes
+/// over.
+///
+/// Like in the game, `spectators` get the full state at the start of each turn and the events that
+/// follow.
+fn run_headless(mut map: LiveMap,
+                turns: u16,
+                triggers: &[Trigger],
+                script: Option<&ModScript>,
+                mut dump: Option<Box<Write>>,
+                mut bot: Option<PipeBot>,
+                spectators: Option<&SpectatorServer>) {
+    let players: Vec<Player> = Some(Player::Me).into_iter()
+                                               .chain(map.units().ai_players())
+                                               .collect();
+    for turn in 1..turns + 1 {
+        map.refresh();
+        if let Some(server) = spectators {
+            server.broadcast_state(&full_state_json(&map, turn));
+        }
+        let mut messages = Vec::new();
+        fire_event(triggers, script, ScriptEvent::TurnStart(turn), &mut map, &mut messages);
+        for player in players.iter() {
+            if *player == Player::Me && bot.is_some() {
+                let result = bot.as_mut().unwrap().play_turn(&mut map, Player::Me, turn);
+                match result {
+                    Ok(()) => continue,
+                    Err(err) => {
+                        println!("Bot disconnected, the AI takes over: {}", err);
+                        bot = None;
+                    }
+                }
+            }
+            play_turn(*player, &mut map);
+        }
+        for event in map.take_events() {
+            if let Some(server) = spectators {
+                server.broadcast(&event_json(&event));
+            }
+            if let Some(script_event) = ScriptEvent::from_game_event(&event) {
+                fire_event(triggers, script, script_event, &mut map, &mut messages);
+            }
+        }
+        for msg in messages {
+            println!("{}", msg);
+        }
+        let counts: Vec<String> = players.iter()
+                                         .map(|p| {
+                                             let count = map.units()
+                                                            .all_units()
+                                                            .filter(|u| u.owner() == *p)
+                                                            .count();
+                                             format!("{}: {}", p.name(), count)
+                                         })
+                                         .collect();
+        println!("Turn {}: {}", turn, counts.join(", "));
+        if let Some(ref mut dest) = dump {
+            let _ = writeln!(dest, "{}", game_state_json(&map, turn, Player::Me));
+        }
+    }
+    let log = map.combat_log();
+    for player in players.iter() {
+        let summary = log.summary(*player);
+        println!("{}: won {} of {} combats, luck {:+.1}",
+                 player.name(),
+                 summary.won,
+                 summary.combats,
+                 summary.average_luck);
+    }
+    println!("Dice deviation: {:+.1} dmg per roll over {} combats",
+             log.summary(Player::Me).average_deviation,
+             log.records().len());
+}
+
+/// Plays `game` until the player quits.
+///
+/// Should the game panic, it's saved to `CRASH_SAVE_PATH` and the terminal is restored before we
+/// print what happened and exit.
+fn run_game<B: Backend>(mut game: Game<B>,
+                        dump: Option<Box<Write>>,
+                        spectators: Option<SpectatorServer>,
+                        debug: bool) {
+    if debug {
+        game.enable_debug_console();
+    }
+    if let Some(dest) = dump {
+        game.set_state_dump(dest);
+    }
+    if let Some(server) = spectators {
+        game.set_spectator_server(server);
+    }
+    keep_panic_messages();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        game.new_turn();
+        loop {
+            game.draw();
+            if !game.handle_events() {
+                break;
+            }
+        }
+    }));
+    if result.is_ok() {
+        return;
+    }
+    // The game might be too broken to be saved, in which case we panic again.
+    let saved = panic::catch_unwind(AssertUnwindSafe(|| game.crash_save()));
+    // Dropping the game drops its terminal, which restores it.
+    drop(game);
+    let _ = panic::take_hook();
+    let message = take_panic_message();
+    println!("civng crashed: {}", message.unwrap_or_else(|| "unknown error".to_owned()));
+    match saved {
+        Ok(Ok(())) => println!("The game was saved to {}.", CRASH_SAVE_PATH),
+        Ok(Err(err)) => println!("The game couldn't be saved: {}", err),
+        Err(_) => println!("The game couldn't be saved."),
+    }
+    process::exit(101);
+}
 
 fn main() {
-    let mut game = Game::new(Path::new("resources/pangea-duel.Civ5Map"));
-    let unitpos = game.map().first_passable(Pos::origin());
-    let _ = game.add_unit(Unit::new(UnitType::Melee, Player::Me, unitpos));
-    let unitpos = game.map().first_passable(Pos::origin());
-    let _ = game.add_unit(Unit::new(UnitType::Ranged, Player::Me, unitpos));
-    let unitpos = game.map().first_passable(OffsetPos::new(4, 3).to_pos());
-    let _ = game.add_unit(Unit::new(UnitType::Melee, Player::NotMe, unitpos));
-    let unitpos = game.map().first_passable(OffsetPos::new(4, 3).to_pos());
-    let _ = game.add_unit(Unit::new(UnitType::Melee, Player::NotMe, unitpos));
-    game.new_turn();
-    loop {
-        game.draw();
-        if !game.handle_events() {
-            break;
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            println!("{}\n\n{}", msg, usage());
+            process::exit(1);
+        }
+    };
+    if options.show_help {
+        println!("{}", usage());
+        return;
+    }
+    let LaunchOptions { map_path,
+                        generate,
+                        symmetry,
+                        heightmap_path,
+                        moisture_path,
+                        scenario_path,
+                        seed,
+                        ai_players,
+                        difficulty,
+                        headless_turns,
+                        config_path,
+                        audit_path,
+                        replay_path,
+                        dump_path,
+                        bot_command,
+                        spectate_addr,
+                        debug,
+                        .. } = options;
+    if let Some(seed) = seed {
+        rng::seed(seed);
+    }
+    if let Some(audit_path) = audit_path {
+        match File::create(&audit_path) {
+            Ok(fp) => rng::start_audit(Box::new(fp)),
+            Err(err) => {
+                println!("Can't create {}: {}", audit_path.display(), err);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(replay_path) = replay_path {
+        match rng::load_rolls(&replay_path) {
+            Ok(rolls) => rng::replay(rolls),
+            Err(err) => {
+                println!("Can't load {}: {}", replay_path.display(), err);
+                process::exit(1);
+            }
+        }
+    }
+    let dump: Option<Box<Write>> = match dump_path {
+        Some(dump_path) => {
+            match File::create(&dump_path) {
+                Ok(fp) => Some(Box::new(fp)),
+                Err(err) => {
+                    println!("Can't create {}: {}", dump_path.display(), err);
+                    process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+    let bot = bot_command.map(|command| spawn_bot(&command));
+    let spectators = spectate_addr.map(|addr| {
+        match SpectatorServer::start(&addr[..]) {
+            Ok(server) => server,
+            Err(err) => {
+                println!("Can't listen for spectators on {}: {}", addr, err);
+                process::exit(1);
+            }
         }
+    });
+    let config = Config::load(&config_path);
+    let quick_start = generate.map(|preset| {
+        let mut options = QuickStart::new(preset);
+        options.ai_players = ai_players;
+        options.symmetry = symmetry;
+        options
+    });
+    // Imported maps don't have scenario data.
+    let imported = heightmap_path.map(|path| {
+        match load_heightmap(&path, moisture_path.as_ref().map(|p| p.as_path())) {
+            Ok(map) => (map, None),
+            Err(err) => {
+                println!("Can't import heightmap {}: {}", path.display(), err);
+                process::exit(1);
+            }
+        }
+    });
+    if let (Some(turns), Some(scenario_path)) = (headless_turns, scenario_path.as_ref()) {
+        let loaded = Scenario::load(scenario_path)
+                         .and_then(|s| {
+                             s.build_map(difficulty.unwrap_or(Difficulty::Prince))
+                              .map(|map| (map, s.triggers, s.script))
+                         });
+        match loaded {
+            Ok((mut map, triggers, script)) => {
+                map.set_rules(config.rules);
+                run_headless(map,
+                             turns,
+                             &triggers,
+                             script.as_ref(),
+                             dump,
+                             bot,
+                             spectators.as_ref())
+            }
+            Err(err) => {
+                println!("Can't load scenario {}: {}", scenario_path.display(), err);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    if let (Some(turns), Some(mut options)) = (headless_turns, quick_start) {
+        options.difficulty = difficulty.unwrap_or(Difficulty::Prince);
+        let mut map = options.build_map();
+        map.set_rules(config.rules);
+        run_headless(map, turns, &[], None, dump, bot, spectators.as_ref());
+        return;
+    }
+    if let Some(turns) = headless_turns {
+        let (terrainmap, scenario) = match (imported, map_path) {
+            (Some(imported), _) => imported,
+            (None, Some(map_path)) => {
+                match load_civ5map_scenario(&map_path) {
+                    Ok(loaded) => loaded,
+                    Err(err) => {
+                        println!("Can't load {}: {}", map_path.display(), err);
+                        process::exit(1);
+                    }
+                }
+            }
+            (None, None) => {
+                println!("Headless mode requires --map, --generate, --heightmap or --scenario");
+                process::exit(1);
+            }
+        };
+        let mut map = LiveMap::new(terrainmap);
+        map.set_difficulty(difficulty.unwrap_or(Difficulty::Prince));
+        map.set_rules(config.rules);
+        if !add_scenario_units(&mut map, scenario.as_ref()) {
+            add_default_units(&mut map, ai_players, &start_positions(scenario.as_ref()));
+        }
+        add_default_cities(&mut map, scenario.as_ref());
+        run_headless(map, turns, &[], None, dump, bot, spectators.as_ref());
+        return;
+    }
+    let mut term = TermBackend::new().unwrap();
+    let theme = Theme::detect(config.theme);
+    if let Some(scenario_path) = scenario_path {
+        let difficulty = difficulty.unwrap_or(Difficulty::Prince);
+        match Game::from_scenario(term, &scenario_path, difficulty, &config) {
+            Ok(game) => run_game(game, dump, spectators, debug),
+            Err(err) => {
+                // The terminal was restored when the game failed to be created.
+                println!("Can't load scenario {}: {}", scenario_path.display(), err);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(mut options) = quick_start {
+        options.difficulty = match difficulty.or_else(|| choose_difficulty(&mut term, &theme)) {
+            Some(d) => d,
+            None => return,
+        };
+        run_game(Game::quick_start(term, &options, &config), dump, spectators, debug);
+        return;
+    }
+    let maps = list_civ5maps(Path::new("resources"));
+    let mut map_path = map_path;
+    // We go back to the map chooser until we get a map we can load.
+    let mut loaded = imported;
+    while loaded.is_none() {
+        let path = match map_path.take() {
+            Some(p) => p,
+            None => {
+                match choose_map(&mut term, &maps, &theme) {
+                    Some(index) => maps[index].path.clone(),
+                    None => return,
+                }
+            }
+        };
+        match load_civ5map_scenario(&path) {
+            Ok(l) => loaded = Some(l),
+            Err(err) => show_error(&mut term, &format!("Can't load map: {}", err), &theme),
+        }
+    }
+    let difficulty = match difficulty.or_else(|| choose_difficulty(&mut term, &theme)) {
+        Some(d) => d,
+        None => return,
+    };
+    let (terrainmap, scenario) = loaded.unwrap();
+    let mut map = LiveMap::new(terrainmap);
+    map.set_difficulty(difficulty);
+    if !add_scenario_units(&mut map, scenario.as_ref()) {
+        add_default_units(&mut map, ai_players, &start_positions(scenario.as_ref()));
     }
+    add_default_cities(&mut map, scenario.as_ref());
+    run_game(Game::with_map(term, map, &config), dump, spectators, debug);
 }
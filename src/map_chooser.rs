@@ -0,0 +1,145 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Map and difficulty selection menus shown before the game starts.
+
+use std::cmp::max;
+use std::time::Duration;
+
+use rustty::{CellAccessor, Attr};
+use rustty::ui::{Painter, Widget, Alignable, HorizontalAlign, VerticalAlign};
+
+use civ5map::MapInfo;
+use theme::Theme;
+use difficulty::Difficulty;
+use menu_dialog::create_message_dialog;
+use backend::Backend;
+
+const KEY_ESCAPE: char = '\x1b';
+
+fn describe_map(map: &MapInfo) -> String {
+    let name = if map.name.is_empty() {
+        map.path.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned())
+    } else {
+        map.name.clone()
+    };
+    format!("{} ({}x{}, {} players)",
+            name,
+            map.width,
+            map.height,
+            map.playercount)
+}
+
+fn draw_chooser<B: Backend>(term: &mut B,
+                            title: &str,
+                            lines: &[String],
+                            selected: usize,
+                            theme: &Theme) {
+    let width = max(lines.iter().map(|l| l.chars().count()).max().unwrap_or(0),
+                    title.chars().count()) + 6;
+    let mut window = Widget::new(width, lines.len() + 6);
+    window.align(term, HorizontalAlign::Middle, VerticalAlign::Middle, 0);
+    window.clear(theme.dialog_cell());
+    let x = window.halign_line(title, HorizontalAlign::Middle, 1);
+    window.printline(x, 1, title);
+    for (i, line) in lines.iter().enumerate() {
+        let mut cell = theme.dialog_cell();
+        if i == selected {
+            cell.set_attrs(Attr::Reverse);
+        }
+        window.printline_with_cell(3, 3 + i, line, cell);
+    }
+    let help = "w/s: select, Enter: choose, Esc: quit";
+    let x = window.halign_line(help, HorizontalAlign::Middle, 1);
+    window.printline(x, lines.len() + 4, help);
+    window.draw_box();
+    term.new_frame();
+    window.draw_into(term);
+    term.present();
+}
+
+/// Lets the user choose among `lines`, starting with `initial` selected, and returns the index of
+/// the chosen one.
+///
+/// Returns `None` if the user quits instead of choosing.
+fn choose<B: Backend>(term: &mut B,
+                      title: &str,
+                      lines: &[String],
+                      initial: usize,
+                      theme: &Theme)
+                      -> Option<usize> {
+    if lines.is_empty() {
+        return None;
+    }
+    let mut selected = initial;
+    loop {
+        draw_chooser(term, title, lines, selected, theme);
+        match term.poll_key(Duration::from_secs(1)) {
+            Some('w') | Some('8') => {
+                selected = if selected == 0 {
+                    lines.len() - 1
+                } else {
+                    selected - 1
+                };
+            }
+            Some('s') | Some('2') => {
+                selected = (selected + 1) % lines.len();
+            }
+            Some('\r') => {
+                return Some(selected);
+            }
+            Some('Q') | Some(KEY_ESCAPE) => {
+                return None;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Lets the user choose among `maps` and returns the index of the chosen one.
+///
+/// Returns `None` if the user quits instead of choosing.
+pub fn choose_map<B: Backend>(term: &mut B, maps: &[MapInfo], theme: &Theme) -> Option<usize> {
+    let lines: Vec<String> = maps.iter().map(describe_map).collect();
+    choose(term, "Choose a map", &lines, 0, theme)
+}
+
+/// Lets the user choose a difficulty level, `Prince` being selected at first.
+///
+/// Returns `None` if the user quits instead of choosing.
+pub fn choose_difficulty<B: Backend>(term: &mut B, theme: &Theme) -> Option<Difficulty> {
+    let levels = Difficulty::all();
+    let lines: Vec<String> = levels.iter()
+                                   .map(|d| {
+                                       format!("{:<10} AI combat bonus: {:+}%, extra units: {}",
+                                               d.name(),
+                                               d.ai_combat_bonus(),
+                                               d.ai_extra_units())
+                                   })
+                                   .collect();
+    let initial = levels.iter().position(|&d| d == Difficulty::Prince).unwrap();
+    choose(term, "Choose a difficulty", &lines, initial, theme).map(|i| levels[i])
+}
+
+/// Shows `msg` in a dialog until the user presses a key.
+///
+/// Used to report errors happening before the game starts, such as a map that can't be loaded.
+pub fn show_error<B: Backend>(term: &mut B, msg: &str, theme: &Theme) {
+    let mut d = create_message_dialog(msg, theme);
+    loop {
+        {
+            let w = d.window_mut();
+            w.align(term, HorizontalAlign::Middle, VerticalAlign::Middle, 0);
+            term.new_frame();
+            w.draw_into(term);
+            term.present();
+        }
+        if term.poll_key(Duration::from_secs(1)).is_some() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+use std::cmp::max;
+
+use rustty::CellAccessor;
+use rustty::ui::{Painter, HorizontalAlign, Dialog, DialogResult};
+
+use theme::Theme;
+
+pub const MENU_SAVE: i32 = 1;
+pub const MENU_LOAD: i32 = 2;
+pub const MENU_OPTIONS: i32 = 3;
+pub const MENU_QUIT: i32 = 4;
+pub const MENU_EMAIL: i32 = 5;
+
+pub const OPTIONS_THEME: i32 = 1;
+pub const OPTIONS_GLYPHS: i32 = 2;
+
+pub const EMAIL_SEND: i32 = 1;
+pub const EMAIL_RECEIVE: i32 = 2;
+
+/// Creates the in-game menu, shown with the escape key.
+///
+/// "Resume" returns `DialogResult::Cancel` and other buttons return `DialogResult::Custom` with one
+/// of the `MENU_*` constants.
+pub fn create_menu_dialog(theme: &Theme) -> Dialog {
+    let mut d = Dialog::new(70, 5);
+    {
+        let w = d.window_mut();
+        w.clear(theme.dialog_cell());
+        let msg = "Game Menu";
+        let x = w.halign_line(msg, HorizontalAlign::Middle, 1);
+        w.printline(x, 1, msg);
+    }
+    d.add_button("Resume", 'r', DialogResult::Cancel);
+    d.add_button("Save", 's', DialogResult::Custom(MENU_SAVE));
+    d.add_button("Load", 'l', DialogResult::Custom(MENU_LOAD));
+    d.add_button("Email", 'e', DialogResult::Custom(MENU_EMAIL));
+    d.add_button("Options", 'o', DialogResult::Custom(MENU_OPTIONS));
+    d.add_button("Quit", 'q', DialogResult::Custom(MENU_QUIT));
+    d.draw_buttons();
+    d.window_mut().draw_box();
+    d
+}
+
+/// Creates the options dialog, showing current option values.
+///
+/// "Back" returns `DialogResult::Cancel` and other buttons return `DialogResult::Custom` with one
+/// of the `OPTIONS_*` constants.
+pub fn create_options_dialog(theme: &Theme, glyphs_name: &str) -> Dialog {
+    let mut d = Dialog::new(40, 8);
+    {
+        let w = d.window_mut();
+        w.clear(theme.dialog_cell());
+        let msg = "Options";
+        let x = w.halign_line(msg, HorizontalAlign::Middle, 1);
+        w.printline(x, 1, msg);
+        w.printline(2, 3, &format!("Theme  | {}", theme.kind().name()));
+        w.printline(2, 4, &format!("Glyphs | {}", glyphs_name));
+    }
+    d.add_button("Theme", 't', DialogResult::Custom(OPTIONS_THEME));
+    d.add_button("Glyphs", 'g', DialogResult::Custom(OPTIONS_GLYPHS));
+    d.add_button("Back", 'b', DialogResult::Cancel);
+    d.draw_buttons();
+    d.window_mut().draw_box();
+    d
+}
+
+/// Creates the play-by-email dialog, telling which file turns are exchanged through.
+///
+/// "Back" returns `DialogResult::Cancel` and other buttons return `DialogResult::Custom` with one
+/// of the `EMAIL_*` constants.
+pub fn create_email_dialog(theme: &Theme, path: &str) -> Dialog {
+    let mut d = Dialog::new(50, 8);
+    {
+        let w = d.window_mut();
+        w.clear(theme.dialog_cell());
+        let msg = "Play by Email";
+        let x = w.halign_line(msg, HorizontalAlign::Middle, 1);
+        w.printline(x, 1, msg);
+        w.printline(2, 3, &format!("Turns are sent and received in {}", path));
+    }
+    d.add_button("Send turn", 's', DialogResult::Custom(EMAIL_SEND));
+    d.add_button("Receive turn", 'r', DialogResult::Custom(EMAIL_RECEIVE));
+    d.add_button("Back", 'b', DialogResult::Cancel);
+    d.draw_buttons();
+    d.window_mut().draw_box();
+    d
+}
+
+/// Creates a dialog showing `msg` with a single "Ok" button.
+pub fn create_message_dialog(msg: &str, theme: &Theme) -> Dialog {
+    let width = max(msg.chars().count() + 6, 20);
+    let mut d = Dialog::new(width, 6);
+    {
+        let w = d.window_mut();
+        w.clear(theme.dialog_cell());
+        w.printline(3, 1, msg);
+    }
+    d.add_button("Ok", 'o', DialogResult::Ok);
+    d.draw_buttons();
+    d.window_mut().draw_box();
+    d
+}
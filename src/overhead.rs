@@ -5,21 +5,174 @@
 // http://www.gnu.org/licenses/gpl-3.0.html
 //
 
-use rustty::{CellAccessor, Color};
+use std::cmp::{min, max};
+
+use rustty::{CellAccessor, Size};
 
 use terrain::TerrainMap;
 use hexpos::{OffsetPos, Pos};
+use fog::FogOfWar;
+use theme::Theme;
+use glyphs::Glyphs;
+
+/// Part of the map the overhead map shows.
+///
+/// Maps larger than the terminal are scrolled through. When downsampled, each cell stands for a
+/// square of 2x2 tiles, which is enough for Civ5's largest maps to almost fit in a terminal.
+pub struct OverheadView {
+    /// Offset position of the tile in the top left corner.
+    left: i32,
+    top: i32,
+    downsampled: bool,
+    /// Whether land is colored by continent rather than by terrain.
+    by_continent: bool,
+}
+
+impl OverheadView {
+    pub fn new() -> OverheadView {
+        OverheadView {
+            left: 0,
+            top: 0,
+            downsampled: false,
+            by_continent: false,
+        }
+    }
+
+    /// Number of tiles, horizontally and vertically, each cell stands for.
+    pub fn scale(&self) -> i32 {
+        if self.downsampled { 2 } else { 1 }
+    }
+
+    pub fn is_downsampled(&self) -> bool {
+        self.downsampled
+    }
+
+    /// Switches between one tile per cell and 2x2 tiles per cell, keeping the same tiles around
+    /// the center of the view.
+    pub fn toggle_downsample(&mut self, screen_size: Size) {
+        let (cols, rows) = (screen_size.0 as i32, screen_size.1 as i32);
+        let center = (self.left + cols * self.scale() / 2, self.top + rows * self.scale() / 2);
+        self.downsampled = !self.downsampled;
+        self.left = center.0 - cols * self.scale() / 2;
+        self.top = center.1 - rows * self.scale() / 2;
+    }
+
+    pub fn is_by_continent(&self) -> bool {
+        self.by_continent
+    }
 
-pub fn draw_overhead_map(target: &mut CellAccessor, map: &TerrainMap, selected_pos: Option<Pos>) {
+    /// Switches between coloring land by terrain and by continent.
+    pub fn toggle_continents(&mut self) {
+        self.by_continent = !self.by_continent;
+    }
+
+    /// Scrolls the view by `dx` and `dy` cells.
+    pub fn scroll(&mut self, dx: i32, dy: i32) {
+        self.left += dx * self.scale();
+        self.top += dy * self.scale();
+    }
+
+    /// Scrolls the view so that `pos` is at its center.
+    pub fn center_on(&mut self, pos: Pos, screen_size: Size) {
+        let opos = pos.to_offset_pos();
+        self.left = opos.x - screen_size.0 as i32 * self.scale() / 2;
+        self.top = opos.y - screen_size.1 as i32 * self.scale() / 2;
+    }
+
+    /// Keeps the view from going past the edges of a map of `map_size` tiles, unless the map
+    /// is smaller than the screen.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::overhead::OverheadView;
+    ///
+    /// let mut view = OverheadView::new();
+    /// view.scroll(100, 100);
+    /// view.clamp((128, 80), (80, 24));
+    /// assert_eq!(view.topleft(), (48, 56));
+    /// view.toggle_downsample((80, 24));
+    /// view.clamp((128, 80), (80, 24));
+    /// assert_eq!(view.topleft(), (0, 32));
+    /// view.scroll(-10, -100);
+    /// view.clamp((128, 80), (80, 24));
+    /// assert_eq!(view.topleft(), (0, 0));
+    /// ```
+    pub fn clamp(&mut self, map_size: (i32, i32), screen_size: Size) {
+        let (mapw, maph) = map_size;
+        let width = screen_size.0 as i32 * self.scale();
+        let height = screen_size.1 as i32 * self.scale();
+        self.left = max(min(self.left, mapw - width), 0);
+        self.top = max(min(self.top, maph - height), 0);
+    }
+
+    /// Offset position of the tile in the top left corner.
+    pub fn topleft(&self) -> (i32, i32) {
+        (self.left, self.top)
+    }
+}
+
+/// Draws the part of `map` that `view` shows, one cell per tile or per square of 2x2 tiles.
+///
+/// A square of tiles shows its land rather than its water, so that small islands don't
+/// disappear. The selected tile wins over both. When `view` colors land by continent, land keeps
+/// its char but gets the color of its continent.
+pub fn draw_overhead_map(target: &mut CellAccessor,
+                         map: &TerrainMap,
+                         fog: Option<&FogOfWar>,
+                         selected_pos: Option<Pos>,
+                         view: &OverheadView,
+                         theme: &Theme,
+                         glyphs: &Glyphs) {
     let (mapw, maph) = map.size();
-    for ih in 0..maph {
-        for iw in 0..mapw {
-            let pos = OffsetPos::new(iw, ih).to_pos();
-            let terrain = map.get_terrain(pos);
-            if let Some(cell) = target.get_mut(iw as usize, ih as usize) {
-                cell.set_ch(terrain.map_char());
-                if selected_pos == Some(pos) {
-                    cell.set_bg(Color::Blue);
+    let (cols, rows) = target.size();
+    let scale = view.scale();
+    let (left, top) = view.topleft();
+    for cy in 0..rows {
+        for cx in 0..cols {
+            let x0 = left + cx as i32 * scale;
+            let y0 = top + cy as i32 * scale;
+            if x0 >= mapw || y0 >= maph {
+                continue;
+            }
+            let block: Vec<Pos> = (0..scale * scale)
+                                      .map(|i| OffsetPos::new(x0 + i % scale, y0 + i / scale))
+                                      .filter(|o| o.x < mapw && o.y < maph)
+                                      .map(|o| o.to_pos())
+                                      .filter(|&p| fog.map_or(true, |f| f.is_explored(p)))
+                                      .collect();
+            let selected = selected_pos.map_or(false, |p| {
+                let o = p.to_offset_pos();
+                o.x >= x0 && o.x < x0 + scale && o.y >= y0 && o.y < y0 + scale
+            });
+            let shown = block.iter()
+                             .cloned()
+                             .find(|&p| Some(p) == selected_pos)
+                             .or_else(|| {
+                                 block.iter().cloned().find(|&p| !map.get_terrain(p).is_water())
+                             })
+                             .or_else(|| block.first().cloned());
+            if let Some(cell) = target.get_mut(cx, cy) {
+                if let Some(pos) = shown {
+                    match map.get_feature(pos) {
+                        Some(f) => {
+                            cell.set_ch(glyphs.feature_char(f));
+                            cell.set_fg(theme.feature_fg(f));
+                        }
+                        None => {
+                            let terrain = map.get_terrain(pos);
+                            cell.set_ch(glyphs.terrain_char(terrain));
+                            cell.set_fg(theme.terrain_fg(terrain));
+                        }
+                    }
+                    if let Some(continent) = map.continent_at(pos) {
+                        if view.is_by_continent() {
+                            cell.set_fg(theme.continent_fg(continent));
+                        }
+                    }
+                }
+                if selected {
+                    theme.selection_highlight().apply(cell);
                 }
             }
         }
@@ -0,0 +1,35 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+use rustty::CellAccessor;
+use rustty::ui::{Painter, HorizontalAlign, Dialog, DialogResult};
+
+use dialog::item_key;
+use religion::Belief;
+use theme::Theme;
+
+/// Creates the dialog asking which of `beliefs` our new pantheon adopts.
+///
+/// Beliefs are listed with the key adopting them. "Later" returns `DialogResult::Cancel`.
+pub fn create_pantheon_dialog(beliefs: &[Belief], theme: &Theme) -> Dialog {
+    let mut d = Dialog::new(70, 8 + beliefs.len());
+    {
+        let w = d.window_mut();
+        w.clear(theme.dialog_cell());
+        let msg = "We have enough faith to found a pantheon! Which belief do we adopt?";
+        let x = w.halign_line(msg, HorizontalAlign::Middle, 1);
+        w.printline(x, 1, msg);
+        for (i, belief) in beliefs.iter().enumerate() {
+            let s = format!("{} {:<22} {}", item_key(i), belief.name(), belief.description());
+            w.printline(2, 3 + i, &s);
+        }
+    }
+    d.add_button("Later", 'l', DialogResult::Cancel);
+    d.draw_buttons();
+    d.window_mut().draw_box();
+    d
+}
@@ -0,0 +1,36 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+use rustty::CellAccessor;
+use rustty::ui::{Painter, HorizontalAlign, Dialog, DialogResult};
+
+use dialog::item_key;
+use unit::Unit;
+use theme::Theme;
+
+/// Creates the dialog asking which of its available promotions `unit` picks.
+///
+/// Promotions are listed with the key picking them. "Later" returns `DialogResult::Cancel`.
+pub fn create_promotion_dialog(unit: &Unit, theme: &Theme) -> Dialog {
+    let promotions = unit.available_promotions();
+    let mut d = Dialog::new(60, 8 + promotions.len());
+    {
+        let w = d.window_mut();
+        w.clear(theme.dialog_cell());
+        let msg = format!("Our {} can be promoted! Which promotion does it pick?", unit.name());
+        let x = w.halign_line(&msg, HorizontalAlign::Middle, 1);
+        w.printline(x, 1, &msg);
+        for (i, promotion) in promotions.iter().enumerate() {
+            let s = format!("{} {:<7} {}", item_key(i), promotion.name(), promotion.description());
+            w.printline(2, 3 + i, &s);
+        }
+    }
+    d.add_button("Later", 'l', DialogResult::Cancel);
+    d.draw_buttons();
+    d.window_mut().draw_box();
+    d
+}
@@ -15,10 +15,14 @@ use rustty::Pos as ScreenPos;
 use rustty::ui::{Painter, Widget};
 
 use hexpos::{Pos, OffsetPos};
-use terrain::{Terrain, TerrainMap};
+use terrain::{Terrain, TerrainMap, Feature};
+use mapgen::{is_choke_point, defense_score, DEFENSIVE_SCORE};
 use map::LiveMap;
-use unit::{Unit, Player};
+use unit::{Unit, UnitID, Player, MAX_HP};
+use city::City;
 use selection::Selection;
+use theme::{Theme, ThemeKind, ColorDepth, Highlight};
+use glyphs::{Glyphs, GlyphMode};
 
 const CELL_WIDTH: usize = 7;
 const CELL_HEIGHT: usize = 4;
@@ -85,20 +89,20 @@ impl HexCell {
         self.widget.draw_into(cells);
     }
 
-    /// Highlight the cell with specified `color`.
+    /// Highlight the cell with specified `highlight`.
     ///
-    /// We proceed by setting the background color of peripherical cells. We don't highlight the
+    /// We proceed by highlighting peripherical cells. We don't highlight the
     /// whole background because it makes the contents of the cell very hard to read. We also don't
     /// highlight the cell's grid because of a technical problem: The upper line of the cell is
     /// not actually made of characters, it's made of the `Underline` attribute of the above cell.
     /// If we changed the color of that line, we would also change the color of the above cell's
     /// lower characters.
-    pub fn highlight(&mut self, color: Color) {
+    ///
+    /// Highlighting with an attribute adds it to the `Underline` of the lower characters, so that
+    /// our grid stays intact.
+    pub fn highlight(&mut self, highlight: Highlight) {
         let (cols, rows) = self.widget.size();
-        let mut doit = |x, y| {
-            let cell = self.widget.get_mut(x, y).unwrap();
-            cell.set_bg(color);
-        };
+        let mut doit = |x, y| highlight.apply(self.widget.get_mut(x, y).unwrap());
         for ix in 1..cols - 1 {
             doit(ix, 0);
             doit(ix, rows - 1);
@@ -109,11 +113,20 @@ impl HexCell {
         }
     }
 
-    pub fn draw_terrain(&mut self, terrain: Terrain) {
-        let ch = terrain.map_char();
+    /// Draws `terrain`, or `feature` if the tile has one, as it hides the terrain.
+    pub fn draw_terrain(&mut self,
+                        terrain: Terrain,
+                        feature: Option<Feature>,
+                        theme: &Theme,
+                        glyphs: &Glyphs) {
+        let (ch, fg) = match feature {
+            Some(f) => (glyphs.feature_char(f), theme.feature_fg(f)),
+            None => (glyphs.terrain_char(terrain), theme.terrain_fg(terrain)),
+        };
         let s: String = (0..5).map(|_| ch).collect();
-        self.widget.printline(1, 0, &s);
-        let cell = Cell::with_style(Color::Default, Color::Default, Attr::Underline);
+        let cell = Cell::with_style(fg, Color::Default, Attr::Default);
+        self.widget.printline_with_cell(1, 0, &s, cell);
+        let cell = Cell::with_style(fg, Color::Default, Attr::Underline);
         self.widget.printline_with_cell(1, 3, &s, cell);
     }
 
@@ -121,17 +134,66 @@ impl HexCell {
         self.widget.printline(1, 1, &pos.fmt());
     }
 
-    pub fn draw_unit(&mut self, unit: &Unit, is_active: bool) {
-        let mut cell = self.widget.get_mut(3, 2).unwrap();
-        cell.set_ch(unit.map_symbol());
-        let color = if unit.owner() != Player::Me {
-            Color::Red
-        } else if is_active {
-            Color::Blue
-        } else {
-            Color::Default
-        };
-        cell.set_fg(color);
+    /// Marks choke points with `X` and defensive tiles with `+`, on each side of the unit symbol.
+    pub fn draw_analysis(&mut self, choke_point: bool, defensive: bool) {
+        let cell = Cell::with_style(Color::Default, Color::Default, Attr::Bold);
+        if choke_point {
+            self.widget.printline_with_cell(1, 2, "X", cell);
+        }
+        if defensive {
+            self.widget.printline_with_cell(5, 2, "+", cell);
+        }
+    }
+
+    /// Draws the city symbol followed by the population of `city` in the middle line of the cell.
+    pub fn draw_city(&mut self, city: &City, theme: &Theme, glyphs: &Glyphs) {
+        let s = format!("{}{}", glyphs.city_char(), city.population());
+        let cell = Cell::with_style(theme.player_color(city.owner()), Color::Default, Attr::Bold);
+        self.widget.printline_with_cell(2, 1, &s, cell);
+    }
+
+    pub fn draw_unit(&mut self, unit: &Unit, is_active: bool, theme: &Theme, glyphs: &Glyphs) {
+        {
+            let cell = self.widget.get_mut(3, 2).unwrap();
+            cell.set_ch(glyphs.unit_char(unit.type_()));
+            let (color, attrs) = theme.unit_style(unit.owner(), is_active);
+            cell.set_fg(color);
+            cell.set_attrs(attrs);
+        }
+        if unit.is_damaged() {
+            self.draw_hp_bar(unit.hp(), theme, glyphs);
+        }
+    }
+
+    /// Draws `dmg`, as a negative number, right above the unit symbol.
+    ///
+    /// It hides the city of the cell, if any, for as long as it's shown.
+    pub fn draw_damage(&mut self, dmg: u8, theme: &Theme) {
+        let s = format!("-{}", dmg);
+        let cell = Cell::with_style(theme.hp_color(0, MAX_HP), Color::Default, Attr::Bold);
+        self.widget.printline_with_cell(2, 1, &s, cell);
+    }
+
+    /// Draws a 3 chars HP bar right under the unit symbol.
+    ///
+    /// The bar replaces the terrain chars of the bottom line of the cell, but keeps their
+    /// `Underline` attribute so that the cell's grid stays intact. Its color goes from green to
+    /// red (in the default theme) as the unit loses HP.
+    fn draw_hp_bar(&mut self, hp: u8, theme: &Theme, glyphs: &Glyphs) {
+        const BAR_LEN: usize = 3;
+        let filled = (hp as usize * BAR_LEN).div_ceil(MAX_HP as usize);
+        let color = theme.hp_color(hp, MAX_HP);
+        let (filled_ch, empty_ch) = glyphs.hp_bar_chars();
+        for i in 0..BAR_LEN {
+            let ch = if i < filled {
+                filled_ch
+            } else {
+                empty_ch
+            };
+            let cell = self.widget.get_mut(2 + i, 3).unwrap();
+            cell.set_ch(ch);
+            cell.set_fg(color);
+        }
     }
 }
 
@@ -139,8 +201,17 @@ impl HexCell {
 pub struct DrawOptions {
     /// Show positional markers in each hex cell.
     pub pos_markers: bool,
-    /// Positions to highlight in yellow.
+    /// Mark choke points and defensive tiles, as the AI sees them.
+    pub terrain_analysis: bool,
+    /// Positions to highlight with the theme's reachable color. A selection outside of them is
+    /// highlighted with the theme's unreachable color.
     pub positions_to_highlight: Option<HashSet<Pos>>,
+    /// Unit being animated, along with the position at which to draw it instead of its real one.
+    pub moving_unit: Option<(UnitID, Pos)>,
+    /// Positions flashed in reverse video to draw attention to them.
+    pub flashing: Vec<Pos>,
+    /// Damage floating above the unit at each position, after a combat.
+    pub damage: Vec<(Pos, u8)>,
 }
 /// Takes care of drawing our main map.
 pub struct Screen {
@@ -151,6 +222,8 @@ pub struct Screen {
     topleft: Pos,
     /// Size of the map during the last draw call.
     map_size: (i32, i32),
+    theme: Theme,
+    glyphs: Glyphs,
 }
 
 impl Screen {
@@ -168,9 +241,27 @@ impl Screen {
             cells: cells,
             topleft: Pos::origin(),
             map_size: (0, 0),
+            theme: Theme::new(ThemeKind::Default, ColorDepth::Basic),
+            glyphs: Glyphs::new(GlyphMode::Ascii),
         }
     }
 
+    pub fn set_glyphs(&mut self, glyphs: Glyphs) {
+        self.glyphs = glyphs;
+    }
+
+    pub fn glyphs(&self) -> &Glyphs {
+        &self.glyphs
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
     pub fn update_screen_size(&mut self, target: &HasSize) {
         self.screensize = size_in_cells(target);
     }
@@ -191,9 +282,9 @@ impl Screen {
     /// # Examples
     ///
     /// ```
-    /// use civng::Widget;
-    /// use civng::screen::Screen;
-    /// use civng::hexpos::{Pos, Direction};
+    /// use civng_tui::Widget;
+    /// use civng_tui::screen::Screen;
+    /// use civng_tui::hexpos::{Pos, Direction};
     ///
     /// let widget = Widget::new(10, 10);
     /// let mut screen = Screen::new(&widget);
@@ -214,10 +305,10 @@ impl Screen {
     /// # Examples
     ///
     /// ```
-    /// use civng::Widget;
-    /// use civng::screen::Screen;
-    /// use civng::terrain::TerrainMap;
-    /// use civng::hexpos::{OffsetPos};
+    /// use civng_tui::Widget;
+    /// use civng_tui::screen::Screen;
+    /// use civng_tui::terrain::TerrainMap;
+    /// use civng_tui::hexpos::{OffsetPos};
     ///
     /// let widget = Widget::new(10, 10);
     /// let mut screen = Screen::new(&widget);
@@ -236,9 +327,120 @@ impl Screen {
         let opos = pos.to_offset_pos();
         let target_x = max(min(opos.x - target_dx, max_x), 0);
         let target_y = max(min(opos.y - target_dy, max_y), 0);
+        self.map_size = map.size();
+        self.scroll_to(OffsetPos::new(target_x, target_y).to_pos());
+    }
+
+    /// Returns the position at the center of the screen.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::Widget;
+    /// use civng_tui::screen::Screen;
+    /// use civng_tui::terrain::TerrainMap;
+    /// use civng_tui::hexpos::OffsetPos;
+    ///
+    /// let widget = Widget::new(80, 24);
+    /// let mut screen = Screen::new(&widget);
+    /// let map = TerrainMap::empty_map(42, 42);
+    /// let pos = OffsetPos::new(21, 20).to_pos();
+    /// screen.center_on_pos(pos, &map);
+    /// assert!(screen.center_pos() == pos);
+    /// ```
+    pub fn center_pos(&self) -> Pos {
+        let (width, height) = self.screensize;
+        let topleft = self.topleft.to_offset_pos();
+        OffsetPos::new(topleft.x + (width / 2) as i32, topleft.y + (height / 2) as i32).to_pos()
+    }
+
+    /// Returns whether `pos` is in the visible part of the map.
+    pub fn is_visible(&self, pos: Pos) -> bool {
+        let opos = pos.to_offset_pos();
+        let otopleft = self.topleft.to_offset_pos();
+        let (width, height) = self.screensize;
+        opos.x >= otopleft.x && opos.x < otopleft.x + width as i32 && opos.y >= otopleft.y &&
+        opos.y < otopleft.y + height as i32
+    }
+
+    /// Scrolls the visible part of the map just enough for `pos` to be visible.
+    ///
+    /// If `pos` is already visible, we don't scroll.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::Widget;
+    /// use civng_tui::screen::Screen;
+    /// use civng_tui::terrain::TerrainMap;
+    /// use civng_tui::hexpos::OffsetPos;
+    ///
+    /// let widget = Widget::new(30, 30);
+    /// let mut screen = Screen::new(&widget);
+    /// let map = TerrainMap::empty_map(42, 42);
+    /// let pos = OffsetPos::new(20, 20).to_pos();
+    /// assert!(!screen.is_visible(pos));
+    /// screen.ensure_visible(pos, &map);
+    /// assert!(screen.is_visible(pos));
+    /// ```
+    pub fn ensure_visible(&mut self, pos: Pos, map: &TerrainMap) {
+        let opos = pos.to_offset_pos();
+        let otopleft = self.topleft.to_offset_pos();
+        let (width, height) = self.screensize;
+        let (width, height) = (width as i32, height as i32);
+        let target_x = if opos.x < otopleft.x {
+            opos.x
+        } else if opos.x >= otopleft.x + width {
+            opos.x - width + 1
+        } else {
+            otopleft.x
+        };
+        let target_y = if opos.y < otopleft.y {
+            opos.y
+        } else if opos.y >= otopleft.y + height {
+            opos.y - height + 1
+        } else {
+            otopleft.y
+        };
+        self.map_size = map.size();
+        self.scroll_to(OffsetPos::new(target_x, target_y).to_pos());
+    }
+
+    /// Scrolls the visible part of the map by a whole screen `dx` times horizontally and `dy` times
+    /// vertically.
+    pub fn scroll_pages(&mut self, dx: i32, dy: i32, map: &TerrainMap) {
+        let otopleft = self.topleft.to_offset_pos();
+        let (width, height) = self.screensize;
+        let target_x = otopleft.x + dx * width as i32;
+        let target_y = otopleft.y + dy * height as i32;
+        self.map_size = map.size();
+        self.scroll_to(OffsetPos::new(target_x, target_y).to_pos());
+    }
+
+    /// Scrolls to the corner of `map` indicated by `right` and `bottom`.
+    pub fn scroll_to_corner(&mut self, right: bool, bottom: bool, map: &TerrainMap) {
+        let (map_width, map_height) = map.size();
+        let target_x = if right {
+            map_width
+        } else {
+            0
+        };
+        let target_y = if bottom {
+            map_height
+        } else {
+            0
+        };
+        self.map_size = map.size();
+        // scroll_to() takes care of keeping us within the map's borders.
         self.scroll_to(OffsetPos::new(target_x, target_y).to_pos());
     }
 
+    /// Scrolls so that the center of `map` is at the center of the screen.
+    pub fn scroll_to_center(&mut self, map: &TerrainMap) {
+        let (map_width, map_height) = map.size();
+        self.center_on_pos(OffsetPos::new(map_width / 2, map_height / 2).to_pos(), map);
+    }
+
     /// Fills the screen with a hex grid.
     fn drawgrid(&self, target: &mut CellAccessor) {
         //  ╱     ╲
@@ -278,7 +480,7 @@ impl Screen {
                     let left_limit = is_at_left && colrepeat == 0;
                     let right_limit = is_at_right && colrepeat == screenx;
                     if top_limit || bottom_limit || left_limit || right_limit {
-                        cell.set_fg(Color::Red);
+                        cell.set_fg(self.theme.map_limit_fg());
                     }
                     cell.set_ch(ch);
                 }
@@ -304,12 +506,48 @@ impl Screen {
     ///
     /// `map` is the terrain map we want to draw and `unitpos` is the position of the test unit
     /// we're moving around.
+    ///
+    /// Tiles we haven't explored are left blank and enemy units we don't see aren't drawn.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::backend::{Backend, FakeBackend};
+    /// use civng_tui::screen::{Screen, DrawOptions};
+    /// use civng_tui::selection::Selection;
+    /// use civng_tui::map::LiveMap;
+    /// use civng_tui::terrain::TerrainMap;
+    /// use civng_tui::unit::{Unit, UnitType, Player};
+    /// use civng_tui::hexpos::Pos;
+    ///
+    /// let mut term = FakeBackend::new(24, 10);
+    /// let mut map = LiveMap::new(TerrainMap::empty_map(3, 2));
+    /// map.add_unit(Unit::new(UnitType::Melee, Player::Me, Pos::origin()));
+    /// let mut screen = Screen::new(&term);
+    /// let options = DrawOptions {
+    ///     pos_markers: false,
+    ///     terrain_analysis: false,
+    ///     positions_to_highlight: None,
+    ///     moving_unit: None,
+    ///     flashing: Vec::new(),
+    ///     damage: Vec::new(),
+    /// };
+    /// term.new_frame();
+    /// screen.draw(&mut term, &map, &Selection::new(), options);
+    /// term.present();
+    /// let expected = [r#" ╱"""""╲       ╱"""""╲"#,
+    ///                 r#"╱       ╲     ╱       ╲"#,
+    ///                 r#"╲   M   ╱"""""╲       ╱"#,
+    ///                 r#" ╲"""""╱       ╲"""""╱"#];
+    /// assert_eq!(&term.lines()[..4], &expected[..]);
+    /// ```
     pub fn draw(&mut self,
                 target: &mut CellAccessor,
                 map: &LiveMap,
                 selection: &Selection,
                 options: DrawOptions) {
         self.map_size = map.terrain().size();
+        let fog = map.fog(Player::Me);
         for cell in self.cells.iter_mut() {
             let pos = cell.pos().translate(self.topleft);
             cell.clear();
@@ -321,25 +559,82 @@ impl Screen {
             if options.pos_markers {
                 cell.draw_posmarker(pos.to_offset_pos());
             }
-            cell.draw_terrain(terrain);
-            if let Some(unit_id) = map.units().unit_at_pos(pos) {
-                let unit = map.units().get(unit_id);
+            if fog.map_or(false, |f| !f.is_explored(pos)) {
+                cell.draw_into(target);
+                continue;
+            }
+            let feature = map.terrain().get_feature(pos);
+            cell.draw_terrain(terrain, feature, &self.theme, &self.glyphs);
+            if options.terrain_analysis {
+                let terrainmap = map.terrain();
+                cell.draw_analysis(is_choke_point(terrainmap, pos),
+                                   terrainmap.is_passable(pos) &&
+                                   defense_score(terrainmap, pos) >= DEFENSIVE_SCORE);
+            }
+            if let Some(city) = map.cities().get_at_pos(pos) {
+                cell.draw_city(city, &self.theme, &self.glyphs);
+            }
+            // Of the units sharing a tile, we draw the active one or the one on top.
+            let active_unit = selection.unit_id.and_then(|uid| map.units().find(uid));
+            let top_id = match active_unit {
+                Some(u) if u.pos() == pos && !u.is_dead() => Some(u.id()),
+                _ => map.units().top_at_pos(pos).map(|u| u.id()),
+            };
+            let unit_to_draw = match options.moving_unit {
+                Some((moving_id, moving_pos)) => {
+                    if moving_pos == pos {
+                        Some(moving_id)
+                    } else {
+                        top_id.and_then(|uid| {
+                            if uid == moving_id {
+                                None
+                            } else {
+                                Some(uid)
+                            }
+                        })
+                    }
+                }
+                None => top_id,
+            };
+            let is_visible = fog.map_or(true, |f| f.is_visible(pos));
+            // An animated unit might have been purged since it moved.
+            let unit_to_draw = unit_to_draw.and_then(|uid| map.units().find(uid)).and_then(|u| {
+                if is_visible || u.owner() == Player::Me {
+                    Some(u)
+                } else {
+                    None
+                }
+            });
+            if let Some(unit) = unit_to_draw {
                 let is_active = selection.is_unit_active(unit.id());
-                cell.draw_unit(unit, is_active);
+                cell.draw_unit(unit, is_active, &self.theme, &self.glyphs);
             }
             if let Some(ref highlight_pos) = options.positions_to_highlight {
                 if selection.pos.is_some() && pos == selection.pos.unwrap() {
-                    cell.highlight(Color::Blue)
+                    // The active unit's own tile is where the selector starts, not a destination.
+                    let reachable = highlight_pos.contains(&pos) ||
+                                    active_unit.map_or(true, |u| u.pos() == pos);
+                    cell.highlight(if reachable {
+                        self.theme.selection_highlight()
+                    } else {
+                        self.theme.unreachable_selection_highlight()
+                    })
                 } else if highlight_pos.contains(&pos) {
-                    let mut color = Color::Yellow;
-                    if let Some(u) = map.units().get_at_pos(pos) {
+                    let mut highlight = self.theme.reachable_highlight();
+                    if let Some(u) = map.units().top_at_pos(pos) {
                         if u.owner() != Player::Me {
-                            color = Color::Red;
+                            highlight = self.theme.attackable_highlight();
                         }
                     }
-                    cell.highlight(color);
+                    cell.highlight(highlight);
                 }
             }
+            if let Some(&(_, dmg)) = options.damage.iter().find(|&&(p, _)| p == pos) {
+                cell.draw_damage(dmg, &self.theme);
+            }
+            if options.flashing.contains(&pos) {
+                cell.highlight(Highlight::Attr(Attr::Reverse));
+            }
             cell.draw_into(target);
         }
         self.drawgrid(target);
@@ -0,0 +1,194 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! WebSocket feed letting browsers spectate a game.
+//!
+//! Spectators receive, as text messages, the full state of the game at the start of each turn and
+//! game events as they happen, in the JSON formats described in `export`. Events tell what changed
+//! since the last state. A spectator connecting in the middle of a turn first receives the last
+//! state.
+//!
+//! We only speak as much WebSocket as this needs: spectators can't send us anything.
+//! `resources/spectator.html` is a page showing the feed.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{sync_channel, SyncSender, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use sha1_smol::Sha1;
+
+/// How long we wait for a spectator to send its request before dropping it.
+const HANDSHAKE_TIMEOUT_MS: u64 = 200;
+
+/// How long a spectator can go without taking any part of a frame before we drop it.
+///
+/// Spectators on slow connections take large states in many writes, each of which can take up
+/// to this long. As it only holds up the spectator's own sender, this can be generous.
+const WRITE_TIMEOUT_SECS: u64 = 30;
+
+/// Messages waiting to be sent to a spectator, past which it's dropped for not keeping up.
+const MAX_QUEUED_MESSAGES: usize = 64;
+
+/// Returns the `Sec-WebSocket-Accept` value answering the `Sec-WebSocket-Key` of a client.
+///
+/// # Examples
+///
+/// ```
+/// use civng_tui::spectator::accept_key;
+///
+/// assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+/// ```
+pub fn accept_key(key: &str) -> String {
+    let magic = format!("{}258EAFA5-E914-47DA-95CA-C5AB0DC85B11", key);
+    STANDARD.encode(Sha1::from(magic).digest().bytes())
+}
+
+/// Returns `msg` as a WebSocket text frame.
+fn text_frame(msg: &str) -> Vec<u8> {
+    let len = msg.len();
+    let mut result = vec![0x81];
+    if len < 126 {
+        result.push(len as u8);
+    } else if len < 0x10000 {
+        result.push(126);
+        result.push((len >> 8) as u8);
+        result.push(len as u8);
+    } else {
+        result.push(127);
+        for i in (0..8).rev() {
+            result.push((len as u64 >> (i * 8)) as u8);
+        }
+    }
+    result.extend_from_slice(msg.as_bytes());
+    result
+}
+
+/// Reads the HTTP request of a new spectator and answers it, turning the connection into a
+/// WebSocket.
+fn handshake(stream: &mut TcpStream) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.ends_with(b"\r\n\r\n") {
+        let read = try!(stream.read(&mut buf));
+        if read == 0 || request.len() > 16 * 1024 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid WebSocket request"));
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let key = request.lines()
+                     .filter_map(|l| {
+                         let mut parts = l.splitn(2, ':');
+                         let name = parts.next().unwrap_or("").trim().to_lowercase();
+                         match parts.next() {
+                             Some(value) if name == "sec-websocket-key" => {
+                                 Some(value.trim().to_owned())
+                             }
+                             _ => None,
+                         }
+                     })
+                     .next();
+    let key = match key {
+        Some(key) => key,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a WebSocket request")),
+    };
+    let response = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                            Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                           accept_key(&key));
+    stream.write_all(response.as_bytes())
+}
+
+struct Spectators {
+    /// Queues of the threads sending messages to each spectator.
+    queues: Vec<SyncSender<Arc<Vec<u8>>>>,
+    /// Last state we've broadcast, sent to newcomers.
+    last_state: Option<String>,
+}
+
+impl Spectators {
+    /// Queues `msg` for every spectator. Those who left or can't keep up are dropped.
+    fn queue(&mut self, msg: &str) {
+        let frame = Arc::new(text_frame(msg));
+        self.queues.retain(|q| q.try_send(frame.clone()).is_ok());
+    }
+}
+
+/// Writes the frames coming from `queue` to `stream`, until either of them is closed.
+///
+/// Every spectator gets its own sender, so that a slow one can't hold up the game, which only
+/// queues messages.
+fn send_frames(mut stream: TcpStream, queue: Receiver<Arc<Vec<u8>>>) {
+    for frame in queue {
+        if stream.write_all(&frame).is_err() {
+            break;
+        }
+    }
+}
+
+/// Server broadcasting the game to spectators connecting to it.
+pub struct SpectatorServer {
+    spectators: Arc<Mutex<Spectators>>,
+}
+
+impl SpectatorServer {
+    /// Starts listening for spectators on `addr` in the background.
+    pub fn start<A: ToSocketAddrs>(addr: A) -> io::Result<SpectatorServer> {
+        let listener = try!(TcpListener::bind(addr));
+        let spectators = Arc::new(Mutex::new(Spectators {
+            queues: Vec::new(),
+            last_state: None,
+        }));
+        let shared = spectators.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(s) => s,
+                    Err(_) => continue,
+                };
+                // Stalled clients mustn't hold up other spectators.
+                let timeout = Some(Duration::from_millis(HANDSHAKE_TIMEOUT_MS));
+                let ready = stream.set_read_timeout(timeout)
+                                  .and_then(|_| stream.set_write_timeout(timeout))
+                                  .and_then(|_| handshake(&mut stream))
+                                  .and_then(|_| {
+                                      let timeout = Duration::from_secs(WRITE_TIMEOUT_SECS);
+                                      stream.set_write_timeout(Some(timeout))
+                                  });
+                if ready.is_err() {
+                    continue;
+                }
+                let (sender, receiver) = sync_channel(MAX_QUEUED_MESSAGES);
+                {
+                    let mut spectators = shared.lock().unwrap();
+                    if let Some(ref state) = spectators.last_state {
+                        let _ = sender.try_send(Arc::new(text_frame(state)));
+                    }
+                    spectators.queues.push(sender);
+                }
+                thread::spawn(move || send_frames(stream, receiver));
+            }
+        });
+        Ok(SpectatorServer { spectators: spectators })
+    }
+
+    /// Sends `msg` to every spectator.
+    pub fn broadcast(&self, msg: &str) {
+        self.spectators.lock().unwrap().queue(msg);
+    }
+
+    /// Sends the state of the game, `state`, to every spectator, and to those joining later.
+    pub fn broadcast_state(&self, state: &str) {
+        let mut spectators = self.spectators.lock().unwrap();
+        spectators.queue(state);
+        spectators.last_state = Some(state.to_owned());
+    }
+}
@@ -0,0 +1,462 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+//! Colors used to draw game elements, adapted to what the terminal supports.
+//!
+//! Our colors are defined in RGB and then converted to the closest color the terminal can show.
+//! rustty can only output colors through terminfo's `setaf`/`setab`, which are limited to 256
+//! colors, so truecolor terminals get the same palette as 256 colors terminals.
+//!
+//! On top of that, the user can choose among a few color schemes, defined by `ThemeKind`.
+
+use std::env;
+use std::cmp::min;
+
+use rustty::{Cell, Color, Attr};
+
+use terrain::{Terrain, Feature};
+use unit::Player;
+
+/// How the border of a highlighted map cell is shown.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Highlight {
+    /// With this background color.
+    Color(Color),
+    /// With this text attribute, on top of the ones its characters already have.
+    Attr(Attr),
+}
+
+impl Highlight {
+    /// Highlights `cell`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate rustty;
+    /// extern crate civng_tui;
+    ///
+    /// use rustty::{Cell, Color, Attr};
+    /// use civng_tui::theme::Highlight;
+    ///
+    /// # fn main() {
+    /// let mut cell = Cell::with_style(Color::Default, Color::Default, Attr::Underline);
+    /// Highlight::Attr(Attr::Reverse).apply(&mut cell);
+    /// assert_eq!(cell.attrs(), Attr::UnderlineReverse);
+    /// Highlight::Color(Color::Yellow).apply(&mut cell);
+    /// assert_eq!(cell.bg(), Color::Yellow);
+    /// # }
+    /// ```
+    pub fn apply(&self, cell: &mut Cell) {
+        match *self {
+            Highlight::Color(color) => {
+                cell.set_bg(color);
+            }
+            Highlight::Attr(attr) => {
+                let attrs = combine_attrs(cell.attrs(), attr);
+                cell.set_attrs(attrs);
+            }
+        }
+    }
+}
+
+/// Returns the attribute made of both `a` and `b`.
+fn combine_attrs(a: Attr, b: Attr) -> Attr {
+    match a as u8 | b as u8 {
+        0b001 => Attr::Bold,
+        0b010 => Attr::Underline,
+        0b011 => Attr::BoldUnderline,
+        0b100 => Attr::Reverse,
+        0b101 => Attr::BoldReverse,
+        0b110 => Attr::UnderlineReverse,
+        0b111 => Attr::BoldReverseUnderline,
+        _ => Attr::Default,
+    }
+}
+
+/// Color capability of a terminal.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorDepth {
+    /// The 8 basic ANSI colors.
+    Basic,
+    /// The xterm 256 colors palette.
+    Palette256,
+    /// 24-bit colors.
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Guesses the color capability of our terminal from environment variables.
+    ///
+    /// `COLORTERM` is checked for truecolor support and `TERM` for 256 colors support. If none of
+    /// them tell us anything, we fall back to `Basic`.
+    pub fn detect() -> ColorDepth {
+        let colorterm = env::var("COLORTERM").unwrap_or(String::new());
+        let term = env::var("TERM").unwrap_or(String::new());
+        ColorDepth::from_env_values(&colorterm, &term)
+    }
+
+    /// Returns the color capability matching `COLORTERM` and `TERM` values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::theme::ColorDepth;
+    ///
+    /// assert_eq!(ColorDepth::from_env_values("truecolor", "xterm"), ColorDepth::TrueColor);
+    /// assert_eq!(ColorDepth::from_env_values("", "xterm-256color"), ColorDepth::Palette256);
+    /// assert_eq!(ColorDepth::from_env_values("", "linux"), ColorDepth::Basic);
+    /// ```
+    pub fn from_env_values(colorterm: &str, term: &str) -> ColorDepth {
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Palette256
+        } else {
+            ColorDepth::Basic
+        }
+    }
+}
+
+/// Returns the index of the xterm 256 colors palette that is the closest to `(r, g, b)`.
+///
+/// We only look in the 6x6x6 color cube (indexes 16 to 231) and the grayscale ramp (indexes 232
+/// to 255).
+///
+/// # Examples
+///
+/// ```
+/// use civng_tui::theme::rgb_to_palette256;
+///
+/// assert_eq!(rgb_to_palette256(0, 0, 0), 16);
+/// assert_eq!(rgb_to_palette256(255, 255, 255), 231);
+/// assert_eq!(rgb_to_palette256(0, 0, 255), 21);
+/// assert_eq!(rgb_to_palette256(128, 128, 128), 244);
+/// ```
+pub fn rgb_to_palette256(r: u8, g: u8, b: u8) -> u8 {
+    const CUBE_LEVELS: [i32; 6] = [0, 95, 135, 175, 215, 255];
+    fn closest_level(value: u8) -> usize {
+        let value = value as i32;
+        let mut result = 0;
+        for (i, level) in CUBE_LEVELS.iter().enumerate() {
+            if (level - value).abs() < (CUBE_LEVELS[result] - value).abs() {
+                result = i;
+            }
+        }
+        result
+    }
+    fn distance(c1: (i32, i32, i32), c2: (i32, i32, i32)) -> i32 {
+        let (dr, dg, db) = (c1.0 - c2.0, c1.1 - c2.1, c1.2 - c2.2);
+        dr * dr + dg * dg + db * db
+    }
+
+    let target = (r as i32, g as i32, b as i32);
+    let (ri, gi, bi) = (closest_level(r), closest_level(g), closest_level(b));
+    let cube_color = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+    // The grayscale ramp goes from 8 to 238 by steps of 10.
+    let avg = (target.0 + target.1 + target.2) / 3;
+    let gray_index = if avg < 8 {
+        0
+    } else {
+        min((avg - 8 + 5) / 10, 23)
+    };
+    let gray_level = 8 + gray_index * 10;
+    let gray_color = (gray_level, gray_level, gray_level);
+    if distance(target, gray_color) < distance(target, cube_color) {
+        (232 + gray_index) as u8
+    } else {
+        cube_index as u8
+    }
+}
+
+/// Color schemes the user can choose from.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ThemeKind {
+    Default,
+    /// Bright colors on a black background.
+    HighContrast,
+    /// Avoids relying on red/green distinctions.
+    ColorblindSafe,
+    /// No colors at all. We use text attributes to make things stand out.
+    Monochrome,
+}
+
+impl ThemeKind {
+    pub fn all() -> [ThemeKind; 4] {
+        [ThemeKind::Default,
+         ThemeKind::HighContrast,
+         ThemeKind::ColorblindSafe,
+         ThemeKind::Monochrome]
+    }
+
+    /// Name of the theme, as written in config files.
+    pub fn name(&self) -> &str {
+        match *self {
+            ThemeKind::Default => "default",
+            ThemeKind::HighContrast => "high-contrast",
+            ThemeKind::ColorblindSafe => "colorblind-safe",
+            ThemeKind::Monochrome => "monochrome",
+        }
+    }
+
+    /// Returns the theme named `name`, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::theme::ThemeKind;
+    ///
+    /// assert_eq!(ThemeKind::from_name("monochrome"), Some(ThemeKind::Monochrome));
+    /// assert_eq!(ThemeKind::from_name("foobar"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<ThemeKind> {
+        ThemeKind::all().iter().find(|k| k.name() == name).cloned()
+    }
+
+    /// Returns the theme that comes after `self` in `all()`, wrapping around.
+    pub fn next(&self) -> ThemeKind {
+        let all = ThemeKind::all();
+        let index = all.iter().position(|k| k == self).unwrap();
+        all[(index + 1) % all.len()]
+    }
+}
+
+/// Colors used to draw game elements.
+pub struct Theme {
+    kind: ThemeKind,
+    depth: ColorDepth,
+}
+
+impl Theme {
+    pub fn new(kind: ThemeKind, depth: ColorDepth) -> Theme {
+        Theme {
+            kind: kind,
+            depth: depth,
+        }
+    }
+
+    /// Returns a theme of type `kind` adapted to our terminal's color capability.
+    pub fn detect(kind: ThemeKind) -> Theme {
+        Theme::new(kind, ColorDepth::detect())
+    }
+
+    pub fn kind(&self) -> ThemeKind {
+        self.kind
+    }
+
+    pub fn depth(&self) -> ColorDepth {
+        self.depth
+    }
+
+    /// Returns `(r, g, b)` if we support more than basic colors, `fallback` otherwise.
+    fn rgb(&self, r: u8, g: u8, b: u8, fallback: Color) -> Color {
+        match self.depth {
+            ColorDepth::Basic => fallback,
+            ColorDepth::Palette256 | ColorDepth::TrueColor => {
+                Color::Byte(rgb_to_palette256(r, g, b))
+            }
+        }
+    }
+
+    /// Color with which the chars of `terrain` are drawn.
+    pub fn terrain_fg(&self, terrain: Terrain) -> Color {
+        match self.kind {
+            ThemeKind::HighContrast | ThemeKind::Monochrome => Color::Default,
+            ThemeKind::Default | ThemeKind::ColorblindSafe => {
+                match terrain {
+                    Terrain::Plain => self.rgb(0xaf, 0xaf, 0x5f, Color::Default),
+                    Terrain::Grassland => self.rgb(0x5f, 0xaf, 0x00, Color::Default),
+                    Terrain::Desert => self.rgb(0xd7, 0xaf, 0x87, Color::Default),
+                    Terrain::Hill => self.rgb(0xaf, 0x87, 0x5f, Color::Default),
+                    Terrain::Mountain => self.rgb(0xa8, 0xa8, 0xa8, Color::Default),
+                    Terrain::Coast => self.rgb(0x00, 0x87, 0xd7, Color::Default),
+                    Terrain::Ocean => self.rgb(0x00, 0x5f, 0xaf, Color::Default),
+                    Terrain::Lake => self.rgb(0x5f, 0xaf, 0xd7, Color::Default),
+                    Terrain::OutOfBounds => Color::Default,
+                }
+            }
+        }
+    }
+
+    /// Color with which the chars of `feature` are drawn.
+    pub fn feature_fg(&self, feature: Feature) -> Color {
+        match self.kind {
+            ThemeKind::HighContrast | ThemeKind::Monochrome => Color::Default,
+            ThemeKind::Default | ThemeKind::ColorblindSafe => {
+                match feature {
+                    Feature::Ice => self.rgb(0xee, 0xee, 0xee, Color::Default),
+                    Feature::MountainRange => self.rgb(0x87, 0x5f, 0x5f, Color::Default),
+                    Feature::Forest => self.rgb(0x00, 0x87, 0x00, Color::Default),
+                    Feature::Jungle => self.rgb(0x00, 0x5f, 0x00, Color::Default),
+                    Feature::Marsh => self.rgb(0x5f, 0x87, 0x87, Color::Default),
+                }
+            }
+        }
+    }
+
+    /// Color telling `continent` apart from its neighbors on the overhead map.
+    pub fn continent_fg(&self, continent: u16) -> Color {
+        match self.kind {
+            ThemeKind::Monochrome => Color::Default,
+            ThemeKind::HighContrast => {
+                [Color::White, Color::Yellow, Color::Cyan, Color::Magenta][continent as usize % 4]
+            }
+            ThemeKind::Default | ThemeKind::ColorblindSafe => {
+                match continent % 6 {
+                    0 => self.rgb(0xe6, 0x9f, 0x00, Color::Yellow),
+                    1 => self.rgb(0x00, 0x9e, 0x73, Color::Green),
+                    2 => self.rgb(0xcc, 0x79, 0xa7, Color::Magenta),
+                    3 => self.rgb(0xf0, 0xe4, 0x42, Color::White),
+                    4 => self.rgb(0xd5, 0x5e, 0x00, Color::Red),
+                    _ => self.rgb(0x56, 0xb4, 0xe9, Color::Cyan),
+                }
+            }
+        }
+    }
+
+    /// Color identifying units belonging to `player`.
+    pub fn player_color(&self, player: Player) -> Color {
+        match (self.kind, player) {
+            (ThemeKind::Monochrome, _) => Color::Default,
+            (ThemeKind::HighContrast, Player::Me) => Color::Cyan,
+            (ThemeKind::HighContrast, Player::AI(n)) => {
+                [Color::Magenta, Color::Yellow, Color::Red][n as usize % 3]
+            }
+            (ThemeKind::ColorblindSafe, Player::Me) => self.rgb(0x00, 0x72, 0xb2, Color::Blue),
+            (ThemeKind::ColorblindSafe, Player::AI(n)) => {
+                match n % 3 {
+                    0 => self.rgb(0xe6, 0x9f, 0x00, Color::Yellow),
+                    1 => self.rgb(0xcc, 0x79, 0xa7, Color::Magenta),
+                    _ => self.rgb(0xd5, 0x5e, 0x00, Color::Red),
+                }
+            }
+            (ThemeKind::Default, Player::Me) => self.rgb(0x00, 0x5f, 0xff, Color::Blue),
+            (ThemeKind::Default, Player::AI(n)) => {
+                match n % 3 {
+                    0 => self.rgb(0xd7, 0x00, 0x00, Color::Red),
+                    1 => self.rgb(0xaf, 0x00, 0xd7, Color::Magenta),
+                    _ => self.rgb(0xff, 0x87, 0x00, Color::Yellow),
+                }
+            }
+        }
+    }
+
+    /// Color and attributes of the symbol of a unit belonging to `player`.
+    ///
+    /// Our own units are only colored when they're active.
+    pub fn unit_style(&self, player: Player, is_active: bool) -> (Color, Attr) {
+        match self.kind {
+            ThemeKind::Monochrome => {
+                if player != Player::Me {
+                    (Color::Default, Attr::Bold)
+                } else if is_active {
+                    (Color::Default, Attr::Reverse)
+                } else {
+                    (Color::Default, Attr::Default)
+                }
+            }
+            ThemeKind::HighContrast => {
+                if player != Player::Me || is_active {
+                    (self.player_color(player), Attr::Bold)
+                } else {
+                    (Color::White, Attr::Default)
+                }
+            }
+            ThemeKind::Default | ThemeKind::ColorblindSafe => {
+                if player != Player::Me || is_active {
+                    (self.player_color(player), Attr::Default)
+                } else {
+                    (Color::Default, Attr::Default)
+                }
+            }
+        }
+    }
+
+    /// Highlight of cells reachable by the active unit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use civng_tui::theme::{Theme, ThemeKind, ColorDepth};
+    ///
+    /// let theme = Theme::new(ThemeKind::Monochrome, ColorDepth::Basic);
+    /// assert!(theme.reachable_highlight() != theme.attackable_highlight());
+    /// assert!(theme.reachable_highlight() != theme.selection_highlight());
+    /// ```
+    pub fn reachable_highlight(&self) -> Highlight {
+        match self.kind {
+            ThemeKind::Default => Highlight::Color(Color::Yellow),
+            ThemeKind::HighContrast => Highlight::Color(Color::White),
+            ThemeKind::ColorblindSafe => Highlight::Color(self.rgb(0x56, 0xb4, 0xe9, Color::Cyan)),
+            ThemeKind::Monochrome => Highlight::Attr(Attr::Reverse),
+        }
+    }
+
+    /// Highlight of reachable cells containing an enemy.
+    pub fn attackable_highlight(&self) -> Highlight {
+        match self.kind {
+            ThemeKind::Monochrome => Highlight::Attr(Attr::Underline),
+            _ => Highlight::Color(self.player_color(Player::AI(0))),
+        }
+    }
+
+    /// Highlight of the selected cell when the active unit can't get there this turn.
+    pub fn unreachable_selection_highlight(&self) -> Highlight {
+        match self.kind {
+            ThemeKind::Default | ThemeKind::HighContrast => Highlight::Color(Color::Red),
+            ThemeKind::ColorblindSafe => Highlight::Color(self.rgb(0xd5, 0x5e, 0x00, Color::Red)),
+            ThemeKind::Monochrome => Highlight::Attr(Attr::UnderlineReverse),
+        }
+    }
+
+    /// Highlight of the selected cell.
+    pub fn selection_highlight(&self) -> Highlight {
+        match self.kind {
+            ThemeKind::Default | ThemeKind::ColorblindSafe => Highlight::Color(Color::Blue),
+            ThemeKind::HighContrast => Highlight::Color(Color::Cyan),
+            ThemeKind::Monochrome => Highlight::Attr(Attr::UnderlineReverse),
+        }
+    }
+
+    /// Color of the grid lines marking the limits of the map.
+    pub fn map_limit_fg(&self) -> Color {
+        match self.kind {
+            ThemeKind::Default => Color::Red,
+            ThemeKind::HighContrast => Color::Yellow,
+            ThemeKind::ColorblindSafe => self.rgb(0xd5, 0x5e, 0x00, Color::Magenta),
+            ThemeKind::Monochrome => Color::White,
+        }
+    }
+
+    /// Color of the HP bar of a unit with `hp` HP out of `max_hp`.
+    pub fn hp_color(&self, hp: u8, max_hp: u8) -> Color {
+        let level = if hp > max_hp / 3 * 2 {
+            2
+        } else if hp > max_hp / 3 {
+            1
+        } else {
+            0
+        };
+        match (self.kind, level) {
+            (ThemeKind::Monochrome, _) => Color::Default,
+            (ThemeKind::ColorblindSafe, 2) => self.rgb(0x56, 0xb4, 0xe9, Color::Cyan),
+            (ThemeKind::ColorblindSafe, 1) => self.rgb(0xf0, 0xe4, 0x42, Color::Yellow),
+            (ThemeKind::ColorblindSafe, _) => self.rgb(0xd5, 0x5e, 0x00, Color::Magenta),
+            (_, 2) => Color::Green,
+            (_, 1) => Color::Yellow,
+            (_, _) => Color::Red,
+        }
+    }
+
+    /// Cell with which dialogs and windows are cleared before being drawn.
+    pub fn dialog_cell(&self) -> Cell {
+        match self.kind {
+            ThemeKind::HighContrast => Cell::with_style(Color::White, Color::Black, Attr::Bold),
+            _ => Cell::default(),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright 2016 Virgil Dupras
+//
+// This software is licensed under the "GPLv3" License as described in the "LICENSE" file,
+// which should be included with this package. The terms are also available at
+// http://www.gnu.org/licenses/gpl-3.0.html
+//
+
+use rustty::CellAccessor;
+use rustty::ui::{Painter, HorizontalAlign, Dialog, DialogResult};
+
+use hexpos::Pos;
+use map::LiveMap;
+use unit::{Unit, Player};
+use theme::Theme;
+
+/// Returns the lines describing `unit`: its stats, then the modifiers it would defend with.
+fn unit_lines(unit: &Unit, map: &LiveMap) -> Vec<String> {
+    let mut stats = format!("{} ({}): ST {} MV {} HP {} XP {}",
+                            unit.name(),
+                            unit.owner().name(),
+                            unit.strength(),
+                            unit.movements(),
+                            unit.hp(),
+                            unit.xp());
+    if let Some(order) = unit.order() {
+        stats = format!("{}, {}", stats, order.description());
+    }
+    let modifiers = map.defense_modifiers(unit.id())
+                       .iter()
+                       .map(|m| m.description())
+                       .collect::<Vec<_>>();
+    let defense = if modifiers.is_empty() {
+        "  Defense: no modifier".to_owned()
+    } else {
+        format!("  Defense: {}", modifiers.join(", "))
+    };
+    vec![stats, defense]
+}
+
+/// Creates a dialog describing the tile at `pos` and the units on it, as far as we can see them.
+pub fn create_tile_info_dialog(pos: Pos, map: &LiveMap, theme: &Theme) -> Dialog {
+    let fog = map.fog(Player::Me);
+    let mut lines = Vec::new();
+    if fog.map_or(false, |f| !f.is_explored(pos)) {
+        lines.push("Unexplored".to_owned());
+    } else {
+        let terrainmap = map.terrain();
+        let terrain = terrainmap.get_terrain(pos);
+        let river = if terrainmap.has_river(pos) { " (river)" } else { "" };
+        lines.push(format!("Terrain: {}{}", terrain.name(), river));
+        let feature = terrainmap.get_feature(pos);
+        lines.push(format!("Feature: {}", feature.as_ref().map_or("None", |f| f.name())));
+        let resource = terrainmap.get_resource(pos)
+                                 .and_then(|r| if map.knows_resource(Player::Me, r) {
+                                     Some(r)
+                                 } else {
+                                     None
+                                 });
+        lines.push(format!("Resource: {}", resource.map_or("None", |r| r.name())));
+        lines.push(format!("Yield: {}", map.yield_at(Player::Me, pos).fmt()));
+        let owner = match map.cities().get_at_pos(pos) {
+            Some(city) => format!("{} ({})", city.owner().name(), city.name()),
+            None => map.territory_owner(pos).map_or("None".to_owned(), |p| p.name()),
+        };
+        lines.push(format!("Owner: {}", owner));
+        lines.push(format!("Defense: {:+}%", terrain.defense_modifier()));
+        let movement = if terrainmap.is_passable(pos) {
+            format!("Movement cost: {}", terrain.movement_cost())
+        } else {
+            "Movement cost: impassable".to_owned()
+        };
+        lines.push(movement);
+        let is_visible = fog.map_or(true, |f| f.is_visible(pos));
+        let units = map.units()
+                       .all_units()
+                       .filter(|u| u.pos() == pos && (is_visible || u.owner() == Player::Me))
+                       .collect::<Vec<_>>();
+        if !units.is_empty() {
+            lines.push("".to_owned());
+            for unit in units {
+                lines.extend(unit_lines(unit, map));
+            }
+        }
+    }
+    let mut d = Dialog::new(60, 6 + lines.len());
+    {
+        let w = d.window_mut();
+        w.clear(theme.dialog_cell());
+        let title = format!("Tile {}", pos.to_offset_pos().fmt());
+        let x = w.halign_line(&title, HorizontalAlign::Middle, 1);
+        w.printline(x, 1, &title);
+        for (i, line) in lines.iter().enumerate() {
+            w.printline(2, 3 + i, line);
+        }
+    }
+    d.add_button("Ok", 'o', DialogResult::Ok);
+    d.draw_buttons();
+    d.window_mut().draw_box();
+    d
+}